@@ -0,0 +1,138 @@
+use crate::csv_import::{parse_date, parse_number, read_table};
+use crate::store::RecordRef;
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Коэффициенты распределения за месяц: замеренный дебит, умноженный на
+// коэффициент, сходится с коммерческим учетом
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Factor {
+    pub liquid: Option<f64>,
+    pub oil: Option<f64>,
+}
+
+// Таблица коэффициентов по скважинам и месяцам
+#[derive(Debug, Clone)]
+pub struct AllocationTable {
+    pub path: PathBuf,
+    pub by_well: BTreeMap<String, BTreeMap<(i32, u32), Factor>>,
+}
+
+impl AllocationTable {
+    pub fn len(&self) -> usize {
+        self.by_well.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn has_well(&self, well: &str) -> bool {
+        self.by_well.contains_key(well)
+    }
+
+    pub fn factor(&self, well: &str, date: NaiveDate) -> Factor {
+        self.by_well
+            .get(well)
+            .and_then(|months| months.get(&(date.year(), date.month())))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // Распределенные дебиты записи; без коэффициента за месяц - пусто
+    pub fn allocate(&self, record: &RecordRef) -> (Option<f64>, Option<f64>) {
+        let Some(date) = record.date else {
+            return (None, None);
+        };
+        let factor = self.factor(record.well_name, date.date());
+        (
+            record.pd_liq.zip(factor.liquid).map(|(v, k)| v * k),
+            record.pd_oil.zip(factor.oil).map(|(v, k)| v * k),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Well,
+    Month,
+    // Один коэффициент на жидкость и нефть
+    Factor,
+    Liquid,
+    Oil,
+}
+
+fn column_kind(header: &str) -> Option<ColumnKind> {
+    let h = header.trim().to_lowercase();
+    if h.contains("скв") || h.contains("well") || h == crate::NAME_COL.to_lowercase() {
+        Some(ColumnKind::Well)
+    } else if ["месяц", "период", "дата", "month", "period", "date"]
+        .iter()
+        .any(|k| h.contains(k))
+    {
+        Some(ColumnKind::Month)
+    } else if h.contains("жидк") || h.contains("liq") {
+        Some(ColumnKind::Liquid)
+    } else if h.contains("нефт") || h.contains("oil") {
+        Some(ColumnKind::Oil)
+    } else if h.contains("коэф") || h.contains("factor") {
+        Some(ColumnKind::Factor)
+    } else {
+        None
+    }
+}
+
+// Месяц как 2021-01, 01.2021 или любая дата внутри месяца
+fn parse_month(s: &str) -> Option<(i32, u32)> {
+    let s = s.trim();
+    let date = parse_date(s)
+        .map(|d| d.date())
+        .or_else(|| NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d").ok())
+        .or_else(|| NaiveDate::parse_from_str(&format!("01.{}", s), "%d.%m.%Y").ok())?;
+    Some((date.year(), date.month()))
+}
+
+fn from_table(path: &Path, rows: Vec<Vec<String>>) -> Result<AllocationTable, String> {
+    let mut rows = rows.into_iter();
+    let header = rows.next().ok_or("пустая таблица коэффициентов")?;
+    let find = |kind| header.iter().position(|h| column_kind(h) == Some(kind));
+    let (Some(well), Some(month)) = (find(ColumnKind::Well), find(ColumnKind::Month)) else {
+        return Err("нужны колонки: скважина, месяц, коэффициент".to_string());
+    };
+    let common = find(ColumnKind::Factor);
+    let liquid = find(ColumnKind::Liquid).or(common);
+    let oil = find(ColumnKind::Oil).or(common);
+    if liquid.is_none() && oil.is_none() {
+        return Err("нет колонки с коэффициентом (жидкости, нефти или общим)".to_string());
+    }
+
+    let mut by_well: BTreeMap<String, BTreeMap<(i32, u32), Factor>> = BTreeMap::new();
+    let cell = |row: &[String], col: Option<usize>| {
+        col.and_then(|c| row.get(c)).and_then(|s| parse_number(s))
+    };
+    for row in rows {
+        let name = row.get(well).map(|s| s.trim()).unwrap_or_default();
+        let Some(month) = row.get(month).and_then(|s| parse_month(s)) else {
+            continue;
+        };
+        let factor = Factor {
+            liquid: cell(&row, liquid),
+            oil: cell(&row, oil),
+        };
+        if !name.is_empty() && (factor.liquid.is_some() || factor.oil.is_some()) {
+            by_well
+                .entry(name.to_string())
+                .or_default()
+                .insert(month, factor);
+        }
+    }
+    if by_well.is_empty() {
+        return Err("в таблице нет строк с месяцем и коэффициентом".to_string());
+    }
+    Ok(AllocationTable {
+        path: path.to_path_buf(),
+        by_well,
+    })
+}
+
+// Коэффициенты из CSV/TXT или с первого листа xlsx
+pub fn read_allocation(path: &Path) -> Result<AllocationTable, String> {
+    from_table(path, read_table(path)?)
+}
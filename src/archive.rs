@@ -0,0 +1,174 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use crate::rules::ValidationRules;
+use crate::store::{Dataset, RecordRef};
+use crate::{ImportOptions, LoadedData, csv_import, download, las, witsml};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+// Окно выбора книги из архива
+#[derive(Debug)]
+pub struct ArchiveDialog {
+    pub path: PathBuf,
+    pub entries: Vec<String>,
+    pub selected: Option<String>,
+    // Порядок приоритета для загрузки всех файлов: верхний важнее
+    pub priority: Vec<String>,
+}
+
+impl ArchiveDialog {
+    pub fn open(path: PathBuf) -> Result<Self, WellDataError> {
+        let entries = list_sources(&path)?;
+        if entries.is_empty() {
+            return Err(WellDataError::BadContent(format!(
+                "в архиве {} нет книг и файлов данных",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            selected: (entries.len() == 1).then(|| entries[0].clone()),
+            priority: entries.clone(),
+            path,
+            entries,
+        })
+    }
+}
+
+pub fn is_zip_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+fn is_source(name: &str) -> bool {
+    let path = Path::new(name);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    // Служебные файлы macOS и временные файлы открытой в Excel книги
+    if name.starts_with("__MACOSX/") || file_name.starts_with("~$") || file_name.starts_with("._") {
+        return false;
+    }
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xlsx"))
+        || csv_import::is_text_file(path)
+        || las::is_las_file(path)
+        || witsml::is_witsml_file(path)
+}
+
+// Файлы, которые программа умеет читать, по порядку имен
+pub fn list_sources(path: &Path) -> Result<Vec<String>, WellDataError> {
+    let archive = ZipArchive::new(File::open(path)?)?;
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/') && is_source(name))
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+// Файлы распаковываются в кэш, в папку по имени архива: по этому пути книга
+// считается исходной, как и скачанная по ссылке
+pub fn target_path(path: &Path, name: &str) -> Result<PathBuf, WellDataError> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(download::downloads_dir()?
+        .join("zip")
+        .join(stem.as_ref())
+        .join(download::file_name(name.rsplit('/').next().unwrap_or(name))))
+}
+
+pub fn extract(path: &Path, name: &str) -> Result<PathBuf, WellDataError> {
+    let target = target_path(path, name)?;
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut entry = archive.by_name(name)?;
+    let part = target.with_extension("part");
+    std::io::copy(&mut entry, &mut File::create(&part)?)?;
+    std::fs::rename(&part, &target)?;
+    Ok(target)
+}
+
+// Несколько файлов архива загружаются как один источник: записи идут подряд,
+// а нарушения и предупреждения подписываются именем файла. names - по приоритету:
+// если год скважины уже есть в файле выше, записи этого года из файла ниже
+// не берутся
+pub fn read_all(
+    path: &Path,
+    names: &[String],
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    let mut records = Dataset::new(import.storage)?;
+    let mut years = BTreeSet::new();
+    let mut wells = BTreeSet::new();
+    let mut violations = Vec::new();
+    let mut warnings = Vec::new();
+    let mut units = BTreeMap::new();
+    // Годы скважин из файлов с более высоким приоритетом
+    let mut covered: HashMap<String, BTreeSet<i32>> = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        cancel.check()?;
+        progress.report(
+            &format!("Файл {} из {}: {}", i + 1, names.len(), name),
+            i,
+            names.len(),
+        );
+        let file = extract(path, name)?;
+        let loaded = crate::read_source(&file, rules, import, progress, cancel)?;
+        let has_year = |map: &HashMap<String, BTreeSet<i32>>, r: &RecordRef| {
+            map.get(r.well_name)
+                .is_some_and(|years| years.contains(&r.year_sheet))
+        };
+        let mut own: HashMap<String, BTreeSet<i32>> = HashMap::new();
+        let mut overridden = 0;
+        records.append(loaded.records, |r| {
+            if has_year(&covered, r) {
+                overridden += 1;
+                return false;
+            }
+            if !has_year(&own, r) {
+                own.entry(r.well_name.to_string())
+                    .or_default()
+                    .insert(r.year_sheet);
+            }
+            true
+        })?;
+        for (well, years) in own {
+            covered.entry(well).or_default().extend(years);
+        }
+        years.extend(loaded.years);
+        wells.extend(loaded.wells);
+        violations.extend(loaded.violations.into_iter().map(|mut v| {
+            v.sheet = format!("{}: {}", name, v.sheet);
+            v
+        }));
+        warnings.extend(loaded.warnings.into_iter().map(|w| WellDataError::InFile {
+            file: name.clone(),
+            source: Box::new(w),
+        }));
+        if overridden > 0 {
+            warnings.push(WellDataError::InFile {
+                file: name.clone(),
+                source: Box::new(WellDataError::Warning(format!(
+                    "{} записей не взято - эти годы скважин есть в файле с более высоким приоритетом",
+                    overridden
+                ))),
+            });
+        }
+        units.extend(loaded.units);
+    }
+    records.finish()?;
+    Ok(LoadedData {
+        records,
+        years: years.into_iter().collect(),
+        wells: wells.into_iter().collect(),
+        violations,
+        warnings,
+        units,
+    })
+}
@@ -0,0 +1,102 @@
+use crate::LoaderMessage;
+use crate::error::WellDataError;
+use crate::tasks::TaskResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const AUDIT_FILE_NAME: &str = "audit.jsonl";
+
+// Запись журнала операций: кто, когда, что сделал с каким файлом
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditEntry {
+    pub time: String,
+    pub user: String,
+    pub action: String,
+    pub file: String,
+    pub wells: Vec<String>,
+    // Настройки операции в том виде, в каком они ушли в задачу
+    pub options: String,
+    // "ok", текст ошибки или отмены
+    pub outcome: String,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str, user: String, file: &Path, wells: &HashSet<String>) -> Self {
+        let mut wells: Vec<String> = wells.iter().cloned().collect();
+        wells.sort();
+        Self {
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            user,
+            action: action.to_string(),
+            file: file.display().to_string(),
+            wells,
+            options: String::new(),
+            outcome: String::new(),
+        }
+    }
+
+    pub fn with_options(self, options: String) -> Self {
+        Self { options, ..self }
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(crate::APP_DIR_NAME).join(AUDIT_FILE_NAME))
+}
+
+// Файл только дописывается: по строке JSON на операцию
+pub fn append(entry: &AuditEntry) -> Result<(), String> {
+    let path = path().ok_or("не найдена папка настроек пользователя")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("не удалось создать {}: {}", dir.display(), e))?;
+    }
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| format!("не удалось записать {}: {}", path.display(), e))
+}
+
+// Итог задачи дописывается в журнал. Операция, которую не удалось записать
+// в журнал, считается неудачной
+pub fn record<E: Into<WellDataError>>(
+    mut entry: AuditEntry,
+    result: Result<LoaderMessage, E>,
+) -> TaskResult {
+    let result = result.map_err(Into::into);
+    entry.outcome = match &result {
+        Ok(_) => "ok".to_string(),
+        Err(e) => e.to_string(),
+    };
+    match append(&entry) {
+        Ok(()) => result,
+        Err(e) if result.is_ok() => Err(WellDataError::AuditLog(e)),
+        Err(_) => result,
+    }
+}
+
+// Файла еще нет - журнал пуст. Поврежденные строки пропускаются
+pub fn load() -> Result<Vec<AuditEntry>, String> {
+    let Some(path) = path().filter(|p| p.is_file()) else {
+        return Ok(Vec::new());
+    };
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("не удалось прочитать {}: {}", path.display(), e))?;
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+// Окно просмотра журнала
+#[derive(Default)]
+pub struct AuditPanel {
+    pub open: bool,
+    pub entries: Vec<AuditEntry>,
+}
@@ -0,0 +1,160 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use crate::rules::ValidationRules;
+use crate::store::{Dataset, RecordStore};
+use crate::{
+    ExportOptions, ImportOptions, LoadedData, LoaderMessage, read_excel_file, save_excel_file,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::sync_channel;
+use std::thread;
+
+// Пакетное задание. start_year/wells: None - все годы/все скважины файла
+pub struct BatchJob {
+    pub input_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub rules: ValidationRules,
+    pub import: ImportOptions,
+    pub options: ExportOptions,
+    pub start_year: Option<i32>,
+    pub wells: Option<HashSet<String>>,
+}
+
+struct FilteredFile {
+    source: PathBuf,
+    records: RecordStore,
+    start_year: i32,
+    wells: HashSet<String>,
+    units: BTreeMap<String, String>,
+}
+
+pub fn list_workbooks(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("xlsx"))
+                // Временные файлы Excel вида ~$name.xlsx
+                && !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with("~$"))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+// Конвейер: чтение -> фильтрация -> запись, стадии соединены каналами на один файл,
+// так что разбор следующего файла идет одновременно с записью предыдущего
+pub fn process_folder(
+    job: &BatchJob,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoaderMessage, WellDataError> {
+    let files = list_workbooks(&job.input_dir)?;
+    if files.is_empty() {
+        return Err(WellDataError::NoData(format!(
+            "В папке {} нет файлов xlsx",
+            job.input_dir.display()
+        )));
+    }
+    let total = files.len();
+
+    thread::scope(|s| -> Result<LoaderMessage, WellDataError> {
+        let (read_tx, read_rx) = sync_channel::<(PathBuf, LoadedData)>(1);
+        let (filter_tx, filter_rx) = sync_channel::<FilteredFile>(1);
+
+        let reader = s.spawn(move || -> Result<Vec<String>, WellDataError> {
+            let mut skipped = Vec::new();
+            for path in files {
+                cancel.check()?;
+                let data = match read_excel_file(&path, &job.rules, &job.import, &(), cancel) {
+                    Ok(data) => data,
+                    // Поврежденный или чужой файл в папке не останавливает пакет
+                    Err(e @ WellDataError::OpenFailed { .. }) => {
+                        skipped.push(e.to_string());
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                if read_tx.send((path, data)).is_err() {
+                    break;
+                }
+            }
+            Ok(skipped)
+        });
+
+        let filter = s.spawn(move || -> Result<(), WellDataError> {
+            for (source, data) in read_rx {
+                let start_year = job
+                    .start_year
+                    .or_else(|| data.years.first().copied())
+                    .unwrap_or(i32::MIN);
+                let wells: HashSet<String> = match &job.wells {
+                    Some(w) => w.clone(),
+                    None => data.wells.into_iter().collect(),
+                };
+                let records = data.records.select(start_year, &wells)?;
+
+                let filtered = FilteredFile {
+                    source,
+                    records,
+                    start_year,
+                    wells,
+                    units: data.units,
+                };
+                if filter_tx.send(filtered).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        let mut written = 0;
+        for (done, file) in filter_rx.into_iter().enumerate() {
+            cancel.check()?;
+            let name = file
+                .source
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            progress.report(
+                &format!("Файл {}/{}: {}", done + 1, total, name),
+                done,
+                total,
+            );
+            if file.records.is_empty() {
+                continue;
+            }
+
+            let out_path = job.output_dir.join(format!("{}_отчет.xlsx", name));
+            save_excel_file(
+                &out_path,
+                &Dataset::in_memory(file.records),
+                file.start_year,
+                &file.wells,
+                &ExportOptions {
+                    source: Some(file.source.clone()),
+                    source_units: file.units,
+                    ..job.options.clone()
+                },
+                progress,
+                cancel,
+            )?;
+            written += 1;
+        }
+
+        let skipped = reader
+            .join()
+            .map_err(|_| WellDataError::ThreadPanicked("чтения"))??;
+        filter
+            .join()
+            .map_err(|_| WellDataError::ThreadPanicked("фильтрации"))??;
+
+        let mut saved = format!("{} файлов в {}", written, job.output_dir.display());
+        if !skipped.is_empty() {
+            saved += &format!(", пропущено {}: {}", skipped.len(), skipped.join("; "));
+        }
+        Ok(LoaderMessage::Saved(saved))
+    })
+}
@@ -0,0 +1,265 @@
+use crate::add_sheet;
+use crate::summary::MonthRow;
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveDateTime, TimeDelta};
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// Замер действует до следующего. Перерыв длиннее трех обычных шагов считается
+// остановкой: замер перед ним действует один шаг
+const MAX_GAP_STEPS: i32 = 3;
+// Шаг замеров не больше месяца
+const MAX_SPACING_DAYS: i64 = 31;
+const MS_PER_DAY: f64 = 86_400_000.0;
+
+// Какие дебиты выводить на лист Rates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RateMode {
+    // Добыча за месяц / все дни месяца
+    #[default]
+    CalendarDay,
+    // Добыча за месяц / дни в работе по колонке Status
+    ProducingDay,
+    Both,
+}
+
+impl RateMode {
+    pub const ALL: [RateMode; 3] = [
+        RateMode::CalendarDay,
+        RateMode::ProducingDay,
+        RateMode::Both,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RateMode::CalendarDay => "Среднекалендарный",
+            RateMode::ProducingDay => "На день работы",
+            RateMode::Both => "Оба",
+        }
+    }
+
+    fn calendar(self) -> bool {
+        self != RateMode::ProducingDay
+    }
+
+    fn producing(self) -> bool {
+        self != RateMode::CalendarDay
+    }
+}
+
+// Замер скважины: дата в мс, дебит в сутки и работала ли скважина
+// (без колонки Status - считается, что работала)
+#[derive(Debug, Clone, Copy)]
+pub struct Sample<'a> {
+    pub well: &'a str,
+    pub date_ms: i64,
+    pub rate: f64,
+    pub producing: bool,
+}
+
+// Баланс скважины за месяц
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonthBalance {
+    pub volume: f64,
+    pub producing_days: f64,
+}
+
+impl MonthBalance {
+    pub fn producing_rate(&self) -> Option<f64> {
+        (self.producing_days > 0.0).then(|| self.volume / self.producing_days)
+    }
+}
+
+// Как MonthRow, но с балансом вместо значения
+pub type BalanceRow = ((i32, u32), Vec<Option<MonthBalance>>);
+
+pub fn days_in_month((year, month): (i32, u32)) -> f64 {
+    let first = NaiveDate::from_ymd_opt(year, month, 1);
+    let next = first.and_then(|d| d.checked_add_months(Months::new(1)));
+    match (first, next) {
+        (Some(first), Some(next)) => (next - first).num_days() as f64,
+        _ => 30.0,
+    }
+}
+
+fn next_month_start(date: NaiveDateTime) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(date.year(), date.month(), 1)?
+        .checked_add_months(Months::new(1))?
+        .and_hms_opt(0, 0, 0)
+}
+
+// Интервал [start, end), разбитый на границах месяцев: (месяц, дней в нем)
+pub fn split_months(start: NaiveDateTime, end: NaiveDateTime) -> Vec<((i32, u32), f64)> {
+    let mut parts = Vec::new();
+    let mut current = start;
+    while current < end {
+        let Some(boundary) = next_month_start(current) else {
+            break;
+        };
+        let until = end.min(boundary);
+        let days = (until - current).num_milliseconds() as f64 / MS_PER_DAY;
+        parts.push(((current.year(), current.month()), days));
+        current = until;
+    }
+    parts
+}
+
+// Обычный шаг замеров скважины (медиана); им же считается длительность
+// последнего замера
+pub fn typical_spacing(dates: &[NaiveDateTime]) -> TimeDelta {
+    let mut gaps: Vec<TimeDelta> = dates
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|gap| *gap > TimeDelta::zero())
+        .collect();
+    gaps.sort();
+    gaps.get(gaps.len() / 2)
+        .copied()
+        .unwrap_or(TimeDelta::days(1))
+        .min(TimeDelta::days(MAX_SPACING_DAYS))
+}
+
+// Добыча скважины по месяцам: дебит замера (в сутки), умноженный на время
+// до следующего замера, с разбиением интервала на границе месяцев.
+// Интервалы простоя и ремонта в добычу и дни работы не входят
+fn monthly_balances(points: &[(NaiveDateTime, f64, bool)]) -> BTreeMap<(i32, u32), MonthBalance> {
+    let dates: Vec<NaiveDateTime> = points.iter().map(|(d, _, _)| *d).collect();
+    let spacing = typical_spacing(&dates);
+    let mut balances: BTreeMap<(i32, u32), MonthBalance> = BTreeMap::new();
+    for (i, (start, rate, producing)) in points.iter().enumerate() {
+        let end = match points.get(i + 1) {
+            Some((next, _, _)) if *next - *start <= spacing * MAX_GAP_STEPS => *next,
+            _ => *start + spacing,
+        };
+        for (month, days) in split_months(*start, end) {
+            let balance = balances.entry(month).or_default();
+            if *producing {
+                balance.volume += rate * days;
+                balance.producing_days += days;
+            }
+        }
+    }
+    balances
+}
+
+// Балансы по месяцам для каждой скважины из wells.
+// series отсортированы по скважине и дате
+pub fn balances(series: &[Sample], wells: &[&str]) -> Vec<BalanceRow> {
+    let well_col: HashMap<&str, usize> = wells.iter().enumerate().map(|(i, w)| (*w, i)).collect();
+    let mut months: BTreeMap<(i32, u32), Vec<Option<MonthBalance>>> = BTreeMap::new();
+    for group in series.chunk_by(|a, b| a.well == b.well) {
+        let Some(&col) = well_col.get(group[0].well) else {
+            continue;
+        };
+        let points: Vec<(NaiveDateTime, f64, bool)> = group
+            .iter()
+            .filter_map(|s| {
+                let date = DateTime::from_timestamp_millis(s.date_ms)?.naive_utc();
+                Some((date, s.rate, s.producing))
+            })
+            .collect();
+        for (month, balance) in monthly_balances(&points) {
+            months
+                .entry(month)
+                .or_insert_with(|| vec![None; wells.len()])[col] = Some(balance);
+        }
+    }
+    months.into_iter().collect()
+}
+
+// Среднекалендарный дебит: добыча за месяц, деленная на все дни месяца
+pub fn monthly(series: &[Sample], wells: &[&str]) -> Vec<MonthRow> {
+    balances(series, wells)
+        .into_iter()
+        .map(|(month, per_well)| {
+            let rates = per_well
+                .iter()
+                .map(|b| b.map(|b| b.volume / days_in_month(month)))
+                .collect();
+            (month, rates)
+        })
+        .collect()
+}
+
+// Лист Rates: по каждой скважине и месяцу - добыча, дни работы и дебиты
+// в выбранном режиме. liquid и oil - балансы из frame::balances
+pub fn write_rates_sheet(
+    workbook: &mut Workbook,
+    constant_memory: bool,
+    liquid: &[BalanceRow],
+    oil: &[BalanceRow],
+    wells: &[&str],
+    mode: RateMode,
+) -> Result<(), XlsxError> {
+    let worksheet = add_sheet(workbook, constant_memory).set_name("Rates")?;
+    let bold = Format::new().set_bold();
+    let number = Format::new().set_num_format("0.00");
+
+    let mut headers = vec![
+        "Скважина",
+        "Месяц",
+        "Дней в месяце",
+        "Дней в работе",
+        "Добыча жидкости",
+        "Добыча нефти",
+    ];
+    if mode.calendar() {
+        headers.extend(["Жидкость, среднекалендарный", "Нефть, среднекалендарный"]);
+    }
+    if mode.producing() {
+        headers.extend(["Жидкость, на день работы", "Нефть, на день работы"]);
+    }
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *header, &bold)?;
+    }
+
+    let liquid: BTreeMap<(i32, u32), &Vec<Option<MonthBalance>>> =
+        liquid.iter().map(|(m, v)| (*m, v)).collect();
+    let oil: BTreeMap<(i32, u32), &Vec<Option<MonthBalance>>> =
+        oil.iter().map(|(m, v)| (*m, v)).collect();
+    let mut months: Vec<(i32, u32)> = liquid.keys().chain(oil.keys()).copied().collect();
+    months.sort();
+    months.dedup();
+
+    let mut row = 1;
+    for (i, well) in wells.iter().enumerate() {
+        for month in &months {
+            let liq = liquid.get(month).and_then(|v| v[i]);
+            let oil = oil.get(month).and_then(|v| v[i]);
+            if liq.is_none() && oil.is_none() {
+                continue;
+            }
+            let days = days_in_month(*month);
+            worksheet.write_string(row, 0, *well)?;
+            worksheet.write_string(row, 1, format!("{}-{:02}", month.0, month.1))?;
+            worksheet.write_number(row, 2, days)?;
+            // Дни работы - по ряду нефти, а без него по жидкости
+            if let Some(b) = oil.or(liq) {
+                worksheet.write_number_with_format(row, 3, b.producing_days, &number)?;
+            }
+            let mut col = 4;
+            let mut write = |value: Option<f64>| -> Result<(), XlsxError> {
+                if let Some(v) = value {
+                    worksheet.write_number_with_format(row, col, v, &number)?;
+                }
+                col += 1;
+                Ok(())
+            };
+            write(liq.map(|b| b.volume))?;
+            write(oil.map(|b| b.volume))?;
+            if mode.calendar() {
+                write(liq.map(|b| b.volume / days))?;
+                write(oil.map(|b| b.volume / days))?;
+            }
+            if mode.producing() {
+                write(liq.and_then(|b| b.producing_rate()))?;
+                write(oil.and_then(|b| b.producing_rate()))?;
+            }
+            row += 1;
+        }
+    }
+
+    worksheet.set_column_width(0, 20)?;
+    worksheet.set_freeze_panes(1, 2)?;
+    Ok(())
+}
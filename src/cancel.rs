@@ -0,0 +1,65 @@
+use crate::error::WellDataError;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+// Кооперативная отмена для функций ядра (чтение, запись, пакетная обработка).
+// Функции сами проверяют токен в точках отмены:
+//   чтение  - перед каждым листом и каждые 5000 строк;
+//   запись  - перед каждой скважиной, каждые 500 строк и перед сохранением на диск;
+//   пакет   - перед каждым файлом.
+// Между точками работа не прерывается, поэтому частично записанных файлов не бывает
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Relaxed);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Точка отмены: возвращает ошибку Cancelled, если отмена запрошена
+    pub fn check(&self) -> Result<(), WellDataError> {
+        if self.is_cancelled() {
+            Err(WellDataError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Для async-задач: завершается, когда запрошена отмена
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    // Ожидание сети в async-задаче: ответа можно ждать минутами, поэтому
+    // ожидание прерывается отменой сразу, а не на следующей точке отмены
+    pub async fn or_cancel<T>(&self, future: impl Future<Output = T>) -> Result<T, WellDataError> {
+        tokio::select! {
+            value = future => Ok(value),
+            _ = self.cancelled() => Err(WellDataError::Cancelled),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::store::RecordRef;
+use serde::{Deserialize, Serialize};
+
+// Какие проверки помечать в колонке Flags при экспорте
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalyChecks {
+    pub outliers: bool,
+    pub outlier_threshold: f64,
+    pub gaps: bool,
+    pub gap_days: i64,
+    // Заливка строки после пропуска на листе скважины
+    pub gap_fill: bool,
+    pub validation: bool,
+}
+
+impl Default for AnomalyChecks {
+    fn default() -> Self {
+        Self {
+            outliers: false,
+            outlier_threshold: 3.5,
+            gaps: false,
+            gap_days: 3,
+            gap_fill: false,
+            validation: false,
+        }
+    }
+}
+
+impl AnomalyChecks {
+    pub fn any_enabled(&self) -> bool {
+        self.outliers || self.gaps || self.validation
+    }
+}
+
+// Флаги для записей одной скважины, записи должны быть отсортированы по дате
+pub fn well_flags(records: &[RecordRef], checks: &AnomalyChecks) -> Vec<Vec<&'static str>> {
+    let mut flags = vec![Vec::new(); records.len()];
+
+    if checks.outliers {
+        let liq = outlier_mask(records, |r| r.pd_liq, checks.outlier_threshold);
+        let oil = outlier_mask(records, |r| r.pd_oil, checks.outlier_threshold);
+        for (i, f) in flags.iter_mut().enumerate() {
+            if liq[i] || oil[i] {
+                f.push("OUTLIER");
+            }
+        }
+    }
+
+    if checks.gaps {
+        for i in gap_rows(records, checks.gap_days) {
+            flags[i].push("GAP_BEFORE");
+        }
+    }
+
+    if checks.validation {
+        for (i, r) in records.iter().enumerate() {
+            if r.out_of_bounds {
+                flags[i].push("OUT_OF_BOUNDS");
+            }
+        }
+    }
+
+    flags
+}
+
+// Записи, перед которыми больше gap_days дней без данных
+pub fn gap_rows(records: &[RecordRef], gap_days: i64) -> Vec<usize> {
+    (1..records.len())
+        .filter(|&i| match (records[i - 1].date, records[i].date) {
+            (Some(prev), Some(cur)) => (cur - prev).num_days() > gap_days,
+            _ => false,
+        })
+        .collect()
+}
+
+// Робастный z-score по медиане и MAD, устойчив к самим выбросам
+fn outlier_mask(
+    records: &[RecordRef],
+    value: impl Fn(&RecordRef) -> Option<f64>,
+    threshold: f64,
+) -> Vec<bool> {
+    let mut values: Vec<f64> = records.iter().filter_map(&value).collect();
+    let mut mask = vec![false; records.len()];
+    if values.len() < 3 {
+        return mask;
+    }
+
+    let med = median(&mut values);
+    let mut deviations: Vec<f64> = values.iter().map(|v| (v - med).abs()).collect();
+    let mad = median(&mut deviations);
+    if mad == 0.0 {
+        return mask;
+    }
+
+    for (i, r) in records.iter().enumerate() {
+        if let Some(v) = value(r) {
+            mask[i] = 0.6745 * (v - med).abs() / mad > threshold;
+        }
+    }
+    mask
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
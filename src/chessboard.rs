@@ -0,0 +1,188 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::frame::{DATE, PD_LIQ, PD_OIL, WELL};
+use crate::locked;
+use crate::progress::ProgressSink;
+use crate::store::Dataset;
+use crate::{ExportOptions, LoaderMessage};
+use chrono::{Datelike, NaiveDate};
+use polars::prelude::*;
+use rust_xlsxwriter::{Format, FormatAlign, FormatBorder, Workbook};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+const MONTH_NAMES: [&str; 12] = [
+    "Январь",
+    "Февраль",
+    "Март",
+    "Апрель",
+    "Май",
+    "Июнь",
+    "Июль",
+    "Август",
+    "Сентябрь",
+    "Октябрь",
+    "Ноябрь",
+    "Декабрь",
+];
+
+// Строки скважины на листе: нефть и жидкость
+const ROWS: [&str; 2] = ["Нефть, т/сут", "Жидкость, м3/сут"];
+
+// Суточные значения скважины за месяц: [день - 1] -> [нефть, жидкость]
+type DailyGrid = Vec<[Option<f64>; 2]>;
+// (год, месяц) -> скважина -> сетка по дням
+type MonthGrids = BTreeMap<(i32, u32), BTreeMap<String, DailyGrid>>;
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    };
+    next.and_then(|d| d.pred_opt()).map_or(31, |d| d.day())
+}
+
+// Несколько замеров за сутки усредняются
+fn daily_values(frame: &DataFrame) -> PolarsResult<MonthGrids> {
+    let grouped = frame
+        .clone()
+        .lazy()
+        .filter(col(DATE).is_not_null())
+        .group_by([
+            col(DATE).dt().year().alias("year"),
+            col(DATE).dt().month().cast(DataType::Int32).alias("month"),
+            col(DATE).dt().day().cast(DataType::Int32).alias("day"),
+            col(WELL),
+        ])
+        .agg([
+            col(PD_OIL).mean().cast(DataType::Float64).alias("oil"),
+            col(PD_LIQ).mean().cast(DataType::Float64).alias("liquid"),
+        ])
+        .collect()?;
+
+    let years = grouped.column("year")?.i32()?;
+    let months = grouped.column("month")?.i32()?;
+    let days = grouped.column("day")?.i32()?;
+    let wells = grouped.column(WELL)?.str()?;
+    let oil = grouped.column("oil")?.f64()?;
+    let liquid = grouped.column("liquid")?.f64()?;
+
+    let mut result = MonthGrids::new();
+    for i in 0..grouped.height() {
+        let (Some(year), Some(month), Some(day), Some(well)) =
+            (years.get(i), months.get(i), days.get(i), wells.get(i))
+        else {
+            continue;
+        };
+        let grid = result
+            .entry((year, month as u32))
+            .or_default()
+            .entry(well.to_string())
+            .or_insert_with(|| vec![[None, None]; 31]);
+        grid[day as usize - 1] = [oil.get(i), liquid.get(i)];
+    }
+    Ok(result)
+}
+
+// «Шахматка»: лист на каждый месяц, строки - скважины (нефть и жидкость),
+// колонки - дни месяца, в конце итог за месяц
+pub fn save_chessboard_file(
+    path: &Path,
+    data: &Dataset,
+    start_year: i32,
+    selected_wells: &HashSet<String>,
+    options: &ExportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoaderMessage, WellDataError> {
+    progress.report("Подготовка данных...", 0, 0);
+    let selected = options.drop_excluded(data.select(start_year, selected_wells)?);
+    cancel.check()?;
+    let months = daily_values(&selected.to_frame()?)?;
+    if months.is_empty() {
+        return Err(WellDataError::NoData(
+            "нет записей с датами для шахматки".to_string(),
+        ));
+    }
+
+    let mut workbook = Workbook::new();
+    let title = Format::new().set_bold().set_font_size(12);
+    let header = Format::new()
+        .set_bold()
+        .set_align(FormatAlign::Center)
+        .set_border(FormatBorder::Thin)
+        .set_background_color("#DDEBF7");
+    let cell = Format::new()
+        .set_num_format("0.0")
+        .set_border(FormatBorder::Thin);
+    let total = cell.clone().set_bold();
+    let name = Format::new().set_border(FormatBorder::Thin);
+
+    let month_count = months.len();
+    for (month_idx, ((year, month), wells)) in months.iter().enumerate() {
+        cancel.check()?;
+        let month_name = MONTH_NAMES[*month as usize - 1];
+        progress.report(
+            &format!("Лист {} {}", month_name, year),
+            month_idx,
+            month_count,
+        );
+
+        let days = days_in_month(*year, *month);
+        let worksheet = workbook
+            .add_worksheet()
+            .set_name(format!("{} {}", month_name, year))?;
+        worksheet.write_string_with_format(
+            0,
+            0,
+            format!(
+                "Суточная добыча за {} {} г.",
+                month_name.to_lowercase(),
+                year
+            ),
+            &title,
+        )?;
+        worksheet.write_string_with_format(1, 0, "Скважина", &header)?;
+        worksheet.write_string_with_format(1, 1, "Параметр", &header)?;
+        for day in 1..=days {
+            worksheet.write_number_with_format(1, 1 + day as u16, day, &header)?;
+        }
+        let total_col = 2 + days as u16;
+        worksheet.write_string_with_format(1, total_col, "Итого", &header)?;
+
+        let mut row = 2;
+        for (well, grid) in wells {
+            for (param, label) in ROWS.iter().enumerate() {
+                worksheet.write_string_with_format(row, 0, well, &name)?;
+                worksheet.write_string_with_format(row, 1, *label, &name)?;
+                let mut sum = 0.0;
+                for (day, values) in grid.iter().take(days as usize).enumerate() {
+                    let col = 2 + day as u16;
+                    match values[param] {
+                        Some(v) => {
+                            sum += v;
+                            worksheet.write_number_with_format(row, col, v, &cell)?;
+                        }
+                        None => {
+                            worksheet.write_blank(row, col, &cell)?;
+                        }
+                    }
+                }
+                worksheet.write_number_with_format(row, total_col, sum, &total)?;
+                row += 1;
+            }
+        }
+
+        worksheet.set_column_width(0, 16)?;
+        worksheet.set_column_width(1, 18)?;
+        worksheet.set_column_range_width(2, total_col - 1, 6)?;
+        worksheet.set_column_width(total_col, 10)?;
+        worksheet.set_freeze_panes(2, 2)?;
+    }
+
+    cancel.check()?;
+    progress.report("Сохранение файла на диск...", 1, 1);
+    locked::save_workbook(&mut workbook, path)?;
+    Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
+}
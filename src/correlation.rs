@@ -0,0 +1,261 @@
+use crate::LoaderMessage;
+use crate::dashboard::SelectionKey;
+use crate::error::WellDataError;
+use crate::store::Dataset;
+use crate::tasks::{TaskKind, TaskManager};
+use chrono::NaiveDate;
+use eframe::egui;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+// Меньше общих дней - коэффициент не считается
+const MIN_OVERLAP: usize = 10;
+
+// Попарные коэффициенты Пирсона по суточным PdOil
+#[derive(Debug, Default)]
+pub struct CorrelationMatrix {
+    pub wells: Vec<String>,
+    // values[i][j] - корреляция скважин i и j, None - мало общих дней
+    pub values: Vec<Vec<Option<f64>>>,
+    pub overlap: Vec<Vec<usize>>,
+}
+
+// Общая сетка - сутки: несколько замеров за день усредняются,
+// дни без замера у одной из скважин пары не учитываются
+fn daily_series(
+    data: &Dataset,
+    start_year: i32,
+    wells: &HashSet<String>,
+) -> Result<BTreeMap<String, BTreeMap<NaiveDate, f64>>, WellDataError> {
+    let selected = data.select(start_year, wells)?;
+    let mut sums: BTreeMap<String, BTreeMap<NaiveDate, (f64, usize)>> = BTreeMap::new();
+    for r in selected.iter() {
+        let (Some(date), Some(oil)) = (r.date, r.pd_oil) else {
+            continue;
+        };
+        let day = sums
+            .entry(r.well_name.to_string())
+            .or_default()
+            .entry(date.date())
+            .or_default();
+        day.0 += oil;
+        day.1 += 1;
+    }
+    Ok(sums
+        .into_iter()
+        .map(|(well, days)| {
+            let means = days
+                .into_iter()
+                .map(|(day, (sum, n))| (day, sum / n as f64))
+                .collect();
+            (well, means)
+        })
+        .collect())
+}
+
+fn pearson(a: &BTreeMap<NaiveDate, f64>, b: &BTreeMap<NaiveDate, f64>) -> (Option<f64>, usize) {
+    let pairs: Vec<(f64, f64)> = a
+        .iter()
+        .filter_map(|(day, x)| Some((*x, *b.get(day)?)))
+        .collect();
+    let n = pairs.len();
+    if n < MIN_OVERLAP {
+        return (None, n);
+    }
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n as f64;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n as f64;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in &pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x) * (x - mean_x);
+        var_y += (y - mean_y) * (y - mean_y);
+    }
+    // Постоянный дебит ни с чем не коррелирует
+    if var_x <= 0.0 || var_y <= 0.0 {
+        return (None, n);
+    }
+    (Some(cov / (var_x * var_y).sqrt()), n)
+}
+
+pub fn compute(
+    data: &Dataset,
+    start_year: i32,
+    wells: &HashSet<String>,
+) -> Result<CorrelationMatrix, WellDataError> {
+    let series = daily_series(data, start_year, wells)?;
+    let names: Vec<String> = series.keys().cloned().collect();
+    let columns: Vec<&BTreeMap<NaiveDate, f64>> = series.values().collect();
+    let n = names.len();
+    let mut values = vec![vec![None; n]; n];
+    let mut overlap = vec![vec![0; n]; n];
+    for i in 0..n {
+        for j in i..n {
+            let (r, days) = pearson(columns[i], columns[j]);
+            values[i][j] = r;
+            values[j][i] = r;
+            overlap[i][j] = days;
+            overlap[j][i] = days;
+        }
+    }
+    Ok(CorrelationMatrix {
+        wells: names,
+        values,
+        overlap,
+    })
+}
+
+// Окно "Корреляция скважин": считается по кнопке для текущего выбора
+#[derive(Debug, Default)]
+pub struct CorrelationPanel {
+    pub open: bool,
+    // Выбор, который считается сейчас
+    pending: Option<SelectionKey>,
+    matrix: Option<CorrelationMatrix>,
+    error: Option<String>,
+    // Строки и колонки по убыванию корреляции с этой скважиной; None - по имени
+    sort_by: Option<usize>,
+}
+
+fn cell_color(r: f64) -> egui::Color32 {
+    let t = (r.abs().clamp(0.0, 1.0) * 160.0) as u8;
+    if r >= 0.0 {
+        egui::Color32::from_rgba_unmultiplied(0, 170, 0, t)
+    } else {
+        egui::Color32::from_rgba_unmultiplied(220, 50, 50, t)
+    }
+}
+
+impl CorrelationPanel {
+    // Матрица - попарно по всем скважинам, поэтому считается фоновой задачей;
+    // до ответа видна прежняя
+    pub fn recompute(
+        &mut self,
+        tasks: &mut TaskManager,
+        data: &Arc<Dataset>,
+        generation: u64,
+        start_year: i32,
+        wells: &HashSet<String>,
+    ) {
+        let key = SelectionKey::new(generation, start_year, wells);
+        if self.pending.as_ref() == Some(&key) {
+            return;
+        }
+        let (data, wells, request) = (Arc::clone(data), wells.clone(), key.clone());
+        tasks.spawn(
+            TaskKind::Chart,
+            "Корреляция скважин".to_string(),
+            move |_tx, _cancel| {
+                let matrix = compute(&data, start_year, &wells).map_err(|e| e.to_string());
+                Ok(LoaderMessage::Correlation(request, matrix))
+            },
+        );
+        self.pending = Some(key);
+    }
+
+    // Ответ фоновой задачи; устаревший не показывается
+    pub fn finish(&mut self, key: SelectionKey, matrix: Result<CorrelationMatrix, String>) {
+        if self.pending.as_ref() != Some(&key) {
+            return;
+        }
+        self.pending = None;
+        self.sort_by = None;
+        match matrix {
+            Ok(matrix) => {
+                self.matrix = Some(matrix);
+                self.error = None;
+            }
+            Err(e) => {
+                self.matrix = None;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn order(&self, matrix: &CorrelationMatrix) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..matrix.wells.len()).collect();
+        if let Some(key) = self.sort_by {
+            let row = &matrix.values[key];
+            // Сама скважина первой, скважины без коэффициента - в конце
+            order.sort_by(|a, b| {
+                (*b == key).cmp(&(*a == key)).then_with(|| {
+                    row[*b]
+                        .unwrap_or(f64::MIN)
+                        .total_cmp(&row[*a].unwrap_or(f64::MIN))
+                })
+            });
+        }
+        order
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if self.pending.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Пересчет...");
+            });
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, format!("Не удалось посчитать: {}", e));
+            return;
+        }
+        let Some(matrix) = &self.matrix else {
+            ui.label("Отметьте скважины и нажмите \"Посчитать\"");
+            return;
+        };
+        if matrix.wells.len() < 2 {
+            ui.label("Нужны хотя бы две скважины с дебитом нефти за период");
+            return;
+        }
+        let order = self.order(matrix);
+        let mut clicked = None;
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("correlation")
+                .striped(false)
+                .spacing([4.0, 2.0])
+                .show(ui, |ui| {
+                    if ui
+                        .selectable_label(self.sort_by.is_none(), "Скважина")
+                        .on_hover_text("По имени")
+                        .clicked()
+                    {
+                        clicked = Some(None);
+                    }
+                    for &j in &order {
+                        if ui
+                            .selectable_label(self.sort_by == Some(j), &matrix.wells[j])
+                            .on_hover_text("Упорядочить по корреляции с этой скважиной")
+                            .clicked()
+                        {
+                            clicked = Some(Some(j));
+                        }
+                    }
+                    ui.end_row();
+                    for &i in &order {
+                        ui.label(&matrix.wells[i]);
+                        for &j in &order {
+                            let days = matrix.overlap[i][j];
+                            match matrix.values[i][j] {
+                                Some(r) => {
+                                    let text = egui::RichText::new(format!("{:+.2}", r))
+                                        .monospace()
+                                        .background_color(cell_color(r));
+                                    ui.label(text)
+                                        .on_hover_text(format!("Общих дней: {}", days));
+                                }
+                                None => {
+                                    ui.weak("—").on_hover_text(format!(
+                                        "Общих дней: {} (нужно от {})",
+                                        days, MIN_OVERLAP
+                                    ));
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(sort_by) = clicked {
+            self.sort_by = sort_by;
+        }
+    }
+}
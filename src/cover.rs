@@ -0,0 +1,122 @@
+use crate::error::WellDataError;
+use crate::store::RecordRef;
+use crate::{ExportOptions, add_sheet};
+use rust_xlsxwriter::{DocProperties, ExcelDateTime, Format, Workbook, XlsxError};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::Path;
+
+// Заголовок по умолчанию, если в настройках он не задан
+pub const DEFAULT_TITLE: &str = "Отчет по скважинам";
+
+// Контрольная сумма исходного файла: по ней архивный отчет сверяется с источником
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Автор из настроек, иначе - пользователь системы
+pub fn author(configured: &str) -> String {
+    let author = configured.trim();
+    if !author.is_empty() {
+        return author.to_string();
+    }
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_default()
+}
+
+fn report_title(options: &ExportOptions) -> &str {
+    match options.report_title.trim() {
+        "" => DEFAULT_TITLE,
+        title => title,
+    }
+}
+
+// Время формирования отчета. В воспроизводимом режиме его нет,
+// чтобы одинаковые данные и настройки давали побайтно одинаковый файл
+pub fn generated_at(options: &ExportOptions) -> Option<chrono::DateTime<chrono::Local>> {
+    (!options.deterministic).then(chrono::Local::now)
+}
+
+// Свойства книги для архива документов. Дата создания - момент сохранения,
+// в воспроизводимом режиме - постоянная
+pub fn doc_properties(options: &ExportOptions) -> Result<DocProperties, XlsxError> {
+    let mut properties = DocProperties::new()
+        .set_title(report_title(options))
+        .set_author(author(&options.author))
+        .set_custom_property("Версия программы", env!("CARGO_PKG_VERSION"));
+    if options.deterministic {
+        properties = properties.set_creation_datetime(&ExcelDateTime::from_ymd(2000, 1, 1)?);
+    }
+    Ok(match &options.source {
+        Some(path) => {
+            properties.set_custom_property("Исходный файл", path.display().to_string().as_str())
+        }
+        None => properties,
+    })
+}
+
+// Титульный лист: заголовок и сведения о выгрузке, первым листом книги
+pub fn write_cover_sheet(
+    workbook: &mut Workbook,
+    constant_memory: bool,
+    options: &ExportOptions,
+    wells: usize,
+    records: &[RecordRef],
+) -> Result<(), WellDataError> {
+    let (source, hash) = match &options.source {
+        Some(path) => (
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            // Источник могли переместить или удалить после загрузки - отчет
+            // все равно сохраняется
+            file_sha256(path).unwrap_or_else(|_| "недоступен".to_string()),
+        ),
+        None => (String::new(), String::new()),
+    };
+    let first = records.iter().filter_map(|r| r.date).min();
+    let last = records.iter().filter_map(|r| r.date).max();
+    let period = match (first, last) {
+        (Some(first), Some(last)) => {
+            format!("{} - {}", first.format("%d.%m.%Y"), last.format("%d.%m.%Y"))
+        }
+        _ => String::new(),
+    };
+
+    let rows = [
+        ("Автор", author(&options.author)),
+        ("Исходный файл", source),
+        ("SHA-256", hash),
+        ("Период", period),
+        ("Скважин", wells.to_string()),
+        ("Записей", records.len().to_string()),
+        ("Единицы дебита", options.rate_units.label().to_string()),
+        (
+            "Сформирован",
+            generated_at(options)
+                .map(|t| t.format("%d.%m.%Y %H:%M:%S").to_string())
+                .unwrap_or_default(),
+        ),
+    ];
+
+    let worksheet = add_sheet(workbook, constant_memory).set_name("Cover")?;
+    let bold = Format::new().set_bold();
+    worksheet.write_string_with_format(
+        0,
+        0,
+        report_title(options),
+        &bold.clone().set_font_size(16),
+    )?;
+    for (i, (label, value)) in rows.iter().enumerate() {
+        let row = i as u32 + 2;
+        worksheet.write_string_with_format(row, 0, *label, &bold)?;
+        worksheet.write_string(row, 1, value)?;
+    }
+    worksheet.set_column_width(0, 18)?;
+    worksheet.set_column_width(1, 70)?;
+    Ok(())
+}
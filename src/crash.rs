@@ -0,0 +1,76 @@
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const CRASH_FILE_NAME: &str = "crash.txt";
+// Отчет, уже показанный пользователю, остается рядом для поддержки
+pub const LAST_CRASH_FILE_NAME: &str = "crash.last.txt";
+// Сколько последних сообщений строки состояния попадает в отчет
+const RECENT_MESSAGES: usize = 20;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+fn dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(crate::APP_DIR_NAME))
+}
+
+// Сообщение для отчета о падении; вызывается из истории сообщений
+pub fn remember(text: &str) {
+    let Ok(mut recent) = RECENT.lock() else {
+        return;
+    };
+    if recent.len() == RECENT_MESSAGES {
+        recent.pop_front();
+    }
+    recent.push_back(format!(
+        "{} {}",
+        chrono::Local::now().format("%H:%M:%S"),
+        text
+    ));
+}
+
+// Паника в окне программы закрывает его без следа - пишем отчет в папку настроек,
+// при следующем запуске он будет показан. Паника фоновой задачи окно не роняет
+// и приходит в интерфейс обычной ошибкой задачи, поэтому отчет пишется только для
+// основного потока
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("main") {
+            let mut report = format!(
+                "Программа: {} {}\nВремя: {}\nОшибка: {}\n",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION"),
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                info
+            );
+            // Мьютекс мог остаться занятым паникующим потоком - тогда без сообщений
+            if let Ok(recent) = RECENT.try_lock() {
+                report += "\nПоследние сообщения:\n";
+                for message in recent.iter() {
+                    report += &format!("  {}\n", message);
+                }
+            }
+            report += &format!("\nСтек вызовов:\n{}\n", Backtrace::force_capture());
+            if let Some(dir) = dir() {
+                let _ = std::fs::create_dir_all(&dir);
+                let _ = std::fs::write(dir.join(CRASH_FILE_NAME), report);
+            }
+        }
+        default_hook(info);
+    }));
+}
+
+// Отчет о прошлом падении, если он есть. Файл переименовывается, чтобы
+// окно не появлялось при каждом запуске
+pub fn take_report() -> Option<(PathBuf, String)> {
+    let dir = dir()?;
+    let path = dir.join(CRASH_FILE_NAME);
+    let text = std::fs::read_to_string(&path).ok()?;
+    let last = dir.join(LAST_CRASH_FILE_NAME);
+    match std::fs::rename(&path, &last) {
+        Ok(()) => Some((last, text)),
+        Err(_) => Some((path, text)),
+    }
+}
@@ -0,0 +1,567 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::header::{self, Header};
+use crate::missing::{MissingStats, NumberCell};
+use crate::progress::ProgressSink;
+use crate::rules::{ValidationRules, Violation, ViolationKind};
+use crate::status::WellStatus;
+use crate::store::{Dataset, RecordRef};
+use crate::timing::Timings;
+use crate::well_type::WellType;
+use crate::year_sheet::{self, month_number};
+use crate::{
+    CHOKE_COL, FREQUENCY_COL, INJECTION_COL, ImportOptions, LoadedData, NAME_COL, P_BOTTOM_COL,
+    P_HEAD_COL, STATUS_COL, TEMPERATURE_COL, TYPE_COL,
+};
+use calamine::{Data, Reader, Xlsx};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use encoding_rs::{UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1251};
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    Utf8,
+    // Выгрузки старых систем
+    #[default]
+    Windows1251,
+    // "Юникод" из Excel (Сохранить как -> Текст Юникод)
+    Utf16Le,
+    Utf16Be,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 4] = [
+        TextEncoding::Utf8,
+        TextEncoding::Windows1251,
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16Be,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Windows1251 => "Windows-1251",
+            TextEncoding::Utf16Le => "UTF-16 LE",
+            TextEncoding::Utf16Be => "UTF-16 BE",
+        }
+    }
+
+    // По BOM, затем по нулевым байтам (UTF-16) и корректности UTF-8.
+    // Все остальное считаем cp1251 - других однобайтовых кодировок у нас не бывает
+    pub fn detect(bytes: &[u8]) -> TextEncoding {
+        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return TextEncoding::Utf8;
+        }
+        if bytes.starts_with(&[0xFF, 0xFE]) {
+            return TextEncoding::Utf16Le;
+        }
+        if bytes.starts_with(&[0xFE, 0xFF]) {
+            return TextEncoding::Utf16Be;
+        }
+
+        let pairs = bytes.len() / 2;
+        if pairs > 0 {
+            let zeros_even = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+            let zeros_odd = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+            // В UTF-16 у латиницы и цифр старший байт нулевой
+            if zeros_odd * 3 > pairs && zeros_even * 10 < pairs {
+                return TextEncoding::Utf16Le;
+            }
+            if zeros_even * 3 > pairs && zeros_odd * 10 < pairs {
+                return TextEncoding::Utf16Be;
+            }
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(_) => TextEncoding::Utf8,
+            // Образец мог оборваться посреди символа - это не ошибка кодировки
+            Err(e) if e.error_len().is_none() => TextEncoding::Utf8,
+            Err(_) => TextEncoding::Windows1251,
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let encoding = match self {
+            TextEncoding::Utf8 => UTF_8,
+            TextEncoding::Windows1251 => WINDOWS_1251,
+            TextEncoding::Utf16Le => UTF_16LE,
+            TextEncoding::Utf16Be => UTF_16BE,
+        };
+        // BOM в начале файла, если есть, главнее выбранной кодировки
+        encoding.decode(bytes).0.into_owned()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Delimiter {
+    #[default]
+    Semicolon,
+    Comma,
+    Tab,
+}
+
+impl Delimiter {
+    pub const ALL: [Delimiter; 3] = [Delimiter::Semicolon, Delimiter::Comma, Delimiter::Tab];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Delimiter::Semicolon => "; (точка с запятой)",
+            Delimiter::Comma => ", (запятая)",
+            Delimiter::Tab => "табуляция",
+        }
+    }
+
+    // Самый частый из разделителей в первой строке
+    pub fn detect(text: &str) -> Delimiter {
+        let header = text.lines().next().unwrap_or("");
+        Delimiter::ALL
+            .into_iter()
+            .max_by_key(|d| header.bytes().filter(|&b| b == d.byte()).count())
+            .unwrap_or_default()
+    }
+
+    pub fn byte(self) -> u8 {
+        match self {
+            Delimiter::Semicolon => b';',
+            Delimiter::Comma => b',',
+            Delimiter::Tab => b'\t',
+        }
+    }
+}
+
+// Настройки разбора текстовых файлов. Поля в кавычках ("...", "" внутри) поддерживаются всегда
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    pub encoding: TextEncoding,
+    pub delimiter: Delimiter,
+}
+
+pub const TEXT_EXTENSIONS: [&str; 3] = ["csv", "tsv", "txt"];
+
+pub fn is_text_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| TEXT_EXTENSIONS.iter().any(|t| ext.eq_ignore_ascii_case(t)))
+}
+
+// Начало файла для предпросмотра до загрузки: пользователь видит первые строки
+// в угаданной кодировке и может поправить ее и разделитель
+pub struct CsvPreview {
+    pub path: PathBuf,
+    pub options: CsvOptions,
+    sample: Vec<u8>,
+}
+
+impl CsvPreview {
+    const SAMPLE_BYTES: u64 = 64 * 1024;
+
+    pub fn open(path: PathBuf) -> std::io::Result<Self> {
+        let mut sample = Vec::new();
+        std::fs::File::open(&path)?
+            .take(Self::SAMPLE_BYTES)
+            .read_to_end(&mut sample)?;
+
+        let encoding = TextEncoding::detect(&sample);
+        let delimiter = Delimiter::detect(&encoding.decode(&sample));
+        Ok(Self {
+            path,
+            options: CsvOptions {
+                encoding,
+                delimiter,
+            },
+            sample,
+        })
+    }
+
+    // Строки образца после первых skip строк файла, как при загрузке
+    pub fn rows(&self, skip: usize, limit: usize) -> Vec<Vec<String>> {
+        let text = self.options.encoding.decode(&self.sample);
+        csv::ReaderBuilder::new()
+            .delimiter(self.options.delimiter.byte())
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(skip_lines(&text, skip).as_bytes())
+            .records()
+            .take(limit)
+            .filter_map(Result::ok)
+            .map(|r| r.iter().map(str::to_string).collect())
+            .collect()
+    }
+}
+
+// Текст после первых lines строк. Строка заголовка считается как в Excel:
+// пустые строки тоже идут в счет
+pub fn skip_lines(text: &str, lines: usize) -> &str {
+    let rest = text
+        .split_inclusive('\n')
+        .skip(lines)
+        .map(str::len)
+        .sum::<usize>();
+    &text[text.len() - rest..]
+}
+
+// Колонки те же, что и на листах Excel. Листов по годам нет,
+// поэтому год записи берется из даты; строки без даты пропускаются
+pub fn read_csv_file(
+    path: &Path,
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    progress.report("Открытие файла...", 0, 0);
+
+    let mut timings = Timings::new(format!("Загрузка {}", file_name));
+    let bytes = timings
+        .measure("Чтение файла", || {
+            import.retry.run("чтение файла", progress, cancel, || {
+                std::fs::read(path)
+            })
+        })
+        .map_err(|e| WellDataError::open_failed(path, e))?;
+    let text = timings.measure("Перекодировка", || {
+        import.csv.encoding.decode(&bytes)
+    });
+    drop(bytes);
+
+    let parse_start = std::time::Instant::now();
+    // Строки над заголовком пропускаются; header_row = 0 - заголовка нет
+    let has_headers = import.header_row > 0;
+    let skipped_lines = import.header_row.saturating_sub(1) as usize;
+    let body = skip_lines(&text, skipped_lines);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(import.csv.delimiter.byte())
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    let mut records = reader.records().peekable();
+
+    // Заголовок в несколько строк; строка единиц под ним - тоже заголовок
+    let mut header_rows: Vec<Vec<String>> = Vec::new();
+    if has_headers {
+        for record in records.by_ref().take(import.header_rows.max(1) as usize) {
+            header_rows.push(record?.iter().map(str::to_string).collect());
+        }
+        let units = match records.peek() {
+            Some(Ok(next)) => next.iter().map(str::to_string).collect(),
+            _ => Vec::new(),
+        };
+        if header::looks_like_units(&units) {
+            records.next();
+            header_rows.push(units);
+        }
+    }
+    let header = Header::parse(&header_rows);
+    let mut col_map = header.columns();
+    import.columns.apply(&mut col_map);
+
+    let mut violations = Vec::new();
+    let mut warnings = Vec::new();
+    for column in &rules.required_columns {
+        if !col_map.contains_key(column) {
+            violations.push(Violation {
+                kind: ViolationKind::MissingColumn,
+                sheet: file_name.clone(),
+                row: None,
+                well: None,
+                details: format!("Нет обязательной колонки '{}'", column),
+            });
+        }
+    }
+
+    let (Some(&idx_n), Some(&idx_d)) = (col_map.get(NAME_COL), col_map.get("Date")) else {
+        return Err(WellDataError::BadContent(format!(
+            "В файле нет колонок '{}' и 'Date'. Проверьте разделитель и кодировку",
+            NAME_COL
+        )));
+    };
+    let idx_liq = col_map.get("PdLiq").copied();
+    let idx_oil = col_map.get("PdOil").copied();
+    let idx_temp = col_map.get(TEMPERATURE_COL).copied();
+    let idx_p_bottom = col_map.get(P_BOTTOM_COL).copied();
+    let idx_p_head = col_map.get(P_HEAD_COL).copied();
+    let idx_frequency = col_map.get(FREQUENCY_COL).copied();
+    let idx_choke = col_map.get(CHOKE_COL).copied();
+    let idx_injection = col_map.get(INJECTION_COL).copied();
+    let idx_status = col_map.get(STATUS_COL).copied();
+    let idx_type = col_map.get(TYPE_COL).copied();
+    let bounded_cols: Vec<(&String, usize, &crate::rules::Bounds)> = rules
+        .bounds
+        .iter()
+        .filter_map(|(col, b)| col_map.get(col).map(|&idx| (col, idx, b)))
+        .collect();
+
+    let mut all_records = Dataset::new(import.storage)?;
+    let mut valid_years = BTreeSet::new();
+    let mut unique_wells = BTreeSet::new();
+    let mut skipped_without_date = 0usize;
+    let mut missing = MissingStats::default();
+    let total_bytes = text.len();
+
+    for (i, row) in records.enumerate() {
+        let row = row?;
+        if i % 5000 == 0 {
+            cancel.check()?;
+            let position = row.position().map_or(0, |p| p.byte()) as usize;
+            progress.report(
+                &format!("{}: обработка строк...", file_name),
+                position,
+                total_bytes,
+            );
+        }
+
+        let well_name = row.get(idx_n).unwrap_or("").trim().to_string();
+        if well_name.is_empty() {
+            continue;
+        }
+
+        let line = (skipped_lines + header.lines + i) as u32 + 1;
+        if rules.is_forbidden(&well_name) {
+            violations.push(Violation {
+                kind: ViolationKind::ForbiddenWell,
+                sheet: file_name.clone(),
+                row: Some(line),
+                well: Some(well_name),
+                details: "Запрещенное имя скважины, строка пропущена".to_string(),
+            });
+            continue;
+        }
+
+        let Some(date) = row
+            .get(idx_d)
+            .and_then(|s| parse_date_with(s, &import.date_format))
+        else {
+            skipped_without_date += 1;
+            continue;
+        };
+
+        let mut out_of_bounds = false;
+        for (col, idx, bounds) in &bounded_cols {
+            if let NumberCell::Value(v) = NumberCell::from_text(row.get(*idx).unwrap_or(""))
+                && let Some(problem) = bounds.check(v)
+            {
+                out_of_bounds = true;
+                violations.push(Violation {
+                    kind: ViolationKind::OutOfBounds,
+                    sheet: file_name.clone(),
+                    row: Some(line),
+                    well: Some(well_name.clone()),
+                    details: format!("{}: {}", col, problem),
+                });
+            }
+        }
+
+        let mut get_float = |idx: Option<usize>| {
+            let cell = idx.and_then(|i| row.get(i)).unwrap_or("");
+            missing.take(NumberCell::from_text(cell))
+        };
+        valid_years.insert(date.year());
+        all_records.push(RecordRef {
+            well_name: &well_name,
+            date: Some(date),
+            pd_liq: get_float(idx_liq),
+            pd_oil: get_float(idx_oil),
+            temperature: get_float(idx_temp),
+            p_bottom: get_float(idx_p_bottom),
+            p_head: get_float(idx_p_head),
+            frequency: get_float(idx_frequency),
+            choke: get_float(idx_choke),
+            injection: get_float(idx_injection),
+            status: idx_status
+                .and_then(|i| row.get(i))
+                .and_then(WellStatus::parse),
+            well_type: idx_type.and_then(|i| row.get(i)).and_then(WellType::parse),
+            year_sheet: date.year(),
+            quarter: None,
+            out_of_bounds,
+        })?;
+        unique_wells.insert(well_name);
+    }
+    timings.record("Разбор строк", parse_start);
+
+    if skipped_without_date > 0 {
+        warnings.push(WellDataError::Warning(format!(
+            "пропущено строк без даты: {}",
+            skipped_without_date
+        )));
+    }
+
+    warnings.extend(missing.warnings());
+
+    timings.measure("Завершение записи", || all_records.finish())?;
+    progress.timings(timings.finish());
+    Ok(LoadedData {
+        records: all_records,
+        years: valid_years.into_iter().collect(),
+        wells: unique_wells.into_iter().collect(),
+        violations,
+        warnings,
+        units: header.units_by_name(&col_map),
+    })
+}
+
+// Числа из русских выгрузок: запятая как десятичный разделитель, пробелы между разрядами.
+// NaN и inf из текста не числа - иначе испортят суммы и средние
+pub fn parse_number(s: &str) -> Option<f64> {
+    let cleaned: String = s
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| if c == ',' { '.' } else { c })
+        .collect();
+    if cleaned.is_empty() {
+        return None;
+    }
+    cleaned.parse().ok().filter(|v: &f64| v.is_finite())
+}
+
+// Сначала формат из профиля загрузки, затем обычные
+pub fn parse_date_with(s: &str, format: &str) -> Option<NaiveDateTime> {
+    let format = format.trim();
+    if !format.is_empty() {
+        let s = s.trim();
+        let parsed = NaiveDateTime::parse_from_str(s, format).ok().or_else(|| {
+            NaiveDate::parse_from_str(s, format)
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        });
+        if parsed.is_some() {
+            return parsed;
+        }
+    }
+    parse_date(s)
+}
+
+pub fn parse_date(s: &str) -> Option<NaiveDateTime> {
+    const DATE_TIME_FORMATS: [&str; 4] = [
+        "%d.%m.%Y %H:%M:%S",
+        "%d.%m.%Y %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ];
+    const DATE_FORMATS: [&str; 3] = ["%d.%m.%Y", "%Y-%m-%d", "%d/%m/%Y"];
+
+    let s = s.trim();
+    DATE_TIME_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|f| NaiveDate::parse_from_str(s, f).ok())
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .or_else(|| parse_russian_date(s))
+}
+
+// Даты из ручных таблиц: "5 марта 2021", "март 2021 г.", "мар. 21". Без числа -
+// первое число месяца. Месяц - только целое название или сокращение, год -
+// четыре цифры или две, как у листов, иначе датой стало бы "Мамонтовская 12"
+fn parse_russian_date(s: &str) -> Option<NaiveDateTime> {
+    let lower = s.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|w| w.trim_end_matches('.'))
+        .map(|w| match w.chars().next() {
+            Some(c) if c.is_ascii_digit() => w.trim_end_matches('г'),
+            _ => w,
+        })
+        .filter(|w| !w.is_empty() && !matches!(*w, "г" | "год" | "года"))
+        .collect();
+    let year = |word: &str| match word.len() {
+        2 | 4 if word.bytes().all(|b| b.is_ascii_digit()) => {
+            year_sheet::sheet_year(word, year_sheet::DEFAULT_PIVOT)
+        }
+        _ => None,
+    };
+    let (day, month, year) = match words.as_slice() {
+        [day, name, y] => (day.parse().ok()?, month_number(name)?, year(y)?),
+        [name, y] => (1, month_number(name)?, year(y)?),
+        _ => return None,
+    };
+    NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(0, 0, 0)
+}
+
+// Небольшая справочная таблица (координаты, журнал событий) из CSV/TXT
+// или с первого листа xlsx; все ячейки - текстом, первая строка - заголовок
+pub fn read_table(path: &Path) -> Result<Vec<Vec<String>>, String> {
+    if is_text_file(path) {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let text = TextEncoding::detect(&bytes).decode(&bytes);
+        return Ok(csv::ReaderBuilder::new()
+            .delimiter(Delimiter::detect(&text).byte())
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes())
+            .records()
+            .filter_map(Result::ok)
+            .map(|r| r.iter().map(str::to_string).collect())
+            .collect());
+    }
+    let mut workbook: Xlsx<_> =
+        calamine::open_workbook(path).map_err(|e: calamine::XlsxError| e.to_string())?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("в книге нет листов")?
+        .map_err(|e| e.to_string())?;
+    Ok(range
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|c| match c {
+                    Data::Float(f) => f.to_string(),
+                    Data::Int(i) => i.to_string(),
+                    Data::DateTime(d) => d
+                        .as_datetime()
+                        .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_default(),
+                    other => other.to_string(),
+                })
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: u32, day: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(0, 0, 0)
+    }
+
+    #[test]
+    fn russian_date_accepts_month_forms() {
+        assert_eq!(parse_russian_date("5 марта 2021"), day(2021, 3, 5));
+        assert_eq!(parse_russian_date("март 2021 г."), day(2021, 3, 1));
+        assert_eq!(parse_russian_date("Май 2020"), day(2020, 5, 1));
+        assert_eq!(parse_russian_date("12 мая 2020г"), day(2020, 5, 12));
+        assert_eq!(parse_russian_date("сент. 2019"), day(2019, 9, 1));
+        assert_eq!(parse_russian_date("1 января, 2022 года"), day(2022, 1, 1));
+    }
+
+    #[test]
+    fn russian_date_two_digit_year_uses_pivot() {
+        assert_eq!(parse_russian_date("мар. 21"), day(2021, 3, 1));
+        assert_eq!(parse_russian_date("янв 00"), day(2000, 1, 1));
+        assert_eq!(parse_russian_date("янв 49"), day(2049, 1, 1));
+        assert_eq!(parse_russian_date("янв 50"), day(1950, 1, 1));
+        assert_eq!(parse_russian_date("дек 99"), day(1999, 12, 1));
+    }
+
+    #[test]
+    fn russian_date_rejects_partial_names_and_odd_years() {
+        assert_eq!(parse_russian_date("Мамонтовская 12"), None);
+        assert_eq!(parse_russian_date("ма 2021"), None);
+        assert_eq!(parse_russian_date("мартовский 2021"), None);
+        assert_eq!(parse_russian_date("март 202"), None);
+        assert_eq!(parse_russian_date("март 20215"), None);
+        assert_eq!(parse_russian_date("31 февраля 2021"), None);
+        assert_eq!(parse_russian_date("2021"), None);
+    }
+}
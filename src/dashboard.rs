@@ -0,0 +1,304 @@
+use crate::error::WellDataError;
+use crate::frame;
+use crate::settings::{NumberLocale, RateUnits};
+use crate::store::Dataset;
+use crate::summary::{Aggregate, MonthRow};
+use crate::tasks::{TaskKind, TaskManager};
+use crate::{LoaderMessage, Parameter};
+use eframe::egui;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+const LIQUID_COLOR: egui::Color32 = egui::Color32::from_rgb(70, 130, 220);
+const OIL_COLOR: egui::Color32 = egui::Color32::from_rgb(200, 110, 30);
+
+// Показатели выбранных скважин за выбранный период
+#[derive(Debug, Default)]
+pub struct Kpi {
+    // Скважины, по которым есть записи
+    pub wells: usize,
+    pub records: usize,
+    // Сумма средних дебитов скважин
+    pub liquid: f64,
+    pub oil: f64,
+    // По месяцам: сумма среднемесячных дебитов скважин [жидкость, нефть]
+    pub trend: Vec<((i32, u32), [f64; 2])>,
+}
+
+impl Kpi {
+    pub fn water_cut(&self) -> Option<f64> {
+        (self.liquid > 0.0).then(|| (1.0 - self.oil / self.liquid) * 100.0)
+    }
+}
+
+// Что уже посчитано: загрузка, начальный год и выбор. Пересчет - только
+// когда что-то из этого поменялось
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectionKey {
+    // Номер загрузки данных, см. WellDataApp::generation
+    generation: u64,
+    start_year: i32,
+    wells: Vec<String>,
+}
+
+impl SelectionKey {
+    pub fn new(generation: u64, start_year: i32, selected: &HashSet<String>) -> Self {
+        let mut wells: Vec<String> = selected.iter().cloned().collect();
+        wells.sort();
+        Self {
+            generation,
+            start_year,
+            wells,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DashboardPanel {
+    pub open: bool,
+    // Выбор, по которому посчитаны показатели, и выбор, который считается сейчас
+    key: Option<SelectionKey>,
+    pending: Option<SelectionKey>,
+    kpi: Kpi,
+    error: Option<String>,
+}
+
+// Сумма по скважинам для каждого месяца
+fn month_totals(months: &[MonthRow]) -> BTreeMap<(i32, u32), f64> {
+    months
+        .iter()
+        .map(|(month, values)| (*month, values.iter().flatten().sum()))
+        .collect()
+}
+
+pub fn compute(
+    data: &Dataset,
+    start_year: i32,
+    wells: &HashSet<String>,
+) -> Result<Kpi, WellDataError> {
+    let selected = data.select(start_year, wells)?;
+    // скважина -> (сумма и число значений жидкости, то же для нефти)
+    let mut per_well: HashMap<&str, [(f64, usize); 2]> = HashMap::new();
+    for r in selected.iter() {
+        let sums = per_well.entry(r.well_name).or_default();
+        for (sum, value) in sums.iter_mut().zip([r.pd_liq, r.pd_oil]) {
+            if let Some(v) = value {
+                sum.0 += v;
+                sum.1 += 1;
+            }
+        }
+    }
+    let average = |i: usize| -> f64 {
+        per_well
+            .values()
+            .filter(|s| s[i].1 > 0)
+            .map(|s| s[i].0 / s[i].1 as f64)
+            .sum()
+    };
+
+    let mut names: Vec<&str> = per_well.keys().copied().collect();
+    names.sort();
+    let frame = selected.to_frame()?;
+    let liquid = month_totals(&frame::monthly(
+        &frame,
+        &names,
+        Parameter::PdLiq,
+        Aggregate::Average,
+    )?);
+    let oil = month_totals(&frame::monthly(
+        &frame,
+        &names,
+        Parameter::PdOil,
+        Aggregate::Average,
+    )?);
+    let mut months: Vec<(i32, u32)> = liquid.keys().chain(oil.keys()).copied().collect();
+    months.sort();
+    months.dedup();
+
+    Ok(Kpi {
+        wells: per_well.len(),
+        records: selected.len(),
+        liquid: average(0),
+        oil: average(1),
+        trend: months
+            .into_iter()
+            .map(|m| {
+                let liq = liquid.get(&m).copied().unwrap_or(0.0);
+                (m, [liq, oil.get(&m).copied().unwrap_or(0.0)])
+            })
+            .collect(),
+    })
+}
+
+impl DashboardPanel {
+    // Вызывается каждый кадр, пока окно открыто: пересчет запускается фоновой
+    // задачей, только если отметили или сняли скважину, сменили год или
+    // загрузили другой файл. До ответа видны прежние показатели
+    pub fn refresh(
+        &mut self,
+        tasks: &mut TaskManager,
+        data: &Arc<Dataset>,
+        generation: u64,
+        start_year: i32,
+        selected: &HashSet<String>,
+    ) {
+        let key = SelectionKey::new(generation, start_year, selected);
+        if self.pending.as_ref().or(self.key.as_ref()) == Some(&key) {
+            return;
+        }
+        let (data, wells, request) = (Arc::clone(data), selected.clone(), key.clone());
+        tasks.spawn(
+            TaskKind::Chart,
+            "Сводка по выборке".to_string(),
+            move |_tx, _cancel| {
+                let kpi = compute(&data, start_year, &wells).map_err(|e| e.to_string());
+                Ok(LoaderMessage::Dashboard(request, kpi))
+            },
+        );
+        self.pending = Some(key);
+    }
+
+    // Ответ фоновой задачи. Если выбор с тех пор снова поменялся, ответ устарел
+    pub fn finish(&mut self, key: SelectionKey, kpi: Result<Kpi, String>) {
+        if self.pending.as_ref() != Some(&key) {
+            return;
+        }
+        match kpi {
+            Ok(kpi) => {
+                self.kpi = kpi;
+                self.error = None;
+            }
+            Err(e) => {
+                self.kpi = Kpi::default();
+                self.error = Some(e);
+            }
+        }
+        self.key = self.pending.take();
+    }
+
+    pub fn show(&self, ui: &mut egui::Ui, units: RateUnits, locale: NumberLocale) {
+        if self.pending.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Пересчет...");
+            });
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, format!("Не удалось посчитать: {}", e));
+            return;
+        }
+        let kpi = &self.kpi;
+        if kpi.wells == 0 {
+            ui.label("Нет записей по выбранным скважинам за выбранный период");
+            return;
+        }
+        egui::Grid::new("kpi").num_columns(2).show(ui, |ui| {
+            ui.label("Скважин с данными:");
+            ui.strong(locale.count(kpi.wells));
+            ui.end_row();
+            ui.label("Записей:");
+            ui.strong(locale.count(kpi.records));
+            ui.end_row();
+            ui.colored_label(LIQUID_COLOR, "Дебит жидкости (сумма средних):");
+            ui.strong(format!(
+                "{} {}",
+                locale.number(kpi.liquid, Some(1)),
+                units.label()
+            ));
+            ui.end_row();
+            ui.colored_label(OIL_COLOR, "Дебит нефти (сумма средних):");
+            ui.strong(format!(
+                "{} {}",
+                locale.number(kpi.oil, Some(1)),
+                units.label()
+            ));
+            ui.end_row();
+            if let Some(water_cut) = kpi.water_cut() {
+                ui.label("Обводненность:");
+                ui.strong(format!("{} %", locale.number(water_cut, Some(1))));
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        show_trend(ui, &kpi.trend);
+    }
+}
+
+// График суммарных дебитов по месяцам: жидкость и нефть на одной оси от нуля
+fn show_trend(ui: &mut egui::Ui, trend: &[((i32, u32), [f64; 2])]) {
+    let (response, painter) = ui.allocate_painter(
+        ui.available_size().max(egui::vec2(300.0, 160.0)),
+        egui::Sense::hover(),
+    );
+    let rect = response.rect;
+    painter.rect_filled(rect, 4.0, ui.visuals().extreme_bg_color);
+    if trend.len() < 2 {
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "Для графика нужно хотя бы два месяца",
+            egui::FontId::proportional(12.0),
+            ui.visuals().weak_text_color(),
+        );
+        return;
+    }
+
+    let plot = rect.shrink2(egui::vec2(12.0, 18.0));
+    let max = trend
+        .iter()
+        .map(|(_, [liq, oil])| liq.max(*oil))
+        .fold(0.0, f64::max)
+        .max(1e-9);
+    let last = (trend.len() - 1) as f32;
+    let point = |i: usize, v: f64| {
+        egui::pos2(
+            plot.left() + plot.width() * i as f32 / last,
+            plot.bottom() - plot.height() * (v / max) as f32,
+        )
+    };
+    for (k, color) in [LIQUID_COLOR, OIL_COLOR].into_iter().enumerate() {
+        let line: Vec<egui::Pos2> = trend
+            .iter()
+            .enumerate()
+            .map(|(i, (_, values))| point(i, values[k]))
+            .collect();
+        painter.add(egui::Shape::line(line, egui::Stroke::new(1.5, color)));
+    }
+
+    let label = |(year, month): (i32, u32)| format!("{}-{:02}", year, month);
+    let font = egui::FontId::proportional(10.0);
+    let color = ui.visuals().weak_text_color();
+    painter.text(
+        egui::pos2(plot.left(), rect.bottom() - 2.0),
+        egui::Align2::LEFT_BOTTOM,
+        label(trend[0].0),
+        font.clone(),
+        color,
+    );
+    painter.text(
+        egui::pos2(plot.right(), rect.bottom() - 2.0),
+        egui::Align2::RIGHT_BOTTOM,
+        label(trend[trend.len() - 1].0),
+        font.clone(),
+        color,
+    );
+    painter.text(
+        egui::pos2(plot.left(), rect.top() + 2.0),
+        egui::Align2::LEFT_TOP,
+        format!("{:.0}", max),
+        font,
+        color,
+    );
+
+    if let Some(hover) = response.hover_pos() {
+        let i =
+            (((hover.x - plot.left()) / plot.width() * last).round() as usize).min(trend.len() - 1);
+        let (month, [liq, oil]) = trend[i];
+        response.on_hover_text_at_pointer(format!(
+            "{}\nЖидкость: {:.1}\nНефть: {:.1}",
+            label(month),
+            liq,
+            oil
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+use crate::csv_import::parse_date_with;
+use crate::error::WellDataError;
+use calamine::{Data, DataType};
+use chrono::NaiveDateTime;
+
+// Как были записаны даты в колонке Date: после ручной правки в одной колонке
+// встречаются и настоящие даты Excel, и текст
+#[derive(Debug, Default)]
+pub struct DateStats {
+    pub serial: usize,
+    pub text: usize,
+    pub failed: usize,
+}
+
+impl DateStats {
+    // Сначала значение даты Excel, затем разбор текста ячейки
+    pub fn read(&mut self, cell: Option<&Data>, format: &str) -> Option<NaiveDateTime> {
+        let cell = cell?;
+        if let Some(date) = cell.as_datetime() {
+            self.serial += 1;
+            return Some(date);
+        }
+        let parsed = match cell {
+            Data::String(s) | Data::DateTimeIso(s) => parse_date_with(s, format),
+            _ => None,
+        };
+        match parsed {
+            Some(_) => self.text += 1,
+            None if !cell.is_empty() => self.failed += 1,
+            None => {}
+        }
+        parsed
+    }
+
+    // Сообщение только если были даты текстом или нераспознанные
+    pub fn warning(&self) -> Option<WellDataError> {
+        if self.text == 0 && self.failed == 0 {
+            return None;
+        }
+        let mut message = format!("даты: {} датой Excel, {} текстом", self.serial, self.text);
+        if self.failed > 0 {
+            message += &format!(", не распознано: {}", self.failed);
+        }
+        Some(WellDataError::Warning(message))
+    }
+}
@@ -0,0 +1,309 @@
+use crate::ImportOptions;
+use crate::csv_import::{self, CsvPreview};
+use crate::error::WellDataError;
+use crate::missing;
+use crate::settings::ColumnMapping;
+use crate::year_sheet;
+use calamine::{Data, DataType, Reader, Xlsx};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// Сколько строк смотреть: хватает, чтобы имена скважин начали повторяться
+const SAMPLE_ROWS: usize = 200;
+// Доля непустых ячеек нужного вида, чтобы признать колонку
+const SHARE: f64 = 0.8;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Empty,
+    Date,
+    Number(f64),
+    Text(String),
+}
+
+fn classify(data: &Data, date_format: &str) -> Cell {
+    match data {
+        Data::Empty | Data::Error(_) => Cell::Empty,
+        Data::DateTime(_) | Data::DateTimeIso(_) => Cell::Date,
+        Data::Float(f) => Cell::Number(*f),
+        Data::Int(i) => Cell::Number(*i as f64),
+        _ => {
+            let s = data.as_string().unwrap_or_default();
+            let s = s.trim();
+            if s.is_empty() || missing::is_marker(s) {
+                Cell::Empty
+            } else if csv_import::parse_date_with(s, date_format).is_some() {
+                Cell::Date
+            } else if let Some(v) = csv_import::parse_number(s) {
+                Cell::Number(v)
+            } else {
+                Cell::Text(s.to_string())
+            }
+        }
+    }
+}
+
+// Что нашлось в колонке под заголовком
+#[derive(Debug, Default)]
+struct ColumnStats {
+    filled: usize,
+    dates: usize,
+    texts: Vec<String>,
+    numbers: Vec<Option<f64>>,
+}
+
+impl ColumnStats {
+    fn share(&self, n: usize) -> f64 {
+        if self.filled == 0 {
+            0.0
+        } else {
+            n as f64 / self.filled as f64
+        }
+    }
+
+    fn numeric(&self) -> bool {
+        self.share(self.numbers.iter().flatten().count()) >= SHARE
+    }
+
+    // Короткие строки, которые повторяются: так выглядят имена скважин
+    fn well_score(&self) -> Option<f64> {
+        let distinct: HashSet<&String> = self.texts.iter().collect();
+        let short = self.texts.iter().all(|t| t.chars().count() <= 20);
+        let share = self.share(self.texts.len());
+        (share >= SHARE && short && distinct.len() * 2 <= self.texts.len()).then_some(share)
+    }
+
+    // Температура держится в узких пределах; чем меньше разброс, тем вероятнее
+    fn temperature_spread(&self) -> Option<f64> {
+        if !self.numeric() {
+            return None;
+        }
+        let values: Vec<f64> = self.numbers.iter().flatten().copied().collect();
+        if values.iter().any(|v| !(-60.0..=150.0).contains(v)) {
+            return None;
+        }
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance =
+            values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64;
+        let spread = variance.sqrt() / mean;
+        (mean > 5.0 && spread < 0.25).then_some(spread)
+    }
+}
+
+// Предложенная колонка для показа: что, где и пример значения
+#[derive(Debug, Clone)]
+pub struct ColumnGuess {
+    pub field: &'static str,
+    pub column: usize,
+    pub header: String,
+    pub sample: String,
+}
+
+// Предложение сопоставления колонок; применяется только после подтверждения
+#[derive(Debug, Clone)]
+pub struct ColumnProposal {
+    pub path: PathBuf,
+    // false - первая строка уже данные, колонки задаются номерами
+    pub has_header: bool,
+    pub columns: ColumnMapping,
+    pub guesses: Vec<ColumnGuess>,
+}
+
+fn text(data: &Data) -> String {
+    let date = match data {
+        Data::DateTime(_) | Data::DateTimeIso(_) => data.as_datetime(),
+        _ => None,
+    };
+    date.map(|d| d.format("%d.%m.%Y").to_string())
+        .or_else(|| data.as_string())
+        .or_else(|| data.as_f64().map(|f| f.to_string()))
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+pub fn propose(
+    path: &Path,
+    rows: &[Vec<Data>],
+    current: &ColumnMapping,
+    date_format: &str,
+) -> ColumnProposal {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let cells: Vec<Vec<Cell>> = rows
+        .iter()
+        .map(|row| row.iter().map(|d| classify(d, date_format)).collect())
+        .collect();
+    let cell = |r: usize, c: usize| cells[r].get(c).unwrap_or(&Cell::Empty);
+
+    // Первая строка пока не учитывается: это может быть заголовок
+    let stats: Vec<ColumnStats> = (0..width)
+        .map(|c| {
+            let mut stats = ColumnStats::default();
+            for r in 1..cells.len() {
+                let value = cell(r, c);
+                if *value != Cell::Empty {
+                    stats.filled += 1;
+                }
+                match value {
+                    Cell::Date => stats.dates += 1,
+                    Cell::Text(t) => stats.texts.push(t.clone()),
+                    _ => {}
+                }
+                stats.numbers.push(match value {
+                    Cell::Number(v) => Some(*v),
+                    _ => None,
+                });
+            }
+            stats
+        })
+        .collect();
+
+    let mut taken = HashSet::new();
+    let best = |taken: &HashSet<usize>, score: &dyn Fn(&ColumnStats) -> Option<f64>| {
+        (0..width)
+            .filter(|c| !taken.contains(c))
+            .filter_map(|c| score(&stats[c]).map(|s| (c, s)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(c, _)| c)
+    };
+
+    let date = best(&taken, &|s| {
+        let share = s.share(s.dates);
+        (share >= SHARE).then_some(share)
+    });
+    taken.extend(date);
+    let well = best(&taken, &ColumnStats::well_score);
+    taken.extend(well);
+    let temperature = best(&taken, &|s| s.temperature_spread().map(|spread| -spread));
+    taken.extend(temperature);
+
+    // Жидкость и нефть: неотрицательные числа, нефти не больше жидкости
+    let rates: Vec<usize> = (0..width)
+        .filter(|c| !taken.contains(c))
+        .filter(|&c| stats[c].numeric() && stats[c].numbers.iter().flatten().all(|v| *v >= 0.0))
+        .collect();
+    let pair = rates.iter().find_map(|&liq| {
+        rates
+            .iter()
+            .copied()
+            .find(|&oil| {
+                let both: Vec<(f64, f64)> = stats[liq]
+                    .numbers
+                    .iter()
+                    .zip(&stats[oil].numbers)
+                    .filter_map(|(l, o)| l.zip(*o))
+                    .collect();
+                oil != liq
+                    && !both.is_empty()
+                    && both.iter().filter(|(l, o)| l >= o).count() as f64
+                        >= both.len() as f64 * 0.95
+            })
+            .map(|oil| (liq, oil))
+    });
+
+    // Заголовок есть, если в первой строке не дата там, где ниже даты
+    let has_header = date.is_none_or(|c| *cell(0, c) != Cell::Date);
+    let header = |c: usize| match rows[0].get(c) {
+        Some(data) if has_header => text(data),
+        _ => String::new(),
+    };
+    let sample = |c: usize| {
+        rows.iter()
+            .skip(1)
+            .filter_map(|r| r.get(c))
+            .map(text)
+            .find(|s| !s.is_empty())
+            .unwrap_or_default()
+    };
+
+    let mut columns = current.clone();
+    let mut guesses = Vec::new();
+    for (field, column, target) in [
+        ("Скважина", well, &mut columns.well),
+        ("Дата", date, &mut columns.date),
+        ("Дебит жидкости", pair.map(|p| p.0), &mut columns.pd_liq),
+        ("Дебит нефти", pair.map(|p| p.1), &mut columns.pd_oil),
+        ("Температура", temperature, &mut columns.temperature),
+    ] {
+        let Some(c) = column else {
+            continue;
+        };
+        let header = header(c);
+        *target = if header.is_empty() {
+            format!("#{}", c + 1)
+        } else {
+            header.clone()
+        };
+        guesses.push(ColumnGuess {
+            field,
+            column: c,
+            header,
+            sample: sample(c),
+        });
+    }
+    ColumnProposal {
+        path: path.to_path_buf(),
+        has_header,
+        columns,
+        guesses,
+    }
+}
+
+// Первые строки листа-года или текстового файла, начиная со строки заголовка
+fn sample_rows(path: &Path, import: &ImportOptions) -> Result<Vec<Vec<Data>>, WellDataError> {
+    let skip = import.header_row.saturating_sub(1) as usize;
+    if csv_import::is_text_file(path) {
+        let preview = CsvPreview::open(path.to_path_buf())
+            .map_err(|e| WellDataError::open_failed(path, e))?;
+        return Ok(preview
+            .rows(skip, SAMPLE_ROWS)
+            .into_iter()
+            .map(|row| row.into_iter().map(Data::String).collect())
+            .collect());
+    }
+
+    let mut workbook: Xlsx<_> =
+        calamine::open_workbook(path).map_err(|e| WellDataError::open_failed(path, e))?;
+    let sheet = workbook
+        .sheet_names()
+        .into_iter()
+        .find(|s| year_sheet::is_data_sheet(s))
+        .ok_or_else(|| {
+            WellDataError::BadContent("в книге нет листов за год или квартал".to_string())
+        })?;
+    let mut reader = workbook.worksheet_cells_reader(&sheet)?;
+    // Номера колонок - от начала заполненной области, как при загрузке
+    let (start, first_col) = reader.dimensions().start;
+    let (start, first_col) = (start as usize, first_col as usize);
+    let last = skip.max(start) + SAMPLE_ROWS;
+    let mut rows: Vec<Vec<Data>> = Vec::new();
+    while let Some(cell) = reader.next_cell()? {
+        let (row, col) = cell.get_position();
+        let (row, col) = (row as usize, col as usize - first_col);
+        if row >= last {
+            break;
+        }
+        if row < skip {
+            continue;
+        }
+        let r = row - skip.max(start);
+        if rows.len() <= r {
+            rows.resize(r + 1, Vec::new());
+        }
+        if rows[r].len() <= col {
+            rows[r].resize(col + 1, Data::Empty);
+        }
+        rows[r][col] = cell.get_value().clone().into();
+    }
+    Ok(rows)
+}
+
+pub fn detect_file(path: &Path, import: &ImportOptions) -> Result<ColumnProposal, WellDataError> {
+    let rows = sample_rows(path, import)?;
+    if rows.len() < 2 {
+        return Err(WellDataError::BadContent(
+            "слишком мало строк, чтобы определить колонки".to_string(),
+        ));
+    }
+    Ok(propose(path, &rows, &import.columns, &import.date_format))
+}
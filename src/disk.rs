@@ -0,0 +1,394 @@
+use crate::error::WellDataError;
+use crate::query::{QueryResult, Value};
+use crate::status::WellStatus;
+use crate::store::{RecordRef, RecordStore};
+use crate::well_type::WellType;
+use chrono::DateTime;
+use duckdb::types::ValueRef;
+use duckdb::{Connection, Rows, params};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Хранилище во временной базе DuckDB для файлов больше оперативной памяти.
+// Разобранные строки пачками уходят в базу, в памяти остается только
+// выборка для выгрузки. Фильтры, дедупликация и запросы пользователя
+// выполняются в самой базе, при нехватке памяти она пишет промежуточные
+// данные на диск. Файлы базы удаляются вместе с хранилищем
+pub struct DiskStore {
+    // Поля удаляются по порядку: сначала закрывается соединение, потом файлы
+    conn: Mutex<Connection>,
+    _file: TempFile,
+    // Строки, еще не записанные в базу
+    batch: Box<RecordStore>,
+    len: usize,
+}
+
+// Файл базы, его журнал и каталог для сброса промежуточных данных
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+        let _ = std::fs::remove_file(self.0.with_extension("duckdb.wal"));
+        let _ = std::fs::remove_dir_all(self.0.with_extension("duckdb.tmp"));
+    }
+}
+
+static NEXT_DB: AtomicUsize = AtomicUsize::new(0);
+
+// Строк в одной пачке вставки
+const BATCH: usize = 64 * 1024;
+
+const COLUMNS: &str = "well_name, date, pd_liq, pd_oil, temperature, p_bottom, p_head, \
+    frequency, choke, injection, status, well_type, year_sheet, quarter, out_of_bounds";
+
+impl DiskStore {
+    pub fn create() -> duckdb::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "well-data-{}-{}.duckdb",
+            std::process::id(),
+            NEXT_DB.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = TempFile(path);
+        let _ = std::fs::remove_file(&file.0);
+
+        let conn = Connection::open(&file.0)?;
+        // Запросы пользователя не должны читать и писать файлы вне базы
+        conn.execute_batch(&format!(
+            "SET temp_directory = '{}';
+             SET enable_external_access = false;
+             SET preserve_insertion_order = true;
+             CREATE TABLE records_raw (
+                 row BIGINT NOT NULL,
+                 well_name VARCHAR NOT NULL,
+                 date BIGINT,
+                 pd_liq DOUBLE,
+                 pd_oil DOUBLE,
+                 temperature DOUBLE,
+                 p_bottom DOUBLE,
+                 p_head DOUBLE,
+                 frequency DOUBLE,
+                 choke DOUBLE,
+                 injection DOUBLE,
+                 status VARCHAR,
+                 well_type VARCHAR,
+                 year_sheet INTEGER NOT NULL,
+                 quarter UTINYINT,
+                 out_of_bounds BOOLEAN NOT NULL
+             );
+             -- Для SQL-запросов пользователя дата как TIMESTAMP
+             CREATE VIEW records AS
+                 SELECT well_name, epoch_ms(date) AS date,
+                        pd_liq, pd_oil, temperature, p_bottom, p_head,
+                        frequency, choke, injection, status, well_type, year_sheet, quarter,
+                        out_of_bounds
+                 FROM records_raw;",
+            file.0
+                .with_extension("duckdb.tmp")
+                .to_string_lossy()
+                .replace('\'', "''")
+        ))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            _file: file,
+            batch: Box::default(),
+            len: 0,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn push(&mut self, r: RecordRef) -> duckdb::Result<()> {
+        self.batch.push(r);
+        self.len += 1;
+        if self.batch.len() >= BATCH {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // Пачка уходит в базу через Appender: построчный INSERT в DuckDB медленный
+    fn flush(&mut self) -> duckdb::Result<()> {
+        let batch = std::mem::take(&mut self.batch);
+        let first = (self.len - batch.len()) as i64;
+        let conn = self.conn.get_mut().unwrap_or_else(|e| e.into_inner());
+        let mut appender = conn.appender("records_raw")?;
+        for (i, r) in batch.iter().enumerate() {
+            appender.append_row(params![
+                first + i as i64,
+                r.well_name,
+                r.date.map(|d| d.and_utc().timestamp_millis()),
+                r.pd_liq,
+                r.pd_oil,
+                r.temperature,
+                r.p_bottom,
+                r.p_head,
+                r.frequency,
+                r.choke,
+                r.injection,
+                r.status.map(WellStatus::code),
+                r.well_type.map(WellType::code),
+                r.year_sheet,
+                r.quarter,
+                r.out_of_bounds,
+            ])?;
+        }
+        appender.flush()
+    }
+
+    // Конец загрузки: дописываем последнюю пачку
+    pub fn finish(&mut self) -> duckdb::Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    // Та же выборка, что и frame::select: годы с start_year, выбранные скважины,
+    // без полных дублей (остается первая строка), по скважине и дате.
+    // Вызывается после finish
+    pub fn select(&self, start_year: i32, wells: &HashSet<String>) -> duckdb::Result<RecordStore> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        conn.execute_batch(
+            "CREATE OR REPLACE TEMP TABLE selected_wells (name VARCHAR PRIMARY KEY);",
+        )?;
+        {
+            let mut appender = conn.appender_to_catalog_and_db("selected_wells", "temp", "main")?;
+            for well in wells {
+                appender.append_row([well])?;
+            }
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS}
+             FROM records_raw
+             WHERE row IN (
+                 SELECT MIN(row) FROM records_raw
+                 WHERE year_sheet >= ?
+                   AND well_name IN (SELECT name FROM temp.selected_wells)
+                 GROUP BY well_name, date, pd_liq, pd_oil, temperature, p_bottom, p_head,
+                          frequency, choke, injection, status, well_type
+             )
+             ORDER BY well_name, date NULLS FIRST, row"
+        ))?;
+        let mut rows = stmt.query([start_year])?;
+        let mut store = RecordStore::default();
+        read_records(&mut rows, |r| store.push(r))?;
+        Ok(store)
+    }
+
+    // Все записи в порядке загрузки, без выборки и дедупликации.
+    // Строки последней пачки, если finish еще не вызван, идут в конце
+    pub fn for_each(&self, mut f: impl FnMut(RecordRef)) -> duckdb::Result<()> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn.prepare(&format!("SELECT {COLUMNS} FROM records_raw ORDER BY row"))?;
+        let mut rows = stmt.query([])?;
+        read_records(&mut rows, &mut f)?;
+        self.batch.iter().for_each(f);
+        Ok(())
+    }
+
+    // Запрос пользователя из окна SQL; изменять данные нельзя.
+    // Проверка: запрос должен разбираться как подзапрос, а туда
+    // DuckDB пропускает только чтение
+    pub fn query(&self, sql: &str) -> Result<QueryResult, WellDataError> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let sql = sql.trim().trim_end_matches(';');
+        let mut stmt = conn.prepare(sql)?;
+        if conn
+            .prepare(&format!("SELECT * FROM (\n{sql}\n) LIMIT 0"))
+            .is_err()
+        {
+            return Err(WellDataError::ReadOnlyQuery);
+        }
+
+        let mut rows = stmt.query([])?;
+        let columns = rows
+            .as_ref()
+            .map(|stmt| stmt.column_names())
+            .unwrap_or_default();
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut values = Vec::with_capacity(columns.len());
+            for i in 0..columns.len() {
+                values.push(to_value(row.get_ref(i)?));
+            }
+            result.push(values);
+        }
+
+        Ok(QueryResult {
+            sql: String::new(),
+            columns,
+            rows: result,
+        })
+    }
+}
+
+// Значение DuckDB для таблицы результата: числа и даты как в Polars
+fn to_value(value: ValueRef) -> Value {
+    let timestamp = |micros| {
+        DateTime::from_timestamp_micros(micros)
+            .map_or(Value::Null, |d| Value::Text(d.naive_utc().to_string()))
+    };
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Boolean(v) => Value::Number(v as u8 as f64),
+        ValueRef::TinyInt(v) => Value::Number(v as f64),
+        ValueRef::SmallInt(v) => Value::Number(v as f64),
+        ValueRef::Int(v) => Value::Number(v as f64),
+        ValueRef::BigInt(v) => Value::Number(v as f64),
+        ValueRef::HugeInt(v) => Value::Number(v as f64),
+        ValueRef::UHugeInt(v) => Value::Number(v as f64),
+        ValueRef::UTinyInt(v) => Value::Number(v as f64),
+        ValueRef::USmallInt(v) => Value::Number(v as f64),
+        ValueRef::UInt(v) => Value::Number(v as f64),
+        ValueRef::UBigInt(v) => Value::Number(v as f64),
+        ValueRef::Float(v) => Value::Number(v as f64),
+        ValueRef::Double(v) => Value::Number(v),
+        ValueRef::Decimal(v) => v
+            .to_string()
+            .parse()
+            .map_or_else(|_| Value::Text(v.to_string()), Value::Number),
+        ValueRef::Timestamp(unit, v) => timestamp(unit.to_micros(v)),
+        ValueRef::Date32(days) => timestamp(days as i64 * 86_400_000_000),
+        ValueRef::Text(t) => Value::Text(String::from_utf8_lossy(t).to_string()),
+        _ => Value::Text(format!("<{}>", value.data_type())),
+    }
+}
+
+// Строки запроса в порядке колонок records_raw (без row)
+fn read_records(rows: &mut Rows, mut f: impl FnMut(RecordRef)) -> duckdb::Result<()> {
+    while let Some(row) = rows.next()? {
+        let well_name: String = row.get(0)?;
+        let date: Option<i64> = row.get(1)?;
+        f(RecordRef {
+            well_name: &well_name,
+            date: date
+                .and_then(DateTime::from_timestamp_millis)
+                .map(|d| d.naive_utc()),
+            pd_liq: row.get(2)?,
+            pd_oil: row.get(3)?,
+            temperature: row.get(4)?,
+            p_bottom: row.get(5)?,
+            p_head: row.get(6)?,
+            frequency: row.get(7)?,
+            choke: row.get(8)?,
+            injection: row.get(9)?,
+            status: row
+                .get::<_, Option<String>>(10)?
+                .as_deref()
+                .and_then(WellStatus::from_code),
+            well_type: row
+                .get::<_, Option<String>>(11)?
+                .as_deref()
+                .and_then(WellType::from_code),
+            year_sheet: row.get(12)?,
+            quarter: row.get(13)?,
+            out_of_bounds: row.get(14)?,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn record(well: &str, day: u32, pd_oil: f64) -> RecordRef<'_> {
+        RecordRef {
+            well_name: well,
+            date: NaiveDate::from_ymd_opt(2021, 3, day).and_then(|d| d.and_hms_opt(0, 0, 0)),
+            pd_liq: None,
+            pd_oil: Some(pd_oil),
+            temperature: None,
+            p_bottom: None,
+            p_head: None,
+            frequency: None,
+            choke: None,
+            injection: None,
+            status: Some(WellStatus::Producing),
+            well_type: None,
+            year_sheet: 2021,
+            quarter: None,
+            out_of_bounds: false,
+        }
+    }
+
+    #[test]
+    fn select_filters_dedups_and_sorts() {
+        let mut store = DiskStore::create().unwrap();
+        store.push(record("2", 1, 5.0)).unwrap();
+        store.push(record("1", 2, 3.0)).unwrap();
+        store.push(record("1", 1, 4.0)).unwrap();
+        store.push(record("1", 2, 3.0)).unwrap();
+        store.push(record("3", 1, 1.0)).unwrap();
+        store.finish().unwrap();
+        assert_eq!(store.len(), 5);
+
+        let wells = HashSet::from(["1".to_string(), "2".to_string()]);
+        let selected = store.select(2021, &wells).unwrap();
+        let rows: Vec<_> = selected
+            .iter()
+            .map(|r| (r.well_name.to_string(), r.date, r.pd_oil, r.status))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "1".to_string(),
+                    record("1", 1, 0.0).date,
+                    Some(4.0),
+                    Some(WellStatus::Producing)
+                ),
+                (
+                    "1".to_string(),
+                    record("1", 2, 0.0).date,
+                    Some(3.0),
+                    Some(WellStatus::Producing)
+                ),
+                (
+                    "2".to_string(),
+                    record("2", 1, 0.0).date,
+                    Some(5.0),
+                    Some(WellStatus::Producing)
+                ),
+            ]
+        );
+        assert_eq!(store.select(2022, &wells).unwrap().len(), 0);
+
+        let mut all = Vec::new();
+        store
+            .for_each(|r| all.push(r.well_name.to_string()))
+            .unwrap();
+        assert_eq!(all, ["2", "1", "1", "1", "3"]);
+    }
+
+    #[test]
+    fn query_runs_only_reads() {
+        let mut store = DiskStore::create().unwrap();
+        store.push(record("1", 5, 2.5)).unwrap();
+        store.finish().unwrap();
+
+        let result = store
+            .query("SELECT well_name, date, sum(pd_oil) AS pd_oil FROM records GROUP BY ALL;")
+            .unwrap();
+        assert_eq!(result.columns, ["well_name", "date", "pd_oil"]);
+        assert_eq!(result.rows[0][0].display(), "1");
+        assert_eq!(result.rows[0][1].display(), "2021-03-05 00:00:00");
+        assert_eq!(result.rows[0][2].display(), "2.5");
+
+        assert!(store.query("DELETE FROM records_raw").is_err());
+        assert!(store.query("SELECT 1; DROP TABLE records_raw").is_err());
+        assert!(store.query("COPY records_raw TO 'out.csv'").is_err());
+        assert_eq!(
+            store.query("SELECT count(*) FROM records").unwrap().rows[0][0].display(),
+            "1"
+        );
+    }
+}
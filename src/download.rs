@@ -0,0 +1,130 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Окно "Открыть по ссылке". Токен в настройки не сохраняется
+#[derive(Debug, Default)]
+pub struct UrlDialog {
+    pub url: String,
+    // "Bearer токен" - значение Authorization, "X-Api-Key: ключ" - свой заголовок
+    pub auth: String,
+}
+
+// Заголовок авторизации из строки окна
+fn auth_header(auth: &str) -> Option<(&str, &str)> {
+    let auth = auth.trim();
+    if auth.is_empty() {
+        return None;
+    }
+    match auth.split_once(':') {
+        Some((name, value)) if !name.is_empty() && !name.contains(' ') => {
+            Some((name.trim(), value.trim()))
+        }
+        _ => Some(("Authorization", auth)),
+    }
+}
+
+// Скачанные файлы лежат в кэше программы под именем из ссылки: по этому пути
+// файл считается исходным (титульный лист, контрольная сумма, имя отчета)
+pub fn target_path(url: &str) -> Result<PathBuf, WellDataError> {
+    let parsed = reqwest::Url::parse(url.trim())
+        .map_err(|e| WellDataError::Config(format!("неверная ссылка {}: {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(WellDataError::Config(format!(
+            "поддерживаются только ссылки http и https: {}",
+            url
+        )));
+    }
+    let name = parsed
+        .path_segments()
+        .and_then(|mut s| s.next_back())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("download");
+    Ok(downloads_dir()?.join(file_name(name)))
+}
+
+// Имя из ссылки или ключа без запрещенных в Windows символов; без расширения - xlsx
+pub fn file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if r#"\/:*?"<>|%"#.contains(c) { '_' } else { c })
+        .collect();
+    if Path::new(&name).extension().is_some() {
+        name
+    } else {
+        format!("{}.xlsx", name)
+    }
+}
+
+pub fn downloads_dir() -> Result<PathBuf, WellDataError> {
+    Ok(dirs::cache_dir()
+        .ok_or_else(|| WellDataError::Config("не найдена папка кэша пользователя".to_string()))?
+        .join(crate::APP_DIR_NAME)
+        .join("downloads"))
+}
+
+pub fn client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(30))
+        .build()
+}
+
+// Скачивание во временный .part и переименование, чтобы оборванная загрузка
+// не оставила битый файл под нормальным именем
+pub async fn download(
+    url: &str,
+    auth: &str,
+    target: &Path,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<(), WellDataError> {
+    progress.report("Подключение...", 0, 0);
+    let client = client()?;
+    let mut request = client.get(url.trim());
+    if let Some((name, value)) = auth_header(auth) {
+        request = request.header(name, value);
+    }
+    let response = cancel
+        .or_cancel(request.send())
+        .await??
+        .error_for_status()?;
+    save_response(response, target, progress, cancel).await
+}
+
+// Тело успешного ответа пишется в target через .part
+pub async fn save_response(
+    mut response: reqwest::Response,
+    target: &Path,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<(), WellDataError> {
+    let total = response.content_length();
+
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let part = target.with_extension("part");
+    let mut file = std::fs::File::create(&part)?;
+    let mut received = 0u64;
+    while let Some(chunk) = cancel.or_cancel(response.chunk()).await?? {
+        file.write_all(&chunk)?;
+        received += chunk.len() as u64;
+        let megabytes = received as f64 / 1048576.0;
+        let text = match total {
+            Some(total) => format!(
+                "Скачивание: {:.1} из {:.1} МБ",
+                megabytes,
+                total as f64 / 1048576.0
+            ),
+            None => format!("Скачивание: {:.1} МБ", megabytes),
+        };
+        progress.report_step(&text, received as usize, total.unwrap_or(0) as usize);
+    }
+    file.flush()?;
+    drop(file);
+    std::fs::rename(&part, target)?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use crate::add_sheet;
+use crate::calendar::{days_in_month, split_months, typical_spacing};
+use crate::store::RecordRef;
+use chrono::NaiveDateTime;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+
+// Простои по нулевому дебиту жидкости: для скважин без колонки Status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DowntimeOptions {
+    // Лист Downtime с часами простоя по месяцам
+    pub sheet: bool,
+    // Дебит жидкости не выше порога считается нулевым
+    pub threshold: f64,
+    // Одна нулевая запись - скорее сбой замера, чем остановка
+    pub min_records: usize,
+    // Записи простоя не входят в средние на листах Monthly и Forecast
+    pub exclude_from_averages: bool,
+}
+
+impl Default for DowntimeOptions {
+    fn default() -> Self {
+        Self {
+            sheet: false,
+            threshold: 0.1,
+            min_records: 2,
+            exclude_from_averages: false,
+        }
+    }
+}
+
+impl DowntimeOptions {
+    pub fn enabled(&self) -> bool {
+        self.sheet || self.exclude_from_averages
+    }
+}
+
+// Простой скважины: с первой нулевой записи до первой записи с дебитом
+#[derive(Debug, Clone)]
+pub struct Shutdown<'a> {
+    pub well: &'a str,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    // Номера записей простоя в общем списке
+    pub rows: Range<usize>,
+}
+
+impl Shutdown<'_> {
+    pub fn hours(&self) -> f64 {
+        (self.end - self.start).num_minutes() as f64 / 60.0
+    }
+}
+
+fn is_zero(record: &RecordRef, threshold: f64) -> bool {
+    record.date.is_some() && record.pd_liq.is_some_and(|v| v.abs() <= threshold)
+}
+
+// Записи отсортированы по скважине и дате, как в выгрузке. Запись без дебита
+// жидкости прерывает серию: про нее неизвестно, работала ли скважина
+pub fn detect<'a>(records: &[RecordRef<'a>], options: &DowntimeOptions) -> Vec<Shutdown<'a>> {
+    let mut shutdowns = Vec::new();
+    let mut offset = 0;
+    for group in records.chunk_by(|a, b| a.well_name == b.well_name) {
+        let dates: Vec<NaiveDateTime> = group.iter().filter_map(|r| r.date).collect();
+        let spacing = typical_spacing(&dates);
+        let mut i = 0;
+        while i < group.len() {
+            if !is_zero(&group[i], options.threshold) {
+                i += 1;
+                continue;
+            }
+            let first = i;
+            while i < group.len() && is_zero(&group[i], options.threshold) {
+                i += 1;
+            }
+            if i - first < options.min_records.max(1) {
+                continue;
+            }
+            let (Some(start), Some(last)) = (group[first].date, group[i - 1].date) else {
+                continue;
+            };
+            // Конец простоя - следующий замер, а в конце данных - один шаг замеров
+            let end = group.get(i).and_then(|r| r.date).unwrap_or(last + spacing);
+            shutdowns.push(Shutdown {
+                well: group[0].well_name,
+                start,
+                end,
+                rows: offset + first..offset + i,
+            });
+        }
+        offset += group.len();
+    }
+    shutdowns
+}
+
+// Номера записей, которые не входят в средние
+pub fn excluded_rows(shutdowns: &[Shutdown]) -> HashSet<usize> {
+    shutdowns.iter().flat_map(|s| s.rows.clone()).collect()
+}
+
+// Лист Downtime: слева часы простоя по скважинам и месяцам, справа - сами простои
+pub fn write_downtime_sheet(
+    workbook: &mut Workbook,
+    constant_memory: bool,
+    shutdowns: &[Shutdown],
+    wells: &[&str],
+) -> Result<(), XlsxError> {
+    let worksheet = add_sheet(workbook, constant_memory).set_name("Downtime")?;
+    let bold = Format::new().set_bold();
+    let number = Format::new().set_num_format("0.0");
+
+    let headers = [
+        "Скважина",
+        "Месяц",
+        "Часов простоя",
+        "Доля месяца, %",
+        "Простоев",
+        "",
+        "Скважина",
+        "Начало",
+        "Конец",
+        "Часов",
+        "Записей",
+    ];
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *header, &bold)?;
+    }
+
+    // Простои по порядку листов скважин
+    let position: HashMap<&str, usize> = wells.iter().enumerate().map(|(i, w)| (*w, i)).collect();
+    let mut ordered: Vec<&Shutdown> = shutdowns
+        .iter()
+        .filter(|s| position.contains_key(s.well))
+        .collect();
+    ordered.sort_by_key(|s| position[s.well]);
+
+    // (скважина, месяц) -> (дней простоя, простоев, начавшихся в месяце)
+    let mut monthly: BTreeMap<(usize, (i32, u32)), (f64, usize)> = BTreeMap::new();
+    for s in &ordered {
+        for (i, (month, days)) in split_months(s.start, s.end).into_iter().enumerate() {
+            let entry = monthly.entry((position[s.well], month)).or_default();
+            entry.0 += days;
+            if i == 0 {
+                entry.1 += 1;
+            }
+        }
+    }
+    let monthly: Vec<_> = monthly.into_iter().collect();
+
+    // Обе таблицы пишутся в одном проходе: в потоковом режиме строки идут сверху вниз
+    for i in 0..monthly.len().max(ordered.len()) {
+        let row = i as u32 + 1;
+        if let Some(((well, month), (days, count))) = monthly.get(i) {
+            worksheet.write_string(row, 0, wells[*well])?;
+            worksheet.write_string(row, 1, format!("{}-{:02}", month.0, month.1))?;
+            worksheet.write_number_with_format(row, 2, days * 24.0, &number)?;
+            worksheet.write_number_with_format(
+                row,
+                3,
+                days / days_in_month(*month) * 100.0,
+                &number,
+            )?;
+            worksheet.write_number(row, 4, *count as f64)?;
+        }
+        if let Some(s) = ordered.get(i) {
+            worksheet.write_string(row, 6, s.well)?;
+            worksheet.write_string(row, 7, s.start.format("%Y-%m-%d %H:%M").to_string())?;
+            worksheet.write_string(row, 8, s.end.format("%Y-%m-%d %H:%M").to_string())?;
+            worksheet.write_number_with_format(row, 9, s.hours(), &number)?;
+            worksheet.write_number(row, 10, s.rows.len() as f64)?;
+        }
+    }
+
+    worksheet.set_column_width(0, 20)?;
+    worksheet.set_column_width(6, 20)?;
+    worksheet.set_column_width(7, 16)?;
+    worksheet.set_column_width(8, 16)?;
+    worksheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use crate::locked::LockedFile;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+// Ошибки загрузки и выгрузки. Варианты разделены, чтобы интерфейс и пакетная
+// обработка могли по-разному реагировать: отмена - не ошибка, занятый файл
+// можно записать повторно, пропущенный лист - только предупреждение
+#[derive(Debug, thiserror::Error)]
+pub enum WellDataError {
+    #[error("не удалось открыть {}: {source}", path.display())]
+    OpenFailed { path: PathBuf, source: BoxError },
+    #[error("лист '{sheet}' пропущен: {reason}")]
+    SheetSkipped { sheet: String, reason: String },
+    #[error("лист '{sheet}' пропущен: нет колонки '{column}'")]
+    HeaderMissing { sheet: String, column: String },
+    #[error("не удалось записать {}: {source}", path.display())]
+    SaveFailed {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("скважина {well}: {source}")]
+    WriteFailed { well: String, source: BoxError },
+    #[error("операция отменена")]
+    Cancelled,
+    #[error(transparent)]
+    Locked(#[from] LockedFile),
+    // Файл прочитан, но в нем нет ожидаемого: колонок, листов, объектов
+    #[error("{0}")]
+    BadContent(String),
+    // Не заполнены или неверны настройки: шаблон, адрес, ключи доступа
+    #[error("{0}")]
+    Config(String),
+    // Сервер отказал или не найден: S3, SFTP, HTTP
+    #[error("{0}")]
+    Remote(String),
+    // Нечего выгружать или показывать
+    #[error("{0}")]
+    NoData(String),
+    // Результат не помещается в лист Excel
+    #[error("{0}")]
+    TooLarge(String),
+    #[error(
+        "файл содержит ~{rows} строк, потребуется ~{needed_mb} МБ памяти при лимите {limit_mb} МБ. \
+         Включите хранение на диске в параметрах загрузки, используйте пакетную \
+         обработку (📁 Пакет) или увеличьте лимит"
+    )]
+    MemoryLimit {
+        rows: u64,
+        needed_mb: u64,
+        limit_mb: u64,
+    },
+    // Записанная книга не сошлась с планом выгрузки (verify.rs)
+    #[error("проверка {} не прошла: {}", path.display(), problems.join("; "))]
+    VerifyFailed {
+        path: PathBuf,
+        problems: Vec<String>,
+    },
+    #[error("разрешены только запросы на чтение (SELECT)")]
+    ReadOnlyQuery,
+    // Предупреждение загрузки: пропуски, нераспознанные даты, отброшенные строки
+    #[error("{0}")]
+    Warning(String),
+    #[error("журнал операций: {0}")]
+    AuditLog(String),
+    #[error("сбой потока {0}")]
+    ThreadPanicked(&'static str),
+    // Ошибка или предупреждение одного файла из архива
+    #[error("{file}: {source}")]
+    InFile {
+        file: String,
+        source: Box<WellDataError>,
+    },
+    // Ошибки библиотек: ввод-вывод, разбор, xlsx, polars, сеть
+    // и ошибки с местом (ResultExt)
+    #[error("{0}")]
+    Other(#[source] BoxError),
+}
+
+impl WellDataError {
+    pub fn open_failed(path: impl Into<PathBuf>, source: impl Into<BoxError>) -> Self {
+        match Self::from(source.into()) {
+            Self::Other(source) => Self::OpenFailed {
+                path: path.into(),
+                source,
+            },
+            e => e,
+        }
+    }
+
+    // Отмена, в том числе обернутая в место ошибки (ResultExt::at)
+    pub fn is_cancelled(&self) -> bool {
+        let mut current: Option<&(dyn Error + 'static)> = Some(self);
+        while let Some(e) = current {
+            if matches!(
+                e.downcast_ref::<WellDataError>(),
+                Some(WellDataError::Cancelled)
+            ) {
+                return true;
+            }
+            current = e.source();
+        }
+        false
+    }
+
+    // Отмена и занятый файл проходят как есть: это не сбой записи скважины
+    pub fn write_failed(well: &str, source: impl Into<BoxError>) -> Self {
+        match Self::from(source.into()) {
+            Self::Other(source) => Self::WriteFailed {
+                well: well.to_string(),
+                source,
+            },
+            e => e,
+        }
+    }
+}
+
+// Ошибка с местом (ResultExt::at) или из чужого BoxError: если внутри уже
+// WellDataError или занятый файл - достаем их, чтобы не потерять вариант
+impl From<BoxError> for WellDataError {
+    fn from(e: BoxError) -> Self {
+        let e = match e.downcast::<WellDataError>() {
+            Ok(e) => return *e,
+            Err(e) => e,
+        };
+        match e.downcast::<LockedFile>() {
+            Ok(file) => Self::Locked(*file),
+            Err(e) => Self::Other(e),
+        }
+    }
+}
+
+macro_rules! other_error {
+    ($($t:ty),* $(,)?) => {
+        $(impl From<$t> for WellDataError {
+            fn from(e: $t) -> Self {
+                Self::Other(e.into())
+            }
+        })*
+    };
+}
+
+other_error!(
+    std::io::Error,
+    serde_json::Error,
+    rust_xlsxwriter::XlsxError,
+    calamine::XlsxError,
+    csv::Error,
+    polars::error::PolarsError,
+    roxmltree::Error,
+    quick_xml::Error,
+    quick_xml::encoding::EncodingError,
+    reqwest::Error,
+    ssh2::Error,
+    duckdb::Error,
+    tokio::task::JoinError,
+    zip::result::ZipError,
+);
+
+// Где случилась ошибка: этап операции, лист и строка книги
+#[derive(Debug, Clone, Default)]
+pub struct Location {
+    pub phase: String,
+    pub sheet: Option<String>,
+    pub row: Option<u32>,
+}
+
+impl Location {
+    pub fn new(phase: &str) -> Self {
+        Self {
+            phase: phase.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn sheet(self, sheet: &str) -> Self {
+        Self {
+            sheet: Some(sheet.to_string()),
+            ..self
+        }
+    }
+
+    // Номер строки как в Excel, с единицы
+    pub fn row(self, row: u32) -> Self {
+        Self {
+            row: Some(row),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.phase)?;
+        if let Some(sheet) = &self.sheet {
+            write!(f, ", лист '{}'", sheet)?;
+        }
+        if let Some(row) = self.row {
+            write!(f, ", строка {}", row)?;
+        }
+        Ok(())
+    }
+}
+
+// Ошибка с местом, где она случилась. Текст включает исходную ошибку,
+// чтобы строка состояния и журнал операций оставались понятными
+#[derive(Debug)]
+pub struct LocatedError {
+    pub location: Location,
+    source: BoxError,
+}
+
+impl fmt::Display for LocatedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.source)
+    }
+}
+
+impl Error for LocatedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+pub trait ResultExt<T> {
+    // Место вычисляется только при ошибке - годится для цикла по строкам
+    fn at(self, location: impl FnOnce() -> Location) -> Result<T, BoxError>;
+}
+
+impl<T, E: Into<BoxError>> ResultExt<T> for Result<T, E> {
+    fn at(self, location: impl FnOnce() -> Location) -> Result<T, BoxError> {
+        self.map_err(|e| {
+            Box::new(LocatedError {
+                location: location(),
+                source: e.into(),
+            }) as BoxError
+        })
+    }
+}
+
+// Ошибка задачи в разобранном виде - для окна с подробностями
+#[derive(Debug, Clone, Default)]
+pub struct ErrorReport {
+    pub message: String,
+    pub location: Location,
+    // Цепочка причин от внешней к исходной, без обертки с местом
+    pub causes: Vec<String>,
+}
+
+impl ErrorReport {
+    pub fn new(error: &(dyn Error + 'static)) -> Self {
+        let mut report = Self {
+            message: error.to_string(),
+            ..Self::default()
+        };
+        let mut current = Some(error);
+        while let Some(e) = current {
+            match e.downcast_ref::<WellDataError>() {
+                // Обертка без своего текста - не отдельная причина
+                Some(WellDataError::Other(_)) => {
+                    current = e.source();
+                    continue;
+                }
+                Some(WellDataError::OpenFailed { .. }) if report.location.phase.is_empty() => {
+                    report.location.phase = "Открытие файла".to_string();
+                }
+                _ => {}
+            }
+            match e.downcast_ref::<LocatedError>() {
+                // Внешнее место общее (этап), внутреннее - точнее (лист, строка)
+                Some(located) => {
+                    let location = &located.location;
+                    if report.location.phase.is_empty() {
+                        report.location.phase = location.phase.clone();
+                    }
+                    if location.sheet.is_some() {
+                        report.location.sheet = location.sheet.clone();
+                    }
+                    if location.row.is_some() {
+                        report.location.row = location.row;
+                    }
+                }
+                None => report.causes.push(e.to_string()),
+            }
+            current = e.source();
+        }
+        report
+    }
+
+    pub fn message(message: String) -> Self {
+        Self {
+            causes: vec![message.clone()],
+            message,
+            ..Self::default()
+        }
+    }
+
+    // Текст для обращения в поддержку
+    pub fn details(&self) -> String {
+        let mut text = format!(
+            "Программа: {} {}\nВремя: {}\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        if !self.location.phase.is_empty() {
+            text += &format!("Этап: {}\n", self.location.phase);
+        }
+        if let Some(sheet) = &self.location.sheet {
+            text += &format!("Лист: {}\n", sheet);
+        }
+        if let Some(row) = self.location.row {
+            text += &format!("Строка: {}\n", row);
+        }
+        text += &format!("Ошибка: {}\n", self.message);
+        if self.causes.len() > 1 {
+            text += "Причины:\n";
+            for (i, cause) in self.causes.iter().enumerate() {
+                text += &format!("  {}. {}\n", i + 1, cause);
+            }
+        }
+        text
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
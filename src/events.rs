@@ -0,0 +1,134 @@
+use crate::csv_import::{parse_date, read_table};
+use crate::status::WellStatus;
+use crate::store::RecordRef;
+use chrono::{NaiveDateTime, TimeDelta};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Сколько дней после ремонта строки выделяются жирным
+pub const POST_WORKOVER_DAYS: i64 = 30;
+
+// Мероприятие на скважине из журнала событий: ремонт, ГТМ, смена насоса
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub date: NaiveDateTime,
+    pub kind: String,
+}
+
+impl Event {
+    pub fn is_workover(&self) -> bool {
+        WellStatus::parse(&self.kind) == Some(WellStatus::Workover)
+    }
+}
+
+// Журнал событий; у каждой скважины события отсортированы по дате
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    pub path: PathBuf,
+    pub by_well: BTreeMap<String, Vec<Event>>,
+}
+
+impl EventLog {
+    pub fn len(&self) -> usize {
+        self.by_well.values().map(Vec::len).sum()
+    }
+
+    pub fn for_well(&self, well: &str) -> &[Event] {
+        self.by_well.get(well).map_or(&[], Vec::as_slice)
+    }
+}
+
+fn column_kind(header: &str) -> Option<&'static str> {
+    let h = header.trim().to_lowercase();
+    if h.contains("скв") || h.contains("well") || h == crate::NAME_COL.to_lowercase() {
+        Some("well")
+    } else if h.contains("дата") || h.contains("date") {
+        Some("date")
+    } else if ["мероприят", "событ", "гтм", "вид", "тип", "event", "type"]
+        .iter()
+        .any(|k| h.contains(k))
+    {
+        Some("kind")
+    } else {
+        None
+    }
+}
+
+fn from_table(path: &Path, rows: Vec<Vec<String>>) -> Result<EventLog, String> {
+    let mut rows = rows.into_iter();
+    let header = rows.next().ok_or("пустой журнал событий")?;
+    let find = |kind: &str| header.iter().position(|h| column_kind(h) == Some(kind));
+    let (Some(well), Some(date), Some(kind)) = (find("well"), find("date"), find("kind")) else {
+        return Err("нужны колонки: скважина, дата, вид мероприятия".to_string());
+    };
+
+    let mut by_well: BTreeMap<String, Vec<Event>> = BTreeMap::new();
+    for row in rows {
+        let name = row.get(well).map(|s| s.trim()).unwrap_or_default();
+        let text = row.get(kind).map(|s| s.trim()).unwrap_or_default();
+        let Some(date) = row.get(date).and_then(|s| parse_date(s)) else {
+            continue;
+        };
+        if !name.is_empty() && !text.is_empty() {
+            by_well.entry(name.to_string()).or_default().push(Event {
+                date,
+                kind: text.to_string(),
+            });
+        }
+    }
+    if by_well.is_empty() {
+        return Err("в журнале нет строк с датой и мероприятием".to_string());
+    }
+    for events in by_well.values_mut() {
+        events.sort_by_key(|e| e.date);
+    }
+    Ok(EventLog {
+        path: path.to_path_buf(),
+        by_well,
+    })
+}
+
+// Журнал событий из CSV/TXT или с первого листа xlsx
+pub fn read_events(path: &Path) -> Result<EventLog, String> {
+    from_table(path, read_table(path)?)
+}
+
+// Событие привязывается к первой записи скважины не раньше его даты.
+// События до начала выгрузки и после ее конца пропускаются
+pub fn event_rows<'a>(
+    records: &[RecordRef],
+    events: &'a [Event],
+) -> BTreeMap<usize, Vec<&'a Event>> {
+    let mut rows: BTreeMap<usize, Vec<&Event>> = BTreeMap::new();
+    let Some(first) = records.iter().find_map(|r| r.date) else {
+        return rows;
+    };
+    for event in events.iter().filter(|e| e.date >= first) {
+        if let Some(i) = records
+            .iter()
+            .position(|r| r.date.is_some_and(|d| d >= event.date))
+        {
+            rows.entry(i).or_default().push(event);
+        }
+    }
+    rows
+}
+
+// Диапазоны строк [начало, конец] в течение POST_WORKOVER_DAYS после каждого ремонта
+pub fn post_workover_rows(records: &[RecordRef], events: &[Event]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    for (&start, row_events) in &event_rows(records, events) {
+        let Some(workover) = row_events.iter().find(|e| e.is_workover()) else {
+            continue;
+        };
+        let until = workover.date + TimeDelta::days(POST_WORKOVER_DAYS);
+        let end = records[start..]
+            .iter()
+            .take_while(|r| r.date.is_some_and(|d| d < until))
+            .count();
+        if end > 0 {
+            ranges.push((start, start + end - 1));
+        }
+    }
+    ranges
+}
@@ -0,0 +1,107 @@
+use crate::error::WellDataError;
+use crate::query::{self, Value};
+use crate::store::{Dataset, RecordRef};
+use crate::{ExportOptions, add_sheet};
+use rust_xlsxwriter::{Format, Workbook};
+use std::collections::HashSet;
+
+// Почему загруженная скважина не попала в отчет
+fn reason(well: &str, last_year: i32, start_year: i32, selected: &HashSet<String>) -> String {
+    if !selected.contains(well) {
+        "не выбрана".to_string()
+    } else if last_year < start_year {
+        format!("нет записей с {} года", start_year)
+    } else {
+        "все записи в исключенных состояниях".to_string()
+    }
+}
+
+// Лист Excluded: фильтры выгрузки и скважины из файла, которых нет в отчете,
+// чтобы проверяющий видел, что ничего не потерялось случайно
+pub fn write_excluded_sheet(
+    workbook: &mut Workbook,
+    constant_memory: bool,
+    data: &Dataset,
+    start_year: i32,
+    selected_wells: &HashSet<String>,
+    well_groups: &[&[RecordRef]],
+    options: &ExportOptions,
+) -> Result<(), WellDataError> {
+    let sql = format!(
+        "SELECT well_name, COUNT(*), MIN(year_sheet), MAX(year_sheet) FROM {} \
+         GROUP BY well_name ORDER BY well_name",
+        query::TABLE
+    );
+    let loaded = query::run(data, &sql)?.rows;
+    let exported: HashSet<&str> = well_groups.iter().map(|g| g[0].well_name).collect();
+
+    let statuses: Vec<&str> = options
+        .excluded_statuses
+        .iter()
+        .map(|s| s.label())
+        .collect();
+    let filters = [
+        ("Начальный год", start_year.to_string()),
+        (
+            "Исключенные состояния",
+            if statuses.is_empty() {
+                "нет".to_string()
+            } else {
+                statuses.join(", ")
+            },
+        ),
+        (
+            "Скважин в отчете",
+            format!("{} из {}", exported.len(), loaded.len()),
+        ),
+    ];
+
+    let worksheet = add_sheet(workbook, constant_memory).set_name("Excluded")?;
+    let bold = Format::new().set_bold();
+    for (i, (label, value)) in filters.iter().enumerate() {
+        worksheet.write_string_with_format(i as u32, 0, *label, &bold)?;
+        worksheet.write_string(i as u32, 1, value)?;
+    }
+
+    let header_row = filters.len() as u32 + 1;
+    for (col, title) in ["Скважина", "Записей в файле", "Годы", "Причина"]
+        .iter()
+        .enumerate()
+    {
+        worksheet.write_string_with_format(header_row, col as u16, *title, &bold)?;
+    }
+    let mut row = header_row + 1;
+    for values in &loaded {
+        let [
+            Value::Text(well),
+            Value::Number(count),
+            Value::Number(first),
+            Value::Number(last),
+        ] = values.as_slice()
+        else {
+            continue;
+        };
+        if exported.contains(well.as_str()) {
+            continue;
+        }
+        let (first, last) = (*first as i32, *last as i32);
+        let years = if first == last {
+            first.to_string()
+        } else {
+            format!("{}-{}", first, last)
+        };
+        worksheet.write_string(row, 0, well)?;
+        worksheet.write_number(row, 1, *count)?;
+        worksheet.write_string(row, 2, years)?;
+        worksheet.write_string(row, 3, reason(well, last, start_year, selected_wells))?;
+        row += 1;
+    }
+    if row == header_row + 1 {
+        worksheet.write_string(row, 0, "Все загруженные скважины вошли в отчет")?;
+    }
+    worksheet.set_column_width(0, 24)?;
+    worksheet.set_column_width(1, 16)?;
+    worksheet.set_column_width(3, 36)?;
+    worksheet.set_freeze_panes(header_row + 1, 0)?;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use crate::add_sheet;
+use crate::calendar::days_in_month;
+use crate::summary::MonthRow;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+
+// Сколько последних месяцев с добычей берется для подбора кривой
+const FIT_MONTHS: usize = 12;
+// Меньше трех точек - не кривая
+const MIN_POINTS: usize = 3;
+
+// Экспоненциальное падение дебита: q(k) = rate * exp(-decline * k), k - месяцы
+// после последнего месяца с данными
+#[derive(Debug, Clone, Copy)]
+pub struct DeclineFit {
+    // Дебит по подобранной кривой в последнем месяце
+    pub rate: f64,
+    // Доля падения в месяц (непрерывная); рост дебита не экстраполируется
+    pub decline: f64,
+    pub last_month: (i32, u32),
+    pub points: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ForecastMonth {
+    pub month: (i32, u32),
+    pub rate: f64,
+    // Добыча за месяц: дебит в сутки на число дней
+    pub volume: f64,
+    // С начала прогноза
+    pub cumulative: f64,
+}
+
+fn month_index((year, month): (i32, u32)) -> i64 {
+    year as i64 * 12 + month as i64 - 1
+}
+
+fn month_from_index(index: i64) -> (i32, u32) {
+    (index.div_euclid(12) as i32, index.rem_euclid(12) as u32 + 1)
+}
+
+// Подбор ln q = a + b * t методом наименьших квадратов по среднемесячным
+// дебитам скважины (колонка well сводки по месяцам)
+pub fn fit(months: &[MonthRow], well: usize) -> Option<DeclineFit> {
+    let points: Vec<(f64, f64)> = months
+        .iter()
+        .filter_map(|(month, values)| {
+            let q = values.get(well).copied().flatten()?;
+            (q > 0.0).then(|| (month_index(*month) as f64, q.ln()))
+        })
+        .collect();
+    let points = &points[points.len().saturating_sub(FIT_MONTHS)..];
+    if points.len() < MIN_POINTS {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (t, y) in points {
+        cov += (t - mean_t) * (y - mean_y);
+        var += (t - mean_t) * (t - mean_t);
+    }
+    let slope = if var > 0.0 { cov / var } else { 0.0 };
+    let last_t = points.last()?.0;
+    Some(DeclineFit {
+        rate: (mean_y + slope * (last_t - mean_t)).exp(),
+        decline: (-slope).max(0.0),
+        last_month: month_from_index(last_t as i64),
+        points: points.len(),
+    })
+}
+
+// Прогноз на months месяцев вперед
+pub fn project(fit: &DeclineFit, months: u32) -> Vec<ForecastMonth> {
+    let start = month_index(fit.last_month);
+    let mut cumulative = 0.0;
+    (1..=months as i64)
+        .map(|k| {
+            let month = month_from_index(start + k);
+            let rate = fit.rate * (-fit.decline * k as f64).exp();
+            let volume = rate * days_in_month(month);
+            cumulative += volume;
+            ForecastMonth {
+                month,
+                rate,
+                volume,
+                cumulative,
+            }
+        })
+        .collect()
+}
+
+// Лист Forecast: прогноз по каждой скважине подряд, справа - параметры кривых.
+// Скважины, по которым кривую не подобрать, есть только в параметрах
+pub fn write_forecast_sheet(
+    workbook: &mut Workbook,
+    constant_memory: bool,
+    // Среднемесячный дебит нефти из frame::monthly
+    months: &[MonthRow],
+    wells: &[&str],
+    horizon: u32,
+) -> Result<(), XlsxError> {
+    let worksheet = add_sheet(workbook, constant_memory).set_name("Forecast")?;
+    let bold = Format::new().set_bold();
+    let number = Format::new().set_num_format("0.00");
+    let fits: Vec<Option<DeclineFit>> = (0..wells.len()).map(|i| fit(months, i)).collect();
+
+    let headers = [
+        "Скважина",
+        "Месяц",
+        "Дебит нефти",
+        "Добыча за месяц",
+        "Накопленная добыча",
+        "",
+        "Скважина",
+        "Дебит в последнем месяце",
+        "Падение, %/мес",
+        "Месяцев в подборе",
+    ];
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *header, &bold)?;
+    }
+
+    let forecast: Vec<(&str, ForecastMonth)> = wells
+        .iter()
+        .zip(&fits)
+        .filter_map(|(well, fit)| fit.as_ref().map(|fit| (*well, fit)))
+        .flat_map(|(well, fit)| project(fit, horizon).into_iter().map(move |p| (well, p)))
+        .collect();
+    // Прогноз и параметры пишутся в одном проходе по строкам:
+    // в потоковом режиме строки идут только сверху вниз
+    for i in 0..forecast.len().max(wells.len()) {
+        let row = i as u32 + 1;
+        if let Some((well, f)) = forecast.get(i) {
+            let (year, month) = f.month;
+            worksheet.write_string(row, 0, *well)?;
+            worksheet.write_string(row, 1, format!("{}-{:02}", year, month))?;
+            worksheet.write_number_with_format(row, 2, f.rate, &number)?;
+            worksheet.write_number_with_format(row, 3, f.volume, &number)?;
+            worksheet.write_number_with_format(row, 4, f.cumulative, &number)?;
+        }
+        if let (Some(well), Some(fit)) = (wells.get(i), fits.get(i)) {
+            worksheet.write_string(row, 6, *well)?;
+            match fit {
+                Some(fit) => {
+                    worksheet.write_number_with_format(row, 7, fit.rate, &number)?;
+                    worksheet.write_number_with_format(
+                        row,
+                        8,
+                        (1.0 - (-fit.decline).exp()) * 100.0,
+                        &number,
+                    )?;
+                    worksheet.write_number(row, 9, fit.points as f64)?;
+                }
+                None => {
+                    worksheet.write_string(row, 7, "мало данных")?;
+                }
+            }
+        }
+    }
+
+    worksheet.set_column_width(0, 20)?;
+    worksheet.set_column_width(6, 20)?;
+    worksheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
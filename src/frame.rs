@@ -0,0 +1,205 @@
+use crate::Parameter;
+use crate::calendar::{self, BalanceRow, Sample};
+use crate::status::WellStatus;
+use crate::summary::{Aggregate, MonthRow};
+use polars::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+// Движок фильтрации и агрегации на Polars.
+// Хранилище (store.rs) отдает DataFrame, запросы идут по нему,
+// а наружу возвращаются номера строк или уже посчитанные значения
+
+pub const ROW: &str = "row";
+pub const WELL: &str = "well_name";
+pub const DATE: &str = "date";
+pub const PD_LIQ: &str = "pd_liq";
+pub const PD_OIL: &str = "pd_oil";
+pub const TEMPERATURE: &str = "temperature";
+pub const P_BOTTOM: &str = "p_bottom";
+pub const P_HEAD: &str = "p_head";
+pub const FREQUENCY: &str = "frequency";
+pub const CHOKE: &str = "choke";
+pub const INJECTION: &str = "injection";
+pub const STATUS: &str = "status";
+pub const WELL_TYPE: &str = "well_type";
+pub const YEAR: &str = "year_sheet";
+pub const QUARTER: &str = "quarter";
+pub const OUT_OF_BOUNDS: &str = "out_of_bounds";
+
+pub fn parameter_column(parameter: Parameter) -> &'static str {
+    match parameter {
+        Parameter::PdLiq => PD_LIQ,
+        Parameter::PdOil => PD_OIL,
+        Parameter::Temperature => TEMPERATURE,
+        Parameter::PBottom => P_BOTTOM,
+        Parameter::PHead => P_HEAD,
+        Parameter::Frequency => FREQUENCY,
+        Parameter::Choke => CHOKE,
+        Parameter::Injection => INJECTION,
+    }
+}
+
+// Как select обходится с повторами: для листа параметров выгрузки
+pub const DEDUP_POLICY: &str =
+    "полные дубли строк удаляются, остается первая; сортировка по скважине и дате";
+
+// Строки для выгрузки: годы начиная с start_year, только выбранные скважины,
+// без полных дублей (одна и та же строка на двух листах), по скважине и дате
+pub fn select(
+    frame: DataFrame,
+    start_year: i32,
+    wells: &HashSet<String>,
+) -> PolarsResult<DataFrame> {
+    let wells = Series::new(
+        "wells".into(),
+        wells.iter().map(String::as_str).collect::<Vec<_>>(),
+    );
+    frame
+        .lazy()
+        .filter(
+            col(YEAR)
+                .gt_eq(lit(start_year))
+                .and(col(WELL).is_in(lit(wells).implode(), false)),
+        )
+        .unique_stable(
+            Some(cols([
+                WELL,
+                DATE,
+                PD_LIQ,
+                PD_OIL,
+                TEMPERATURE,
+                P_BOTTOM,
+                P_HEAD,
+                FREQUENCY,
+                CHOKE,
+                INJECTION,
+                STATUS,
+                WELL_TYPE,
+            ])),
+            UniqueKeepStrategy::First,
+        )
+        .sort(
+            [WELL, DATE],
+            SortMultipleOptions::default().with_maintain_order(true),
+        )
+        .collect()
+}
+
+pub fn row_indices(frame: &DataFrame) -> PolarsResult<Vec<usize>> {
+    Ok(frame
+        .column(ROW)?
+        .idx()?
+        .into_no_null_iter()
+        .map(|i| i as usize)
+        .collect())
+}
+
+// Сводка по месяцам: для каждого (год, месяц) - значение по каждой скважине из wells
+pub fn monthly(
+    frame: &DataFrame,
+    wells: &[&str],
+    parameter: Parameter,
+    aggregate: Aggregate,
+) -> PolarsResult<Vec<MonthRow>> {
+    let value = col(parameter_column(parameter));
+    let agg = match aggregate {
+        Aggregate::Average => value.clone().mean(),
+        Aggregate::Sum => value.clone().sum(),
+        Aggregate::Min => value.clone().min(),
+        Aggregate::Max => value.clone().max(),
+        Aggregate::Count => value.clone().count(),
+        Aggregate::CalendarDay => return calendar_monthly(frame, wells, value),
+    };
+
+    let grouped = frame
+        .clone()
+        .lazy()
+        .filter(col(DATE).is_not_null().and(value.is_not_null()))
+        .group_by([
+            col(DATE).dt().year().alias("year"),
+            col(DATE).dt().month().cast(DataType::Int32).alias("month"),
+            col(WELL),
+        ])
+        .agg([agg.cast(DataType::Float64).alias("value")])
+        .sort(["year", "month"], SortMultipleOptions::default())
+        .collect()?;
+
+    let years = grouped.column("year")?.i32()?;
+    let months = grouped.column("month")?.i32()?;
+    let names = grouped.column(WELL)?.str()?;
+    let values = grouped.column("value")?.f64()?;
+
+    let well_col: HashMap<&str, usize> = wells.iter().enumerate().map(|(i, w)| (*w, i)).collect();
+    let mut result: Vec<MonthRow> = Vec::new();
+    for i in 0..grouped.height() {
+        let (Some(year), Some(month), Some(name)) = (years.get(i), months.get(i), names.get(i))
+        else {
+            continue;
+        };
+        let Some(&col) = well_col.get(name) else {
+            continue;
+        };
+        let key = (year, month as u32);
+        if result.last().is_none_or(|(k, _)| *k != key) {
+            result.push((key, vec![None; wells.len()]));
+        }
+        if let Some((_, row)) = result.last_mut() {
+            row[col] = values.get(i);
+        }
+    }
+    Ok(result)
+}
+
+// Ряды замеров для calendar.rs: скважина, дата в мс, значение и признак работы,
+// по скважине и дате
+fn calendar_series(frame: &DataFrame, value: Expr) -> PolarsResult<DataFrame> {
+    frame
+        .clone()
+        .lazy()
+        .filter(col(DATE).is_not_null().and(value.clone().is_not_null()))
+        .select([
+            col(WELL),
+            col(DATE).cast(DataType::Int64),
+            value.cast(DataType::Float64).alias("value"),
+            col(STATUS)
+                .is_null()
+                .or(col(STATUS).eq(lit(WellStatus::Producing.code())))
+                .alias("producing"),
+        ])
+        .sort([WELL, DATE], SortMultipleOptions::default())
+        .collect()
+}
+
+fn samples(series: &DataFrame) -> PolarsResult<Vec<Sample<'_>>> {
+    let names = series.column(WELL)?.str()?;
+    let dates = series.column(DATE)?.i64()?;
+    let values = series.column("value")?.f64()?;
+    let producing = series.column("producing")?.bool()?;
+    Ok((0..series.height())
+        .filter_map(|i| {
+            Some(Sample {
+                well: names.get(i)?,
+                date_ms: dates.get(i)?,
+                rate: values.get(i)?,
+                producing: producing.get(i).unwrap_or(true),
+            })
+        })
+        .collect())
+}
+
+// Календарный дебит считается по интервалам между замерами (calendar.rs),
+// а не агрегатом Polars
+fn calendar_monthly(frame: &DataFrame, wells: &[&str], value: Expr) -> PolarsResult<Vec<MonthRow>> {
+    let series = calendar_series(frame, value)?;
+    Ok(calendar::monthly(&samples(&series)?, wells))
+}
+
+// Добыча и дни работы по месяцам для листа Rates
+pub fn balances(
+    frame: &DataFrame,
+    wells: &[&str],
+    parameter: Parameter,
+) -> PolarsResult<Vec<BalanceRow>> {
+    let series = calendar_series(frame, col(parameter_column(parameter)))?;
+    Ok(calendar::balances(&samples(&series)?, wells))
+}
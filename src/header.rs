@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, HashMap};
+
+// Обозначения, по которым строка заголовка узнается как строка единиц
+const UNIT_MARKS: [&str; 18] = [
+    "м3", "м³", "т/", "/сут", "°", "атм", "мпа", "кгс", "гц", "мм", "%", "bar", "psi", "bbl", "m3",
+    "degc", "hz", "сут",
+];
+
+fn is_unit(cell: &str) -> bool {
+    let cell = cell.trim().to_lowercase();
+    let bracketed = cell.starts_with('(') && cell.ends_with(')')
+        || cell.starts_with('[') && cell.ends_with(']');
+    cell.chars().count() <= 16 && (bracketed || UNIT_MARKS.iter().any(|m| cell.contains(m)))
+}
+
+// Все непустые ячейки строки похожи на единицы измерения
+pub fn looks_like_units(row: &[String]) -> bool {
+    let mut cells = row.iter().filter(|c| !c.trim().is_empty()).peekable();
+    cells.peek().is_some() && cells.all(|c| is_unit(c))
+}
+
+// Заголовок из нескольких строк: имена колонок и единицы по колонкам
+#[derive(Debug, Default)]
+pub struct Header {
+    // Сколько строк занял заголовок вместе со строкой единиц
+    pub lines: usize,
+    pub names: Vec<String>,
+    pub units: Vec<Option<String>>,
+}
+
+impl Header {
+    // rows - строки заголовка сверху вниз. Имя колонки - непустые ячейки через
+    // пробел; объединенная ячейка сверху продолжается вправо, пока под ней
+    // есть подписи. Строка единиц в имя не входит
+    pub fn parse(rows: &[Vec<String>]) -> Self {
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let units_row = rows
+            .iter()
+            .skip(1)
+            .position(|row| looks_like_units(row))
+            .map(|i| i + 1);
+        let cell = |r: usize, c: usize| rows[r].get(c).map(|s| s.trim()).filter(|s| !s.is_empty());
+
+        let name_rows: Vec<usize> = (0..rows.len()).filter(|r| Some(*r) != units_row).collect();
+        let mut names = vec![Vec::new(); width];
+        for (k, &r) in name_rows.iter().enumerate() {
+            let below = name_rows.get(k + 1).copied();
+            let mut carried: Option<&str> = None;
+            for (c, name) in names.iter_mut().enumerate() {
+                let value = match cell(r, c) {
+                    Some(v) => Some(v),
+                    None if below.is_some_and(|b| cell(b, c).is_some()) => carried,
+                    None => None,
+                };
+                carried = value;
+                name.extend(value);
+            }
+        }
+        Self {
+            lines: rows.len(),
+            names: names.into_iter().map(|parts| parts.join(" ")).collect(),
+            units: (0..width)
+                .map(|c| units_row.and_then(|r| cell(r, c)).map(str::to_string))
+                .collect(),
+        }
+    }
+
+    pub fn columns(&self) -> HashMap<String, usize> {
+        self.names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| !name.is_empty())
+            .map(|(i, name)| (name.clone(), i))
+            .collect()
+    }
+
+    // Единицы по именам колонок - как в файле и стандартным
+    pub fn units_by_name(&self, col_map: &HashMap<String, usize>) -> BTreeMap<String, String> {
+        col_map
+            .iter()
+            .filter_map(|(name, &i)| Some((name.clone(), self.units.get(i)?.clone()?)))
+            .collect()
+    }
+}
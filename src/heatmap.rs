@@ -0,0 +1,187 @@
+use crate::dashboard::SelectionKey;
+use crate::error::WellDataError;
+use crate::frame;
+use crate::map::rate_color;
+use crate::store::Dataset;
+use crate::summary::{Aggregate, MonthRow};
+use crate::tasks::{TaskKind, TaskManager};
+use crate::{LoaderMessage, Parameter};
+use eframe::egui;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const CELL_HEIGHT: f32 = 14.0;
+const MIN_CELL_WIDTH: f32 = 6.0;
+const LABEL_WIDTH: f32 = 110.0;
+
+// Тепловая карта: скважины по вертикали, месяцы по горизонтали,
+// цвет - среднемесячный PdOil. Пустая клетка - нет данных за месяц
+#[derive(Debug, Default)]
+pub struct HeatmapPanel {
+    pub open: bool,
+    key: Option<SelectionKey>,
+    pending: Option<SelectionKey>,
+    wells: Vec<String>,
+    months: Vec<MonthRow>,
+    error: Option<String>,
+}
+
+// Скважины по порядку и среднемесячный PdOil по ним
+pub fn compute(
+    data: &Dataset,
+    start_year: i32,
+    selected: &HashSet<String>,
+) -> Result<(Vec<String>, Vec<MonthRow>), WellDataError> {
+    let mut wells: Vec<&str> = selected.iter().map(String::as_str).collect();
+    wells.sort();
+    let frame = data.select(start_year, selected)?.to_frame()?;
+    let months = frame::monthly(&frame, &wells, Parameter::PdOil, Aggregate::Average)?;
+    Ok((wells.into_iter().map(str::to_string).collect(), months))
+}
+
+impl HeatmapPanel {
+    // Как и сводка, пересчитывается фоновой задачей только при смене выбора,
+    // года или данных; до ответа видна прежняя карта
+    pub fn refresh(
+        &mut self,
+        tasks: &mut TaskManager,
+        data: &Arc<Dataset>,
+        generation: u64,
+        start_year: i32,
+        selected: &HashSet<String>,
+    ) {
+        let key = SelectionKey::new(generation, start_year, selected);
+        if self.pending.as_ref().or(self.key.as_ref()) == Some(&key) {
+            return;
+        }
+        let (data, wells, request) = (Arc::clone(data), selected.clone(), key.clone());
+        tasks.spawn(
+            TaskKind::Chart,
+            "Тепловая карта".to_string(),
+            move |_tx, _cancel| {
+                let result = compute(&data, start_year, &wells).map_err(|e| e.to_string());
+                Ok(LoaderMessage::Heatmap(request, result))
+            },
+        );
+        self.pending = Some(key);
+    }
+
+    // Ответ фоновой задачи; устаревший не показывается
+    pub fn finish(
+        &mut self,
+        key: SelectionKey,
+        result: Result<(Vec<String>, Vec<MonthRow>), String>,
+    ) {
+        if self.pending.as_ref() != Some(&key) {
+            return;
+        }
+        match result {
+            Ok((wells, months)) => {
+                self.wells = wells;
+                self.months = months;
+                self.error = None;
+            }
+            Err(e) => {
+                self.wells.clear();
+                self.months.clear();
+                self.error = Some(e);
+            }
+        }
+        self.key = self.pending.take();
+    }
+
+    pub fn show(&self, ui: &mut egui::Ui) {
+        if self.pending.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Пересчет...");
+            });
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, format!("Не удалось посчитать: {}", e));
+            return;
+        }
+        if self.months.is_empty() {
+            ui.label("Нет записей по выбранным скважинам за выбранный период");
+            return;
+        }
+        let max = self
+            .months
+            .iter()
+            .flat_map(|(_, values)| values.iter().flatten())
+            .fold(0.0, |m: f64, v| m.max(*v))
+            .max(1e-9);
+        ui.label(format!(
+            "Месяцев: {}, скважин: {}. Синий - 0, красный - {:.1}",
+            self.months.len(),
+            self.wells.len(),
+            max
+        ));
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            let cell_width = ((ui.available_width() - LABEL_WIDTH) / self.months.len() as f32)
+                .max(MIN_CELL_WIDTH);
+            let size = egui::vec2(
+                LABEL_WIDTH + cell_width * self.months.len() as f32,
+                CELL_HEIGHT * (self.wells.len() + 1) as f32,
+            );
+            let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+            let origin = response.rect.left_top();
+            let font = egui::FontId::proportional(10.0);
+            let text_color = ui.visuals().text_color();
+            let empty = ui.visuals().extreme_bg_color;
+
+            for (row, well) in self.wells.iter().enumerate() {
+                painter.text(
+                    origin + egui::vec2(0.0, CELL_HEIGHT * (row as f32 + 0.5)),
+                    egui::Align2::LEFT_CENTER,
+                    well,
+                    font.clone(),
+                    text_color,
+                );
+            }
+            for (col, ((year, month), values)) in self.months.iter().enumerate() {
+                let x = origin.x + LABEL_WIDTH + cell_width * col as f32;
+                for (row, value) in values.iter().enumerate() {
+                    let cell = egui::Rect::from_min_size(
+                        egui::pos2(x, origin.y + CELL_HEIGHT * row as f32),
+                        egui::vec2(cell_width, CELL_HEIGHT),
+                    )
+                    .shrink(0.5);
+                    let color = value.map_or(empty, |v| rate_color(v / max));
+                    painter.rect_filled(cell, 0.0, color);
+                }
+                // Подпись - у каждого января и у первого месяца
+                if col == 0 || *month == 1 {
+                    painter.text(
+                        egui::pos2(x, origin.y + CELL_HEIGHT * self.wells.len() as f32),
+                        egui::Align2::LEFT_TOP,
+                        format!("{}-{:02}", year, month),
+                        font.clone(),
+                        text_color,
+                    );
+                }
+            }
+
+            if let Some(hover) = response.hover_pos() {
+                let col = ((hover.x - origin.x - LABEL_WIDTH) / cell_width).floor();
+                let row = ((hover.y - origin.y) / CELL_HEIGHT).floor();
+                if col >= 0.0 && row >= 0.0 {
+                    let (col, row) = (col as usize, row as usize);
+                    if let (Some(((year, month), values)), Some(well)) =
+                        (self.months.get(col), self.wells.get(row))
+                    {
+                        let value = match values[row] {
+                            Some(v) => format!("{:.1}", v),
+                            None => "нет данных".to_string(),
+                        };
+                        response.on_hover_text_at_pointer(format!(
+                            "{}, {}-{:02}: {}",
+                            well, year, month, value
+                        ));
+                    }
+                }
+            }
+        });
+    }
+}
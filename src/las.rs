@@ -0,0 +1,191 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use crate::rules::{ValidationRules, Violation, ViolationKind};
+use crate::store::Dataset;
+use crate::timing::Timings;
+use crate::{ImportOptions, LoadedData};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    pub mnemonic: String,
+    pub unit: String,
+}
+
+// Каротаж одной скважины из LAS-файла. Первая кривая - индекс (глубина или время),
+// в каждой строке rows значения всех кривых по порядку curves
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WellLog {
+    pub well_name: String,
+    pub curves: Vec<Curve>,
+    pub rows: Vec<Vec<Option<f64>>>,
+}
+
+pub fn is_las_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("las"))
+}
+
+// Строка секции LAS 2.0: "MNEM.UNIT  DATA : DESCRIPTION"
+struct HeaderLine {
+    mnemonic: String,
+    unit: String,
+    data: String,
+}
+
+fn parse_header_line(line: &str) -> Option<HeaderLine> {
+    let (mnemonic, rest) = line.split_once('.')?;
+    // Единица измерения идет сразу за точкой, до первого пробела
+    let (unit, rest) = match rest.find(char::is_whitespace) {
+        Some(pos) => rest.split_at(pos),
+        None => (rest, ""),
+    };
+    // Описание после последнего двоеточия не нужно
+    let data = rest.rfind(':').map_or(rest, |pos| &rest[..pos]);
+    Some(HeaderLine {
+        mnemonic: mnemonic.trim().to_string(),
+        unit: unit.trim().to_string(),
+        data: data.trim().to_string(),
+    })
+}
+
+// Данные ~A читаются потоком чисел и режутся по числу кривых,
+// поэтому режим WRAP YES разбирается так же, как NO
+pub fn parse_las(text: &str, source: &str) -> Result<WellLog, String> {
+    let mut section = ' ';
+    let mut well_name = String::new();
+    let mut null_value: Option<f64> = None;
+    let mut curves = Vec::new();
+    let mut values: Vec<Option<f64>> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('~') {
+            section = name.chars().next().unwrap_or(' ').to_ascii_uppercase();
+            continue;
+        }
+
+        match section {
+            'V' => {
+                if let Some(h) = parse_header_line(line)
+                    && h.mnemonic.eq_ignore_ascii_case("VERS")
+                    && !h.data.starts_with('2')
+                {
+                    return Err(format!("поддерживается LAS 2.0, в файле версия {}", h.data));
+                }
+            }
+            'W' => {
+                if let Some(h) = parse_header_line(line) {
+                    match h.mnemonic.to_ascii_uppercase().as_str() {
+                        "WELL" => well_name = h.data,
+                        "NULL" => null_value = h.data.parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+            'C' => {
+                if let Some(h) = parse_header_line(line) {
+                    curves.push(Curve {
+                        mnemonic: h.mnemonic,
+                        unit: h.unit,
+                    });
+                }
+            }
+            'A' => {
+                for token in line.split_whitespace() {
+                    let v: f64 = token
+                        .parse()
+                        .map_err(|_| format!("не число в секции ~A: '{}'", token))?;
+                    values.push(if Some(v) == null_value { None } else { Some(v) });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if curves.is_empty() {
+        return Err("в файле нет секции ~C с описанием кривых".to_string());
+    }
+    if well_name.is_empty() {
+        well_name = Path::new(source)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+    }
+
+    let rows = values
+        .chunks_exact(curves.len())
+        .map(<[_]>::to_vec)
+        .collect();
+    Ok(WellLog {
+        well_name,
+        curves,
+        rows,
+    })
+}
+
+pub fn read_las_file(
+    path: &Path,
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    progress.report("Чтение LAS...", 0, 0);
+
+    let mut timings = Timings::new(format!("Загрузка {}", file_name));
+    let bytes = timings
+        .measure("Чтение файла", || {
+            import.retry.run("чтение файла", progress, cancel, || {
+                std::fs::read(path)
+            })
+        })
+        .map_err(|e| WellDataError::open_failed(path, e))?;
+    // LAS из старых систем бывают в cp1251
+    let text = timings.measure("Перекодировка", || {
+        crate::csv_import::TextEncoding::detect(&bytes).decode(&bytes)
+    });
+    cancel.check()?;
+    let log = timings
+        .measure("Разбор", || parse_las(&text, &file_name))
+        .map_err(WellDataError::BadContent)?;
+
+    let mut dataset = Dataset::new(import.storage)?;
+    dataset.finish()?;
+    let mut violations = Vec::new();
+    let mut wells = Vec::new();
+    if rules.is_forbidden(&log.well_name) {
+        violations.push(Violation {
+            kind: ViolationKind::ForbiddenWell,
+            sheet: file_name,
+            row: None,
+            well: Some(log.well_name),
+            details: "Запрещенное имя скважины, файл пропущен".to_string(),
+        });
+    } else {
+        wells.push(log.well_name.clone());
+        dataset.logs.push(log);
+    }
+
+    progress.timings(timings.finish());
+    Ok(LoadedData {
+        records: dataset,
+        years: Vec::new(),
+        wells,
+        violations,
+        warnings: Vec::new(),
+        units: BTreeMap::new(),
+    })
+}
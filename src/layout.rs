@@ -0,0 +1,186 @@
+use crate::cancel::CancellationToken;
+use crate::error::{Location, ResultExt, WellDataError};
+use crate::progress::ProgressSink;
+use crate::store::RecordRef;
+use crate::units::{self, UnitsHeader};
+use crate::verify::PlannedSheet;
+use crate::{
+    ExportOptions, INJECTION_COL, NAME_COL, PRODUCTION_COLUMNS, STATUS_COL, ValueColumn, add_sheet,
+    filter_and_freeze,
+};
+use rust_xlsxwriter::{Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Столбцов в листе Excel
+const MAX_COLUMNS: u32 = 16384;
+
+// Как раскладывать записи по листам отчета
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SheetLayout {
+    // Лист на скважину
+    #[default]
+    Wells,
+    // Как в исходной книге: лист на год, на нем все выбранные скважины.
+    // Для макросов, которые ждут исходную раскладку
+    Years,
+    // Лист на скважину, параметры по строкам, даты по столбцам - как в
+    // старых шаблонах отчетов
+    Transposed,
+}
+
+impl SheetLayout {
+    pub const ALL: [SheetLayout; 3] = [
+        SheetLayout::Wells,
+        SheetLayout::Years,
+        SheetLayout::Transposed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SheetLayout::Wells => "Лист на скважину",
+            SheetLayout::Years => "Лист на год, как в исходной книге",
+            SheetLayout::Transposed => "Лист на скважину, даты по столбцам",
+        }
+    }
+}
+
+// Ячейка листа скважины: в повернутом листе строка записи становится столбцом
+pub fn cell(transposed: bool, row: u32, col: u16) -> (u32, u16) {
+    if transposed {
+        (col as u32, row as u16)
+    } else {
+        (row, col)
+    }
+}
+
+// Даты по столбцам: первые столбцы заняты заголовком
+pub fn check_transposed(records: usize, first_col: u32) -> Result<(), WellDataError> {
+    let max = MAX_COLUMNS - first_col;
+    if records > max as usize {
+        return Err(WellDataError::TooLarge(format!(
+            "{} дат не помещаются по столбцам (не больше {}); выберите лист на скважину",
+            records, max
+        )));
+    }
+    Ok(())
+}
+
+// Потоковый лист пишется только сверху вниз, а в повернутом листе каждая
+// запись - столбец через все строки: уже сброшенные строки не дописать
+pub fn check_streaming(layout: SheetLayout, constant_memory: bool) -> Result<(), WellDataError> {
+    if constant_memory && layout == SheetLayout::Transposed {
+        return Err(WellDataError::Config(
+            "даты по столбцам нельзя записать потоково; выберите лист на \
+             скважину или запись в памяти"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Листы по годам исходной книги: скважины в порядке выгрузки, внутри - по дате.
+// Возвращает план листов для проверки записанной книги
+#[allow(clippy::too_many_arguments)]
+pub fn write_year_sheets(
+    workbook: &mut Workbook,
+    constant_memory: bool,
+    well_groups: &[&[RecordRef]],
+    optional: &[ValueColumn],
+    has_status: bool,
+    options: &ExportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Vec<PlannedSheet>, WellDataError> {
+    let mut years: BTreeMap<i32, Vec<&RecordRef>> = BTreeMap::new();
+    for record in well_groups.iter().flat_map(|g| g.iter()) {
+        years.entry(record.year_sheet).or_default().push(record);
+    }
+    let has_injection = well_groups
+        .iter()
+        .flat_map(|g| g.iter())
+        .any(|r| r.injection.is_some());
+    let injection: ValueColumn = (INJECTION_COL, |r| r.injection);
+    let columns: Vec<ValueColumn> = PRODUCTION_COLUMNS
+        .iter()
+        .chain(optional)
+        .chain(has_injection.then_some(&injection))
+        .copied()
+        .collect();
+    let mut headers = vec![NAME_COL, "Date"];
+    headers.extend(columns.iter().map(|(name, _)| *name));
+    let status_col = headers.len() as u16;
+    if has_status {
+        headers.push(STATUS_COL);
+    }
+    let units: Vec<Option<String>> = headers
+        .iter()
+        .map(|h| units::column_unit(h, &options.source_units, options.rate_units))
+        .collect();
+    let units_row = options.units_row();
+    let first_row = 1 + u32::from(units_row);
+
+    let mut plan = Vec::new();
+    for (idx, (year, records)) in years.iter().enumerate() {
+        cancel.check()?;
+        let sheet_name = year.to_string();
+        progress.report(&format!("Лист {}", sheet_name), idx, years.len());
+        let worksheet = add_sheet(workbook, constant_memory)
+            .set_name(&sheet_name)
+            .at(|| Location::new("Запись").sheet(&sheet_name))?;
+        for (col, header) in headers.iter().enumerate() {
+            let title = match options.units_header {
+                UnitsHeader::InHeader => units::with_unit(header, units[col].as_deref()),
+                _ => header.to_string(),
+            };
+            worksheet.write_string(0, col as u16, title)?;
+            if units_row && let Some(unit) = &units[col] {
+                worksheet.write_string(1, col as u16, unit)?;
+            }
+        }
+        for (i, record) in records.iter().enumerate() {
+            if i % 5000 == 0 {
+                cancel.check()?;
+            }
+            let row_idx = i as u32 + first_row;
+            let mut write_row = || -> Result<(), XlsxError> {
+                worksheet.write_string(row_idx, 0, record.well_name)?;
+                if let Some(d) = record.date {
+                    worksheet.write_string(
+                        row_idx,
+                        1,
+                        d.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    )?;
+                }
+                for (k, (_, get)) in columns.iter().enumerate() {
+                    if let Some(v) = get(record) {
+                        worksheet.write_number(row_idx, 2 + k as u16, v)?;
+                    }
+                }
+                if let Some(s) = record.status {
+                    worksheet.write_string(row_idx, status_col, s.label())?;
+                }
+                Ok(())
+            };
+            write_row().at(|| Location::new("Запись").sheet(&sheet_name).row(row_idx + 1))?;
+        }
+        if options.filter_and_freeze {
+            filter_and_freeze(
+                worksheet,
+                first_row,
+                first_row + records.len() as u32 - 1,
+                headers.len() as u16 - 1,
+                true,
+            )?;
+        }
+        plan.push(PlannedSheet {
+            well: sheet_name.clone(),
+            sheet: sheet_name,
+            rows: records.len(),
+            header_rows: first_row as usize,
+            footer_rows: 0,
+            transposed: false,
+        });
+    }
+    Ok(plan)
+}
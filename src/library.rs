@@ -0,0 +1,164 @@
+use crate::settings::{ColumnMapping, RateUnits};
+use crate::summary::Aggregate;
+use crate::{ExportOptions, Parameter};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const LIBRARY_FILE_NAME: &str = "library.toml";
+
+// Сохраненные запросы и анализы пользователя (папка настроек, library.toml)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Library {
+    pub queries: Vec<SavedQuery>,
+    pub analyses: Vec<SavedAnalysis>,
+    pub profiles: Vec<ExportProfile>,
+    pub import_profiles: Vec<ImportProfile>,
+    // Автор отчетов: свойства книги и титульный лист
+    pub author: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub name: String,
+    pub sql: String,
+}
+
+// Настройки отчета, которые можно применить к любому загруженному файлу
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedAnalysis {
+    pub name: String,
+    // None - первый год файла
+    pub start_year: Option<i32>,
+    // Пусто - все скважины файла
+    pub wells: Vec<String>,
+    pub monthly_summary: bool,
+    pub parameter: Parameter,
+    pub aggregate: Aggregate,
+}
+
+// Именованный набор настроек выгрузки ("Месячный отчет", "Для симулятора").
+// Профилем можно поделиться: он сохраняется в отдельный файл .toml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportProfile {
+    pub name: String,
+    pub options: ExportOptions,
+}
+
+// Профиль из файла .toml; без имени его не отличить от других в списке
+fn load_profile<T: DeserializeOwned>(path: &Path, name: impl Fn(&T) -> &str) -> Result<T, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("не удалось прочитать {}: {}", path.display(), e))?;
+    let profile: T =
+        toml::from_str(&text).map_err(|e| format!("ошибка в {}: {}", path.display(), e))?;
+    if name(&profile).trim().is_empty() {
+        return Err(format!("в {} не указано имя профиля", path.display()));
+    }
+    Ok(profile)
+}
+
+fn save_profile<T: Serialize>(profile: &T, path: &Path) -> Result<(), String> {
+    let text = toml::to_string_pretty(profile).map_err(|e| e.to_string())?;
+    std::fs::write(path, text).map_err(|e| format!("не удалось записать {}: {}", path.display(), e))
+}
+
+impl ExportProfile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_profile(path, |p: &Self| &p.name)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        save_profile(self, path)
+    }
+}
+
+// Раскладка файлов одного подрядчика: как называются колонки, в какой строке
+// заголовок, как записаны даты и в чем дебиты. Выбирается перед загрузкой,
+// передается коллегам файлом .toml, как и профиль выгрузки
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportProfile {
+    pub name: String,
+    pub columns: ColumnMapping,
+    // Номер строки заголовка как в Excel (см. ImportOptions::header_row)
+    pub header_row: u32,
+    // Сколько строк занимают имена колонок
+    pub header_rows: u32,
+    // Формат текстовых дат (chrono, например %d.%m.%y); пусто - распознать самим
+    pub date_format: String,
+    pub units: RateUnits,
+}
+
+impl Default for ImportProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            columns: ColumnMapping::default(),
+            header_row: 1,
+            header_rows: 1,
+            date_format: String::new(),
+            units: RateUnits::default(),
+        }
+    }
+}
+
+impl ImportProfile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_profile(path, |p: &Self| &p.name)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        save_profile(self, path)
+    }
+}
+
+impl Library {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join(crate::APP_DIR_NAME).join(LIBRARY_FILE_NAME))
+    }
+
+    // Файла еще нет - пустая библиотека
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path().filter(|p| p.is_file()) else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("не удалось прочитать {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("ошибка в {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("не найдена папка настроек пользователя")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("не удалось создать {}: {}", dir.display(), e))?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, text)
+            .map_err(|e| format!("не удалось записать {}: {}", path.display(), e))
+    }
+
+    // Запись с тем же именем заменяется
+    pub fn put_query(&mut self, query: SavedQuery) {
+        self.queries.retain(|q| q.name != query.name);
+        self.queries.push(query);
+    }
+
+    pub fn put_analysis(&mut self, analysis: SavedAnalysis) {
+        self.analyses.retain(|a| a.name != analysis.name);
+        self.analyses.push(analysis);
+    }
+
+    pub fn put_profile(&mut self, profile: ExportProfile) {
+        self.profiles.retain(|p| p.name != profile.name);
+        self.profiles.push(profile);
+    }
+
+    pub fn put_import_profile(&mut self, profile: ImportProfile) {
+        self.import_profiles.retain(|p| p.name != profile.name);
+        self.import_profiles.push(profile);
+    }
+}
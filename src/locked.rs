@@ -0,0 +1,97 @@
+use crate::error::WellDataError;
+use rust_xlsxwriter::Workbook;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Windows: ERROR_SHARING_VIOLATION и ERROR_LOCK_VIOLATION - файл открыт в Excel
+#[cfg(windows)]
+const LOCK_ERRORS: [i32; 2] = [32, 33];
+// Unix: EBUSY и ETXTBSY
+#[cfg(unix)]
+const LOCK_ERRORS: [i32; 2] = [16, 26];
+#[cfg(not(any(windows, unix)))]
+const LOCK_ERRORS: [i32; 0] = [];
+
+// Что делается после записи книги (проверка, манифест): при повторной записи
+// занятого файла - то же самое
+pub type AfterSave = Box<dyn FnOnce(&Path) -> Result<(), WellDataError> + Send + Sync>;
+
+// Файл назначения занят другой программой. Готовая книга остается в памяти,
+// чтобы ее можно было записать повторно или под другим именем, не выгружая заново
+pub struct LockedFile {
+    pub path: PathBuf,
+    pub bytes: Vec<u8>,
+    pub after_save: Option<AfterSave>,
+}
+
+impl fmt::Debug for LockedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockedFile")
+            .field("path", &self.path)
+            .field("bytes", &self.bytes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for LockedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "файл {} занят другой программой (открыт в Excel?)",
+            self.path.display()
+        )
+    }
+}
+
+impl Error for LockedFile {}
+
+// Только блокировка другой программой: нет прав или только чтение -
+// повтор не поможет, это обычная ошибка записи
+pub fn is_lock_error(error: &std::io::Error) -> bool {
+    error
+        .raw_os_error()
+        .is_some_and(|code| LOCK_ERRORS.contains(&code))
+}
+
+// Проба записи без изменения файла: существующий открывается на запись, не обрезаясь
+pub fn is_locked(path: &Path) -> bool {
+    path.exists()
+        && std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .is_err_and(|e| is_lock_error(&e))
+}
+
+// Сохранение книги; если файл занят - книга собирается в памяти и возвращается
+// ошибкой LockedFile, шаги после записи добавляет вызывающий
+pub fn save_workbook(workbook: &mut Workbook, path: &Path) -> Result<(), WellDataError> {
+    if is_locked(path) {
+        return Err(WellDataError::Locked(LockedFile {
+            path: path.to_path_buf(),
+            bytes: workbook.save_to_buffer()?,
+            after_save: None,
+        }));
+    }
+    workbook.save(path)?;
+    Ok(())
+}
+
+// Повторная запись готовой книги и шаги после записи. Если файл все еще
+// занят, книга возвращается той же ошибкой LockedFile - ждать следующей попытки
+pub fn write(mut file: LockedFile, path: &Path) -> Result<(), WellDataError> {
+    if let Err(e) = std::fs::write(path, &file.bytes) {
+        if is_lock_error(&e) {
+            file.path = path.to_path_buf();
+            return Err(WellDataError::Locked(file));
+        }
+        return Err(WellDataError::SaveFailed {
+            path: path.to_path_buf(),
+            source: e,
+        });
+    }
+    match file.after_save.take() {
+        Some(after_save) => after_save(path),
+        None => Ok(()),
+    }
+}
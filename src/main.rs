@@ -1,566 +1,5308 @@
+mod allocation;
+mod archive;
+mod audit;
+mod batch;
+mod calendar;
+mod cancel;
+mod checks;
+mod chessboard;
+mod correlation;
+mod cover;
+mod crash;
+mod csv_import;
+mod dashboard;
+mod dates;
+mod detect;
+mod disk;
+mod download;
+mod downtime;
+mod error;
+mod events;
+mod excluded;
+mod forecast;
+mod frame;
+mod header;
+mod heatmap;
+mod las;
+mod layout;
+mod library;
+mod locked;
+mod manifest;
+mod map;
+mod memory;
+mod mer;
+mod missing;
+mod model;
+mod notify;
+mod ofm;
+mod outline;
+mod progress;
+mod query;
+mod queue;
+mod retry;
+mod rules;
+mod s3;
+mod settings;
+mod sftp;
+mod sheet_order;
+mod snapshot;
+mod status;
+mod status_log;
+mod store;
+mod summary;
+mod tasks;
+mod template;
+mod timing;
+mod totals;
+mod units;
+mod verify;
+mod well_list;
+mod well_type;
+mod window_state;
+mod witsml;
+mod year_sheet;
+
 use calamine::{Data, DataType, Reader, Xlsx};
-use chrono::NaiveDateTime;
 use eframe::egui;
+use rayon::prelude::*;
 use rfd::FileDialog;
-use rust_xlsxwriter::Workbook;
-use std::collections::{BTreeSet, HashSet};
-use std::error::Error;
-use std::path::PathBuf;
-use std::sync::mpsc::{Receiver, Sender, channel};
-use std::thread;
+use rust_xlsxwriter::{
+    Chart, ChartDataLabel, ConditionalFormat3ColorScale, ConditionalFormatFormula, Format, Table,
+    TableColumn, Url, Workbook, Worksheet, XlsxError,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use allocation::AllocationTable;
+use archive::ArchiveDialog;
+use audit::{AuditEntry, AuditPanel};
+use calendar::RateMode;
+use cancel::CancellationToken;
+use checks::AnomalyChecks;
+use correlation::{CorrelationMatrix, CorrelationPanel};
+use csv_import::{CsvOptions, CsvPreview, Delimiter, TextEncoding};
+use dashboard::{DashboardPanel, Kpi, SelectionKey};
+use dates::DateStats;
+use detect::ColumnProposal;
+use download::UrlDialog;
+use downtime::DowntimeOptions;
+use error::{ErrorReport, Location, ResultExt, WellDataError};
+use events::EventLog;
+use header::Header;
+use heatmap::HeatmapPanel;
+use layout::SheetLayout;
+use library::{ExportProfile, ImportProfile, Library, SavedAnalysis, SavedQuery};
+use locked::LockedFile;
+use map::{MapColoring, MapPanel, MapTool};
+use missing::{MissingStats, NumberCell};
+use progress::ProgressSink;
+use query::{QueryResult, SqlPanel, Value};
+use queue::{ExportQueue, JobState, QueuedExport};
+use retry::RetryPolicy;
+use rules::{ValidationRules, Violation, ViolationKind};
+use s3::{S3Action, S3Config, S3Dialog, S3Object};
+use settings::{
+    ColumnMapping, DateFormat, NumberLocale, RateUnits, Settings, SetupWizard, WizardStep,
+};
+use sftp::{RemoteEntry, SftpAction, SftpAuth, SftpConfig, SftpDialog};
+use sheet_order::SheetOrder;
+use snapshot::LocalCopy;
+use status::WellStatus;
+use status_log::StatusLog;
+use store::{Dataset, RecordRef, RecordStore};
+use summary::{Aggregate, MonthRow};
+use tasks::{TaskId, TaskKind, TaskManager};
+use timing::Timings;
+use totals::{RateTotal, TotalsRow};
+use units::UnitsHeader;
+use verify::PlannedSheet;
+use well_list::{ListNavigation, SearchQuery, YearFilter};
+use well_type::{TypeExport, WellType};
+use window_state::MonitorProbe;
+use winit::event_loop::EventLoop;
 
+const APP_DIR_NAME: &str = "well-data-collector";
 const NAME_COL: &str = "@Name( )";
 const TEMPERATURE_COL: &str = "Тemperature";
+// Забойное и буферное давление
+const P_BOTTOM_COL: &str = "Pзаб";
+const P_HEAD_COL: &str = "Pбуф";
+// Частота ЭЦН, Гц, и диаметр штуцера, мм
+const FREQUENCY_COL: &str = "Частота";
+const CHOKE_COL: &str = "Штуцер";
+// Приемистость нагнетательной скважины, м3/сут
+const INJECTION_COL: &str = "Qзак";
+// Состояние скважины: в работе, простой, ремонт
+const STATUS_COL: &str = "Status";
+const EVENT_COL: &str = "Событие";
+// Дебиты, умноженные на коэффициенты распределения
+const ALLOCATED_LIQ_COL: &str = "PdLiq распр.";
+const ALLOCATED_OIL_COL: &str = "PdOil распр.";
+// Тип скважины: добывающая, нагнетательная, наблюдательная
+const TYPE_COL: &str = "Type";
+
+// Числовые параметры записи, по которым строятся сводки
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum Parameter {
+    PdLiq,
+    #[default]
+    PdOil,
+    Temperature,
+    PBottom,
+    PHead,
+    Frequency,
+    Choke,
+    Injection,
+}
+
+impl Parameter {
+    const ALL: [Parameter; 8] = [
+        Parameter::PdLiq,
+        Parameter::PdOil,
+        Parameter::Temperature,
+        Parameter::PBottom,
+        Parameter::PHead,
+        Parameter::Frequency,
+        Parameter::Choke,
+        Parameter::Injection,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Parameter::PdLiq => "PdLiq",
+            Parameter::PdOil => "PdOil",
+            Parameter::Temperature => TEMPERATURE_COL,
+            Parameter::PBottom => P_BOTTOM_COL,
+            Parameter::PHead => P_HEAD_COL,
+            Parameter::Frequency => FREQUENCY_COL,
+            Parameter::Choke => CHOKE_COL,
+            Parameter::Injection => INJECTION_COL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum WriteMode {
+    // Потоковая запись включается, если строк больше порога
+    #[default]
+    Auto,
+    InMemory,
+    ConstantMemory,
+}
+
+impl WriteMode {
+    const ALL: [WriteMode; 3] = [
+        WriteMode::Auto,
+        WriteMode::InMemory,
+        WriteMode::ConstantMemory,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            WriteMode::Auto => "Автоматически",
+            WriteMode::InMemory => "В памяти",
+            WriteMode::ConstantMemory => "Потоковая (экономия памяти)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct StreamingWrite {
+    mode: WriteMode,
+    threshold_rows: usize,
+}
+
+impl Default for StreamingWrite {
+    fn default() -> Self {
+        Self {
+            mode: WriteMode::Auto,
+            threshold_rows: 1_000_000,
+        }
+    }
+}
+
+impl StreamingWrite {
+    fn use_constant_memory(&self, rows: usize) -> bool {
+        match self.mode {
+            WriteMode::Auto => rows > self.threshold_rows,
+            WriteMode::InMemory => false,
+            WriteMode::ConstantMemory => true,
+        }
+    }
+}
+
+// Настройки формирования отчета. Сохраняются в профилях выгрузки,
+// кроме того, что относится к текущему файлу и пользователю
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ExportOptions {
+    checks: AnomalyChecks,
+    highlight_zero_rows: bool,
+    temperature_color_scale: bool,
+    monthly_summary: bool,
+    monthly_parameter: Parameter,
+    monthly_aggregate: Aggregate,
+    // Лист Forecast: прогноз добычи нефти на столько месяцев; 0 - без прогноза
+    forecast_months: u32,
+    // Лист Rates: среднекалендарные дебиты и дебиты на день работы по месяцам
+    rates_sheet: bool,
+    rate_mode: RateMode,
+    downtime: DowntimeOptions,
+    table_of_contents: bool,
+    excel_tables: bool,
+    well_charts: bool,
+    // Группировка строк листа скважины по месяцам и годам (структура Excel)
+    month_outline: bool,
+    // Автофильтр и закрепленный заголовок на листах с записями
+    filter_and_freeze: bool,
+    streaming: StreamingWrite,
+    // Готовая книга с метками {{records}}/{{monthly}} вместо новой книги
+    template: Option<PathBuf>,
+    type_export: TypeExport,
+    sheet_layout: SheetLayout,
+    sheet_order: SheetOrder,
+    // Порядок выбора скважин для SheetOrder::Selection
+    #[serde(skip)]
+    selection_order: Vec<String>,
+    // Периоды в этих состояниях (например, простой) в выгрузку не попадают
+    excluded_statuses: BTreeSet<WellStatus>,
+    // Журнал мероприятий: колонка на листах скважин и подписи на графиках
+    #[serde(skip)]
+    events: Option<EventLog>,
+    bold_after_workover: bool,
+    // Коэффициенты распределения: колонки распределенных дебитов рядом с замеренными
+    #[serde(skip)]
+    allocation: Option<AllocationTable>,
+    // Титульный лист для архивных отчетов
+    cover_sheet: bool,
+    report_title: String,
+    #[serde(skip)]
+    author: String,
+    // Исходный файл: имя и контрольная сумма на титульном листе
+    #[serde(skip)]
+    source: Option<PathBuf>,
+    // JSON рядом с книгой: контрольная сумма, записи по скважинам, параметры
+    manifest: bool,
+    // Скрытый лист Parameters с настройками выгрузки
+    parameters_sheet: bool,
+    // Лист Excluded: загруженные скважины, не попавшие в отчет, и фильтры
+    excluded_sheet: bool,
+    // Побайтно одинаковый файл при тех же данных и настройках
+    deterministic: bool,
+    // Перечитать книгу после записи и сверить листы и строки
+    verify: bool,
+    // Имя файла отчета по умолчанию: {source} - имя исходного файла, {year} - начальный год
+    output_name: String,
+    rate_units: RateUnits,
+    units_header: UnitsHeader,
+    // Строка "Итого" под записями листа скважины
+    totals_row: TotalsRow,
+    totals_rates: RateTotal,
+    // Единицы колонок из заголовка исходного файла
+    #[serde(skip)]
+    source_units: BTreeMap<String, String>,
+    // Повторы записи при сбоях сети - из настроек программы, не из профиля
+    #[serde(skip)]
+    retry: RetryPolicy,
+}
+
+impl ExportOptions {
+    // У таблицы Excel одна строка заголовка - тогда единицы пишутся в него
+    fn units_row(&self) -> bool {
+        self.units_header == UnitsHeader::Row && !self.excel_tables
+    }
 
-// Структура данных
+    fn drop_excluded(&self, store: RecordStore) -> RecordStore {
+        if self.excluded_statuses.is_empty() {
+            return store;
+        }
+        let rows: Vec<usize> = store
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| {
+                r.status
+                    .is_none_or(|s| !self.excluded_statuses.contains(&s))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        store.subset(&rows)
+    }
+}
+
+// Где держать загруженные записи
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum StorageMode {
+    #[default]
+    Memory,
+    // Временная база DuckDB: для файлов, которые не помещаются в память
+    Disk,
+}
+
+impl StorageMode {
+    const ALL: [StorageMode; 2] = [StorageMode::Memory, StorageMode::Disk];
+
+    fn label(self) -> &'static str {
+        match self {
+            StorageMode::Memory => "В памяти",
+            StorageMode::Disk => "На диске (временная база)",
+        }
+    }
+}
+
+// Настройки чтения исходного файла
 #[derive(Debug, Clone)]
-struct WellRecord {
-    well_name: String,
-    date: Option<NaiveDateTime>,
-    pd_liq: Option<f64>,
-    pd_oil: Option<f64>,
-    temperature: Option<f64>,
-    year_sheet: i32,
+struct ImportOptions {
+    storage: StorageMode,
+    memory_limit_mb: u64,
+    // true - не загружать файл сверх лимита, false - только предупредить
+    refuse_over_limit: bool,
+    csv: CsvOptions,
+    // Свои названия основных колонок (из мастера первого запуска)
+    columns: ColumnMapping,
+    retry: RetryPolicy,
+    // Сначала скопировать файл на локальный диск и разбирать копию
+    local_copy: LocalCopy,
+    // Сколько ждать ответа сетевого диска при копировании
+    network_timeout_s: u64,
+    // Номер строки заголовка, как его показывает Excel: строка листа, в
+    // CSV - строка файла, пустые тоже считаются. Пустые строки над данными
+    // листа ничего не сдвигают: 1 - первая заполненная. 0 - заголовка нет
+    header_row: u32,
+    // Сколько строк занимают имена колонок; строка единиц под ними находится сама
+    header_rows: u32,
+    // Формат текстовых дат; пусто - распознаются сами
+    date_format: String,
+    // Листы "98", "00": двузначный год меньше границы - 20xx, иначе 19xx
+    century_pivot: u32,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            storage: StorageMode::Memory,
+            memory_limit_mb: 2048,
+            refuse_over_limit: true,
+            csv: CsvOptions::default(),
+            columns: ColumnMapping::default(),
+            retry: RetryPolicy::default(),
+            local_copy: LocalCopy::default(),
+            network_timeout_s: 30,
+            header_row: 1,
+            header_rows: 1,
+            date_format: String::new(),
+            century_pivot: year_sheet::DEFAULT_PIVOT,
+        }
+    }
+}
+
+// Результат чтения исходного файла
+struct LoadedData {
+    records: Dataset,
+    years: Vec<i32>,
+    wells: Vec<String>,
+    violations: Vec<Violation>,
+    // Пропущенные листы и замечания к данным; загрузка при этом не прерывается
+    warnings: Vec<WellDataError>,
+    // Единицы колонок из строки единиц заголовка
+    units: BTreeMap<String, String>,
 }
 
 // Типы сообщений от воркера к UI
 enum LoaderMessage {
     Progress(f32, f32, String),
-    Loaded((Vec<WellRecord>, Vec<i32>, Vec<String>)),
+    // Ход внутри этапа: вторая полоса и текст, доля этапа не меняется
+    StepProgress(f32, String),
+    Loaded(Box<LoadedData>),
     Saved(String),
-    Error(String),
+    QueryDone(QueryResult),
+    // Пересчитанные сводка, тепловая карта и корреляция для выбора
+    Dashboard(SelectionKey, Result<Kpi, String>),
+    Heatmap(SelectionKey, Result<(Vec<String>, Vec<MonthRow>), String>),
+    Correlation(SelectionKey, Result<CorrelationMatrix, String>),
+    // Мини-графики PdOil для загрузки с этим номером
+    Sparklines(u64, HashMap<String, Vec<f32>>),
+    // Список объектов хранилища S3 по префиксу
+    S3Listed(Vec<S3Object>),
+    // Содержимое папки на сервере SFTP
+    SftpListed(String, Vec<RemoteEntry>),
+    Error(ErrorReport),
+    // Файл назначения занят; готовая книга ждет повторной записи
+    Locked(LockedFile),
+    Cancelled,
+    // Промежуточное сообщение: замеры времени по фазам
+    Profile(Timings),
 }
 
 struct WellDataApp {
-    raw_data: Vec<WellRecord>,
+    raw_data: Arc<Dataset>,
+    // Номер загрузки: растет с каждым загруженным файлом. По нему ответы
+    // фоновых расчетов узнают, для тех ли они данных
+    generation: u64,
     available_years: Vec<i32>,
     unique_wells: Vec<String>,
+    violations: Vec<Violation>,
+    // Единицы колонок из заголовка загруженного файла
+    source_units: BTreeMap<String, String>,
 
     source_file_path: Option<String>,
+    rules_path: Option<PathBuf>,
     selected_start_year: Option<i32>,
     selected_wells: HashSet<String>,
+    // Выбранные скважины в порядке выбора
+    selection_order: Vec<String>,
+    import_options: ImportOptions,
+    export_options: ExportOptions,
+    // Имя профиля выгрузки для сохранения и выбранный профиль
+    profile_name: String,
+    import_profile_name: String,
 
     search_query: String,
+    list_nav: ListNavigation,
+    // Какой фонд показывать в списке скважин
+    well_kind: WellType,
+    well_types: HashMap<String, WellType>,
+    // Последнее состояние скважины из колонки Status и фильтр списка по нему
+    well_statuses: HashMap<String, WellStatus>,
+    status_filter: Option<WellStatus>,
+    // Годы с записями у каждой скважины и фильтр списка по ним
+    well_years: HashMap<String, BTreeSet<i32>>,
+    well_quarters: HashMap<String, BTreeSet<(i32, u8)>>,
+    // Мини-графики PdOil в списке скважин
+    sparklines: HashMap<String, Vec<f32>>,
+    year_filter: YearFilter,
+    // Скрыть скважины без записей за период выгрузки (от начального года)
+    hide_outside_period: bool,
+    // В левом списке только еще не выбранные скважины
+    only_unselected: bool,
+    // Сколько скважин выбирает кнопка "Топ по нефти"
+    top_n: usize,
 
     status_message: String,
-    is_loading: bool,
-    progress_global: f32,
-    progress_local: f32,
-
-    rx: Option<Receiver<LoaderMessage>>,
+    status_log: StatusLog,
+    // Последняя ошибка задачи - показывается окном с подробностями
+    error_report: Option<ErrorReport>,
+    // Отчет о падении при прошлом запуске: путь к файлу и текст
+    crash_report: Option<(PathBuf, String)>,
+    locked_file: Option<LockedFile>,
+    url_dialog: Option<UrlDialog>,
+    archive_dialog: Option<ArchiveDialog>,
+    s3_dialog: Option<S3Dialog>,
+    sftp_dialog: Option<SftpDialog>,
+    tasks: TaskManager,
+    last_timings: Option<Timings>,
+    sql: SqlPanel,
+    library: Library,
+    analysis_name: String,
+    csv_preview: Option<CsvPreview>,
+    column_proposal: Option<ColumnProposal>,
+    map: MapPanel,
+    dashboard: DashboardPanel,
+    correlation: CorrelationPanel,
+    heatmap: HeatmapPanel,
+    audit: AuditPanel,
+    export_queue: ExportQueue,
+    settings: Settings,
+    wizard: Option<SetupWizard>,
+    // Окно проверено на отключенный монитор, дальше его положение запоминается
+    window_checked: bool,
 }
 
 impl Default for WellDataApp {
     fn default() -> Self {
         Self {
-            raw_data: Vec::new(),
+            raw_data: Arc::new(Dataset::default()),
+            generation: 0,
             available_years: Vec::new(),
             unique_wells: Vec::new(),
+            violations: Vec::new(),
+            source_units: BTreeMap::new(),
             source_file_path: None,
+            rules_path: ValidationRules::find_file(),
             selected_start_year: None,
             selected_wells: HashSet::new(),
+            selection_order: Vec::new(),
+            import_options: ImportOptions::default(),
+            export_options: ExportOptions::default(),
+            profile_name: String::new(),
+            import_profile_name: String::new(),
             search_query: String::new(),
+            list_nav: ListNavigation::default(),
+            well_kind: WellType::Producer,
+            well_types: HashMap::new(),
+            well_statuses: HashMap::new(),
+            status_filter: None,
+            well_years: HashMap::new(),
+            well_quarters: HashMap::new(),
+            sparklines: HashMap::new(),
+            year_filter: YearFilter::Any,
+            hide_outside_period: false,
+            only_unselected: false,
+            top_n: 10,
             status_message: "Файл не выбран".to_string(),
-            is_loading: false,
-            progress_global: 0.0,
-            progress_local: 0.0,
-            rx: None,
+            status_log: StatusLog::default(),
+            error_report: None,
+            crash_report: None,
+            locked_file: None,
+            url_dialog: None,
+            archive_dialog: None,
+            s3_dialog: None,
+            sftp_dialog: None,
+            tasks: TaskManager::default(),
+            last_timings: None,
+            sql: SqlPanel::default(),
+            library: Library::default(),
+            analysis_name: String::new(),
+            csv_preview: None,
+            column_proposal: None,
+            map: MapPanel::default(),
+            dashboard: DashboardPanel::default(),
+            correlation: CorrelationPanel::default(),
+            heatmap: HeatmapPanel::default(),
+            audit: AuditPanel::default(),
+            export_queue: ExportQueue::default(),
+            settings: Settings::default(),
+            wizard: None,
+            window_checked: false,
         }
     }
 }
 
 impl WellDataApp {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Self::default()
+        let mut app = Self {
+            crash_report: crash::take_report(),
+            ..Self::default()
+        };
+        match Library::load() {
+            Ok(library) => {
+                app.export_options.author = library.author.clone();
+                app.library = library;
+            }
+            Err(e) => app.set_status(format!("ОШИБКА в библиотеке запросов: {}", e)),
+        }
+        match Settings::load() {
+            Ok(Some(settings)) => app.apply_settings(settings),
+            Ok(None) => app.wizard = Some(SetupWizard::new(Settings::default())),
+            Err(e) => app.set_status(format!("ОШИБКА в настройках: {}", e)),
+        }
+        app
     }
 
-    fn load_file(&mut self) {
-        if let Some(path) = FileDialog::new().add_filter("Excel", &["xlsx"]).pick_file() {
-            self.source_file_path = Some(path.to_string_lossy().to_string());
-            self.start_worker(move |tx| read_excel_file(&path, tx));
+    // Строка состояния заодно попадает в историю сообщений
+    fn set_status(&mut self, text: String) {
+        self.status_log.status(&text);
+        self.status_message = text;
+    }
+
+    fn apply_settings(&mut self, settings: Settings) {
+        self.import_options.columns = settings.columns.clone();
+        self.export_options.rate_units = settings.rate_units;
+        self.import_options.retry = settings.retry;
+        self.export_options.retry = settings.retry;
+        self.map.number_locale = settings.number_locale;
+        self.settings = settings;
+    }
+
+    // Диалог сохранения открывается в папке для отчетов из настроек
+    fn save_dialog(&self) -> FileDialog {
+        match &self.settings.output_dir {
+            Some(dir) => FileDialog::new().set_directory(dir),
+            None => FileDialog::new(),
         }
     }
 
-    fn process_data(&mut self) {
-        if self.raw_data.is_empty() {
-            return;
+    // Запись журнала операций от имени автора отчетов
+    fn audit_entry(&self, action: &str, file: &Path, wells: &HashSet<String>) -> AuditEntry {
+        AuditEntry::new(
+            action,
+            cover::author(&self.export_options.author),
+            file,
+            wells,
+        )
+    }
+
+    fn save_library(&mut self) {
+        if let Err(e) = self.library.save() {
+            self.set_status(format!("ОШИБКА сохранения библиотеки: {}", e));
         }
+    }
 
-        let start_year = match self.selected_start_year {
-            Some(y) => y,
-            None => {
-                self.status_message = "Выберите год!".to_string();
-                return;
-            }
+    fn current_analysis(&self) -> SavedAnalysis {
+        let mut wells: Vec<String> = self.selected_wells.iter().cloned().collect();
+        wells.sort();
+        SavedAnalysis {
+            name: self.analysis_name.trim().to_string(),
+            start_year: self.selected_start_year,
+            wells,
+            monthly_summary: self.export_options.monthly_summary,
+            parameter: self.export_options.monthly_parameter,
+            aggregate: self.export_options.monthly_aggregate,
+        }
+    }
+
+    // Применяет сохраненный анализ к текущему файлу и сразу формирует отчет.
+    // Годы и скважины, которых в файле нет, пропускаются
+    fn run_analysis(&mut self, analysis: &SavedAnalysis) {
+        self.selected_start_year = analysis
+            .start_year
+            .filter(|y| self.available_years.contains(y))
+            .or_else(|| self.available_years.first().copied());
+        self.selected_wells = if analysis.wells.is_empty() {
+            self.unique_wells.iter().cloned().collect()
+        } else {
+            analysis
+                .wells
+                .iter()
+                .filter(|w| self.unique_wells.contains(w))
+                .cloned()
+                .collect()
         };
+        self.export_options.monthly_summary = analysis.monthly_summary;
+        self.export_options.monthly_parameter = analysis.parameter;
+        self.export_options.monthly_aggregate = analysis.aggregate;
+
         if self.selected_wells.is_empty() {
-            self.status_message = "Выберите скважины!".to_string();
+            self.set_status(format!(
+                "В файле нет ни одной скважины из анализа '{}'",
+                analysis.name
+            ));
             return;
         }
+        self.process_data();
+    }
 
-        if let Some(path) = FileDialog::new().add_filter("Excel", &["xlsx"]).save_file() {
-            let data = self.raw_data.clone();
-            let wells = self.selected_wells.clone();
+    fn load_file(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("Excel", &["xlsx"])
+            .add_filter("CSV", &csv_import::TEXT_EXTENSIONS)
+            .add_filter("LAS", &["las"])
+            .add_filter("WITSML", &["xml", "witsml"])
+            .add_filter("ZIP", &["zip"])
+            .add_filter("Данные (JSON)", &["json"])
+            .pick_file()
+        else {
+            return;
+        };
+
+        // Из архива сначала выбираем, какие файлы загружать
+        if archive::is_zip_file(&path) {
+            match ArchiveDialog::open(path) {
+                Ok(dialog) => self.archive_dialog = Some(dialog),
+                Err(e) => self.set_status(format!("ОШИБКА: {}", e)),
+            }
+            return;
+        }
 
-            self.start_worker(move |tx| save_excel_file(&path, &data, start_year, &wells, tx));
+        // Текстовый файл сначала показываем, чтобы не разбирать его в неверной кодировке
+        if csv_import::is_text_file(&path) {
+            match CsvPreview::open(path) {
+                Ok(preview) => self.csv_preview = Some(preview),
+                Err(e) => self.set_status(format!("ОШИБКА: {}", e)),
+            }
+            return;
         }
+        self.start_load(path);
     }
 
-    fn start_worker<F>(&mut self, task: F)
-    where
-        F: FnOnce(Sender<LoaderMessage>) -> Result<LoaderMessage, Box<dyn Error + Send + Sync>>
-            + Send
-            + 'static,
-    {
-        self.is_loading = true;
-        self.progress_global = 0.0;
-        self.progress_local = 0.0;
-        self.status_message = "Запуск...".to_string();
+    fn start_load(&mut self, path: PathBuf) {
+        let Some(rules) = self.load_rules() else {
+            return;
+        };
 
-        let (tx, rx) = channel();
-        self.rx = Some(rx);
-        let tx_for_thread = tx.clone();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let title = format!("Загрузка {}", file_name);
+        let import = self.import_options.clone();
+        let entry = self
+            .audit_entry("Загрузка", &path, &HashSet::new())
+            .with_options(format!("{:?}", import));
+        self.source_file_path = Some(path.to_string_lossy().to_string());
+        self.tasks.spawn(TaskKind::Load, title, move |tx, cancel| {
+            // Локальная копия живет до конца разбора
+            let data = snapshot::prepare(&path, &import, &tx, &cancel).and_then(|snapshot| {
+                let path = snapshot.as_ref().map_or(&path, |s| &s.path);
+                read_source(path, &rules, &import, &tx, &cancel)
+            });
+            audit::record(
+                entry,
+                data.map(|data| LoaderMessage::Loaded(Box::new(data))),
+            )
+        });
+    }
 
-        thread::spawn(move || match task(tx_for_thread.clone()) {
-            Ok(msg) => {
-                let _ = tx_for_thread.send(msg);
-            }
+    // Один файл архива распаковывается в кэш и загружается как обычный;
+    // несколько - загружаются одним источником
+    fn start_archive_load(&mut self, path: PathBuf, names: Vec<String>) {
+        let source = match names.as_slice() {
+            [name] => archive::target_path(&path, name),
+            _ => Ok(path.clone()),
+        };
+        let source = match source {
+            Ok(source) => source,
             Err(e) => {
-                let _ = tx_for_thread.send(LoaderMessage::Error(e.to_string()));
+                self.set_status(format!("ОШИБКА: {}", e));
+                return;
             }
+        };
+        let Some(rules) = self.load_rules() else {
+            return;
+        };
+
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let title = match names.as_slice() {
+            [name] => format!("Загрузка {}: {}", file_name, name),
+            _ => format!("Загрузка {}: {} файлов", file_name, names.len()),
+        };
+        let import = self.import_options.clone();
+        let entry = self
+            .audit_entry("Загрузка из архива", &path, &HashSet::new())
+            .with_options(format!("{:?}", import));
+        self.source_file_path = Some(source.to_string_lossy().to_string());
+        self.tasks.spawn(TaskKind::Load, title, move |tx, cancel| {
+            let data = match names.as_slice() {
+                [name] => archive::extract(&path, name)
+                    .map_err(|e| WellDataError::open_failed(&path, e))
+                    .and_then(|file| read_source(&file, &rules, &import, &tx, &cancel)),
+                _ => archive::read_all(&path, &names, &rules, &import, &tx, &cancel),
+            };
+            audit::record(
+                entry,
+                data.map(|data| LoaderMessage::Loaded(Box::new(data))),
+            )
         });
     }
-}
-
-// --- ФУНКЦИИ РАБОТЫ С ДАННЫМИ ---
-
-fn read_excel_file(
-    path: &PathBuf,
-    tx: Sender<LoaderMessage>,
-) -> Result<LoaderMessage, Box<dyn Error + Send + Sync>> {
-    let _ = tx.send(LoaderMessage::Progress(
-        0.0,
-        0.0,
-        "Открытие файла...".to_string(),
-    ));
 
-    let mut workbook: Xlsx<_> = calamine::open_workbook(path)?;
-    let sheets = workbook.sheet_names().to_owned();
-    let total_sheets = sheets.len();
-
-    let mut all_records = Vec::new();
-    let mut valid_years = BTreeSet::new();
-    let mut unique_wells = BTreeSet::new();
-
-    for (sheet_idx, sheet_name) in sheets.iter().enumerate() {
-        let global_prog = sheet_idx as f32 / total_sheets as f32;
-
-        let _ = tx.send(LoaderMessage::Progress(
-            global_prog,
-            0.0,
-            format!("Лист '{}': чтение и парсинг (ждите)...", sheet_name),
-        ));
-
-        if let Ok(year) = sheet_name.parse::<i32>() {
-            if let Ok(range) = workbook.worksheet_range(sheet_name) {
-                let total_rows_in_sheet = range.height();
+    // Файл по ссылке скачивается в кэш и дальше загружается как обычный
+    fn start_url_load(&mut self, url: String, auth: String) {
+        let target = match download::target_path(&url) {
+            Ok(target) => target,
+            Err(e) => {
+                self.set_status(format!("ОШИБКА: {}", e));
+                return;
+            }
+        };
+        let Some(rules) = self.load_rules() else {
+            return;
+        };
 
-                let mut headers = range.rows().next().ok_or("Пустой лист")?.iter();
-                let mut col_map = std::collections::HashMap::new();
-                for (i, cell) in headers.enumerate() {
-                    if let Some(s) = cell.get_string() {
-                        col_map.insert(s.to_string(), i);
+        let title = format!("Загрузка {}", url);
+        let import = self.import_options.clone();
+        let entry = self
+            .audit_entry("Загрузка по ссылке", Path::new(&url), &HashSet::new())
+            .with_options(format!("{:?}", import));
+        self.source_file_path = Some(target.to_string_lossy().to_string());
+        self.tasks
+            .spawn_async(TaskKind::Load, title, move |tx, cancel| async move {
+                let data = match download::download(&url, &auth, &target, &tx, &cancel).await {
+                    Ok(()) => {
+                        tokio::task::spawn_blocking(move || {
+                            read_source(&target, &rules, &import, &tx, &cancel)
+                        })
+                        .await?
                     }
-                }
-
-                if let (Some(&idx_n), Some(&idx_d)) = (col_map.get(NAME_COL), col_map.get("Date")) {
-                    valid_years.insert(year);
-                    let idx_liq = col_map.get("PdLiq").copied();
-                    let idx_oil = col_map.get("PdOil").copied();
-                    let idx_temp = col_map.get(TEMPERATURE_COL).copied();
-
-                    for (i, row) in range.rows().skip(1).enumerate() {
-                        if i % 5000 == 0 {
-                            let local_prog = i as f32 / total_rows_in_sheet as f32;
-                            let _ = tx.send(LoaderMessage::Progress(
-                                global_prog,
-                                local_prog,
-                                format!("Лист '{}': обработка строк...", sheet_name),
-                            ));
-                        }
-
-                        let well_name = match row.get(idx_n) {
-                            Some(Data::String(s)) => s.clone(),
-                            Some(Data::Float(f)) => f.to_string(),
-                            Some(Data::Int(i)) => i.to_string(),
-                            _ => continue,
-                        };
-
-                        let date = match row.get(idx_d) {
-                            Some(d) => d.as_datetime(),
-                            None => None,
-                        };
-
-                        let get_float = |idx_opt: Option<usize>| -> Option<f64> {
-                            idx_opt.and_then(|i| row.get(i).and_then(|c| c.get_float()))
-                        };
+                    Err(e) => Err(e),
+                };
+                audit::record(
+                    entry,
+                    data.map(|data| LoaderMessage::Loaded(Box::new(data))),
+                )
+            });
+    }
 
-                        unique_wells.insert(well_name.clone());
-                        all_records.push(WellRecord {
-                            well_name,
-                            date,
-                            pd_liq: get_float(idx_liq),
-                            pd_oil: get_float(idx_oil),
-                            temperature: get_float(idx_temp),
-                            year_sheet: year,
-                        });
-                    }
-                }
+    // Список объектов хранилища; настройки подключения запоминаются
+    fn start_s3_list(&mut self, config: S3Config, secret_key: String) {
+        if self.settings.s3 != config {
+            self.settings.s3 = config.clone();
+            if let Err(e) = self.settings.save() {
+                self.set_status(format!("ОШИБКА: {}", e));
             }
         }
+        let title = format!("Список {}/{}", config.bucket.trim(), config.prefix.trim());
+        self.tasks
+            .spawn_async(TaskKind::Query, title, move |tx, cancel| async move {
+                s3::list(&config, &secret_key, &tx, &cancel)
+                    .await
+                    .map(LoaderMessage::S3Listed)
+            });
     }
 
-    let _ = tx.send(LoaderMessage::Progress(
-        1.0,
-        1.0,
-        "Финализация...".to_string(),
-    ));
-    Ok(LoaderMessage::Loaded((
-        all_records,
-        valid_years.into_iter().collect(),
-        unique_wells.into_iter().collect(),
-    )))
-}
-
-fn save_excel_file(
-    path: &PathBuf,
-    data: &[WellRecord],
-    start_year: i32,
-    selected_wells: &HashSet<String>,
-    tx: Sender<LoaderMessage>,
-) -> Result<LoaderMessage, Box<dyn Error + Send + Sync>> {
-    let _ = tx.send(LoaderMessage::Progress(
-        0.0,
-        0.0,
-        "Подготовка данных...".to_string(),
-    ));
+    // Объект хранилища скачивается в кэш и загружается как обычный файл
+    fn start_s3_load(&mut self, config: S3Config, secret_key: String, key: String) {
+        let target = match s3::target_path(&config, &key) {
+            Ok(target) => target,
+            Err(e) => {
+                self.set_status(format!("ОШИБКА: {}", e));
+                return;
+            }
+        };
+        let Some(rules) = self.load_rules() else {
+            return;
+        };
 
-    let mut filtered_data: Vec<&WellRecord> = data
-        .iter()
-        .filter(|r| r.year_sheet >= start_year && selected_wells.contains(&r.well_name))
-        .collect();
+        let source = format!("s3://{}/{}", config.bucket.trim(), key);
+        let title = format!("Загрузка {}", source);
+        let import = self.import_options.clone();
+        let entry = self
+            .audit_entry("Загрузка из S3", Path::new(&source), &HashSet::new())
+            .with_options(format!("{:?}", import));
+        self.source_file_path = Some(target.to_string_lossy().to_string());
+        self.tasks
+            .spawn_async(TaskKind::Load, title, move |tx, cancel| async move {
+                let data = match s3::get(&config, &secret_key, &key, &target, &tx, &cancel).await {
+                    Ok(()) => {
+                        tokio::task::spawn_blocking(move || {
+                            read_source(&target, &rules, &import, &tx, &cancel)
+                        })
+                        .await?
+                    }
+                    Err(e) => Err(e),
+                };
+                audit::record(
+                    entry,
+                    data.map(|data| LoaderMessage::Loaded(Box::new(data))),
+                )
+            });
+    }
 
-    filtered_data.sort_by(|a, b| a.well_name.cmp(&b.well_name).then(a.date.cmp(&b.date)));
+    // Содержимое папки сервера; настройки подключения запоминаются вместе с папкой
+    fn start_sftp_list(&mut self, mut config: SftpConfig, secret: String, dir: String) {
+        config.remote_dir = dir.clone();
+        if self.settings.sftp != config {
+            self.settings.sftp = config.clone();
+            if let Err(e) = self.settings.save() {
+                self.set_status(format!("ОШИБКА: {}", e));
+            }
+        }
+        let timeout = Duration::from_secs(self.import_options.network_timeout_s.max(1));
+        let title = format!("Папка {}:{}", config.host.trim(), dir);
+        self.tasks
+            .spawn(TaskKind::Query, title, move |_tx, _cancel| {
+                sftp::list(&config, &secret, &dir, timeout)
+                    .map(|entries| LoaderMessage::SftpListed(dir, entries))
+            });
+    }
 
-    let mut workbook = Workbook::new();
-    let wells_to_export: Vec<&String> = filtered_data
-        .iter()
-        .map(|r| &r.well_name)
-        .collect::<BTreeSet<_>>()
-        .into_iter()
-        .collect();
+    // Файл с сервера скачивается в кэш и загружается как обычный
+    fn start_sftp_load(&mut self, config: SftpConfig, secret: String, remote_path: String) {
+        let target = match sftp::target_path(&config, &remote_path) {
+            Ok(target) => target,
+            Err(e) => {
+                self.set_status(format!("ОШИБКА: {}", e));
+                return;
+            }
+        };
+        let Some(rules) = self.load_rules() else {
+            return;
+        };
 
-    let total_wells = wells_to_export.len();
+        let source = format!("sftp://{}{}", config.host.trim(), remote_path);
+        let title = format!("Загрузка {}", source);
+        let import = self.import_options.clone();
+        let timeout = Duration::from_secs(import.network_timeout_s.max(1));
+        let entry = self
+            .audit_entry("Загрузка по SFTP", Path::new(&source), &HashSet::new())
+            .with_options(format!("{:?}", import));
+        self.source_file_path = Some(target.to_string_lossy().to_string());
+        self.tasks.spawn(TaskKind::Load, title, move |tx, cancel| {
+            let data = sftp::get(
+                &config,
+                &secret,
+                &remote_path,
+                &target,
+                timeout,
+                &tx,
+                &cancel,
+            )
+            .and_then(|()| read_source(&target, &rules, &import, &tx, &cancel));
+            audit::record(
+                entry,
+                data.map(|data| LoaderMessage::Loaded(Box::new(data))),
+            )
+        });
+    }
 
-    for (idx, well_name) in wells_to_export.iter().enumerate() {
-        let global_prog = idx as f32 / total_wells as f32;
-        let _ = tx.send(LoaderMessage::Progress(
-            global_prog,
-            0.0,
-            format!("Запись скважины: {}", well_name),
-        ));
+    // Правила перечитываем при каждом импорте, чтобы правки администратора
+    // применялись без перезапуска
+    fn load_rules(&mut self) -> Option<ValidationRules> {
+        self.rules_path = ValidationRules::find_file();
+        match &self.rules_path {
+            Some(rules_path) => match ValidationRules::load(rules_path) {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    self.set_status(format!("ОШИБКА в правилах проверки: {}", e));
+                    None
+                }
+            },
+            None => Some(ValidationRules::default()),
+        }
+    }
 
-        let safe_name = well_name.replace(['/', '\\', '?', '*', '[', ']'], "_");
-        let sheet_name = if safe_name.len() > 30 {
-            &safe_name[..30]
-        } else {
-            &safe_name
+    // Пакетная обработка: каждый xlsx из папки превращается в отчет с текущими настройками.
+    // Если скважины не выбраны, выгружаются все скважины файла
+    fn process_folder(&mut self) {
+        let Some(input_dir) = FileDialog::new()
+            .set_title("Папка с исходными файлами")
+            .pick_folder()
+        else {
+            return;
+        };
+        let Some(output_dir) = FileDialog::new()
+            .set_title("Папка для отчетов")
+            .pick_folder()
+        else {
+            return;
+        };
+        let Some(rules) = self.load_rules() else {
+            return;
         };
 
-        let worksheet = workbook.add_worksheet().set_name(sheet_name)?;
+        let title = format!(
+            "Пакет {}",
+            input_dir.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let entry = self
+            .audit_entry("Пакет", &input_dir, &self.selected_wells)
+            .with_options(export_parameters(
+                self.selected_start_year.unwrap_or(i32::MIN),
+                &self.export_options,
+            ));
+        let job = batch::BatchJob {
+            input_dir,
+            output_dir,
+            rules,
+            import: self.import_options.clone(),
+            options: self.export_options.clone(),
+            start_year: self.selected_start_year,
+            wells: (!self.selected_wells.is_empty()).then(|| self.selected_wells.clone()),
+        };
 
-        worksheet.write_string(0, 0, NAME_COL)?;
-        worksheet.write_string(0, 1, "Date")?;
-        worksheet.write_string(0, 2, "PdLiq")?;
-        worksheet.write_string(0, 3, "PdOil")?;
-        worksheet.write_string(0, 4, TEMPERATURE_COL)?;
+        self.tasks
+            .spawn(TaskKind::Export, title, move |tx, cancel| {
+                audit::record(entry, batch::process_folder(&job, &tx, &cancel))
+            });
+    }
 
-        let records_for_well: Vec<&&WellRecord> = filtered_data
-            .iter()
-            .filter(|r| &r.well_name == *well_name)
-            .collect();
+    fn save_violations(&mut self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("Excel", &["xlsx"])
+            .set_file_name("нарушения.xlsx")
+            .save_file()
+        {
+            let violations = self.violations.clone();
+            let title = "Отчет о нарушениях".to_string();
+            let entry = self.audit_entry("Нарушения", &path, &HashSet::new());
+            self.tasks
+                .spawn(TaskKind::Export, title, move |_tx, _cancel| {
+                    let result = rules::save_violations_report(&path, &violations)
+                        .map(|_| LoaderMessage::Saved(path.to_string_lossy().to_string()));
+                    audit::record(entry, result)
+                });
+        }
+    }
 
-        let total_rows = records_for_well.len();
+    // Все загруженные данные в JSON - для других программ и повторной загрузки
+    fn save_model(&mut self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("Данные", &["json"])
+            .set_file_name(format!("данные.{}", model::EXTENSION))
+            .save_file()
+        {
+            let data = self.raw_data.clone();
+            let title = format!(
+                "Сохранение {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+            let entry = self.audit_entry("Данные в JSON", &path, &HashSet::new());
+            self.tasks
+                .spawn(TaskKind::Export, title, move |tx, cancel| {
+                    audit::record(entry, model::save_model_file(&path, &data, &tx, &cancel))
+                });
+        }
+    }
 
-        let mut row_idx = 1;
-        for (i, record) in records_for_well.iter().enumerate() {
-            if i % 500 == 0 {
-                let local_prog = i as f32 / total_rows as f32;
-                let _ = tx.send(LoaderMessage::Progress(
-                    global_prog,
-                    local_prog,
-                    format!("Скважина {}: строка {}/{}", well_name, i, total_rows),
-                ));
-            }
+    // Начальный год выгрузки; None - выбор не закончен, причина в статусе
+    fn export_start_year(&mut self) -> Option<i32> {
+        if self.raw_data.is_empty() {
+            return None;
+        }
 
-            worksheet.write_string(row_idx, 0, &record.well_name)?;
-            if let Some(d) = record.date {
-                worksheet.write_string(row_idx, 1, d.format("%Y-%m-%d %H:%M:%S").to_string())?;
-            }
-            if let Some(v) = record.pd_liq {
-                worksheet.write_number(row_idx, 2, v)?;
-            }
-            if let Some(v) = record.pd_oil {
-                worksheet.write_number(row_idx, 3, v)?;
-            }
-            if let Some(v) = record.temperature {
-                worksheet.write_number(row_idx, 4, v)?;
+        let start_year = match self.selected_start_year {
+            Some(y) => y,
+            // Только каротаж: годов нет, фильтровать нечего
+            None if self.available_years.is_empty() => i32::MIN,
+            None => {
+                self.set_status("Выберите год!".to_string());
+                return None;
             }
-            row_idx += 1;
+        };
+        if self.selected_wells.is_empty() {
+            self.set_status("Выберите скважины!".to_string());
+            return None;
         }
+        Some(start_year)
     }
 
-    let _ = tx.send(LoaderMessage::Progress(
-        1.0,
-        1.0,
-        "Сохранение файла на диск...".to_string(),
-    ));
-    workbook.save(path)?;
-    Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
-}
+    fn process_data(&mut self) {
+        let Some(start_year) = self.export_start_year() else {
+            return;
+        };
 
-// --- ИНТЕРФЕЙС ---
+        if let Some(path) = self.report_dialog(start_year).save_file() {
+            let data = Arc::clone(&self.raw_data);
+            let wells = self.selected_wells.clone();
+            let options = self.current_export_options();
+            self.spawn_export(path, data, start_year, wells, options);
+        }
+    }
 
-impl eframe::App for WellDataApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut should_close_channel = false;
+    // Настройки выгрузки вместе с исходным файлом для титульного листа и манифеста
+    fn current_export_options(&self) -> ExportOptions {
+        ExportOptions {
+            source: self.source_file_path.as_ref().map(PathBuf::from),
+            selection_order: self.selection_order.clone(),
+            source_units: self.source_units.clone(),
+            ..self.export_options.clone()
+        }
+    }
 
-        if let Some(rx) = &self.rx {
-            while let Ok(msg) = rx.try_recv() {
-                match msg {
-                    LoaderMessage::Progress(global, local, text) => {
-                        self.progress_global = global;
-                        self.progress_local = local;
-                        self.status_message = text;
-                    }
-                    LoaderMessage::Loaded((data, years, wells)) => {
-                        self.raw_data = data;
-                        self.available_years = years;
-                        self.unique_wells = wells;
-                        if let Some(first) = self.available_years.first() {
-                            self.selected_start_year = Some(*first);
-                        }
-                        self.status_message =
-                            format!("Готово. Загружено: {} записей", self.raw_data.len());
-                        self.is_loading = false;
-                        should_close_channel = true;
-                    }
-                    LoaderMessage::Saved(path) => {
-                        self.status_message = format!("Успех! Файл сохранен: {}", path);
-                        self.is_loading = false;
-                        should_close_channel = true;
-                    }
-                    LoaderMessage::Error(e) => {
-                        self.status_message = format!("ОШИБКА: {}", e);
-                        self.is_loading = false;
-                        should_close_channel = true;
-                    }
-                }
-            }
+    // Имя файла из шаблона профиля для диалога сохранения
+    fn default_file_name(&self, start_year: i32) -> Option<String> {
+        let pattern = self.export_options.output_name.trim();
+        if pattern.is_empty() {
+            return None;
         }
+        let source = self
+            .source_file_path
+            .as_deref()
+            .and_then(|p| std::path::Path::new(p).file_stem())
+            .unwrap_or_default()
+            .to_string_lossy();
+        let year = if start_year == i32::MIN {
+            "все".to_string()
+        } else {
+            start_year.to_string()
+        };
+        let name = pattern
+            .replace("{source}", &source)
+            .replace("{year}", &year);
+        Some(if name.to_lowercase().ends_with(".xlsx") {
+            name
+        } else {
+            name + ".xlsx"
+        })
+    }
 
-        if should_close_channel {
-            self.rx = None;
+    fn report_dialog(&self, start_year: i32) -> FileDialog {
+        let dialog = self.save_dialog().add_filter("Excel", &["xlsx"]);
+        match self.default_file_name(start_year) {
+            Some(name) => dialog.set_file_name(name),
+            None => dialog,
         }
+    }
 
-        if self.is_loading {
-            ctx.request_repaint();
+    // Профиль меняет настройки выгрузки; автор, журнал мероприятий, коэффициенты
+    // и исходный файл остаются
+    fn apply_profile(&mut self, profile: &ExportProfile) {
+        self.export_options = ExportOptions {
+            author: std::mem::take(&mut self.export_options.author),
+            events: self.export_options.events.take(),
+            allocation: self.export_options.allocation.take(),
+            retry: self.export_options.retry,
+            ..profile.options.clone()
+        };
+        self.profile_name = profile.name.clone();
+        self.set_status(format!("Профиль выгрузки: {}", profile.name));
+    }
+
+    // Единицы дебита относятся к отчету, остальное - к чтению файла
+    fn apply_import_profile(&mut self, profile: &ImportProfile) {
+        self.import_options.columns = profile.columns.clone();
+        self.import_options.header_row = profile.header_row;
+        self.import_options.header_rows = profile.header_rows;
+        self.import_options.date_format = profile.date_format.clone();
+        self.export_options.rate_units = profile.units;
+        self.import_profile_name = profile.name.clone();
+        self.set_status(format!("Профиль загрузки: {}", profile.name));
+    }
+
+    fn current_import_profile(&self) -> ImportProfile {
+        ImportProfile {
+            name: self.import_profile_name.trim().to_string(),
+            columns: self.import_options.columns.clone(),
+            header_row: self.import_options.header_row,
+            header_rows: self.import_options.header_rows,
+            date_format: self.import_options.date_format.clone(),
+            units: self.export_options.rate_units,
         }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Обработка данных скважин");
-            ui.add_space(5.0);
+    fn spawn_export(
+        &mut self,
+        path: PathBuf,
+        data: Arc<Dataset>,
+        start_year: i32,
+        wells: HashSet<String>,
+        options: ExportOptions,
+    ) -> TaskId {
+        let title = format!(
+            "Отчет {}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let entry = self
+            .audit_entry("Отчет", &path, &wells)
+            .with_options(export_parameters(start_year, &options));
 
-            ui.set_enabled(!self.is_loading);
+        self.tasks
+            .spawn(TaskKind::Export, title, move |tx, cancel| {
+                let result =
+                    save_excel_file(&path, &data, start_year, &wells, &options, &tx, &cancel);
+                audit::record(entry, result)
+            })
+    }
+
+    // Текущий выбор и настройки - в очередь; файл выбирается сразу
+    fn enqueue_export(&mut self) {
+        let Some(start_year) = self.export_start_year() else {
+            return;
+        };
+        let Some(path) = self.report_dialog(start_year).save_file() else {
+            return;
+        };
+        let year = if start_year == i32::MIN {
+            "все годы".to_string()
+        } else {
+            format!("с {}", start_year)
+        };
+        let title = format!(
+            "{}: {}, скважин {}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            year,
+            self.selected_wells.len()
+        );
+        self.export_queue.jobs.push(QueuedExport {
+            title,
+            path,
+            data: Arc::clone(&self.raw_data),
+            start_year,
+            wells: self.selected_wells.clone(),
+            options: self.current_export_options(),
+            state: JobState::Pending,
+        });
+        self.set_status(format!(
+            "В очереди выгрузок: {}",
+            self.export_queue.jobs.len()
+        ));
+    }
+
+    // Следующая выгрузка запускается, когда закончилась предыдущая
+    fn run_queue(&mut self) {
+        if self.export_queue.is_running() {
+            return;
+        }
+        let Some(job) = self.export_queue.next_pending() else {
+            return;
+        };
+        let (path, data, start_year, wells, options) = (
+            job.path.clone(),
+            Arc::clone(&job.data),
+            job.start_year,
+            job.wells.clone(),
+            job.options.clone(),
+        );
+        let id = self.spawn_export(path, data, start_year, wells, options);
+        if let Some(job) = self.export_queue.next_pending() {
+            job.state = JobState::Running(id);
+        }
+    }
+
+    fn export_ofm(&mut self) {
+        let Some(start_year) = self.export_start_year() else {
+            return;
+        };
+
+        if let Some(path) = self.save_dialog().add_filter("OFM", &["txt"]).save_file() {
+            let data = Arc::clone(&self.raw_data);
+            let wells = self.selected_wells.clone();
+            let options = self.export_options.clone();
+            let title = format!(
+                "OFM {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+            let entry = self
+                .audit_entry("OFM", &path, &wells)
+                .with_options(format!("start_year: {}", start_year));
+
+            self.tasks
+                .spawn(TaskKind::Export, title, move |tx, cancel| {
+                    let result = ofm::save_ofm_file(
+                        &path, &data, start_year, &wells, &options, &tx, &cancel,
+                    );
+                    audit::record(entry, result)
+                });
+        }
+    }
+
+    fn export_chessboard(&mut self) {
+        let Some(start_year) = self.export_start_year() else {
+            return;
+        };
+
+        if let Some(path) = self
+            .save_dialog()
+            .add_filter("Excel", &["xlsx"])
+            .save_file()
+        {
+            let data = Arc::clone(&self.raw_data);
+            let wells = self.selected_wells.clone();
+            let options = self.export_options.clone();
+            let title = format!(
+                "Шахматка {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+            let entry = self
+                .audit_entry("Шахматка", &path, &wells)
+                .with_options(format!("start_year: {}", start_year));
+
+            self.tasks
+                .spawn(TaskKind::Export, title, move |tx, cancel| {
+                    let result = chessboard::save_chessboard_file(
+                        &path, &data, start_year, &wells, &options, &tx, &cancel,
+                    );
+                    audit::record(entry, result)
+                });
+        }
+    }
+
+    fn run_query(&mut self) {
+        let data = Arc::clone(&self.raw_data);
+        let sql = self.sql.text.trim().to_string();
+        if sql.is_empty() {
+            return;
+        }
+        self.tasks.spawn(
+            TaskKind::Query,
+            "SQL-запрос".to_string(),
+            move |_tx, _cancel| query::run(&data, &sql).map(LoaderMessage::QueryDone),
+        );
+    }
+
+    fn save_query_result(&mut self) {
+        let Some(result) = self.sql.result.clone() else {
+            return;
+        };
+        if let Some(path) = FileDialog::new()
+            .add_filter("Excel", &["xlsx"])
+            .add_filter("CSV", &["csv"])
+            .set_file_name("запрос.xlsx")
+            .save_file()
+        {
+            let title = format!(
+                "Результат запроса {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+            let entry = self
+                .audit_entry("Запрос", &path, &HashSet::new())
+                .with_options(result.sql.clone());
+            self.tasks
+                .spawn(TaskKind::Export, title, move |tx, cancel| {
+                    audit::record(entry, query::save_result(&path, &result, &tx, &cancel))
+                });
+        }
+    }
+
+    fn handle_message(&mut self, kind: TaskKind, msg: LoaderMessage) {
+        match msg {
+            LoaderMessage::Loaded(loaded) => {
+                self.raw_data = Arc::new(loaded.records);
+                self.generation += 1;
+                self.available_years = loaded.years;
+                self.unique_wells = loaded.wells;
+                self.violations = loaded.violations;
+                self.source_units = loaded.units;
+                self.selected_start_year = self.available_years.first().copied();
+                self.map.rates = map::average_rates(&self.raw_data);
+                self.well_types = well_type::well_types(&self.raw_data);
+                self.well_kind = WellType::ALL
+                    .into_iter()
+                    .find(|t| self.well_types.values().any(|w| w == t))
+                    .unwrap_or(WellType::Producer);
+                self.well_statuses = status::last_statuses(&self.raw_data);
+                self.status_filter = None;
+                self.well_years = well_list::well_years(&self.raw_data);
+                self.well_quarters = well_list::well_quarters(&self.raw_data);
+                // Мини-графики считаются в фоне, пока видны прежние
+                let (data, generation) = (Arc::clone(&self.raw_data), self.generation);
+                self.tasks.spawn(
+                    TaskKind::Chart,
+                    "Мини-графики".to_string(),
+                    move |_tx, _cancel| {
+                        let sparklines = well_list::oil_sparklines(&data);
+                        Ok(LoaderMessage::Sparklines(generation, sparklines))
+                    },
+                );
+                self.year_filter = YearFilter::Any;
+                let mut status = format!(
+                    "Готово. Загружено: {} записей",
+                    self.settings.number_locale.count(self.raw_data.len())
+                );
+                if !self.raw_data.logs.is_empty() {
+                    status += &format!(", каротажей: {}", self.raw_data.logs.len());
+                }
+                if !self.violations.is_empty() {
+                    status += &format!(", нарушений правил: {}", self.violations.len());
+                }
+                if !self.source_units.is_empty() {
+                    let units: Vec<String> = [NAME_COL, "Date", "PdLiq", "PdOil", TEMPERATURE_COL]
+                        .iter()
+                        .filter_map(|c| Some(format!("{} {}", c, self.source_units.get(*c)?)))
+                        .collect();
+                    if !units.is_empty() {
+                        status += &format!(", единицы: {}", units.join(", "));
+                    }
+                }
+                for warning in loaded.warnings {
+                    status += &format!(". ⚠ {}", warning);
+                }
+                self.set_status(status);
+            }
+            LoaderMessage::QueryDone(result) => {
+                self.set_status(format!("Запрос выполнен: {} строк", result.rows.len()));
+                self.sql.result = Some(Arc::new(result));
+            }
+            LoaderMessage::Dashboard(key, kpi) => self.dashboard.finish(key, kpi),
+            LoaderMessage::Heatmap(key, result) => self.heatmap.finish(key, result),
+            LoaderMessage::Correlation(key, matrix) => self.correlation.finish(key, matrix),
+            LoaderMessage::Sparklines(generation, sparklines) => {
+                if generation == self.generation {
+                    self.sparklines = sparklines;
+                }
+            }
+            LoaderMessage::S3Listed(objects) => {
+                self.set_status(format!("Объектов в хранилище: {}", objects.len()));
+                if let Some(dialog) = &mut self.s3_dialog {
+                    dialog
+                        .selected
+                        .take_if(|key| !objects.iter().any(|o| &o.key == key));
+                    dialog.objects = objects;
+                }
+            }
+            LoaderMessage::SftpListed(dir, entries) => {
+                self.set_status(format!("{}: {} объектов", dir, entries.len()));
+                if let Some(dialog) = &mut self.sftp_dialog {
+                    dialog.dir = dir;
+                    dialog.entries = entries;
+                    dialog.selected = None;
+                }
+            }
+            LoaderMessage::Saved(path) => {
+                self.set_status(format!("Успех! Файл сохранен: {}", path));
+            }
+            LoaderMessage::Error(e) => {
+                self.set_status(format!("ОШИБКА: {}", e));
+                self.error_report = Some(e);
+            }
+            LoaderMessage::Locked(file) => {
+                self.set_status(format!("ОШИБКА: {}", file));
+                self.locked_file = Some(file);
+            }
+            LoaderMessage::Cancelled => {
+                self.set_status(match kind {
+                    TaskKind::Load => "Загрузка отменена".to_string(),
+                    TaskKind::Export => "Экспорт отменен".to_string(),
+                    TaskKind::Query => "Запрос отменен".to_string(),
+                    TaskKind::Chart => "Пересчет отменен".to_string(),
+                });
+            }
+            LoaderMessage::Profile(timings) => {
+                self.last_timings = Some(timings);
+            }
+            LoaderMessage::Progress(..) | LoaderMessage::StepProgress(..) => {}
+        }
+    }
+}
+
+// --- ФУНКЦИИ РАБОТЫ С ДАННЫМИ ---
+
+// Формат определяется по расширению и содержимому файла
+fn read_source(
+    path: &PathBuf,
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    if model::is_model_file(path) {
+        model::read_model_file(path, import, progress, cancel)
+    } else if csv_import::is_text_file(path) {
+        csv_import::read_csv_file(path, rules, import, progress, cancel)
+    } else if las::is_las_file(path) {
+        las::read_las_file(path, rules, import, progress, cancel)
+    } else if witsml::is_witsml_file(path) {
+        witsml::read_witsml_file(path, rules, import, progress, cancel)
+    } else if mer::is_mer_file(path, import) {
+        mer::read_mer_file(path, rules, import, progress, cancel)
+    } else {
+        read_excel_file(path, rules, import, progress, cancel)
+    }
+}
+
+fn read_excel_file(
+    path: &PathBuf,
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    progress.report("Открытие файла...", 0, 0);
+
+    let mut timings = Timings::new(format!(
+        "Загрузка {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let mut workbook: Xlsx<_> = timings
+        .measure("Открытие книги", || {
+            import.retry.run("открытие книги", progress, cancel, || {
+                calamine::open_workbook(path)
+            })
+        })
+        .map_err(|e| WellDataError::open_failed(path, e))?;
+    let loaded = parse_workbook(&mut workbook, rules, import, progress, cancel, &mut timings)?;
+    progress.timings(timings.finish());
+    Ok(loaded)
+}
+
+// Разбор книги, уже открытой из файла или из памяти: листы-годы в записи,
+// нарушения правил и предупреждения. Диск не трогает (кроме хранения на диске)
+fn parse_workbook<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+    timings: &mut Timings,
+) -> Result<LoadedData, WellDataError> {
+    let sheets = workbook.sheet_names().to_owned();
+    let total_sheets = sheets.len();
+    let mut warnings = Vec::new();
+
+    // Оценка памяти до разбора, чтобы не подвесить ноутбук огромным файлом
+    let year_sheets: Vec<&String> = sheets
+        .iter()
+        .filter(|s| year_sheet::is_data_sheet(s))
+        .collect();
+    let estimate = memory::estimate(workbook, &year_sheets, import.storage);
+    if estimate.projected_mb() > import.memory_limit_mb {
+        let limit = WellDataError::MemoryLimit {
+            rows: estimate.total_rows,
+            needed_mb: estimate.projected_mb(),
+            limit_mb: import.memory_limit_mb,
+        };
+        if import.refuse_over_limit {
+            return Err(limit);
+        }
+        warnings.push(limit);
+    }
+
+    let mut all_records = Dataset::new(import.storage)?;
+    let mut valid_years = BTreeSet::new();
+    let mut unique_wells = BTreeSet::new();
+    let mut violations = Vec::new();
+    let mut units = BTreeMap::new();
+    let mut missing = MissingStats::default();
+    let mut dates = DateStats::default();
+    // Строки помесячных листов с датой из другого месяца
+    let mut outside_month = 0;
+
+    for (sheet_idx, sheet_name) in sheets.iter().enumerate() {
+        cancel.check()?;
+        progress.report(
+            &format!("Лист '{}': чтение и парсинг (ждите)...", sheet_name),
+            sheet_idx,
+            total_sheets,
+        );
+
+        let Some(period) = year_sheet::sheet_period(sheet_name, import.century_pivot) else {
+            continue;
+        };
+        let year = period.year;
+        let range = timings.measure(format!("Лист '{}': чтение", sheet_name), || {
+            let what = format!("чтение листа '{}'", sheet_name);
+            import.retry.run(&what, progress, cancel, || {
+                workbook.worksheet_range(sheet_name)
+            })
+        });
+        let range = match range {
+            Ok(range) => range,
+            Err(WellDataError::Cancelled) => return Err(WellDataError::Cancelled),
+            Err(e) => {
+                warnings.push(WellDataError::SheetSkipped {
+                    sheet: sheet_name.clone(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let parse_start = Instant::now();
+        let total_rows_in_sheet = range.height();
+
+        // Строки над заголовком (шапка подрядчика) пропускаются.
+        // header_row = 0 - заголовка нет, данные с первой строки. Номер - как в
+        // Excel; пустые строки над данными в range не входят
+        let start_row = range.start().map(|(r, _)| r).unwrap_or(0);
+        let header_idx = import
+            .header_row
+            .checked_sub(1)
+            .map(|row| row.saturating_sub(start_row) as usize);
+        let mut header = Header::default();
+        if let Some(header_idx) = header_idx {
+            let text = |row: &[Data]| -> Vec<String> {
+                row.iter()
+                    .map(|c| c.get_string().unwrap_or_default().to_string())
+                    .collect()
+            };
+            range
+                .rows()
+                .nth(header_idx)
+                .ok_or_else(|| WellDataError::BadContent("Пустой лист".to_string()))
+                .at(|| Location::new("Чтение").sheet(sheet_name))?;
+            let mut rows: Vec<Vec<String>> = range
+                .rows()
+                .skip(header_idx)
+                .take(import.header_rows.max(1) as usize)
+                .map(text)
+                .collect();
+            // Строка единиц сразу под заголовком - тоже часть заголовка
+            if let Some(next) = range.rows().nth(header_idx + rows.len()).map(text)
+                && header::looks_like_units(&next)
+            {
+                rows.push(next);
+            }
+            header = Header::parse(&rows);
+        }
+        let data_idx = header_idx.map_or(0, |i| i + header.lines);
+        let mut col_map = header.columns();
+        import.columns.apply(&mut col_map);
+        units.extend(header.units_by_name(&col_map));
+
+        for column in &rules.required_columns {
+            if !col_map.contains_key(column) {
+                violations.push(Violation {
+                    kind: ViolationKind::MissingColumn,
+                    sheet: sheet_name.clone(),
+                    row: None,
+                    well: None,
+                    details: format!("Нет обязательной колонки '{}'", column),
+                });
+            }
+        }
+
+        // Колонки, для которых заданы границы значений
+        let bounded_cols: Vec<(&String, usize, &rules::Bounds)> = rules
+            .bounds
+            .iter()
+            .filter_map(|(col, b)| col_map.get(col).map(|&idx| (col, idx, b)))
+            .collect();
+        let first_row = start_row + data_idx as u32 + 1;
+
+        if let (Some(&idx_n), Some(&idx_d)) = (col_map.get(NAME_COL), col_map.get("Date")) {
+            valid_years.insert(year);
+            let idx_liq = col_map.get("PdLiq").copied();
+            let idx_oil = col_map.get("PdOil").copied();
+            let idx_temp = col_map.get(TEMPERATURE_COL).copied();
+            let idx_p_bottom = col_map.get(P_BOTTOM_COL).copied();
+            let idx_p_head = col_map.get(P_HEAD_COL).copied();
+            let idx_frequency = col_map.get(FREQUENCY_COL).copied();
+            let idx_choke = col_map.get(CHOKE_COL).copied();
+            let idx_injection = col_map.get(INJECTION_COL).copied();
+            let idx_status = col_map.get(STATUS_COL).copied();
+            let idx_type = col_map.get(TYPE_COL).copied();
+
+            for (i, row) in range.rows().skip(data_idx).enumerate() {
+                if i % 5000 == 0 {
+                    cancel.check()?;
+                    progress.report_step(
+                        &format!("Лист '{}': обработка строк...", sheet_name),
+                        i,
+                        total_rows_in_sheet,
+                    );
+                }
+
+                let well_name = match row.get(idx_n) {
+                    Some(Data::String(s)) => s.clone(),
+                    Some(Data::Float(f)) => f.to_string(),
+                    Some(Data::Int(i)) => i.to_string(),
+                    _ => continue,
+                };
+
+                let excel_row = first_row + i as u32;
+                if rules.is_forbidden(&well_name) {
+                    violations.push(Violation {
+                        kind: ViolationKind::ForbiddenWell,
+                        sheet: sheet_name.clone(),
+                        row: Some(excel_row),
+                        well: Some(well_name),
+                        details: "Запрещенное имя скважины, строка пропущена".to_string(),
+                    });
+                    continue;
+                }
+
+                let mut out_of_bounds = false;
+                for (col, idx, bounds) in &bounded_cols {
+                    if let NumberCell::Value(v) = NumberCell::from_data(row.get(*idx))
+                        && let Some(problem) = bounds.check(v)
+                    {
+                        out_of_bounds = true;
+                        violations.push(Violation {
+                            kind: ViolationKind::OutOfBounds,
+                            sheet: sheet_name.clone(),
+                            row: Some(excel_row),
+                            well: Some(well_name.clone()),
+                            details: format!("{}: {}", col, problem),
+                        });
+                    }
+                }
+
+                let date = dates.read(row.get(idx_d), &import.date_format);
+                if date.is_some_and(|d| !period.covers(d)) {
+                    outside_month += 1;
+                }
+
+                let mut get_float = |idx_opt: Option<usize>| -> Option<f64> {
+                    idx_opt.and_then(|i| missing.take(NumberCell::from_data(row.get(i))))
+                };
+
+                all_records
+                    .push(RecordRef {
+                        well_name: &well_name,
+                        date,
+                        pd_liq: get_float(idx_liq),
+                        pd_oil: get_float(idx_oil),
+                        temperature: get_float(idx_temp),
+                        p_bottom: get_float(idx_p_bottom),
+                        p_head: get_float(idx_p_head),
+                        frequency: get_float(idx_frequency),
+                        choke: get_float(idx_choke),
+                        injection: get_float(idx_injection),
+                        status: match idx_status.and_then(|i| row.get(i)) {
+                            Some(Data::String(s)) => WellStatus::parse(s),
+                            _ => None,
+                        },
+                        well_type: match idx_type.and_then(|i| row.get(i)) {
+                            Some(Data::String(s)) => WellType::parse(s),
+                            _ => None,
+                        },
+                        year_sheet: year,
+                        quarter: period.quarter,
+                        out_of_bounds,
+                    })
+                    .at(|| Location::new("Чтение").sheet(sheet_name).row(excel_row))?;
+                unique_wells.insert(well_name);
+            }
+        } else {
+            let column = if col_map.contains_key(NAME_COL) {
+                "Date"
+            } else {
+                NAME_COL
+            };
+            warnings.push(WellDataError::HeaderMissing {
+                sheet: sheet_name.clone(),
+                column: column.to_string(),
+            });
+        }
+        timings.record(format!("Лист '{}': разбор строк", sheet_name), parse_start);
+    }
+
+    warnings.extend(dates.warning());
+    warnings.extend(missing.warnings());
+    if outside_month > 0 {
+        warnings.push(WellDataError::Warning(format!(
+            "строк с датой не из месяца своего листа: {}",
+            outside_month
+        )));
+    }
+    progress.report("Финализация...", total_sheets, total_sheets);
+    timings
+        .measure("Завершение записи", || all_records.finish())
+        .at(|| Location::new("Завершение чтения"))?;
+    Ok(LoadedData {
+        records: all_records,
+        years: valid_years.into_iter().collect(),
+        wells: unique_wells.into_iter().collect(),
+        violations,
+        warnings,
+        units,
+    })
+}
+
+// Колонка значений на листе скважины (после имени и даты)
+type ValueColumn = (&'static str, fn(&RecordRef) -> Option<f64>);
+const PRODUCTION_COLUMNS: [ValueColumn; 3] = [
+    ("PdLiq", |r| r.pd_liq),
+    ("PdOil", |r| r.pd_oil),
+    (TEMPERATURE_COL, |r| r.temperature),
+];
+// У нагнетательной скважины вместо дебитов приемистость и давление на устье
+const INJECTION_COLUMNS: [ValueColumn; 3] = [
+    (INJECTION_COL, |r| r.injection),
+    (P_HEAD_COL, |r| r.p_head),
+    (TEMPERATURE_COL, |r| r.temperature),
+];
+// Колонки, которых может не быть в источнике
+const OPTIONAL_COLUMNS: [ValueColumn; 4] = [
+    (P_BOTTOM_COL, |r| r.p_bottom),
+    (P_HEAD_COL, |r| r.p_head),
+    (FREQUENCY_COL, |r| r.frequency),
+    (CHOKE_COL, |r| r.choke),
+];
+
+// Общее для листов скважин одной выгрузки
+struct WellSheets<'a> {
+    options: &'a ExportOptions,
+    // Необязательные колонки, которые есть в выгрузке
+    optional: &'a [ValueColumn],
+    has_status: bool,
+    has_events: bool,
+    has_allocation: bool,
+    // Группы строк по месяцам: в потоковом режиме не пишутся
+    month_outline: bool,
+    total_wells: usize,
+    progress: &'a dyn ProgressSink,
+    cancel: &'a CancellationToken,
+}
+
+impl WellSheets<'_> {
+    // Лист скважины: строки, оформление и график
+    fn write(
+        &self,
+        worksheet: &mut Worksheet,
+        idx: usize,
+        well_name: &str,
+        records_for_well: &[RecordRef],
+    ) -> Result<(), WellDataError> {
+        self.cancel.check()?;
+        self.progress.report(
+            &format!("Запись скважины: {}", well_name),
+            idx,
+            self.total_wells,
+        );
+
+        let sheet_name = well_sheet_name(well_name);
+        worksheet
+            .set_name(&sheet_name)
+            .at(|| Location::new("Запись").sheet(&sheet_name))?;
+
+        let write_flags = self.options.checks.any_enabled();
+        let main_columns = if records_for_well.iter().any(|r| r.injection.is_some()) {
+            &INJECTION_COLUMNS
+        } else {
+            &PRODUCTION_COLUMNS
+        };
+        let columns: Vec<ValueColumn> = main_columns
+            .iter()
+            .chain(
+                self.optional
+                    .iter()
+                    .filter(|(name, _)| main_columns.iter().all(|(main, _)| main != name)),
+            )
+            .copied()
+            .collect();
+        let mut headers = vec![NAME_COL, "Date"];
+        headers.extend(columns.iter().map(|(name, _)| *name));
+        let allocated_col = headers.len() as u16;
+        let allocation = self
+            .options
+            .allocation
+            .as_ref()
+            .filter(|_| self.has_allocation);
+        if allocation.is_some() {
+            headers.extend([ALLOCATED_LIQ_COL, ALLOCATED_OIL_COL]);
+        }
+        let status_col = headers.len() as u16;
+        if self.has_status {
+            headers.push(STATUS_COL);
+        }
+        let event_col = headers.len() as u16;
+        if self.has_events {
+            headers.push(EVENT_COL);
+        }
+        let well_events = match &self.options.events {
+            Some(log) => events::event_rows(records_for_well, log.for_well(well_name)),
+            None => Default::default(),
+        };
+        let flags_col = headers.len() as u16;
+        if write_flags {
+            headers.push("Flags");
+        }
+        let units: Vec<Option<String>> = headers
+            .iter()
+            .map(|h| units::column_unit(h, &self.options.source_units, self.options.rate_units))
+            .collect();
+        let units_row = self.options.units_row();
+        let titles: Vec<String> = headers
+            .iter()
+            .zip(&units)
+            .map(|(header, unit)| match self.options.units_header {
+                UnitsHeader::Off => header.to_string(),
+                _ if units_row => header.to_string(),
+                _ => units::with_unit(header, unit.as_deref()),
+            })
+            .collect();
+        // Первая строка данных
+        let first_row = 1 + u32::from(units_row);
+        let total_rows = records_for_well.len();
+        let transposed = self.options.sheet_layout == SheetLayout::Transposed;
+        if transposed {
+            let footer = usize::from(self.options.totals_row != TotalsRow::Off);
+            layout::check_transposed(total_rows + footer, first_row)
+                .at(|| Location::new("Запись").sheet(&sheet_name))?;
+        }
+        let at = |row: u32, col: u16| layout::cell(transposed, row, col);
+
+        if self.month_outline {
+            outline::group_by_month(worksheet, records_for_well, first_row, transposed)
+                .at(|| Location::new("Запись").sheet(&sheet_name))?;
+        }
+
+        for (col, title) in titles.iter().enumerate() {
+            let (r, c) = at(0, col as u16);
+            worksheet.write_string(r, c, title)?;
+            if units_row && let Some(unit) = &units[col] {
+                let (r, c) = at(1, col as u16);
+                worksheet.write_string(r, c, unit)?;
+            }
+        }
+
+        let flags = if write_flags {
+            checks::well_flags(records_for_well, &self.options.checks)
+        } else {
+            Vec::new()
+        };
+
+        for (i, record) in records_for_well.iter().enumerate() {
+            if i % 500 == 0 {
+                self.cancel.check()?;
+                self.progress.report_step(
+                    &format!("Скважина {}: строка {}/{}", well_name, i, total_rows),
+                    i,
+                    total_rows,
+                );
+            }
+
+            let row_idx = i as u32 + first_row;
+            let mut write_row = || -> Result<(), XlsxError> {
+                let (r, c) = at(row_idx, 0);
+                worksheet.write_string(r, c, record.well_name)?;
+                if let Some(d) = record.date {
+                    let (r, c) = at(row_idx, 1);
+                    worksheet.write_string(r, c, d.format("%Y-%m-%d %H:%M:%S").to_string())?;
+                }
+                for (k, (_, get)) in columns.iter().enumerate() {
+                    if let Some(v) = get(record) {
+                        let (r, c) = at(row_idx, 2 + k as u16);
+                        worksheet.write_number(r, c, v)?;
+                    }
+                }
+                if let Some(table) = allocation {
+                    let (liquid, oil) = table.allocate(record);
+                    if let Some(v) = liquid {
+                        let (r, c) = at(row_idx, allocated_col);
+                        worksheet.write_number(r, c, v)?;
+                    }
+                    if let Some(v) = oil {
+                        let (r, c) = at(row_idx, allocated_col + 1);
+                        worksheet.write_number(r, c, v)?;
+                    }
+                }
+                if let Some(s) = record.status {
+                    let (r, c) = at(row_idx, status_col);
+                    worksheet.write_string(r, c, s.label())?;
+                }
+                if let Some(row_events) = well_events.get(&i) {
+                    let text: Vec<&str> = row_events.iter().map(|e| e.kind.as_str()).collect();
+                    let (r, c) = at(row_idx, event_col);
+                    worksheet.write_string(r, c, text.join("; "))?;
+                }
+                if let Some(f) = flags.get(i).filter(|f| !f.is_empty()) {
+                    let (r, c) = at(row_idx, flags_col);
+                    worksheet.write_string(r, c, f.join(";"))?;
+                }
+                Ok(())
+            };
+            write_row().at(|| Location::new("Запись").sheet(&sheet_name).row(row_idx + 1))?;
+        }
+        totals::write_totals(
+            worksheet,
+            self.options.totals_row,
+            self.options.totals_rates,
+            records_for_well,
+            &columns,
+            first_row,
+            transposed,
+        )
+        .at(|| {
+            Location::new("Запись")
+                .sheet(&sheet_name)
+                .row(first_row + total_rows as u32 + 1)
+        })?;
+
+        // У таблицы Excel свой фильтр, второй на том же диапазоне не нужен
+        if self.options.filter_and_freeze {
+            if transposed {
+                worksheet.set_freeze_panes(1, first_row as u16)?;
+            } else {
+                filter_and_freeze(
+                    worksheet,
+                    first_row,
+                    first_row + total_rows as u32 - 1,
+                    headers.len() as u16 - 1,
+                    !self.options.excel_tables,
+                )?;
+            }
+        }
+
+        // Оформление записанного диапазона; таблица, подсветка и график
+        // рассчитаны на даты по строкам
+        if total_rows > 0 && !transposed {
+            let last_row = total_rows as u32 + first_row - 1;
+            let last_col = headers.len() as u16 - 1;
+
+            if self.options.excel_tables {
+                let columns: Vec<TableColumn> = titles
+                    .iter()
+                    .map(|h| TableColumn::new().set_header(h))
+                    .collect();
+                let table = Table::new()
+                    .set_name(well_table_name(idx, well_name))
+                    .set_banded_rows(true)
+                    .set_columns(&columns);
+                worksheet.add_table(0, 0, last_row, last_col, &table)?;
+            }
+
+            if self.options.highlight_zero_rows {
+                let zero_format = ConditionalFormatFormula::new()
+                    .set_rule(
+                        format!("=AND(ISNUMBER($C{row}),$C{row}=0)", row = first_row + 1).as_str(),
+                    )
+                    .set_format(
+                        Format::new()
+                            .set_font_color("9C0006")
+                            .set_background_color("FFC7CE"),
+                    );
+                worksheet.add_conditional_format(first_row, 0, last_row, last_col, &zero_format)?;
+            }
+            if self.options.temperature_color_scale {
+                let scale = ConditionalFormat3ColorScale::new()
+                    .set_minimum_color("5A8AC6")
+                    .set_midpoint_color("FCFCFF")
+                    .set_maximum_color("F8696B");
+                worksheet.add_conditional_format(first_row, 4, last_row, 4, &scale)?;
+            }
+            if self.options.bold_after_workover
+                && let Some(log) = &self.options.events
+            {
+                let bold = ConditionalFormatFormula::new()
+                    .set_rule("=TRUE")
+                    .set_format(Format::new().set_bold());
+                for (first, last) in
+                    events::post_workover_rows(records_for_well, log.for_well(well_name))
+                {
+                    worksheet.add_conditional_format(
+                        first as u32 + first_row,
+                        0,
+                        last as u32 + first_row,
+                        last_col,
+                        &bold,
+                    )?;
+                }
+            }
+
+            // Строка после пропуска заливается целиком, без колонки Flags тоже
+            let gaps = &self.options.checks;
+            if gaps.gaps && gaps.gap_fill {
+                let fill = ConditionalFormatFormula::new()
+                    .set_rule("=TRUE")
+                    .set_format(
+                        Format::new()
+                            .set_font_color("9C5700")
+                            .set_background_color("FFEB9C"),
+                    );
+                for i in checks::gap_rows(records_for_well, gaps.gap_days) {
+                    let row = i as u32 + first_row;
+                    worksheet.add_conditional_format(row, 0, row, last_col, &fill)?;
+                }
+            }
+
+            // График справа от данных, с отступом в одну колонку
+            if self.options.well_charts {
+                let mut chart = Chart::new_line();
+                chart.title().set_name(well_name);
+                for (k, (name, _)) in columns.iter().enumerate() {
+                    // Давление - на вспомогательной оси справа
+                    let secondary = match *name {
+                        "PdLiq" | "PdOil" | INJECTION_COL => false,
+                        P_BOTTOM_COL | P_HEAD_COL => true,
+                        _ => continue,
+                    };
+                    let col = 2 + k as u16;
+                    let series = chart
+                        .add_series()
+                        .set_name(*name)
+                        .set_categories((sheet_name.as_str(), first_row, 1, last_row, 1))
+                        .set_values((sheet_name.as_str(), first_row, col, last_row, col))
+                        .set_secondary_axis(secondary);
+                    // Мероприятия - подписями к точкам первой кривой
+                    if k == 0
+                        && let Some(&last_event) = well_events.keys().last()
+                    {
+                        let labels: Vec<ChartDataLabel> = (0..=last_event)
+                            .map(|i| match well_events.get(&i) {
+                                Some(row_events) => {
+                                    let text: Vec<&str> =
+                                        row_events.iter().map(|e| e.kind.as_str()).collect();
+                                    ChartDataLabel::new()
+                                        .set_value(text.join("; ").as_str())
+                                        .to_custom()
+                                }
+                                None => ChartDataLabel::default(),
+                            })
+                            .collect();
+                        // Подписи остальных точек скрыты пустым числовым форматом
+                        series
+                            .set_data_label(
+                                ChartDataLabel::new().show_value().set_num_format(";;;"),
+                            )
+                            .set_custom_data_labels(&labels);
+                    }
+                }
+                worksheet.insert_chart(first_row, last_col + 2, &chart)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn save_excel_file(
+    path: &Path,
+    data: &Dataset,
+    start_year: i32,
+    selected_wells: &HashSet<String>,
+    options: &ExportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoaderMessage, WellDataError> {
+    if options.type_export == TypeExport::Split {
+        let types = well_type::well_types(data);
+        let mut groups: BTreeMap<WellType, HashSet<String>> = BTreeMap::new();
+        for well in selected_wells {
+            let well_type = types.get(well).copied().unwrap_or(WellType::Producer);
+            groups.entry(well_type).or_default().insert(well.clone());
+        }
+        // Если выбраны скважины одного типа, делить нечего
+        if groups.len() > 1 {
+            let combined = ExportOptions {
+                type_export: TypeExport::Combined,
+                ..options.clone()
+            };
+            let mut saved = Vec::new();
+            for (well_type, wells) in &groups {
+                let type_path = well_type::split_path(path, *well_type);
+                save_excel_file(
+                    &type_path, data, start_year, wells, &combined, progress, cancel,
+                )?;
+                saved.push(type_path.display().to_string());
+            }
+            return Ok(LoaderMessage::Saved(saved.join(", ")));
+        }
+    }
+    if options.template.is_some() {
+        return template::fill_template(
+            path,
+            data,
+            start_year,
+            selected_wells,
+            options,
+            progress,
+            cancel,
+        );
+    }
+    let mut timings = Timings::new(format!(
+        "Экспорт {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let mut report = build_report(
+        data,
+        start_year,
+        selected_wells,
+        options,
+        progress,
+        cancel,
+        &mut timings,
+    )?;
+    let total_wells = report.plan.len();
+
+    let wells = manifest::well_counts(report.records.iter());
+
+    progress.report("Сохранение файла на диск...", total_wells, total_wells);
+    let saved = timings.measure("Сохранение на диск", || {
+        options.retry.run("сохранение файла", progress, cancel, || {
+            locked::save_workbook(&mut report.workbook, path)
+        })
+    });
+    if let Err(WellDataError::Locked(mut file)) = saved {
+        let (plan, options) = (report.plan, options.clone());
+        file.after_save = Some(Box::new(move |path| {
+            after_save(path, &plan, &wells, start_year, &options)
+        }));
+        return Err(WellDataError::Locked(file));
+    }
+    saved?;
+    if options.verify {
+        progress.report("Проверка записанного файла...", total_wells, total_wells);
+    }
+    if options.verify || options.manifest {
+        timings.measure("Проверка файла и манифест", || {
+            after_save(path, &report.plan, &wells, start_year, options)
+        })?;
+    }
+    progress.timings(timings.finish());
+    Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
+}
+
+// Проверка записанной книги и манифест - и сразу после записи, и после
+// повторной записи занятого файла
+fn after_save(
+    path: &Path,
+    plan: &[PlannedSheet],
+    wells: &BTreeMap<String, usize>,
+    start_year: i32,
+    options: &ExportOptions,
+) -> Result<(), WellDataError> {
+    if options.verify {
+        verify::verify_workbook(path, plan)?;
+    }
+    if options.manifest {
+        manifest::write_manifest(path, wells, start_year, options)?;
+    }
+    Ok(())
+}
+
+// Книга отчета, собранная в памяти. Путь, запись на диск, проверка
+// и манифест - забота save_excel_file
+struct Report {
+    workbook: Workbook,
+    // Записи, попавшие в отчет
+    records: RecordStore,
+    // Листы скважин по порядку
+    plan: Vec<PlannedSheet>,
+}
+
+// Отбор, сортировка, сводки и оформление листов: из данных в книгу,
+// без диалогов, каналов и файлов
+fn build_report(
+    data: &Dataset,
+    start_year: i32,
+    selected_wells: &HashSet<String>,
+    options: &ExportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+    timings: &mut Timings,
+) -> Result<Report, WellDataError> {
+    progress.report("Подготовка данных...", 0, 0);
+    let filter_start = Instant::now();
+    let selected = options.drop_excluded(data.select(start_year, selected_wells)?);
+    let filtered_data: Vec<RecordRef> = selected.iter().collect();
+    // Данные отсортированы по скважине, поэтому каждая скважина - непрерывный срез
+    let mut well_groups: Vec<&[RecordRef]> = filtered_data
+        .chunk_by(|a, b| a.well_name == b.well_name)
+        .collect();
+    sheet_order::sort(
+        &mut well_groups,
+        options.sheet_order,
+        &options.selection_order,
+    );
+    // Разделы по типу: листы одного типа подряд, перед каждой группой - лист-заголовок
+    let types = if options.type_export == TypeExport::Sections {
+        well_type::well_types(data)
+    } else {
+        HashMap::new()
+    };
+    if !types.is_empty() {
+        well_groups.sort_by_key(|g| types.get(g[0].well_name).copied());
+    }
+    // Необязательные колонки пишутся, только если они есть в выгрузке
+    let optional: Vec<ValueColumn> = OPTIONAL_COLUMNS
+        .into_iter()
+        .filter(|(_, get)| filtered_data.iter().any(|r| get(r).is_some()))
+        .collect();
+    let has_status = filtered_data.iter().any(|r| r.status.is_some());
+    let has_events = options.events.as_ref().is_some_and(|log| {
+        well_groups
+            .iter()
+            .any(|g| !log.for_well(g[0].well_name).is_empty())
+    });
+    let has_allocation = options
+        .allocation
+        .as_ref()
+        .is_some_and(|table| well_groups.iter().any(|g| table.has_well(g[0].well_name)));
+    timings.record("Фильтрация и сортировка", filter_start);
+
+    let shutdowns = if options.downtime.enabled() {
+        timings.measure("Поиск простоев", || {
+            downtime::detect(&filtered_data, &options.downtime)
+        })
+    } else {
+        Vec::new()
+    };
+    // Записи для средних по месяцам: без простоев, если так выбрано
+    let without_downtime =
+        (options.downtime.exclude_from_averages && !shutdowns.is_empty()).then(|| {
+            let excluded = downtime::excluded_rows(&shutdowns);
+            let rows: Vec<usize> = (0..selected.len())
+                .filter(|i| !excluded.contains(i))
+                .collect();
+            selected.subset(&rows)
+        });
+    let averaged = without_downtime.as_ref().unwrap_or(&selected);
+
+    // Потоковый режим сбрасывает строки листа во временный файл по мере записи,
+    // поэтому писать можно только сверху вниз
+    let constant_memory = options.streaming.use_constant_memory(filtered_data.len());
+    layout::check_streaming(options.sheet_layout, constant_memory)?;
+    if constant_memory {
+        progress.report(
+            &format!(
+                "Потоковая запись: {} строк не будут держаться в памяти{}",
+                filtered_data.len(),
+                if options.month_outline {
+                    "; группировка по месяцам в этом режиме не записывается"
+                } else {
+                    ""
+                }
+            ),
+            0,
+            0,
+        );
+    }
+
+    let mut workbook = Workbook::new();
+    workbook.set_properties(&cover::doc_properties(options)?);
+    let wells_to_export: Vec<&str> = well_groups.iter().map(|g| g[0].well_name).collect();
+
+    let total_wells = wells_to_export.len();
+
+    if options.cover_sheet {
+        cover::write_cover_sheet(
+            &mut workbook,
+            constant_memory,
+            options,
+            total_wells,
+            &filtered_data,
+        )?;
+    }
+
+    let per_well = options.sheet_layout != SheetLayout::Years;
+    if options.table_of_contents && per_well {
+        let toc = add_sheet(&mut workbook, constant_memory).set_name("TOC")?;
+        let bold = Format::new().set_bold();
+        toc.write_string_with_format(0, 0, "Скважина", &bold)?;
+        toc.write_string_with_format(0, 1, "Записей", &bold)?;
+        toc.set_column_width(0, 30)?;
+        if !types.is_empty() {
+            toc.write_string_with_format(0, 2, "Тип", &bold)?;
+            toc.set_column_width(2, 18)?;
+        }
+        for (i, (well_name, group)) in wells_to_export.iter().zip(&well_groups).enumerate() {
+            let row = i as u32 + 1;
+            let link = format!(
+                "internal:'{}'!A1",
+                well_sheet_name(well_name).replace('\'', "''")
+            );
+            toc.write_url_with_text(row, 0, Url::new(link), *well_name)?;
+            toc.write_number(row, 1, group.len() as f64)?;
+            if let Some(well_type) = types.get(*well_name) {
+                toc.write_string(row, 2, well_type.label())?;
+            }
+        }
+    }
+
+    let sheets = WellSheets {
+        options,
+        optional: &optional,
+        has_status,
+        has_events,
+        has_allocation,
+        month_outline: options.month_outline && !constant_memory,
+        total_wells,
+        progress,
+        cancel,
+    };
+    // Листы скважин не зависят друг от друга: в обычном режиме они заполняются
+    // параллельно и добавляются в книгу по порядку. Потоковый лист существует
+    // только внутри книги, поэтому в потоковом режиме - по одному
+    let mut prepared = if constant_memory || !per_well {
+        Vec::new()
+    } else {
+        wells_to_export
+            .par_iter()
+            .zip(well_groups.par_iter())
+            .enumerate()
+            .map(|(idx, (well_name, records_for_well))| {
+                let well_start = Instant::now();
+                let mut worksheet = Worksheet::new();
+                sheets
+                    .write(&mut worksheet, idx, well_name, records_for_well)
+                    .map_err(|e| WellDataError::write_failed(well_name, e))?;
+                Ok((worksheet, well_start.elapsed()))
+            })
+            .collect::<Result<Vec<_>, WellDataError>>()?
+    }
+    .into_iter();
+
+    let mut section = None;
+    let year_plan = if per_well {
+        Vec::new()
+    } else {
+        timings.measure("Листы по годам", || {
+            layout::write_year_sheets(
+                &mut workbook,
+                constant_memory,
+                &well_groups,
+                &optional,
+                has_status,
+                options,
+                progress,
+                cancel,
+            )
+        })?
+    };
+
+    for (idx, (well_name, records_for_well)) in wells_to_export
+        .iter()
+        .zip(&well_groups)
+        .enumerate()
+        .filter(|_| per_well)
+    {
+        cancel.check()?;
+        if let Some(&well_type) = types.get(*well_name)
+            && section != Some(well_type)
+        {
+            section = Some(well_type);
+            let count = well_groups
+                .iter()
+                .filter(|g| types.get(g[0].well_name) == Some(&well_type))
+                .count();
+            let title = add_sheet(&mut workbook, constant_memory).set_name(well_type.label())?;
+            title.write_string_with_format(
+                0,
+                0,
+                format!("{} скважины: {}", well_type.label(), count),
+                &Format::new().set_bold().set_font_size(14),
+            )?;
+        }
+
+        let elapsed = match prepared.next() {
+            Some((worksheet, elapsed)) => {
+                workbook.push_worksheet(worksheet);
+                elapsed
+            }
+            None => {
+                let well_start = Instant::now();
+                let worksheet = add_sheet(&mut workbook, constant_memory);
+                sheets
+                    .write(worksheet, idx, well_name, records_for_well)
+                    .map_err(|e| WellDataError::write_failed(well_name, e))?;
+                well_start.elapsed()
+            }
+        };
+        timings.push(format!("Скважина {}", well_name), elapsed);
+    }
+
+    // Каротаж (LAS): отдельный лист на каждую выбранную скважину
+    for log in data
+        .logs
+        .iter()
+        .filter(|l| selected_wells.contains(&l.well_name))
+    {
+        cancel.check()?;
+        let log_start = Instant::now();
+        let sheet_name = well_sheet_name(&format!("LAS {}", log.well_name));
+        let worksheet = add_sheet(&mut workbook, constant_memory).set_name(&sheet_name)?;
+        for (col, curve) in log.curves.iter().enumerate() {
+            let header = if curve.unit.is_empty() {
+                curve.mnemonic.clone()
+            } else {
+                format!("{}, {}", curve.mnemonic, curve.unit)
+            };
+            worksheet.write_string(0, col as u16, header)?;
+        }
+        for (i, row) in log.rows.iter().enumerate() {
+            for (col, value) in row.iter().enumerate() {
+                if let Some(v) = value {
+                    worksheet.write_number(i as u32 + 1, col as u16, *v)?;
+                }
+            }
+        }
+        if options.filter_and_freeze {
+            filter_and_freeze(
+                worksheet,
+                1,
+                log.rows.len() as u32,
+                log.curves.len().saturating_sub(1) as u16,
+                true,
+            )?;
+        }
+        timings.record(format!("Каротаж {}", log.well_name), log_start);
+    }
+
+    if options.monthly_summary {
+        progress.report("Сводка по месяцам...", total_wells, total_wells);
+        let source = match options.monthly_aggregate {
+            Aggregate::Average => averaged,
+            _ => &selected,
+        };
+        let mut months = timings.measure("Сводка по месяцам", || {
+            source.to_frame().and_then(|frame| {
+                frame::monthly(
+                    &frame,
+                    &wells_to_export,
+                    options.monthly_parameter,
+                    options.monthly_aggregate,
+                )
+            })
+        })?;
+        if options.deterministic {
+            summary::round_stable(&mut months);
+        }
+        timings.measure("Лист Monthly", || {
+            summary::write_monthly_sheet(
+                &mut workbook,
+                &months,
+                &wells_to_export,
+                options.monthly_parameter,
+                options.monthly_aggregate,
+            )
+        })?;
+    }
+
+    if options.forecast_months > 0 {
+        progress.report("Прогноз добычи...", total_wells, total_wells);
+        let mut months = timings.measure("Подбор кривых падения", || {
+            averaged.to_frame().and_then(|frame| {
+                frame::monthly(
+                    &frame,
+                    &wells_to_export,
+                    Parameter::PdOil,
+                    Aggregate::Average,
+                )
+            })
+        })?;
+        if options.deterministic {
+            summary::round_stable(&mut months);
+        }
+        timings.measure("Лист Forecast", || {
+            forecast::write_forecast_sheet(
+                &mut workbook,
+                constant_memory,
+                &months,
+                &wells_to_export,
+                options.forecast_months,
+            )
+        })?;
+    }
+
+    if options.rates_sheet {
+        progress.report("Дебиты по дням работы...", total_wells, total_wells);
+        let (liquid, oil) = timings.measure("Балансы по месяцам", || {
+            selected.to_frame().and_then(|frame| {
+                Ok((
+                    frame::balances(&frame, &wells_to_export, Parameter::PdLiq)?,
+                    frame::balances(&frame, &wells_to_export, Parameter::PdOil)?,
+                ))
+            })
+        })?;
+        timings.measure("Лист Rates", || {
+            calendar::write_rates_sheet(
+                &mut workbook,
+                constant_memory,
+                &liquid,
+                &oil,
+                &wells_to_export,
+                options.rate_mode,
+            )
+        })?;
+    }
+
+    if options.downtime.sheet {
+        timings.measure("Лист Downtime", || {
+            downtime::write_downtime_sheet(
+                &mut workbook,
+                constant_memory,
+                &shutdowns,
+                &wells_to_export,
+            )
+        })?;
+    }
+
+    cancel.check()?;
+    if options.excluded_sheet {
+        timings.measure("Лист Excluded", || {
+            excluded::write_excluded_sheet(
+                &mut workbook,
+                constant_memory,
+                data,
+                start_year,
+                selected_wells,
+                &well_groups,
+                options,
+            )
+        })?;
+    }
+    if options.parameters_sheet {
+        manifest::write_parameters_sheet(
+            &mut workbook,
+            constant_memory,
+            start_year,
+            &wells_to_export,
+            options,
+        )?;
+    }
+
+    let plan = if per_well {
+        wells_to_export
+            .iter()
+            .zip(&well_groups)
+            .map(|(well, group)| PlannedSheet {
+                well: well.to_string(),
+                sheet: well_sheet_name(well),
+                rows: group.len(),
+                header_rows: 1 + usize::from(options.units_row()),
+                footer_rows: usize::from(options.totals_row != TotalsRow::Off),
+                transposed: options.sheet_layout == SheetLayout::Transposed,
+            })
+            .collect()
+    } else {
+        year_plan
+    };
+    Ok(Report {
+        workbook,
+        records: selected,
+        plan,
+    })
+}
+
+// Параметры выгрузки для журнала операций
+fn export_parameters(start_year: i32, options: &ExportOptions) -> String {
+    serde_json::to_string(&manifest::parameters(start_year, options)).unwrap_or_default()
+}
+
+fn add_sheet(workbook: &mut Workbook, constant_memory: bool) -> &mut Worksheet {
+    if constant_memory {
+        workbook.add_worksheet_with_constant_memory()
+    } else {
+        workbook.add_worksheet()
+    }
+}
+
+// Закрепленные строки заголовка и первая колонка; автофильтр - от строки
+// прямо над данными, чтобы строка единиц не попадала в сортировку
+fn filter_and_freeze(
+    worksheet: &mut Worksheet,
+    first_row: u32,
+    last_row: u32,
+    last_col: u16,
+    autofilter: bool,
+) -> Result<(), XlsxError> {
+    worksheet.set_freeze_panes(first_row, 1)?;
+    if autofilter && last_row >= first_row {
+        worksheet.autofilter(first_row - 1, 0, last_row, last_col)?;
+    }
+    Ok(())
+}
+
+// Имя листа Excel: без запрещенных символов и не длиннее 30 знаков
+fn well_sheet_name(well_name: &str) -> String {
+    well_name
+        .replace(['/', '\\', '?', '*', '[', ']', ':'], "_")
+        .chars()
+        .take(30)
+        .collect()
+}
+
+// Имя таблицы Excel: только буквы, цифры и '_', уникально за счет номера
+fn well_table_name(idx: usize, well_name: &str) -> String {
+    let safe: String = well_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("Well{}_{}", idx + 1, safe)
+}
+
+// --- ИНТЕРФЕЙС ---
+
+impl eframe::App for WellDataApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        for (id, kind, msg) in self.tasks.poll() {
+            self.export_queue.finish(id, &msg);
+            let summary = match (kind, &msg) {
+                (TaskKind::Load, LoaderMessage::Loaded(_)) => Some("Загрузка завершена"),
+                (TaskKind::Export, LoaderMessage::Saved(_)) => Some("Экспорт завершен"),
+                (TaskKind::Load, LoaderMessage::Error(_)) => Some("Ошибка загрузки"),
+                (TaskKind::Export, LoaderMessage::Error(_) | LoaderMessage::Locked(_)) => {
+                    Some("Ошибка экспорта")
+                }
+                _ => None,
+            };
+            self.handle_message(kind, msg);
+            if let Some(summary) = summary
+                && self.settings.notifications
+                && notify::in_background(ctx)
+            {
+                notify::job_finished(ctx, summary, &self.status_message);
+            }
+        }
+        if self.window_checked {
+            window_state::track(ctx, &mut self.settings.window);
+        } else {
+            self.window_checked = window_state::check_monitor(ctx, self.settings.window.as_ref());
+        }
+        // До мастера первого запуска файла настроек нет - окно не запоминается.
+        // При закрытии ошибку показать уже негде
+        if ctx.input(|i| i.viewport().close_requested())
+            && Settings::path().is_some_and(|p| p.is_file())
+        {
+            let _ = self.settings.save();
+        }
+        self.run_queue();
+        sheet_order::sync(&mut self.selection_order, &self.selected_wells);
+        for task in self.tasks.iter().filter(|t| t.kind != TaskKind::Chart) {
+            self.status_log.progress(task.id, &task.title, &task.status);
+        }
+
+        if !self.tasks.is_empty() {
+            ctx.request_repaint();
+        }
+
+        // Пока идет загрузка, данные заменяются, поэтому основной интерфейс блокируется.
+        // Экспорт работает со своей копией и не мешает дальнейшей работе
+        let is_loading = self.tasks.is_running(TaskKind::Load);
+
+        // --- БЛОК ЗАДАЧ И ПРОГРЕССА ---
+        egui::TopBottomPanel::bottom("tasks").show(ctx, |ui| {
+            ui.add_space(5.0);
+            let mut cancel_id = None;
+            for task in self.tasks.iter().filter(|t| t.kind != TaskKind::Chart) {
+                ui.group(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(&task.title).strong());
+                        if task.is_cancelling() {
+                            ui.label("отмена...");
+                        } else if ui.button("✖ Отмена").clicked() {
+                            cancel_id = Some(task.id);
+                        }
+                    });
+                    ui.label(&task.status);
+                    ui.add(egui::ProgressBar::new(task.progress_global).animate(true));
+                    if task.progress_local < 0.01 {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Обработка данных...");
+                        });
+                    } else {
+                        ui.add(egui::ProgressBar::new(task.progress_local).animate(true));
+                    }
+                });
+            }
+            if let Some(id) = cancel_id {
+                self.tasks.cancel(id);
+            }
+            if !self.export_queue.jobs.is_empty() {
+                show_export_queue(ui, &mut self.export_queue);
+            }
+            ui.label(egui::RichText::new(&self.status_message).color(egui::Color32::GRAY));
+            if !self.status_log.is_empty() {
+                self.status_log.show(ui, self.settings.date_format);
+            }
+            if let Some(timings) = &self.last_timings {
+                show_timings(ui, timings, self.settings.number_locale);
+            }
+            ui.add_space(5.0);
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Обработка данных скважин");
+            ui.add_space(5.0);
+
+            if is_loading {
+                ui.disable();
+            }
 
             // 1. Файл
             ui.horizontal(|ui| {
                 if ui.button("📂 Открыть файл").clicked() {
                     self.load_file();
                 }
-                ui.label(self.source_file_path.as_deref().unwrap_or("..."));
+                if ui
+                    .button("🌐 По ссылке...")
+                    .on_hover_text("Скачать книгу по ссылке http(s) и загрузить")
+                    .clicked()
+                {
+                    self.url_dialog.get_or_insert_with(UrlDialog::default);
+                }
+                if ui
+                    .button("🪣 S3...")
+                    .on_hover_text("Выбрать книгу в хранилище S3 (MinIO)")
+                    .clicked()
+                {
+                    let config = self.settings.s3.clone();
+                    self.s3_dialog.get_or_insert_with(|| S3Dialog::new(config));
+                }
+                if ui
+                    .button("🖧 SFTP...")
+                    .on_hover_text("Выбрать книгу на сервере SFTP")
+                    .clicked()
+                {
+                    let config = self.settings.sftp.clone();
+                    self.sftp_dialog
+                        .get_or_insert_with(|| SftpDialog::new(config));
+                }
+                if ui
+                    .button("📁 Пакет...")
+                    .on_hover_text("Сформировать отчеты по всем xlsx из папки")
+                    .clicked()
+                {
+                    self.process_folder();
+                }
+                if ui
+                    .add_enabled(!self.raw_data.is_empty(), egui::Button::new("🔎 SQL"))
+                    .on_hover_text("Запрос к загруженным данным")
+                    .clicked()
+                {
+                    self.sql.open = true;
+                }
+                if ui
+                    .button("🗺 Карта")
+                    .on_hover_text("Скважины на карте по таблице координат")
+                    .clicked()
+                {
+                    self.map.open = true;
+                }
+                if ui
+                    .add_enabled(!self.raw_data.is_empty(), egui::Button::new("📊 Сводка"))
+                    .on_hover_text("Дебиты и динамика выбранных скважин за выбранный период")
+                    .clicked()
+                {
+                    self.dashboard.open = true;
+                }
+                if ui
+                    .add_enabled(!self.raw_data.is_empty(), egui::Button::new("🔗 Корреляция"))
+                    .on_hover_text("Попарная корреляция дебита нефти выбранных скважин")
+                    .clicked()
+                {
+                    self.correlation.open = true;
+                }
+                if ui
+                    .add_enabled(!self.raw_data.is_empty(), egui::Button::new("🟥 Тепловая карта"))
+                    .on_hover_text("Среднемесячный дебит нефти выбранных скважин по месяцам")
+                    .clicked()
+                {
+                    self.heatmap.open = true;
+                }
+                if ui
+                    .button("📜 Журнал")
+                    .on_hover_text("Журнал загрузок и выгрузок")
+                    .clicked()
+                {
+                    match audit::load() {
+                        Ok(entries) => {
+                            self.audit.entries = entries;
+                            self.audit.open = true;
+                        }
+                        Err(e) => self.set_status(format!("ОШИБКА: {}", e)),
+                    }
+                }
+                ui.label(self.source_file_path.as_deref().unwrap_or("..."));
+            });
+
+            // Правила проверки
+            ui.horizontal(|ui| {
+                match &self.rules_path {
+                    Some(p) => ui.label(format!("📋 Правила: {}", p.display())),
+                    None => ui.label(
+                        egui::RichText::new("📋 Правила проверки не заданы")
+                            .color(egui::Color32::GRAY),
+                    ),
+                };
+                if !self.violations.is_empty() {
+                    ui.label(
+                        egui::RichText::new(format!("⚠ Нарушений: {}", self.violations.len()))
+                            .color(egui::Color32::from_rgb(220, 140, 0)),
+                    );
+                    if ui.button("💾 Отчет о нарушениях").clicked() {
+                        self.save_violations();
+                    }
+                }
+                if !self.raw_data.is_empty()
+                    && ui
+                        .button("💾 Данные (JSON)")
+                        .on_hover_text("Загруженные записи и каротаж для других программ")
+                        .clicked()
+                {
+                    self.save_model();
+                }
+            });
+
+            let mut apply_import_profile = None;
+            egui::CollapsingHeader::new("⚙ Параметры загрузки").show(ui, |ui| {
+                let mut import_profiles_changed = false;
+                ui.horizontal(|ui| {
+                    ui.label("Профиль подрядчика:");
+                    egui::ComboBox::from_id_salt("import_profile")
+                        .selected_text(&self.import_profile_name)
+                        .show_ui(ui, |ui| {
+                            for profile in &self.library.import_profiles {
+                                if ui
+                                    .selectable_label(
+                                        self.import_profile_name == profile.name,
+                                        &profile.name,
+                                    )
+                                    .clicked()
+                                {
+                                    apply_import_profile = Some(profile.clone());
+                                }
+                            }
+                        });
+                    ui.text_edit_singleline(&mut self.import_profile_name);
+                    let named = !self.import_profile_name.trim().is_empty();
+                    if ui
+                        .add_enabled(named, egui::Button::new("💾"))
+                        .on_hover_text("Сохранить колонки, строку заголовка, формат дат и единицы")
+                        .clicked()
+                    {
+                        let profile = self.current_import_profile();
+                        self.library.put_import_profile(profile);
+                        import_profiles_changed = true;
+                    }
+                    let saved = self
+                        .library
+                        .import_profiles
+                        .iter()
+                        .position(|p| p.name == self.import_profile_name);
+                    if ui
+                        .add_enabled(saved.is_some(), egui::Button::new("🗑"))
+                        .on_hover_text("Удалить профиль")
+                        .clicked()
+                        && let Some(i) = saved
+                    {
+                        self.library.import_profiles.remove(i);
+                        import_profiles_changed = true;
+                    }
+                    if ui
+                        .add_enabled(named, egui::Button::new("📤"))
+                        .on_hover_text("Сохранить профиль в файл, чтобы передать коллегам")
+                        .clicked()
+                        && let Some(path) = FileDialog::new()
+                            .add_filter("Профиль", &["toml"])
+                            .set_file_name(format!("{}.toml", self.import_profile_name.trim()))
+                            .save_file()
+                    {
+                        let result = self.current_import_profile().save(&path);
+                        self.set_status(match result {
+                            Ok(()) => format!("Профиль сохранен: {}", path.display()),
+                            Err(e) => format!("ОШИБКА: {}", e),
+                        });
+                    }
+                    if ui
+                        .button("📥")
+                        .on_hover_text("Загрузить профиль из файла")
+                        .clicked()
+                        && let Some(path) =
+                            FileDialog::new().add_filter("Профиль", &["toml"]).pick_file()
+                    {
+                        match ImportProfile::load(&path) {
+                            Ok(profile) => {
+                                self.library.put_import_profile(profile.clone());
+                                import_profiles_changed = true;
+                                apply_import_profile = Some(profile);
+                            }
+                            Err(e) => self.set_status(format!("ОШИБКА: {}", e)),
+                        }
+                    }
+                });
+                if import_profiles_changed {
+                    self.save_library();
+                }
+                if ui
+                    .button("🔎 Определить колонки по файлу...")
+                    .on_hover_text("Если заголовки не подписаны или искажены: колонки угадываются по значениям")
+                    .clicked()
+                    && let Some(path) = FileDialog::new()
+                        .add_filter("Excel или текст", &["xlsx", "csv", "txt"])
+                        .pick_file()
+                {
+                    match detect::detect_file(&path, &self.import_options) {
+                        Ok(proposal) => self.column_proposal = Some(proposal),
+                        Err(e) => self.set_status(format!("ОШИБКА: {}", e)),
+                    }
+                }
+                let import = &mut self.import_options;
+                ui.horizontal(|ui| {
+                    ui.label("Строка заголовка:");
+                    ui.add(egui::DragValue::new(&mut import.header_row).range(0..=100))
+                        .on_hover_text(
+                            "Номер строки как в Excel, в CSV - строка файла; строки выше пропускаются. \
+                             0 - заголовка нет, колонки задаются номерами: #1, #2...",
+                        );
+                    ui.label("строк:");
+                    ui.add(egui::DragValue::new(&mut import.header_rows).range(1..=5))
+                        .on_hover_text(
+                            "Сколько строк занимают имена колонок. Строка единиц \
+                             (м3/сут, °C) под ними узнается сама",
+                        );
+                    ui.label("Формат дат:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut import.date_format)
+                            .hint_text("%d.%m.%Y")
+                            .desired_width(100.0),
+                    )
+                    .on_hover_text("Для дат, записанных текстом; пусто - распознать самим");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Листы с двузначным годом: меньше");
+                    ui.add(egui::DragValue::new(&mut import.century_pivot).range(0..=100))
+                        .on_hover_text("Лист \"98\" при границе 50 - 1998 год, \"05\" - 2005");
+                    ui.label("- 20xx, остальные - 19xx");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Хранение записей:");
+                    for mode in StorageMode::ALL {
+                        ui.radio_value(&mut import.storage, mode, mode.label());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Лимит памяти, МБ:");
+                    ui.add(
+                        egui::DragValue::new(&mut import.memory_limit_mb)
+                            .range(256..=65536)
+                            .speed(64),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("При превышении:");
+                    ui.radio_value(&mut import.refuse_over_limit, true, "не загружать");
+                    ui.radio_value(&mut import.refuse_over_limit, false, "предупредить");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Копировать файл на локальный диск:");
+                    for mode in LocalCopy::ALL {
+                        ui.radio_value(&mut import.local_copy, mode, mode.label());
+                    }
+                })
+                .response
+                .on_hover_text("Разбор идет по локальной копии, обрыв VPN не подвесит загрузку");
+                ui.horizontal(|ui| {
+                    ui.label("Ожидание сетевого диска, с:");
+                    ui.add(egui::DragValue::new(&mut import.network_timeout_s).range(5..=600));
+                });
+                let retry = &mut self.settings.retry;
+                let mut settings_changed = false;
+                ui.horizontal(|ui| {
+                    ui.label("Сбои сети: попыток");
+                    settings_changed |= ui
+                        .add(egui::DragValue::new(&mut retry.attempts).range(1..=10))
+                        .on_hover_text("Всего попыток чтения и записи файла, 1 - без повторов")
+                        .changed();
+                    ui.label("пауза, мс");
+                    settings_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut retry.initial_delay_ms)
+                                .range(0..=30000)
+                                .speed(100),
+                        )
+                        .on_hover_text("Перед первым повтором; каждая следующая вдвое длиннее")
+                        .changed();
+                });
+                settings_changed |= ui
+                    .checkbox(
+                        &mut self.settings.notifications,
+                        "Уведомлять о конце загрузки и экспорта, когда окно свернуто",
+                    )
+                    .changed();
+                if settings_changed {
+                    self.import_options.retry = self.settings.retry;
+                    self.export_options.retry = self.settings.retry;
+                    if let Err(e) = self.settings.save() {
+                        self.set_status(format!("ОШИБКА: {}", e));
+                    }
+                }
+                if ui
+                    .button("🧭 Мастер настройки")
+                    .on_hover_text("Колонки, папка для отчетов, единицы")
+                    .clicked()
+                {
+                    self.wizard = Some(SetupWizard::new(self.settings.clone()));
+                }
+            });
+            if let Some(profile) = apply_import_profile {
+                self.apply_import_profile(&profile);
+            }
+
+            // 2. Год
+            ui.horizontal(|ui| {
+                ui.label("📅 Год начала:");
+                let txt = self
+                    .selected_start_year
+                    .map(|y| y.to_string())
+                    .unwrap_or_default();
+                egui::ComboBox::from_id_salt("y")
+                    .selected_text(txt)
+                    .show_ui(ui, |ui| {
+                        for y in &self.available_years {
+                            ui.selectable_value(
+                                &mut self.selected_start_year,
+                                Some(*y),
+                                y.to_string(),
+                            );
+                        }
+                    });
+            });
+
+            ui.separator();
+
+            // =========================================================
+            //               ДВУХКОЛОНОЧНЫЙ ИНТЕРФЕЙС
+            // =========================================================
+
+            // Задаем 2 колонки
+            ui.columns(2, |columns| {
+                // --- ЛЕВАЯ КОЛОНКА: ПОИСК И ВЫБОР ---
+                columns[0].vertical(|ui| {
+                    ui.heading("🔍 Поиск");
+
+                    // Строка поиска
+                    ui.horizontal(|ui| {
+                        let search = ui.add(
+                            egui::TextEdit::singleline(&mut self.search_query)
+                                .hint_text("Enter - к списку"),
+                        )
+                        .on_hover_text("Слова через пробел - все сразу, -слово - исключить, | - или.\nПример: 105 -bis, G | ST");
+                        if std::mem::take(&mut self.list_nav.focus_search) {
+                            search.request_focus();
+                        }
+                        if search.has_focus() {
+                            self.list_nav.focused = false;
+                        }
+                        if search.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            self.list_nav.enter();
+                        }
+                        if !self.search_query.is_empty() && ui.button("✖").clicked() {
+                            self.search_query.clear();
+                        }
+                    });
+
+                    // Отдельные списки по типам, только если в файле больше одного типа
+                    let type_counts: Vec<(WellType, usize)> = WellType::ALL
+                        .into_iter()
+                        .map(|t| (t, self.well_types.values().filter(|w| **w == t).count()))
+                        .filter(|(_, count)| *count > 0)
+                        .collect();
+                    let split_lists = type_counts.len() > 1;
+                    if split_lists {
+                        ui.horizontal(|ui| {
+                            for (kind, count) in type_counts {
+                                ui.radio_value(
+                                    &mut self.well_kind,
+                                    kind,
+                                    format!("{} ({})", kind.label(), count),
+                                );
+                            }
+                        });
+                    }
+
+                    if !self.well_statuses.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label("Состояние:");
+                            egui::ComboBox::from_id_salt("status_filter")
+                                .selected_text(self.status_filter.map_or("Все", WellStatus::label))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.status_filter, None, "Все");
+                                    for s in WellStatus::ALL {
+                                        ui.selectable_value(
+                                            &mut self.status_filter,
+                                            Some(s),
+                                            s.label(),
+                                        );
+                                    }
+                                });
+                        });
+                    }
+
+                    if self.available_years.len() > 1 || !self.well_quarters.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label("Данные за год:");
+                            egui::ComboBox::from_id_salt("year_filter")
+                                .selected_text(self.year_filter.label())
+                                .show_ui(ui, |ui| {
+                                    let filters = [YearFilter::Any, YearFilter::AllSelected]
+                                        .into_iter()
+                                        .chain(self.available_years.iter().map(|y| YearFilter::Year(*y)));
+                                    let quarters: BTreeSet<(i32, u8)> =
+                                        self.well_quarters.values().flatten().copied().collect();
+                                    let filters = filters.chain(
+                                        quarters.into_iter().map(|(y, q)| YearFilter::Quarter(y, q)),
+                                    );
+                                    for filter in filters {
+                                        ui.selectable_value(
+                                            &mut self.year_filter,
+                                            filter,
+                                            filter.label(),
+                                        );
+                                    }
+                                });
+                        });
+                    }
+
+                    if self.selected_start_year.is_some() {
+                        ui.checkbox(
+                            &mut self.hide_outside_period,
+                            "Скрыть скважины без данных за период выгрузки",
+                        );
+                    }
+
+                    ui.checkbox(&mut self.only_unselected, "Только невыбранные");
+
+                    // Фильтрация
+                    let search = SearchQuery::parse(&self.search_query);
+                    let selected_years: Vec<i32> = self
+                        .available_years
+                        .iter()
+                        .copied()
+                        .filter(|y| self.selected_start_year.is_none_or(|start| *y >= start))
+                        .collect();
+                    let filtered_wells: Vec<&String> = self
+                        .unique_wells
+                        .iter()
+                        .filter(|w| !split_lists || self.well_types.get(*w) == Some(&self.well_kind))
+                        .filter(|w| {
+                            self.status_filter
+                                .is_none_or(|s| self.well_statuses.get(*w) == Some(&s))
+                        })
+                        .filter(|w| search.matches(w))
+                        .filter(|w| {
+                            self.year_filter
+                                .matches(
+                                    self.well_years.get(*w),
+                                    self.well_quarters.get(*w),
+                                    &selected_years,
+                                )
+                        })
+                        .filter(|w| {
+                            !self.hide_outside_period
+                                || self.selected_start_year.is_none()
+                                || self
+                                    .well_years
+                                    .get(*w)
+                                    .is_some_and(|y| selected_years.iter().any(|s| y.contains(s)))
+                        })
+                        .filter(|w| !self.only_unselected || !self.selected_wells.contains(*w))
+                        .collect();
+
+                    if let Some(row) = self.list_nav.handle_keys(ui.ctx(), filtered_wells.len()) {
+                        let well = filtered_wells[row];
+                        if !self.selected_wells.remove(well) {
+                            self.selected_wells.insert(well.clone());
+                        }
+                    }
+
+                    // Общая галочка: все видимые выбраны, часть или ни одной
+                    let visible_selected = filtered_wells
+                        .iter()
+                        .filter(|w| self.selected_wells.contains(**w))
+                        .count();
+                    let all_selected =
+                        !filtered_wells.is_empty() && visible_selected == filtered_wells.len();
+                    ui.horizontal(|ui| {
+                        let mut checked = all_selected;
+                        let master = egui::Checkbox::new(
+                            &mut checked,
+                            format!("Видимые: {} из {}", visible_selected, filtered_wells.len()),
+                        )
+                        .indeterminate(visible_selected > 0 && !all_selected);
+                        if ui
+                            .add_enabled(!filtered_wells.is_empty(), master)
+                            .clicked()
+                        {
+                            if all_selected {
+                                for well in &filtered_wells {
+                                    self.selected_wells.remove(*well);
+                                }
+                            } else {
+                                for well in &filtered_wells {
+                                    self.selected_wells.insert((*well).clone());
+                                }
+                            }
+                        }
+                        if ui
+                            .add_enabled(visible_selected > 0, egui::Button::new("Снять видимые"))
+                            .clicked()
+                        {
+                            for well in &filtered_wells {
+                                self.selected_wells.remove(*well);
+                            }
+                        }
+                    });
+                    let mut top_selection = None;
+                    ui.horizontal(|ui| {
+                        let clicked = ui
+                            .add_enabled(
+                                !filtered_wells.is_empty(),
+                                egui::Button::new("⭐ Топ по нефти:"),
+                            )
+                            .on_hover_text(
+                                "Выбрать видимые скважины с наибольшим средним дебитом нефти \
+                                 с начального года; прежний выбор сбрасывается",
+                            )
+                            .clicked();
+                        ui.add(egui::DragValue::new(&mut self.top_n).range(1..=1000));
+                        if clicked {
+                            let visible: HashSet<&str> =
+                                filtered_wells.iter().map(|w| w.as_str()).collect();
+                            top_selection = Some(
+                                well_list::oil_ranking(&self.raw_data, self.selected_start_year)
+                                    .map(|ranking| {
+                                        ranking
+                                            .into_iter()
+                                            .filter(|(well, _)| visible.contains(well.as_str()))
+                                            .take(self.top_n)
+                                            .map(|(well, _)| well)
+                                            .collect::<Vec<String>>()
+                                    }),
+                            );
+                        }
+                    });
+
+                    ui.add_space(5.0);
+
+                    // Список (левый)
+                    ui.push_id("left_list", |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                if filtered_wells.is_empty() && !self.unique_wells.is_empty() {
+                                    ui.label("Нет совпадений");
+                                }
+                                for (row, well) in filtered_wells.into_iter().enumerate() {
+                                    let mut is_sel = self.selected_wells.contains(well);
+                                    let text = match self.well_statuses.get(well) {
+                                        Some(s) => format!("{}  ({})", well, s.label()),
+                                        None => well.clone(),
+                                    };
+                                    let response = ui
+                                        .horizontal(|ui| {
+                                            let response = ui.checkbox(&mut is_sel, text);
+                                            if let Some(values) = self.sparklines.get(well) {
+                                                well_list::sparkline(ui, values);
+                                            }
+                                            response
+                                        })
+                                        .inner;
+                                    self.list_nav.mark(ui, row, &response);
+                                    if response.clicked() {
+                                        self.list_nav.cursor = row;
+                                    }
+                                    if response.changed() {
+                                        if is_sel {
+                                            self.selected_wells.insert(well.clone());
+                                        } else {
+                                            self.selected_wells.remove(well);
+                                        }
+                                    }
+                                }
+                            });
+                    });
+
+                    // Без рейтинга прежний выбор остается как был
+                    match top_selection {
+                        Some(Ok(top)) => {
+                            self.set_status(format!(
+                                "Выбрано скважин с наибольшим дебитом нефти: {}",
+                                top.len()
+                            ));
+                            self.selected_wells = top.into_iter().collect();
+                        }
+                        Some(Err(e)) => {
+                            self.set_status(format!("ОШИБКА: рейтинг по нефти: {}", e));
+                        }
+                        None => {}
+                    }
+                });
+
+                // --- ПРАВАЯ КОЛОНКА: ВЫБРАННЫЕ ---
+                columns[1].vertical(|ui| {
+                    ui.heading(format!("✅ Выбрано: {}", self.selected_wells.len()));
+
+                    if ui.button("🗑 Сбросить всё").clicked() {
+                        self.selected_wells.clear();
+                    }
+
+                    ui.add_space(5.0);
+
+                    // Сортируем выбранные, чтобы список не прыгал
+                    let mut sorted_selected: Vec<String> =
+                        self.selected_wells.iter().cloned().collect();
+                    sorted_selected.sort();
+
+                    // Список (правый)
+                    ui.push_id("right_list", |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                if sorted_selected.is_empty() {
+                                    ui.label(
+                                        egui::RichText::new("Список пуст")
+                                            .color(egui::Color32::GRAY),
+                                    );
+                                }
+
+                                // Отображаем список выбранных с кнопкой удаления
+                                for well in sorted_selected {
+                                    ui.horizontal(|ui| {
+                                        if ui.button("✖").clicked() {
+                                            self.selected_wells.remove(&well);
+                                        }
+                                        ui.label(&well);
+                                    });
+                                }
+                            });
+                    });
+                });
+            });
+
+            ui.add_space(10.0);
+            ui.separator();
+
+            let mut run_analysis = None;
+            let mut library_changed = false;
+            egui::CollapsingHeader::new("📚 Сохраненные анализы").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Название:");
+                    ui.text_edit_singleline(&mut self.analysis_name);
+                    if ui
+                        .add_enabled(
+                            !self.analysis_name.trim().is_empty(),
+                            egui::Button::new("⭐ Сохранить текущие настройки"),
+                        )
+                        .clicked()
+                    {
+                        let analysis = self.current_analysis();
+                        self.library.put_analysis(analysis);
+                        library_changed = true;
+                    }
+                });
+                let mut remove = None;
+                for (i, analysis) in self.library.analyses.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(!self.raw_data.is_empty(), egui::Button::new("▶"))
+                            .on_hover_text("Применить к текущему файлу и сформировать отчет")
+                            .clicked()
+                        {
+                            run_analysis = Some(analysis.clone());
+                        }
+                        if ui.button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                        let wells = if analysis.wells.is_empty() {
+                            "все скважины".to_string()
+                        } else {
+                            format!("скважин: {}", analysis.wells.len())
+                        };
+                        ui.label(format!("{} ({})", analysis.name, wells));
+                    });
+                }
+                if let Some(i) = remove {
+                    self.library.analyses.remove(i);
+                    library_changed = true;
+                }
             });
+            if library_changed {
+                self.save_library();
+            }
+            if let Some(analysis) = run_analysis {
+                self.run_analysis(&analysis);
+            }
 
-            // 2. Год
+            // 3. Параметры экспорта
+            let mut apply_profile = None;
+            let mut profiles_changed = false;
+            egui::CollapsingHeader::new("⚙ Параметры экспорта").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Профиль:");
+                    egui::ComboBox::from_id_salt("export_profile")
+                        .selected_text(&self.profile_name)
+                        .show_ui(ui, |ui| {
+                            for profile in &self.library.profiles {
+                                if ui
+                                    .selectable_label(self.profile_name == profile.name, &profile.name)
+                                    .clicked()
+                                {
+                                    apply_profile = Some(profile.clone());
+                                }
+                            }
+                        });
+                    ui.text_edit_singleline(&mut self.profile_name);
+                    let named = !self.profile_name.trim().is_empty();
+                    if ui
+                        .add_enabled(named, egui::Button::new("💾"))
+                        .on_hover_text("Сохранить текущие настройки под этим именем")
+                        .clicked()
+                    {
+                        self.library.put_profile(ExportProfile {
+                            name: self.profile_name.trim().to_string(),
+                            options: self.export_options.clone(),
+                        });
+                        profiles_changed = true;
+                    }
+                    let saved = self
+                        .library
+                        .profiles
+                        .iter()
+                        .position(|p| p.name == self.profile_name);
+                    if ui
+                        .add_enabled(saved.is_some(), egui::Button::new("🗑"))
+                        .on_hover_text("Удалить профиль")
+                        .clicked()
+                        && let Some(i) = saved
+                    {
+                        self.library.profiles.remove(i);
+                        profiles_changed = true;
+                    }
+                    if ui
+                        .add_enabled(named, egui::Button::new("📤"))
+                        .on_hover_text("Сохранить профиль в файл, чтобы передать коллегам")
+                        .clicked()
+                        && let Some(path) = FileDialog::new()
+                            .add_filter("Профиль", &["toml"])
+                            .set_file_name(format!("{}.toml", self.profile_name.trim()))
+                            .save_file()
+                    {
+                        let profile = ExportProfile {
+                            name: self.profile_name.trim().to_string(),
+                            options: self.export_options.clone(),
+                        };
+                        self.set_status(match profile.save(&path) {
+                            Ok(()) => format!("Профиль сохранен: {}", path.display()),
+                            Err(e) => format!("ОШИБКА: {}", e),
+                        });
+                    }
+                    if ui
+                        .button("📥")
+                        .on_hover_text("Загрузить профиль из файла")
+                        .clicked()
+                        && let Some(path) =
+                            FileDialog::new().add_filter("Профиль", &["toml"]).pick_file()
+                    {
+                        match ExportProfile::load(&path) {
+                            Ok(profile) => {
+                                self.library.put_profile(profile.clone());
+                                profiles_changed = true;
+                                apply_profile = Some(profile);
+                            }
+                            Err(e) => self.set_status(format!("ОШИБКА: {}", e)),
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Имя файла:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.export_options.output_name)
+                            .hint_text("{source}_{year}"),
+                    )
+                    .on_hover_text("{source} - имя исходного файла, {year} - начальный год");
+                });
+                ui.add_space(5.0);
+                let checks = &mut self.export_options.checks;
+                ui.label("Колонка Flags с пометками подозрительных строк:");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut checks.outliers, "Выбросы (OUTLIER), порог");
+                    ui.add_enabled(
+                        checks.outliers,
+                        egui::DragValue::new(&mut checks.outlier_threshold)
+                            .range(1.0..=10.0)
+                            .speed(0.1),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut checks.gaps, "Пропуски (GAP_BEFORE), дней больше");
+                    ui.add_enabled(
+                        checks.gaps,
+                        egui::DragValue::new(&mut checks.gap_days).range(1..=365),
+                    );
+                    ui.add_enabled(
+                        checks.gaps,
+                        egui::Checkbox::new(&mut checks.gap_fill, "подсвечивать на листе"),
+                    );
+                });
+                ui.checkbox(
+                    &mut checks.validation,
+                    "Нарушения правил проверки (OUT_OF_BOUNDS)",
+                );
+
+                ui.add_space(5.0);
+                let downtime = &mut self.export_options.downtime;
+                ui.horizontal(|ui| {
+                    ui.label("Простой - дебит жидкости не выше");
+                    ui.add(
+                        egui::DragValue::new(&mut downtime.threshold)
+                            .range(0.0..=100.0)
+                            .speed(0.1),
+                    );
+                    ui.label("записей подряд от");
+                    ui.add(egui::DragValue::new(&mut downtime.min_records).range(1..=100));
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut downtime.sheet, "Лист Downtime");
+                    ui.checkbox(
+                        &mut downtime.exclude_from_averages,
+                        "Не учитывать простои в средних",
+                    )
+                    .on_hover_text("Среднее на листе Monthly и кривые падения на листе Forecast");
+                });
+
+                ui.add_space(5.0);
+                ui.label("Условное форматирование:");
+                ui.checkbox(
+                    &mut self.export_options.highlight_zero_rows,
+                    "Подсвечивать строки с нулевым дебитом жидкости",
+                );
+                ui.checkbox(
+                    &mut self.export_options.temperature_color_scale,
+                    "Цветовая шкала для температуры",
+                );
+
+                ui.add_space(5.0);
+                // Заголовок и автор идут в свойства книги и на титульный лист
+                let mut author_changed = false;
+                ui.horizontal(|ui| {
+                    ui.label("Заголовок:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.export_options.report_title)
+                            .hint_text(cover::DEFAULT_TITLE),
+                    );
+                    ui.label("Автор:");
+                    let hint = cover::author("");
+                    author_changed = ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.export_options.author)
+                                .hint_text(hint),
+                        )
+                        .lost_focus();
+                });
+                if author_changed && self.library.author != self.export_options.author {
+                    self.library.author = self.export_options.author.clone();
+                    self.save_library();
+                }
+                ui.checkbox(
+                    &mut self.export_options.cover_sheet,
+                    "Титульный лист: автор, исходный файл, период, дата формирования",
+                );
+                ui.checkbox(
+                    &mut self.export_options.manifest,
+                    "Манифест .manifest.json: контрольная сумма и число записей по скважинам",
+                );
+                ui.checkbox(
+                    &mut self.export_options.excluded_sheet,
+                    "Лист Excluded: скважины, не вошедшие в отчет, с причиной",
+                );
+                ui.checkbox(
+                    &mut self.export_options.parameters_sheet,
+                    "Скрытый лист Parameters: настройки, по которым сформирован отчет",
+                );
+                ui.checkbox(
+                    &mut self.export_options.deterministic,
+                    "Воспроизводимый файл: без времени формирования, для сравнения отчетов",
+                );
+                ui.checkbox(
+                    &mut self.export_options.verify,
+                    "Проверять файл после записи: перечитать и сверить строки по скважинам",
+                );
+                ui.checkbox(
+                    &mut self.export_options.table_of_contents,
+                    "Лист TOC: оглавление со ссылками на листы скважин",
+                );
+                ui.checkbox(
+                    &mut self.export_options.excel_tables,
+                    "Оформлять данные скважин как таблицы Excel",
+                );
+                ui.checkbox(
+                    &mut self.export_options.well_charts,
+                    "График PdLiq/PdOil на листе каждой скважины",
+                );
+                ui.checkbox(
+                    &mut self.export_options.filter_and_freeze,
+                    "Автофильтр и закрепленный заголовок на листах с записями",
+                );
+                ui.checkbox(
+                    &mut self.export_options.month_outline,
+                    "Группировать строки скважин по месяцам и годам (можно сворачивать)",
+                );
+                ui.horizontal(|ui| {
+                    let streaming = &mut self.export_options.streaming;
+                    ui.label("Запись файла:");
+                    egui::ComboBox::from_id_salt("write_mode")
+                        .selected_text(streaming.mode.label())
+                        .show_ui(ui, |ui| {
+                            for m in WriteMode::ALL {
+                                ui.selectable_value(&mut streaming.mode, m, m.label());
+                            }
+                        });
+                    if streaming.mode == WriteMode::Auto {
+                        ui.label("потоковая, если строк больше");
+                        ui.add(
+                            egui::DragValue::new(&mut streaming.threshold_rows)
+                                .range(10_000..=100_000_000)
+                                .speed(10_000),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let opts = &mut self.export_options;
+                    ui.checkbox(&mut opts.monthly_summary, "Лист Monthly:");
+                    ui.add_enabled_ui(opts.monthly_summary, |ui| {
+                        egui::ComboBox::from_id_salt("monthly_param")
+                            .selected_text(opts.monthly_parameter.label())
+                            .show_ui(ui, |ui| {
+                                for p in Parameter::ALL {
+                                    ui.selectable_value(&mut opts.monthly_parameter, p, p.label());
+                                }
+                            });
+                        egui::ComboBox::from_id_salt("monthly_agg")
+                            .selected_text(opts.monthly_aggregate.label())
+                            .show_ui(ui, |ui| {
+                                for a in Aggregate::ALL {
+                                    ui.selectable_value(&mut opts.monthly_aggregate, a, a.label());
+                                }
+                            });
+                    });
+                });
+                ui.horizontal(|ui| {
+                    let months = &mut self.export_options.forecast_months;
+                    let mut enabled = *months > 0;
+                    if ui
+                        .checkbox(&mut enabled, "Лист Forecast на")
+                        .on_hover_text(
+                            "Экспоненциальная кривая падения по среднемесячному дебиту нефти \
+                             за последние 12 месяцев",
+                        )
+                        .changed()
+                    {
+                        *months = if enabled { 12 } else { 0 };
+                    }
+                    ui.add_enabled(
+                        enabled,
+                        egui::DragValue::new(months).range(1..=120).suffix(" мес."),
+                    );
+                });
+
+                ui.horizontal(|ui| {
+                    let has_status = !self.well_statuses.is_empty();
+                    let opts = &mut self.export_options;
+                    ui.checkbox(&mut opts.rates_sheet, "Лист Rates:")
+                        .on_hover_text(
+                            "Добыча и дебиты по месяцам с учетом шага замеров; \
+                             простой и ремонт по колонке Status в добычу не входят",
+                        );
+                    ui.add_enabled_ui(opts.rates_sheet, |ui| {
+                        egui::ComboBox::from_id_salt("rate_mode")
+                            .selected_text(opts.rate_mode.label())
+                            .show_ui(ui, |ui| {
+                                for mode in RateMode::ALL {
+                                    // Без состояния скважины дни работы не отличить от календарных
+                                    ui.add_enabled_ui(
+                                        has_status || mode == RateMode::CalendarDay,
+                                        |ui| {
+                                            ui.selectable_value(
+                                                &mut opts.rate_mode,
+                                                mode,
+                                                mode.label(),
+                                            )
+                                        },
+                                    );
+                                }
+                            });
+                    });
+                });
+
+                if !self.well_statuses.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("Не выгружать периоды:");
+                        let excluded = &mut self.export_options.excluded_statuses;
+                        for s in WellStatus::ALL {
+                            let mut on = excluded.contains(&s);
+                            if ui.checkbox(&mut on, s.label()).changed() {
+                                if on {
+                                    excluded.insert(s);
+                                } else {
+                                    excluded.remove(&s);
+                                }
+                            }
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    let sheet_layout = &mut self.export_options.sheet_layout;
+                    ui.label("Листы:");
+                    egui::ComboBox::from_id_salt("sheet_layout")
+                        .selected_text(sheet_layout.label())
+                        .show_ui(ui, |ui| {
+                            for l in SheetLayout::ALL {
+                                ui.selectable_value(sheet_layout, l, l.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Лист на год - выбранные скважины и период в раскладке исходной \
+                             книги; оглавление и разделы по типам не пишутся. Даты по \
+                             столбцам - без таблиц Excel, подсветки и графиков",
+                        );
+                });
+                ui.horizontal(|ui| {
+                    let order = &mut self.export_options.sheet_order;
+                    ui.label("Порядок листов:");
+                    egui::ComboBox::from_id_salt("sheet_order")
+                        .selected_text(order.label())
+                        .show_ui(ui, |ui| {
+                            for o in SheetOrder::ALL {
+                                ui.selectable_value(order, o, o.label());
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    let units = &mut self.export_options.units_header;
+                    ui.label("Единицы измерения:");
+                    egui::ComboBox::from_id_salt("units_header")
+                        .selected_text(units.label())
+                        .show_ui(ui, |ui| {
+                            for u in UnitsHeader::ALL {
+                                ui.selectable_value(units, u, u.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Из строки единиц исходного файла, для дебитов - из настроек. \
+                             В таблицах Excel единицы всегда пишутся в заголовок",
+                        );
+                });
+                ui.horizontal(|ui| {
+                    let opts = &mut self.export_options;
+                    ui.label("Строка \"Итого\":");
+                    egui::ComboBox::from_id_salt("totals_row")
+                        .selected_text(opts.totals_row.label())
+                        .show_ui(ui, |ui| {
+                            for t in TotalsRow::ALL {
+                                ui.selectable_value(&mut opts.totals_row, t, t.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Число записей, дебиты и средняя температура под данными");
+                    if opts.totals_row != TotalsRow::Off {
+                        ui.label("Дебиты:");
+                        egui::ComboBox::from_id_salt("totals_rates")
+                            .selected_text(opts.totals_rates.label())
+                            .show_ui(ui, |ui| {
+                                for r in RateTotal::ALL {
+                                    ui.selectable_value(&mut opts.totals_rates, r, r.label());
+                                }
+                            });
+                    }
+                });
+                let types: BTreeSet<WellType> = self.well_types.values().copied().collect();
+                if types.len() > 1 {
+                    ui.horizontal(|ui| {
+                        let export = &mut self.export_options.type_export;
+                        ui.label("Типы скважин:");
+                        egui::ComboBox::from_id_salt("type_export")
+                            .selected_text(export.label())
+                            .show_ui(ui, |ui| {
+                                for e in TypeExport::ALL {
+                                    ui.selectable_value(export, e, e.label());
+                                }
+                            });
+                    });
+                }
+
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.label("Журнал мероприятий:");
+                    match &self.export_options.events {
+                        Some(log) => {
+                            ui.label(format!(
+                                "{} (событий: {})",
+                                log.path.file_name().unwrap_or_default().to_string_lossy(),
+                                log.len()
+                            ));
+                            if ui.button("✖").on_hover_text("Выгружать без мероприятий").clicked() {
+                                self.export_options.events = None;
+                            }
+                        }
+                        None => {
+                            ui.label("не загружен");
+                        }
+                    }
+                    if ui
+                        .button("📂 Загрузить...")
+                        .on_hover_text("Таблица CSV или xlsx: скважина, дата, вид мероприятия")
+                        .clicked()
+                        && let Some(path) = FileDialog::new()
+                            .add_filter("Таблица", &["xlsx", "csv", "txt"])
+                            .pick_file()
+                    {
+                        match events::read_events(&path) {
+                            Ok(log) => {
+                                self.set_status(format!("Журнал мероприятий: {} событий", log.len()));
+                                self.export_options.events = Some(log);
+                            }
+                            Err(e) => {
+                                self.set_status(format!("ОШИБКА в журнале мероприятий: {}", e))
+                            }
+                        }
+                    }
+                });
+                ui.add_enabled(
+                    self.export_options.events.is_some(),
+                    egui::Checkbox::new(
+                        &mut self.export_options.bold_after_workover,
+                        format!(
+                            "Жирным - строки {} дней после ремонта",
+                            events::POST_WORKOVER_DAYS
+                        ),
+                    ),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Коэффициенты распределения:");
+                    match &self.export_options.allocation {
+                        Some(table) => {
+                            ui.label(format!(
+                                "{} (скважино-месяцев: {})",
+                                table.path.file_name().unwrap_or_default().to_string_lossy(),
+                                table.len()
+                            ));
+                            if ui
+                                .button("✖")
+                                .on_hover_text("Выгружать только замеренные дебиты")
+                                .clicked()
+                            {
+                                self.export_options.allocation = None;
+                            }
+                        }
+                        None => {
+                            ui.label("не загружены");
+                        }
+                    }
+                    if ui
+                        .button("📂 Загрузить...")
+                        .on_hover_text(
+                            "Таблица CSV или xlsx: скважина, месяц, коэффициент \
+                             (общий или отдельно для жидкости и нефти)",
+                        )
+                        .clicked()
+                        && let Some(path) = FileDialog::new()
+                            .add_filter("Таблица", &["xlsx", "csv", "txt"])
+                            .pick_file()
+                    {
+                        match allocation::read_allocation(&path) {
+                            Ok(table) => {
+                                self.set_status(format!(
+                                    "Коэффициенты распределения: {} скважино-месяцев",
+                                    table.len()
+                                ));
+                                self.export_options.allocation = Some(table);
+                            }
+                            Err(e) => self.set_status(format!(
+                                "ОШИБКА в таблице коэффициентов: {}",
+                                e
+                            )),
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Шаблон книги:");
+                    match &self.export_options.template {
+                        Some(path) => {
+                            ui.label(path.file_name().unwrap_or_default().to_string_lossy());
+                            if ui.button("✖").on_hover_text("Создавать новую книгу").clicked() {
+                                self.export_options.template = None;
+                            }
+                        }
+                        None => {
+                            ui.label("нет (новая книга)");
+                        }
+                    }
+                    if ui
+                        .button("📂 Выбрать...")
+                        .on_hover_text(format!(
+                            "Данные пишутся на место ячеек {} и {}, остальное в книге сохраняется",
+                            template::RECORDS_MARKER,
+                            template::MONTHLY_MARKER
+                        ))
+                        .clicked()
+                        && let Some(path) = FileDialog::new().add_filter("Excel", &["xlsx"]).pick_file()
+                    {
+                        self.export_options.template = Some(path);
+                    }
+                });
+            });
+
+            if profiles_changed {
+                self.save_library();
+            }
+            if let Some(profile) = apply_profile {
+                self.apply_profile(&profile);
+            }
+
+            // 4. Кнопка
+            let ready = !self.raw_data.is_empty()
+                && (self.selected_start_year.is_some() || self.available_years.is_empty())
+                && !self.selected_wells.is_empty();
             ui.horizontal(|ui| {
-                ui.label("📅 Год начала:");
-                let txt = self
-                    .selected_start_year
-                    .map(|y| y.to_string())
-                    .unwrap_or_default();
-                egui::ComboBox::from_id_source("y")
-                    .selected_text(txt)
-                    .show_ui(ui, |ui| {
-                        for y in &self.available_years {
-                            ui.selectable_value(
-                                &mut self.selected_start_year,
-                                Some(*y),
-                                y.to_string(),
+                if ui
+                    .add_enabled(
+                        ready,
+                        egui::Button::new("🚀 Сформировать отчет").min_size(egui::vec2(0.0, 30.0)),
+                    )
+                    .clicked()
+                {
+                    self.process_data();
+                }
+                if ui
+                    .add_enabled(ready, egui::Button::new("➕ В очередь").min_size(egui::vec2(0.0, 30.0)))
+                    .on_hover_text("Запомнить скважины, год и настройки; выгрузки из очереди идут по одной")
+                    .clicked()
+                {
+                    self.enqueue_export();
+                }
+                if ui
+                    .add_enabled(
+                        ready && !self.available_years.is_empty(),
+                        egui::Button::new("📄 Выгрузка для OFM").min_size(egui::vec2(0.0, 30.0)),
+                    )
+                    .on_hover_text("Месячная добыча: скважина, дата, дни, нефть, жидкость")
+                    .clicked()
+                {
+                    self.export_ofm();
+                }
+                if ui
+                    .add_enabled(
+                        ready && !self.available_years.is_empty(),
+                        egui::Button::new("🗓 Шахматка").min_size(egui::vec2(0.0, 30.0)),
+                    )
+                    .on_hover_text("Суточная добыча: скважины по строкам, дни месяца по колонкам, лист на месяц")
+                    .clicked()
+                {
+                    self.export_chessboard();
+                }
+            });
+        });
+
+        if let Some((path, text)) = &self.crash_report {
+            let mut close = false;
+            egui::Window::new("⚠ Программа аварийно завершилась")
+                .collapsible(false)
+                .default_width(560.0)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(
+                        "При прошлом запуске программа закрылась из-за внутренней ошибки. \
+                         Отправьте отчет разработчикам - это поможет ее исправить.",
+                    );
+                    ui.label(format!("Отчет сохранен: {}", path.display()));
+                    egui::CollapsingHeader::new("Подробности")
+                        .id_salt("crash_details")
+                        .show(ui, |ui| {
+                            egui::ScrollArea::vertical()
+                                .max_height(250.0)
+                                .show(ui, |ui| {
+                                    ui.label(egui::RichText::new(text).monospace().small());
+                                });
+                        });
+                    ui.horizontal(|ui| {
+                        if ui.button("📋 Копировать").clicked() {
+                            ctx.copy_text(text.clone());
+                        }
+                        if ui.button("Закрыть").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+            if close {
+                self.crash_report = None;
+            }
+        }
+
+        if let Some(dialog) = &mut self.archive_dialog {
+            // Some(файлы) - загрузить, Some(пусто) - закрыть
+            let mut action: Option<Vec<String>> = None;
+            egui::Window::new(format!(
+                "🗜 Архив {}",
+                dialog
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            ))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("Файлы данных в архиве:");
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for name in &dialog.entries {
+                            let selected = dialog.selected.as_ref() == Some(name);
+                            let response = ui.selectable_label(selected, name);
+                            if response.clicked() {
+                                dialog.selected = Some(name.clone());
+                            }
+                            if response.double_clicked() {
+                                action = Some(vec![name.clone()]);
+                            }
+                        }
+                    });
+                if dialog.entries.len() > 1 {
+                    ui.separator();
+                    ui.label(
+                        "Приоритет при совпадающих годах скважин (перетащите, верхний важнее):",
+                    );
+                    let mut moved: Option<(usize, usize)> = None;
+                    egui::ScrollArea::vertical()
+                        .id_salt("archive_priority")
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for (i, name) in dialog.priority.iter().enumerate() {
+                                let response = ui
+                                    .dnd_drag_source(
+                                        egui::Id::new(("archive_priority", i)),
+                                        i,
+                                        |ui| {
+                                            ui.label(format!("☰ {}. {}", i + 1, name));
+                                        },
+                                    )
+                                    .response;
+                                if response.dnd_hover_payload::<usize>().is_some() {
+                                    ui.painter().hline(
+                                        response.rect.x_range(),
+                                        response.rect.top(),
+                                        ui.visuals().selection.stroke,
+                                    );
+                                }
+                                if let Some(from) = response.dnd_release_payload::<usize>() {
+                                    moved = Some((*from, i));
+                                }
+                            }
+                        });
+                    if let Some((from, to)) = moved {
+                        let name = dialog.priority.remove(from);
+                        dialog.priority.insert(to, name);
+                    }
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            dialog.selected.is_some(),
+                            egui::Button::new("⬇ Загрузить выбранный"),
+                        )
+                        .clicked()
+                    {
+                        action = dialog.selected.clone().map(|name| vec![name]);
+                    }
+                    if ui
+                        .add_enabled(
+                            dialog.entries.len() > 1,
+                            egui::Button::new(format!(
+                                "⬇ Загрузить все ({})",
+                                dialog.entries.len()
+                            )),
+                        )
+                        .on_hover_text(
+                            "Записи всех файлов загружаются вместе, как из одной книги; \
+                             год скважины берется из файла выше по приоритету",
+                        )
+                        .clicked()
+                    {
+                        action = Some(dialog.priority.clone());
+                    }
+                    if ui.button("Отмена").clicked() {
+                        action = Some(Vec::new());
+                    }
+                });
+            });
+            if let Some(names) = action {
+                let path = dialog.path.clone();
+                self.archive_dialog = None;
+                if !names.is_empty() {
+                    self.start_archive_load(path, names);
+                }
+            }
+        }
+
+        if let Some(dialog) = &mut self.url_dialog {
+            let mut action = None;
+            egui::Window::new("🌐 Открыть по ссылке")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("url_dialog").num_columns(2).show(ui, |ui| {
+                        ui.label("Ссылка:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut dialog.url)
+                                .hint_text("https://portal/files/2024-05.xlsx")
+                                .desired_width(360.0),
+                        );
+                        ui.end_row();
+                        ui.label("Авторизация:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut dialog.auth)
+                                .hint_text("Bearer токен или Заголовок: значение")
+                                .password(true)
+                                .desired_width(360.0),
+                        )
+                        .on_hover_text("Не сохраняется; пусто - без авторизации");
+                        ui.end_row();
+                    });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(
+                                !dialog.url.trim().is_empty(),
+                                egui::Button::new("⬇ Загрузить"),
+                            )
+                            .clicked()
+                        {
+                            action = Some(true);
+                        }
+                        if ui.button("Отмена").clicked() {
+                            action = Some(false);
+                        }
+                    });
+                });
+            match action {
+                Some(true) => {
+                    let url = dialog.url.trim().to_string();
+                    let auth = dialog.auth.clone();
+                    self.url_dialog = None;
+                    self.start_url_load(url, auth);
+                }
+                Some(false) => self.url_dialog = None,
+                None => {}
+            }
+        }
+
+        if let Some(dialog) = &mut self.s3_dialog {
+            let mut action = None;
+            let listing = self.tasks.is_running(TaskKind::Query);
+            egui::Window::new("🪣 Хранилище S3")
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("s3_dialog").num_columns(2).show(ui, |ui| {
+                        let config = &mut dialog.config;
+                        for (label, value, hint) in [
+                            ("Адрес:", &mut config.endpoint, "http://minio:9000"),
+                            ("Регион:", &mut config.region, "us-east-1"),
+                            ("Корзина:", &mut config.bucket, "well-data"),
+                            ("Ключ доступа:", &mut config.access_key, "AWS_ACCESS_KEY_ID"),
+                        ] {
+                            ui.label(label);
+                            ui.add(
+                                egui::TextEdit::singleline(value)
+                                    .hint_text(hint)
+                                    .desired_width(300.0),
                             );
+                            ui.end_row();
+                        }
+                        ui.label("Секретный ключ:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut dialog.secret_key)
+                                .hint_text("AWS_SECRET_ACCESS_KEY")
+                                .password(true)
+                                .desired_width(300.0),
+                        )
+                        .on_hover_text("Не сохраняется; пусто - из переменной окружения");
+                        ui.end_row();
+                        ui.label("Префикс:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut config.prefix)
+                                    .hint_text("daily/2024-05")
+                                    .desired_width(220.0),
+                            );
+                            if ui
+                                .add_enabled(!listing, egui::Button::new("🔄 Список"))
+                                .clicked()
+                            {
+                                action = Some(S3Action::List);
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                    ui.separator();
+                    if dialog.objects.is_empty() {
+                        ui.label("Объектов нет - задайте префикс и нажмите \"Список\"");
+                    }
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for object in &dialog.objects {
+                                let selected = dialog.selected.as_ref() == Some(&object.key);
+                                let text = format!(
+                                    "{}   {}   {} КБ",
+                                    object.key,
+                                    object.modified,
+                                    self.settings
+                                        .number_locale
+                                        .count(object.size.div_ceil(1024) as usize)
+                                );
+                                let response = ui.selectable_label(selected, text);
+                                if response.clicked() {
+                                    dialog.selected = Some(object.key.clone());
+                                }
+                                if response.double_clicked() {
+                                    action = Some(S3Action::Load);
+                                }
+                            }
+                        });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(
+                                dialog.selected.is_some(),
+                                egui::Button::new("⬇ Загрузить"),
+                            )
+                            .clicked()
+                        {
+                            action = Some(S3Action::Load);
+                        }
+                        if ui.button("Закрыть").clicked() {
+                            action = Some(S3Action::Close);
+                        }
+                    });
+                });
+            match action {
+                Some(S3Action::Close) => self.s3_dialog = None,
+                Some(S3Action::List) => {
+                    let (config, secret_key) = (dialog.config.clone(), dialog.secret_key.clone());
+                    self.start_s3_list(config, secret_key);
+                }
+                Some(S3Action::Load) => {
+                    if let Some(key) = dialog.selected.clone() {
+                        let (config, secret_key) =
+                            (dialog.config.clone(), dialog.secret_key.clone());
+                        self.s3_dialog = None;
+                        self.start_s3_load(config, secret_key, key);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        if let Some(dialog) = &mut self.sftp_dialog {
+            let mut action = None;
+            let listing = self.tasks.is_running(TaskKind::Query);
+            egui::Window::new("🖧 Сервер SFTP")
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("sftp_dialog")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            let config = &mut dialog.config;
+                            ui.label("Сервер:");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut config.host)
+                                        .hint_text("field-srv01")
+                                        .desired_width(220.0),
+                                );
+                                ui.label("порт");
+                                ui.add(egui::DragValue::new(&mut config.port).range(1..=65535));
+                            });
+                            ui.end_row();
+                            ui.label("Пользователь:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut config.user).desired_width(300.0),
+                            );
+                            ui.end_row();
+                            ui.label("Вход:");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut config.auth, SftpAuth::Password, "по паролю");
+                                ui.radio_value(&mut config.auth, SftpAuth::Key, "по ключу");
+                            });
+                            ui.end_row();
+                            if config.auth == SftpAuth::Key {
+                                ui.label("Закрытый ключ:");
+                                ui.horizontal(|ui| {
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut config.key_path)
+                                            .hint_text("C:\\Users\\...\\.ssh\\id_rsa")
+                                            .desired_width(260.0),
+                                    );
+                                    if ui.button("...").clicked()
+                                        && let Some(path) = FileDialog::new()
+                                            .set_title("Закрытый ключ SSH")
+                                            .pick_file()
+                                    {
+                                        config.key_path = path.to_string_lossy().to_string();
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                            ui.label(match config.auth {
+                                SftpAuth::Password => "Пароль:",
+                                SftpAuth::Key => "Фраза ключа:",
+                            });
+                            ui.add(
+                                egui::TextEdit::singleline(&mut dialog.secret)
+                                    .password(true)
+                                    .desired_width(300.0),
+                            )
+                            .on_hover_text("Не сохраняется");
+                            ui.end_row();
+                            ui.label("Папка:");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut dialog.dir)
+                                        .hint_text("/data/daily")
+                                        .desired_width(220.0),
+                                );
+                                if ui
+                                    .add_enabled(!listing, egui::Button::new("🔄 Открыть"))
+                                    .clicked()
+                                {
+                                    action = Some(SftpAction::List(dialog.dir.trim().to_string()));
+                                }
+                            });
+                            ui.end_row();
+                        });
+
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            if dialog.dir.trim() != "/"
+                                && ui
+                                    .add_enabled(!listing, egui::Button::new("⬆ ..").frame(false))
+                                    .clicked()
+                            {
+                                action = Some(SftpAction::List(sftp::parent(&dialog.dir)));
+                            }
+                            for entry in &dialog.entries {
+                                if entry.is_dir {
+                                    if ui
+                                        .add_enabled(
+                                            !listing,
+                                            egui::Button::new(format!("📁 {}", entry.name))
+                                                .frame(false),
+                                        )
+                                        .clicked()
+                                    {
+                                        action = Some(SftpAction::List(sftp::join(
+                                            &dialog.dir,
+                                            &entry.name,
+                                        )));
+                                    }
+                                    continue;
+                                }
+                                let modified = entry
+                                    .modified
+                                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                                    .map(|t| {
+                                        self.settings
+                                            .date_format
+                                            .datetime(t.with_timezone(&chrono::Local).naive_local())
+                                    })
+                                    .unwrap_or_default();
+                                let text = format!(
+                                    "📄 {}   {}   {} КБ",
+                                    entry.name,
+                                    modified,
+                                    self.settings
+                                        .number_locale
+                                        .count(entry.size.div_ceil(1024) as usize)
+                                );
+                                let selected = dialog.selected.as_ref() == Some(&entry.name);
+                                let response = ui.selectable_label(selected, text);
+                                if response.clicked() {
+                                    dialog.selected = Some(entry.name.clone());
+                                }
+                                if response.double_clicked() {
+                                    action = Some(SftpAction::Load);
+                                }
+                            }
+                        });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(
+                                dialog.selected.is_some(),
+                                egui::Button::new("⬇ Загрузить"),
+                            )
+                            .clicked()
+                        {
+                            action = Some(SftpAction::Load);
+                        }
+                        if ui.button("Закрыть").clicked() {
+                            action = Some(SftpAction::Close);
                         }
                     });
-            });
-
-            ui.separator();
-
-            // =========================================================
-            //               ДВУХКОЛОНОЧНЫЙ ИНТЕРФЕЙС
-            // =========================================================
-
-            // Задаем 2 колонки
-            ui.columns(2, |columns| {
-                // --- ЛЕВАЯ КОЛОНКА: ПОИСК И ВЫБОР ---
-                columns[0].vertical(|ui| {
-                    ui.heading("🔍 Поиск");
+                });
+            match action {
+                Some(SftpAction::Close) => self.sftp_dialog = None,
+                Some(SftpAction::List(dir)) => {
+                    let (config, secret) = (dialog.config.clone(), dialog.secret.clone());
+                    self.start_sftp_list(config, secret, dir);
+                }
+                Some(SftpAction::Load) => {
+                    if let Some(name) = &dialog.selected {
+                        let remote_path = sftp::join(&dialog.dir, name);
+                        let (config, secret) = (dialog.config.clone(), dialog.secret.clone());
+                        self.sftp_dialog = None;
+                        self.start_sftp_load(config, secret, remote_path);
+                    }
+                }
+                None => {}
+            }
+        }
 
-                    // Строка поиска
+        if let Some(file) = &self.locked_file {
+            let mut action = None;
+            egui::Window::new("🔒 Файл занят")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Не удалось сохранить {}: файл открыт в другой программе, скорее всего в Excel.",
+                        file.path.display()
+                    ));
+                    ui.label("Закройте его и повторите запись или сохраните под другим именем. Выгрузка уже готова и заново не выполняется.");
                     ui.horizontal(|ui| {
-                        ui.text_edit_singleline(&mut self.search_query);
-                        if !self.search_query.is_empty() && ui.button("✖").clicked() {
-                            self.search_query.clear();
+                        if ui.button("🔁 Повторить").clicked() {
+                            action = Some(Some(file.path.clone()));
+                        }
+                        if ui.button("💾 Сохранить как...").clicked()
+                            && let Some(path) = FileDialog::new()
+                                .add_filter("Excel", &["xlsx"])
+                                .set_directory(file.path.parent().unwrap_or(Path::new(".")))
+                                .set_file_name(
+                                    file.path.file_name().unwrap_or_default().to_string_lossy(),
+                                )
+                                .save_file()
+                        {
+                            action = Some(Some(path));
+                        }
+                        if ui.button("Отмена").clicked() {
+                            action = Some(None);
                         }
                     });
+                });
+            match action {
+                // Запись, проверка и манифест - в фоне, как при первом сохранении:
+                // сетевой диск может отвечать долго
+                Some(Some(path)) => {
+                    if let Some(file) = self.locked_file.take() {
+                        let title = format!(
+                            "Запись {}",
+                            path.file_name().unwrap_or_default().to_string_lossy()
+                        );
+                        self.tasks
+                            .spawn(TaskKind::Export, title, move |_tx, _cancel| {
+                                locked::write(file, &path)?;
+                                Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
+                            });
+                    }
+                }
+                Some(None) => self.locked_file = None,
+                None => {}
+            }
+        }
 
-                    // Фильтрация
-                    let filtered_wells: Vec<&String> = self
-                        .unique_wells
-                        .iter()
-                        .filter(|w| w.to_lowercase().contains(&self.search_query.to_lowercase()))
-                        .collect();
-
-                    if ui.button("Выбрать видимые").clicked() {
-                        for well in &filtered_wells {
-                            self.selected_wells.insert((*well).clone());
+        if let Some(report) = &self.error_report {
+            let mut close = false;
+            egui::Window::new("❌ Ошибка")
+                .collapsible(false)
+                .default_width(480.0)
+                .show(ctx, |ui| {
+                    show_error_report(ui, report);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("📋 Копировать")
+                            .on_hover_text("Подробности для обращения в поддержку")
+                            .clicked()
+                        {
+                            ctx.copy_text(report.details());
                         }
-                    }
+                        if ui.button("Закрыть").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+            if close {
+                self.error_report = None;
+            }
+        }
 
-                    ui.add_space(5.0);
+        if let Some(wizard) = &mut self.wizard {
+            let mut finished = false;
+            egui::Window::new("🧭 Первоначальная настройка")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Шаг {} из {}: {}",
+                        wizard.step + 1,
+                        WizardStep::ALL.len(),
+                        wizard.current().title()
+                    ));
+                    ui.separator();
+                    show_wizard_step(ui, wizard);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(wizard.step > 0, egui::Button::new("◀ Назад"))
+                            .clicked()
+                        {
+                            wizard.step -= 1;
+                        }
+                        if wizard.is_last() {
+                            if ui.button("✔ Готово").clicked() {
+                                finished = true;
+                            }
+                        } else if ui.button("Далее ▶").clicked() {
+                            wizard.step += 1;
+                        }
+                    });
+                });
+            if finished && let Some(wizard) = self.wizard.take() {
+                match wizard.settings.save() {
+                    Ok(()) => self.set_status("Настройки сохранены".to_string()),
+                    Err(e) => self.set_status(format!("ОШИБКА: {}", e)),
+                }
+                self.apply_settings(wizard.settings);
+            }
+        }
 
-                    // Список (левый)
-                    ui.push_id("left_list", |ui| {
-                        egui::ScrollArea::vertical()
-                            .max_height(300.0)
-                            .show(ui, |ui| {
-                                if filtered_wells.is_empty() && !self.unique_wells.is_empty() {
-                                    ui.label("Нет совпадений");
+        if let Some(preview) = &mut self.csv_preview {
+            let mut action = None;
+            egui::Window::new("📄 Предпросмотр CSV")
+                .collapsible(false)
+                .default_size([640.0, 360.0])
+                .show(ctx, |ui| {
+                    ui.label(preview.path.display().to_string());
+                    ui.horizontal(|ui| {
+                        ui.label("Кодировка:");
+                        egui::ComboBox::from_id_salt("csv_encoding")
+                            .selected_text(preview.options.encoding.label())
+                            .show_ui(ui, |ui| {
+                                for e in TextEncoding::ALL {
+                                    ui.selectable_value(
+                                        &mut preview.options.encoding,
+                                        e,
+                                        e.label(),
+                                    );
                                 }
-                                for well in filtered_wells {
-                                    let mut is_sel = self.selected_wells.contains(well);
-                                    if ui.checkbox(&mut is_sel, well).changed() {
-                                        if is_sel {
-                                            self.selected_wells.insert(well.clone());
-                                        } else {
-                                            self.selected_wells.remove(well);
-                                        }
-                                    }
+                            });
+                        ui.label("Разделитель:");
+                        egui::ComboBox::from_id_salt("csv_delimiter")
+                            .selected_text(preview.options.delimiter.label())
+                            .show_ui(ui, |ui| {
+                                for d in Delimiter::ALL {
+                                    ui.selectable_value(
+                                        &mut preview.options.delimiter,
+                                        d,
+                                        d.label(),
+                                    );
                                 }
                             });
                     });
+                    egui::ScrollArea::both().max_height(240.0).show(ui, |ui| {
+                        egui::Grid::new("csv_preview").striped(true).show(ui, |ui| {
+                            for row in preview.rows(0, 10) {
+                                for cell in row {
+                                    ui.label(cell);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("✔ Загрузить").clicked() {
+                            action = Some(true);
+                        }
+                        if ui.button("Отмена").clicked() {
+                            action = Some(false);
+                        }
+                    });
                 });
+            match action {
+                Some(true) => {
+                    if let Some(preview) = self.csv_preview.take() {
+                        self.import_options.csv = preview.options;
+                        self.start_load(preview.path);
+                    }
+                }
+                Some(false) => self.csv_preview = None,
+                None => {}
+            }
+        }
 
-                // --- ПРАВАЯ КОЛОНКА: ВЫБРАННЫЕ ---
-                columns[1].vertical(|ui| {
-                    ui.heading(format!("✅ Выбрано: {}", self.selected_wells.len()));
+        if let Some(proposal) = &self.column_proposal {
+            let mut action = None;
+            egui::Window::new("🔎 Колонки по содержимому")
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.label(proposal.path.display().to_string());
+                    if !proposal.has_header {
+                        ui.label("Заголовка нет: первая строка - уже данные, колонки будут заданы номерами");
+                    }
+                    if proposal.guesses.is_empty() {
+                        ui.label("Не удалось узнать ни одной колонки");
+                    }
+                    egui::Grid::new("column_proposal").striped(true).show(ui, |ui| {
+                        ui.strong("Поле");
+                        ui.strong("Колонка");
+                        ui.strong("Заголовок");
+                        ui.strong("Пример");
+                        ui.end_row();
+                        for guess in &proposal.guesses {
+                            ui.label(guess.field);
+                            ui.label((guess.column + 1).to_string());
+                            ui.label(&guess.header);
+                            ui.label(&guess.sample);
+                            ui.end_row();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(!proposal.guesses.is_empty(), egui::Button::new("✔ Применить"))
+                            .clicked()
+                        {
+                            action = Some(true);
+                        }
+                        if ui.button("Отмена").clicked() {
+                            action = Some(false);
+                        }
+                    });
+                });
+            match action {
+                Some(true) => {
+                    if let Some(proposal) = self.column_proposal.take() {
+                        self.import_options.columns = proposal.columns;
+                        if !proposal.has_header {
+                            self.import_options.header_row = 0;
+                        } else if self.import_options.header_row == 0 {
+                            self.import_options.header_row = 1;
+                        }
+                        self.set_status(format!(
+                            "Колонки определены по содержимому: {}",
+                            proposal.guesses.len()
+                        ));
+                    }
+                }
+                Some(false) => self.column_proposal = None,
+                None => {}
+            }
+        }
 
-                    if ui.button("🗑 Сбросить всё").clicked() {
-                        self.selected_wells.clear();
+        let mut audit_open = self.audit.open;
+        egui::Window::new("📜 Журнал операций")
+            .open(&mut audit_open)
+            .default_size([700.0, 400.0])
+            .show(ctx, |ui| {
+                show_audit_log(ui, &self.audit.entries, self.settings.date_format);
+            });
+        self.audit.open = audit_open;
+
+        let mut dashboard_open = self.dashboard.open;
+        if dashboard_open {
+            self.dashboard.refresh(
+                &mut self.tasks,
+                &self.raw_data,
+                self.generation,
+                self.selected_start_year.unwrap_or(i32::MIN),
+                &self.selected_wells,
+            );
+        }
+        egui::Window::new("📊 Сводка по выборке")
+            .open(&mut dashboard_open)
+            .default_size([420.0, 360.0])
+            .show(ctx, |ui| {
+                self.dashboard.show(
+                    ui,
+                    self.export_options.rate_units,
+                    self.settings.number_locale,
+                );
+            });
+        self.dashboard.open = dashboard_open;
+
+        let mut heatmap_open = self.heatmap.open;
+        if heatmap_open {
+            self.heatmap.refresh(
+                &mut self.tasks,
+                &self.raw_data,
+                self.generation,
+                self.selected_start_year.unwrap_or(i32::MIN),
+                &self.selected_wells,
+            );
+        }
+        egui::Window::new("🟥 Тепловая карта добычи")
+            .open(&mut heatmap_open)
+            .default_size([720.0, 420.0])
+            .show(ctx, |ui| self.heatmap.show(ui));
+        self.heatmap.open = heatmap_open;
+
+        let mut correlation_open = self.correlation.open;
+        egui::Window::new("🔗 Корреляция скважин")
+            .open(&mut correlation_open)
+            .default_size([600.0, 420.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            self.selected_wells.len() >= 2,
+                            egui::Button::new("▶ Посчитать"),
+                        )
+                        .clicked()
+                    {
+                        self.correlation.recompute(
+                            &mut self.tasks,
+                            &self.raw_data,
+                            self.generation,
+                            self.selected_start_year.unwrap_or(i32::MIN),
+                            &self.selected_wells,
+                        );
                     }
+                    ui.label(format!(
+                        "Выбрано скважин: {}. Суточные средние PdOil с начального года",
+                        self.selected_wells.len()
+                    ));
+                });
+                ui.separator();
+                self.correlation.show(ui);
+            });
+        self.correlation.open = correlation_open;
 
-                    ui.add_space(5.0);
+        let mut sql_open = self.sql.open;
+        let mut run_query = false;
+        let mut save_result = false;
+        let mut library_changed = false;
+        let mut map_open = self.map.open;
+        egui::Window::new("🗺 Карта скважин")
+            .open(&mut map_open)
+            .default_size([520.0, 520.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("📂 Координаты...").clicked()
+                        && let Some(path) = FileDialog::new()
+                            .add_filter("Таблица", &["xlsx", "csv", "txt"])
+                            .pick_file()
+                    {
+                        match map::read_coordinates(&path) {
+                            Ok(coords) => {
+                                self.set_status(format!(
+                                    "Координаты загружены: {} скважин",
+                                    coords.points.len()
+                                ));
+                                self.map.coordinates = Some(coords);
+                            }
+                            Err(e) => self.set_status(format!("ОШИБКА в координатах: {}", e)),
+                        }
+                    }
+                    ui.label("Цвет:");
+                    egui::ComboBox::from_id_salt("map_coloring")
+                        .selected_text(self.map.coloring.label())
+                        .show_ui(ui, |ui| {
+                            for c in MapColoring::ALL {
+                                ui.selectable_value(&mut self.map.coloring, c, c.label());
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    for tool in MapTool::ALL {
+                        ui.radio_value(&mut self.map.tool, tool, tool.label());
+                    }
+                    match self.map.tool {
+                        MapTool::Click => {}
+                        MapTool::Radius => {
+                            ui.add(
+                                egui::DragValue::new(&mut self.map.radius_m)
+                                    .range(10.0..=100_000.0)
+                                    .speed(50.0)
+                                    .suffix(" м"),
+                            );
+                        }
+                        MapTool::Polygon => {
+                            if ui
+                                .add_enabled(
+                                    self.map.polygon.len() >= 3,
+                                    egui::Button::new("✔ Выбрать"),
+                                )
+                                .clicked()
+                            {
+                                self.map
+                                    .apply_polygon(&self.unique_wells, &mut self.selected_wells);
+                            }
+                            if ui.button("✖ Сбросить").clicked() {
+                                self.map.polygon.clear();
+                            }
+                        }
+                    }
+                });
+                if self.map.coordinates.is_some() {
+                    self.map
+                        .show(ui, &self.unique_wells, &mut self.selected_wells);
+                } else {
+                    ui.label("Загрузите таблицу координат: скважина, X, Y или широта, долгота");
+                }
+            });
+        self.map.open = map_open;
 
-                    // Сортируем выбранные, чтобы список не прыгал
-                    let mut sorted_selected: Vec<String> =
-                        self.selected_wells.iter().cloned().collect();
-                    sorted_selected.sort();
+        egui::Window::new("🔎 SQL-запрос")
+            .open(&mut sql_open)
+            .default_size([640.0, 420.0])
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(query::TABLE_HINT).color(egui::Color32::GRAY));
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.sql.text)
+                        .code_editor()
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+                let running = self.tasks.is_running(TaskKind::Query);
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !running && !self.raw_data.is_empty(),
+                            egui::Button::new("▶ Выполнить"),
+                        )
+                        .clicked()
+                    {
+                        run_query = true;
+                    }
+                    if ui
+                        .add_enabled(self.sql.result.is_some(), egui::Button::new("💾 Сохранить"))
+                        .on_hover_text("В Excel или CSV")
+                        .clicked()
+                    {
+                        save_result = true;
+                    }
+                });
 
-                    // Список (правый)
-                    ui.push_id("right_list", |ui| {
-                        egui::ScrollArea::vertical()
-                            .max_height(300.0)
-                            .show(ui, |ui| {
-                                if sorted_selected.is_empty() {
-                                    ui.label(
-                                        egui::RichText::new("Список пуст")
-                                            .color(egui::Color32::GRAY),
-                                    );
+                ui.horizontal(|ui| {
+                    ui.label("Название:");
+                    ui.text_edit_singleline(&mut self.sql.save_name);
+                    if ui
+                        .add_enabled(
+                            !self.sql.save_name.trim().is_empty(),
+                            egui::Button::new("⭐ В библиотеку"),
+                        )
+                        .clicked()
+                    {
+                        self.library.put_query(SavedQuery {
+                            name: self.sql.save_name.trim().to_string(),
+                            sql: self.sql.text.clone(),
+                        });
+                        library_changed = true;
+                    }
+                });
+                if !self.library.queries.is_empty() {
+                    egui::CollapsingHeader::new("📚 Библиотека запросов").show(ui, |ui| {
+                        let mut remove = None;
+                        for (i, saved) in self.library.queries.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add_enabled(!running, egui::Button::new("▶"))
+                                    .on_hover_text(&saved.sql)
+                                    .clicked()
+                                {
+                                    self.sql.text = saved.sql.clone();
+                                    self.sql.save_name = saved.name.clone();
+                                    run_query = true;
                                 }
-
-                                // Отображаем список выбранных с кнопкой удаления
-                                for well in sorted_selected {
-                                    ui.horizontal(|ui| {
-                                        if ui.button("✖").clicked() {
-                                            self.selected_wells.remove(&well);
-                                        }
-                                        ui.label(&well);
-                                    });
+                                if ui.button("🗑").clicked() {
+                                    remove = Some(i);
                                 }
+                                ui.label(&saved.name);
                             });
+                        }
+                        if let Some(i) = remove {
+                            self.library.queries.remove(i);
+                            library_changed = true;
+                        }
                     });
-                });
-            });
+                }
 
-            ui.add_space(10.0);
-            ui.separator();
+                if let Some(result) = &self.sql.result {
+                    show_query_result(ui, result, &self.settings);
+                }
+            });
+        self.sql.open = sql_open;
+        if library_changed {
+            self.save_library();
+        }
+        if run_query {
+            self.run_query();
+        }
+        if save_result {
+            self.save_query_result();
+        }
+    }
+}
 
-            // 4. Кнопка
-            let ready = !self.raw_data.is_empty()
-                && self.selected_start_year.is_some()
-                && !self.selected_wells.is_empty();
-            if ui
-                .add_enabled(
-                    ready,
-                    egui::Button::new("🚀 Сформировать отчет").min_size(egui::vec2(0.0, 30.0)),
-                )
-                .clicked()
-            {
-                self.process_data();
+fn show_wizard_step(ui: &mut egui::Ui, wizard: &mut SetupWizard) {
+    let step = wizard.current();
+    let settings = &mut wizard.settings;
+    match step {
+        WizardStep::Formats => {
+            ui.label("Числа в окнах программы:");
+            for locale in NumberLocale::ALL {
+                ui.radio_value(&mut settings.number_locale, locale, locale.label());
             }
+            ui.label("Даты в окнах программы:");
+            for format in DateFormat::ALL {
+                ui.radio_value(&mut settings.date_format, format, format.label());
+            }
+        }
+        WizardStep::Columns => {
+            ui.label("Как называются колонки на листах-годах ваших файлов:");
+            let columns = &mut settings.columns;
+            egui::Grid::new("wizard_columns").show(ui, |ui| {
+                for (label, value) in [
+                    ("Скважина", &mut columns.well),
+                    ("Дата", &mut columns.date),
+                    ("Дебит жидкости", &mut columns.pd_liq),
+                    ("Дебит нефти", &mut columns.pd_oil),
+                    ("Температура", &mut columns.temperature),
+                ] {
+                    ui.label(label);
+                    ui.text_edit_singleline(value);
+                    ui.end_row();
+                }
+            });
+            if ui.button("Как в стандартной выгрузке").clicked() {
+                *columns = ColumnMapping::default();
+            }
+        }
+        WizardStep::OutputDir => {
+            ui.label("Куда по умолчанию сохранять отчеты:");
+            ui.horizontal(|ui| {
+                match &settings.output_dir {
+                    Some(dir) => ui.label(dir.display().to_string()),
+                    None => ui.label("не задана"),
+                };
+                if ui.button("📂 Выбрать...").clicked()
+                    && let Some(dir) = FileDialog::new().pick_folder()
+                {
+                    settings.output_dir = Some(dir);
+                }
+            });
+        }
+        WizardStep::Units => {
+            ui.label("В чем записаны дебиты в исходных файлах:");
+            for units in RateUnits::ALL {
+                ui.radio_value(&mut settings.rate_units, units, units.label());
+            }
+        }
+    }
+}
 
-            ui.add_space(10.0);
-
-            // --- БЛОК ПРОГРЕССА ---
-            ui.set_enabled(true);
+// Где и почему упала задача; цепочка причин - от внешней к исходной
+fn show_error_report(ui: &mut egui::Ui, report: &ErrorReport) {
+    ui.label(egui::RichText::new(&report.message).color(ui.visuals().error_fg_color));
+    let location = &report.location;
+    egui::Grid::new("error_report").show(ui, |ui| {
+        if !location.phase.is_empty() {
+            ui.label("Этап:");
+            ui.label(&location.phase);
+            ui.end_row();
+        }
+        if let Some(sheet) = &location.sheet {
+            ui.label("Лист:");
+            ui.label(sheet);
+            ui.end_row();
+        }
+        if let Some(row) = location.row {
+            ui.label("Строка:");
+            ui.label(row.to_string());
+            ui.end_row();
+        }
+    });
+    if report.causes.len() > 1 {
+        ui.label("Причины:");
+        for (i, cause) in report.causes.iter().enumerate() {
+            ui.label(format!("{}. {}", i + 1, cause));
+        }
+    }
+}
 
-            if self.is_loading {
-                ui.label(egui::RichText::new(&self.status_message).strong());
-                ui.add_space(5.0);
-                ui.label("Общий прогресс:");
-                ui.add(egui::ProgressBar::new(self.progress_global).animate(true));
+// Очередь выгрузок: состояние каждой, снять ожидающую, убрать завершенные
+fn show_export_queue(ui: &mut egui::Ui, queue: &mut ExportQueue) {
+    let mut remove = None;
+    egui::CollapsingHeader::new(format!("📋 Очередь выгрузок ({})", queue.jobs.len()))
+        .id_salt("export_queue")
+        .default_open(true)
+        .show(ui, |ui| {
+            for (i, job) in queue.jobs.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(job.state.label());
+                    let label = ui.label(&job.title);
+                    if let JobState::Done(path) = &job.state {
+                        label.on_hover_text(path);
+                    }
+                    if matches!(job.state, JobState::Pending) && ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if ui.button("Убрать завершенные").clicked() {
+                queue.clear_finished();
+            }
+        });
+    if let Some(i) = remove {
+        queue.jobs.remove(i);
+    }
+}
 
-                ui.add_space(5.0);
-                if self.progress_local < 0.01 {
-                    ui.horizontal(|ui| {
-                        ui.spinner();
-                        ui.label("Обработка данных...");
-                    });
-                } else {
-                    ui.add(egui::ProgressBar::new(self.progress_local).animate(true));
-                }
-            } else {
-                ui.label(egui::RichText::new(&self.status_message).color(egui::Color32::GRAY));
+// Последние операции сверху; полные настройки - во всплывающей подсказке
+fn show_audit_log(ui: &mut egui::Ui, entries: &[AuditEntry], dates: DateFormat) {
+    if let Some(path) = audit::path() {
+        ui.label(format!("Файл журнала: {}", path.display()));
+    }
+    if entries.is_empty() {
+        ui.label("Журнал пуст");
+        return;
+    }
+    egui::ScrollArea::both().show(ui, |ui| {
+        egui::Grid::new("audit_log").striped(true).show(ui, |ui| {
+            for column in [
+                "Время",
+                "Пользователь",
+                "Операция",
+                "Файл",
+                "Скважин",
+                "Итог",
+            ] {
+                ui.label(egui::RichText::new(column).strong());
+            }
+            ui.end_row();
+            for entry in entries.iter().rev() {
+                ui.label(dates.text(&entry.time));
+                ui.label(&entry.user);
+                ui.label(&entry.action).on_hover_text(&entry.options);
+                ui.label(&entry.file);
+                ui.label(entry.wells.len().to_string())
+                    .on_hover_text(entry.wells.join(", "));
+                ui.label(&entry.outcome);
+                ui.end_row();
             }
         });
+    });
+}
+
+// Таблица результата запроса; большие результаты показываем не целиком
+fn show_query_result(ui: &mut egui::Ui, result: &QueryResult, settings: &Settings) {
+    const MAX_SHOWN_ROWS: usize = 1000;
+
+    let locale = settings.number_locale;
+    if result.rows.len() > MAX_SHOWN_ROWS {
+        ui.label(format!(
+            "Строк: {} (показаны первые {})",
+            locale.count(result.rows.len()),
+            locale.count(MAX_SHOWN_ROWS)
+        ));
+    } else {
+        ui.label(format!("Строк: {}", locale.count(result.rows.len())));
     }
+    egui::ScrollArea::both().show(ui, |ui| {
+        egui::Grid::new("query_result")
+            .striped(true)
+            .show(ui, |ui| {
+                for column in &result.columns {
+                    ui.label(egui::RichText::new(column).strong());
+                }
+                ui.end_row();
+                for row in result.rows.iter().take(MAX_SHOWN_ROWS) {
+                    for value in row {
+                        match value {
+                            Value::Number(v) => ui.label(locale.number(*v, None)),
+                            Value::Text(s) => ui.label(settings.date_format.text(s)),
+                            Value::Null => ui.label(""),
+                        };
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+// Диагностика: сколько заняла каждая фаза последней операции
+fn show_timings(ui: &mut egui::Ui, timings: &Timings, locale: NumberLocale) {
+    egui::CollapsingHeader::new(format!(
+        "⏱ {}: {} с",
+        timings.operation,
+        locale.number(timings.total.as_secs_f64(), Some(1))
+    ))
+    .id_salt("timings")
+    .show(ui, |ui| {
+        let total = timings.total.as_secs_f64().max(f64::EPSILON);
+        egui::ScrollArea::vertical()
+            .max_height(150.0)
+            .show(ui, |ui| {
+                egui::Grid::new("timings_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (phase, duration) in &timings.phases {
+                            let secs = duration.as_secs_f64();
+                            ui.label(phase);
+                            ui.label(format!("{} с", locale.number(secs, Some(3))));
+                            ui.label(format!("{}%", locale.number(secs / total * 100.0, Some(1))));
+                            ui.end_row();
+                        }
+                    });
+            });
+    });
 }
 
 fn main() -> eframe::Result<()> {
-    eframe::run_native(
+    crash::install();
+    // Окно нужно до запуска интерфейса; ошибки в настройках покажет само приложение
+    let window = Settings::load().ok().flatten().and_then(|s| s.window);
+    // Свой цикл событий - чтобы до создания окна узнать мониторы
+    let event_loop = EventLoop::<eframe::UserEvent>::with_user_event().build()?;
+    let app = eframe::create_native(
         "Well Data App",
         eframe::NativeOptions {
-            // Увеличили ширину, чтобы влезли 2 колонки
-            viewport: egui::ViewportBuilder::default().with_inner_size([700.0, 650.0]),
+            viewport: window_state::viewport(window.as_ref()),
             ..Default::default()
         },
         Box::new(|cc| Ok(Box::new(WellDataApp::new(cc)))),
-    )
+        &event_loop,
+    );
+    event_loop.run_app(&mut MonitorProbe(app))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_xlsxwriter::ExcelDateTime;
+    use std::io::Cursor;
+
+    // Книга в памяти с листом "2021": над шапкой banner строк шапки
+    // подрядчика, у каждой скважины записи за январь-март
+    fn workbook(banner: u32, wells: &[&str]) -> Xlsx<Cursor<Vec<u8>>> {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("2021").unwrap();
+        if banner > 0 {
+            sheet.write_string(0, 0, "ООО Подрядчик").unwrap();
+        }
+        for (col, name) in [NAME_COL, "Date", "PdLiq", "PdOil"].iter().enumerate() {
+            sheet.write_string(banner, col as u16, *name).unwrap();
+        }
+        let date = Format::new().set_num_format("dd.mm.yyyy");
+        let mut row = banner + 1;
+        for well in wells {
+            for month in 1..=3u8 {
+                let day = ExcelDateTime::from_ymd(2021, month, 1).unwrap();
+                sheet.write_string(row, 0, *well).unwrap();
+                sheet
+                    .write_datetime_with_format(row, 1, &day, &date)
+                    .unwrap();
+                sheet.write_number(row, 2, 10.0 * month as f64).unwrap();
+                sheet.write_number(row, 3, 5.0).unwrap();
+                row += 1;
+            }
+        }
+        Xlsx::new(Cursor::new(workbook.save_to_buffer().unwrap())).unwrap()
+    }
+
+    fn load(mut workbook: Xlsx<Cursor<Vec<u8>>>, import: &ImportOptions) -> LoadedData {
+        parse_workbook(
+            &mut workbook,
+            &ValidationRules::default(),
+            import,
+            &(),
+            &CancellationToken::new(),
+            &mut Timings::new("тест"),
+        )
+        .unwrap()
+    }
+
+    fn report(loaded: &LoadedData, options: &ExportOptions) -> Result<Report, WellDataError> {
+        let wells: HashSet<String> = loaded.wells.iter().cloned().collect();
+        build_report(
+            &loaded.records,
+            2021,
+            &wells,
+            options,
+            &(),
+            &CancellationToken::new(),
+            &mut Timings::new("тест"),
+        )
+    }
+
+    fn plan(report: &Report) -> Vec<(&str, usize)> {
+        report
+            .plan
+            .iter()
+            .map(|sheet| (sheet.well.as_str(), sheet.rows))
+            .collect()
+    }
+
+    #[test]
+    fn parse_workbook_reads_year_sheet() {
+        let loaded = load(workbook(0, &["101", "205"]), &ImportOptions::default());
+        assert_eq!(loaded.records.len(), 6);
+        assert_eq!(loaded.years, [2021]);
+        assert_eq!(loaded.wells, ["101", "205"]);
+        assert!(loaded.violations.is_empty());
+    }
+
+    #[test]
+    fn parse_workbook_skips_rows_above_header() {
+        let import = ImportOptions {
+            header_row: 3,
+            ..ImportOptions::default()
+        };
+        let loaded = load(workbook(2, &["101"]), &import);
+        assert_eq!(loaded.records.len(), 3);
+        assert_eq!(loaded.wells, ["101"]);
+    }
+
+    #[test]
+    fn build_report_plans_sheet_per_well() {
+        let loaded = load(workbook(0, &["101", "205"]), &ImportOptions::default());
+        for mode in [WriteMode::InMemory, WriteMode::ConstantMemory] {
+            let mut options = ExportOptions::default();
+            options.streaming.mode = mode;
+            let report = report(&loaded, &options).unwrap();
+            assert_eq!(plan(&report), [("101", 3), ("205", 3)]);
+            assert_eq!(report.records.len(), 6);
+        }
+    }
+
+    #[test]
+    fn build_report_transposed_only_in_memory() {
+        let loaded = load(workbook(0, &["101"]), &ImportOptions::default());
+        let mut options = ExportOptions {
+            sheet_layout: SheetLayout::Transposed,
+            ..ExportOptions::default()
+        };
+        options.streaming.mode = WriteMode::ConstantMemory;
+        assert!(report(&loaded, &options).is_err());
+        options.streaming.mode = WriteMode::InMemory;
+        assert_eq!(plan(&report(&loaded, &options).unwrap()), [("101", 3)]);
+    }
 }
@@ -0,0 +1,178 @@
+use crate::calendar::RateMode;
+use crate::checks::AnomalyChecks;
+use crate::cover::{file_sha256, generated_at};
+use crate::downtime::DowntimeOptions;
+use crate::error::WellDataError;
+use crate::layout::SheetLayout;
+use crate::settings::RateUnits;
+use crate::status::WellStatus;
+use crate::store::RecordRef;
+use crate::summary::Aggregate;
+use crate::well_type::TypeExport;
+use crate::{ExportOptions, Parameter, add_sheet, frame};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Манифест выгрузки: по нему внешняя автоматика проверяет,
+// что файл передан целиком и не изменен
+#[derive(Debug, Serialize)]
+struct Manifest<'a> {
+    file: String,
+    sha256: String,
+    generated: Option<String>,
+    app_version: &'static str,
+    source: Option<&'a Path>,
+    records: usize,
+    // Записей по каждой скважине
+    wells: &'a BTreeMap<String, usize>,
+    parameters: Parameters<'a>,
+}
+
+// Параметры выгрузки: в манифест и в журнал операций
+#[derive(Debug, Serialize)]
+pub struct Parameters<'a> {
+    start_year: i32,
+    checks: &'a AnomalyChecks,
+    monthly_summary: Option<(Parameter, Aggregate)>,
+    forecast_months: Option<u32>,
+    rates_sheet: Option<RateMode>,
+    downtime: Option<&'a DowntimeOptions>,
+    type_export: TypeExport,
+    sheet_layout: SheetLayout,
+    excluded_statuses: Vec<&'static str>,
+    template: Option<&'a Path>,
+    events: Option<&'a Path>,
+    allocation: Option<&'a Path>,
+    rate_units: RateUnits,
+}
+
+pub fn parameters(start_year: i32, options: &ExportOptions) -> Parameters<'_> {
+    Parameters {
+        start_year,
+        checks: &options.checks,
+        monthly_summary: options
+            .monthly_summary
+            .then_some((options.monthly_parameter, options.monthly_aggregate)),
+        forecast_months: (options.forecast_months > 0).then_some(options.forecast_months),
+        rates_sheet: options.rates_sheet.then_some(options.rate_mode),
+        downtime: options.downtime.enabled().then_some(&options.downtime),
+        type_export: options.type_export,
+        sheet_layout: options.sheet_layout,
+        excluded_statuses: options
+            .excluded_statuses
+            .iter()
+            .map(|s| WellStatus::code(*s))
+            .collect(),
+        template: options.template.as_deref(),
+        events: options.events.as_ref().map(|log| log.path.as_path()),
+        allocation: options.allocation.as_ref().map(|t| t.path.as_path()),
+        rate_units: options.rate_units,
+    }
+}
+
+// отчет.xlsx -> отчет.manifest.json
+pub fn manifest_path(path: &Path) -> PathBuf {
+    path.with_extension("manifest.json")
+}
+
+// Записей по каждой скважине; считается до записи, пока записи под рукой
+pub fn well_counts<'a>(records: impl Iterator<Item = RecordRef<'a>>) -> BTreeMap<String, usize> {
+    let mut wells: BTreeMap<String, usize> = BTreeMap::new();
+    for record in records {
+        *wells.entry(record.well_name.to_string()).or_default() += 1;
+    }
+    wells
+}
+
+// Пишется после сохранения книги, чтобы контрольная сумма была от готового файла
+pub fn write_manifest(
+    path: &Path,
+    wells: &BTreeMap<String, usize>,
+    start_year: i32,
+    options: &ExportOptions,
+) -> Result<PathBuf, WellDataError> {
+    let manifest = Manifest {
+        file: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        sha256: file_sha256(path)?,
+        generated: generated_at(options).map(|t| t.to_rfc3339()),
+        app_version: env!("CARGO_PKG_VERSION"),
+        source: options.source.as_deref(),
+        records: wells.values().sum(),
+        wells,
+        parameters: parameters(start_year, options),
+    };
+
+    let manifest_path = manifest_path(path);
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+    Ok(manifest_path)
+}
+
+// Вложенные поля параметров разворачиваются в строки вида checks.gap_days
+fn flatten(prefix: &str, value: &Value, rows: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, field, rows);
+            }
+        }
+        Value::String(text) => rows.push((prefix.to_string(), text.clone())),
+        Value::Null => rows.push((prefix.to_string(), String::new())),
+        other => rows.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+// Скрытый последний лист с параметрами выгрузки, чтобы отчет можно было повторить
+pub fn write_parameters_sheet(
+    workbook: &mut Workbook,
+    constant_memory: bool,
+    start_year: i32,
+    wells: &[&str],
+    options: &ExportOptions,
+) -> Result<(), WellDataError> {
+    let mut rows = vec![
+        (
+            "app_version".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+        (
+            "source".to_string(),
+            options
+                .source
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+        ),
+    ];
+    flatten(
+        "",
+        &serde_json::to_value(parameters(start_year, options))?,
+        &mut rows,
+    );
+    rows.push(("wells".to_string(), wells.join(", ")));
+    rows.push(("dedup".to_string(), frame::DEDUP_POLICY.to_string()));
+
+    let worksheet = add_sheet(workbook, constant_memory).set_name("Parameters")?;
+    let bold = Format::new().set_bold();
+    worksheet.write_string_with_format(0, 0, "Параметр", &bold)?;
+    worksheet.write_string_with_format(0, 1, "Значение", &bold)?;
+    for (i, (name, value)) in rows.iter().enumerate() {
+        worksheet.write_string(i as u32 + 1, 0, name)?;
+        worksheet.write_string(i as u32 + 1, 1, value)?;
+    }
+    worksheet.set_column_width(0, 28)?;
+    worksheet.set_column_width(1, 60)?;
+    worksheet.set_hidden(true);
+    Ok(())
+}
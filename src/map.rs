@@ -0,0 +1,411 @@
+use crate::csv_import::{parse_number, read_table};
+use crate::query::{self, Value};
+use crate::settings::NumberLocale;
+use crate::store::Dataset;
+use eframe::egui;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+// Координаты устьев скважин: прямоугольные (X, Y в метрах) или географические
+#[derive(Debug, Clone, Default)]
+pub struct Coordinates {
+    // Скважина -> [x, y]; для широты/долготы - [долгота, широта]
+    pub points: BTreeMap<String, [f64; 2]>,
+    pub geographic: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapColoring {
+    #[default]
+    Selection,
+    // Средний дебит нефти за весь файл
+    Rate,
+}
+
+impl MapColoring {
+    pub const ALL: [MapColoring; 2] = [MapColoring::Selection, MapColoring::Rate];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MapColoring::Selection => "по выбору",
+            MapColoring::Rate => "по среднему дебиту нефти",
+        }
+    }
+}
+
+// Что делает клик по карте
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapTool {
+    #[default]
+    Click,
+    Radius,
+    Polygon,
+}
+
+impl MapTool {
+    pub const ALL: [MapTool; 3] = [MapTool::Click, MapTool::Radius, MapTool::Polygon];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MapTool::Click => "Выбор по клику",
+            MapTool::Radius => "В радиусе",
+            MapTool::Polygon => "В многоугольнике",
+        }
+    }
+}
+
+pub struct MapPanel {
+    pub open: bool,
+    pub coordinates: Option<Coordinates>,
+    pub coloring: MapColoring,
+    // Средний PdOil по скважинам, считается после загрузки данных
+    pub rates: HashMap<String, f64>,
+    pub tool: MapTool,
+    pub radius_m: f64,
+    // Последний центр круга и вершины многоугольника - в метрах, как Coordinates::planar
+    center: Option<[f64; 2]>,
+    pub polygon: Vec<[f64; 2]>,
+    pub number_locale: NumberLocale,
+}
+
+impl Default for MapPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            coordinates: None,
+            coloring: MapColoring::default(),
+            rates: HashMap::new(),
+            tool: MapTool::default(),
+            radius_m: 1000.0,
+            center: None,
+            polygon: Vec::new(),
+            number_locale: NumberLocale::default(),
+        }
+    }
+}
+
+// Метров в градусе широты и в градусе долготы на экваторе
+const METERS_PER_DEG_LAT: f64 = 110_574.0;
+const METERS_PER_DEG_LON: f64 = 111_320.0;
+
+impl Coordinates {
+    // Плоские координаты в метрах. Широта/долгота переводятся равнопромежуточной
+    // проекцией вокруг средней широты - на размерах месторождения погрешность мала
+    pub fn planar(&self) -> Vec<(&str, [f64; 2])> {
+        let scale = if self.geographic {
+            let mean_lat =
+                self.points.values().map(|p| p[1]).sum::<f64>() / self.points.len().max(1) as f64;
+            [
+                METERS_PER_DEG_LON * mean_lat.to_radians().cos(),
+                METERS_PER_DEG_LAT,
+            ]
+        } else {
+            [1.0, 1.0]
+        };
+        self.points
+            .iter()
+            .map(|(w, p)| (w.as_str(), [p[0] * scale[0], p[1] * scale[1]]))
+            .collect()
+    }
+}
+
+// Скважины в радиусе radius_m от центра
+pub fn wells_within(points: &[(&str, [f64; 2])], center: [f64; 2], radius_m: f64) -> Vec<String> {
+    points
+        .iter()
+        .filter(|(_, p)| (p[0] - center[0]).hypot(p[1] - center[1]) <= radius_m)
+        .map(|(w, _)| w.to_string())
+        .collect()
+}
+
+// Скважины внутри многоугольника (правило четности пересечений луча)
+pub fn wells_in_polygon(points: &[(&str, [f64; 2])], polygon: &[[f64; 2]]) -> Vec<String> {
+    if polygon.len() < 3 {
+        return Vec::new();
+    }
+    let inside = |p: [f64; 2]| {
+        let mut inside = false;
+        let mut j = polygon.len() - 1;
+        for i in 0..polygon.len() {
+            let (a, b) = (polygon[i], polygon[j]);
+            if (a[1] > p[1]) != (b[1] > p[1])
+                && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+            {
+                inside = !inside;
+            }
+            j = i;
+        }
+        inside
+    };
+    points
+        .iter()
+        .filter(|(_, p)| inside(*p))
+        .map(|(w, _)| w.to_string())
+        .collect()
+}
+
+// Перевод плоских координат в точки на экране и обратно; масштаб одинаков по осям
+struct Projection {
+    min: [f64; 2],
+    // Пикселей на метр
+    scale: f64,
+    origin: egui::Pos2,
+}
+
+impl Projection {
+    fn fit(points: &[(&str, [f64; 2])], rect: egui::Rect) -> Self {
+        let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+        for (_, p) in points {
+            for i in 0..2 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        let span = (max[0] - min[0]).max(max[1] - min[1]).max(1e-9);
+        let inner = rect.shrink(20.0);
+        Self {
+            min,
+            scale: inner.width().min(inner.height()) as f64 / span,
+            // Север сверху: ось Y экрана идет вниз
+            origin: inner.left_bottom(),
+        }
+    }
+
+    fn to_screen(&self, p: [f64; 2]) -> egui::Pos2 {
+        egui::pos2(
+            self.origin.x + ((p[0] - self.min[0]) * self.scale) as f32,
+            self.origin.y - ((p[1] - self.min[1]) * self.scale) as f32,
+        )
+    }
+
+    fn to_world(&self, pos: egui::Pos2) -> [f64; 2] {
+        [
+            self.min[0] + (pos.x - self.origin.x) as f64 / self.scale,
+            self.min[1] + (self.origin.y - pos.y) as f64 / self.scale,
+        ]
+    }
+}
+
+// Колонки таблицы координат по заголовку
+fn column_kind(header: &str) -> Option<&'static str> {
+    let h = header.trim().to_lowercase();
+    if h.contains("скв") || h.contains("well") || h == crate::NAME_COL.to_lowercase() {
+        Some("well")
+    } else if h == "x" || h.starts_with("x ") || h.starts_with("x,") {
+        Some("x")
+    } else if h == "y" || h.starts_with("y ") || h.starts_with("y,") {
+        Some("y")
+    } else if h.starts_with("lat") || h.starts_with("шир") {
+        Some("lat")
+    } else if h.starts_with("lon") || h.starts_with("дол") {
+        Some("lon")
+    } else {
+        None
+    }
+}
+
+fn from_table(rows: Vec<Vec<String>>) -> Result<Coordinates, String> {
+    let mut rows = rows.into_iter();
+    let header = rows.next().ok_or("пустая таблица координат")?;
+    let find = |kind: &str| header.iter().position(|h| column_kind(h) == Some(kind));
+    let well = find("well").ok_or("нет колонки со скважиной")?;
+    let (x, y, geographic) = match (find("x"), find("y"), find("lon"), find("lat")) {
+        (Some(x), Some(y), _, _) => (x, y, false),
+        (_, _, Some(lon), Some(lat)) => (lon, lat, true),
+        _ => return Err("нужны колонки X и Y или широта и долгота".to_string()),
+    };
+
+    let mut points = BTreeMap::new();
+    for row in rows {
+        let name = row.get(well).map(|s| s.trim()).unwrap_or_default();
+        let (Some(px), Some(py)) = (
+            row.get(x).and_then(|s| parse_number(s)),
+            row.get(y).and_then(|s| parse_number(s)),
+        ) else {
+            continue;
+        };
+        if !name.is_empty() {
+            points.insert(name.to_string(), [px, py]);
+        }
+    }
+    if points.is_empty() {
+        return Err("в таблице нет строк с координатами".to_string());
+    }
+    Ok(Coordinates { points, geographic })
+}
+
+// Таблица координат из CSV/TXT или с первого листа xlsx
+pub fn read_coordinates(path: &Path) -> Result<Coordinates, String> {
+    from_table(read_table(path)?)
+}
+
+// Средний дебит нефти по скважинам тем же запросом, что доступен в окне SQL
+pub fn average_rates(data: &Dataset) -> HashMap<String, f64> {
+    let sql = format!(
+        "SELECT well_name, avg(pd_oil) AS rate FROM {} GROUP BY well_name",
+        query::TABLE
+    );
+    let Ok(result) = query::run(data, &sql) else {
+        return HashMap::new();
+    };
+    result
+        .rows
+        .into_iter()
+        .filter_map(|row| match (&row[0], &row[1]) {
+            (Value::Text(well), Value::Number(rate)) => Some((well.clone(), *rate)),
+            _ => None,
+        })
+        .collect()
+}
+
+// Синий (минимум) -> красный (максимум)
+pub fn rate_color(t: f64) -> egui::Color32 {
+    let t = t.clamp(0.0, 1.0) as f32;
+    egui::Color32::from_rgb((40.0 + 215.0 * t) as u8, 90, (255.0 - 215.0 * t) as u8)
+}
+
+impl MapPanel {
+    // Выбор скважин многоугольником; вершины ставятся кликами по карте
+    pub fn apply_polygon(&mut self, wells: &[String], selected: &mut HashSet<String>) {
+        let Some(coords) = &self.coordinates else {
+            return;
+        };
+        let loaded: HashSet<&str> = wells.iter().map(String::as_str).collect();
+        let points: Vec<_> = coords
+            .planar()
+            .into_iter()
+            .filter(|(w, _)| loaded.contains(w))
+            .collect();
+        *selected = wells_in_polygon(&points, &self.polygon)
+            .into_iter()
+            .collect();
+        self.polygon.clear();
+    }
+
+    // Карта скважин. Клик по точке добавляет скважину в выбор или убирает из него,
+    // в режиме радиуса выбирает скважины вокруг точки клика, в режиме
+    // многоугольника добавляет вершину
+    pub fn show(&mut self, ui: &mut egui::Ui, wells: &[String], selected: &mut HashSet<String>) {
+        let Some(coords) = &self.coordinates else {
+            return;
+        };
+        let (response, painter) = ui.allocate_painter(
+            ui.available_size().max(egui::vec2(200.0, 200.0)),
+            egui::Sense::click(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 4.0, ui.visuals().extreme_bg_color);
+
+        let loaded: HashSet<&str> = wells.iter().map(String::as_str).collect();
+        let (min_rate, max_rate) = self
+            .rates
+            .values()
+            .fold((f64::MAX, f64::MIN), |(lo, hi), r| (lo.min(*r), hi.max(*r)));
+        let planar = coords.planar();
+        let projection = Projection::fit(&planar, rect);
+        let hover = response.hover_pos();
+        let mut hovered = None;
+
+        for (well, p) in &planar {
+            let pos = projection.to_screen(*p);
+            let is_loaded = loaded.contains(well);
+            let is_selected = selected.contains(*well);
+            let color = match (is_loaded, self.coloring) {
+                (false, _) => egui::Color32::DARK_GRAY,
+                (true, MapColoring::Selection) if is_selected => egui::Color32::from_rgb(0, 170, 0),
+                (true, MapColoring::Selection) => egui::Color32::GRAY,
+                (true, MapColoring::Rate) => {
+                    self.rates.get(*well).map_or(egui::Color32::GRAY, |r| {
+                        rate_color((r - min_rate) / (max_rate - min_rate).max(1e-9))
+                    })
+                }
+            };
+            let radius = if is_loaded { 5.0 } else { 3.0 };
+            painter.circle_filled(pos, radius, color);
+            if is_selected {
+                painter.circle_stroke(
+                    pos,
+                    radius + 2.5,
+                    egui::Stroke::new(1.5, ui.visuals().text_color()),
+                );
+            }
+            if planar.len() <= 60 {
+                painter.text(
+                    pos + egui::vec2(7.0, -7.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    *well,
+                    egui::FontId::proportional(11.0),
+                    ui.visuals().weak_text_color(),
+                );
+            }
+            if is_loaded && hover.is_some_and(|h| h.distance(pos) < 8.0) {
+                hovered = Some(*well);
+            }
+        }
+
+        let tool_stroke = egui::Stroke::new(1.5, egui::Color32::from_rgb(255, 160, 0));
+        if self.tool == MapTool::Radius
+            && let Some(center) = self.center
+        {
+            painter.circle_stroke(
+                projection.to_screen(center),
+                (self.radius_m * projection.scale) as f32,
+                tool_stroke,
+            );
+        }
+        if self.tool == MapTool::Polygon && !self.polygon.is_empty() {
+            let mut outline: Vec<egui::Pos2> = self
+                .polygon
+                .iter()
+                .map(|p| projection.to_screen(*p))
+                .collect();
+            if let Some(h) = hover {
+                outline.push(h);
+            }
+            outline.push(outline[0]);
+            painter.add(egui::Shape::line(outline, tool_stroke));
+        }
+
+        if let Some(well) = hovered {
+            let text = match self.rates.get(well) {
+                Some(rate) => format!(
+                    "{}: {} т/сут",
+                    well,
+                    self.number_locale.number(*rate, Some(1))
+                ),
+                None => well.to_string(),
+            };
+            response.clone().on_hover_text_at_pointer(text);
+        }
+        let Some(click) = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+        else {
+            return;
+        };
+        match self.tool {
+            MapTool::Click => {
+                if let Some(well) = hovered
+                    && !selected.remove(well)
+                {
+                    selected.insert(well.to_string());
+                }
+            }
+            MapTool::Radius => {
+                let center = projection.to_world(click);
+                let points: Vec<_> = planar
+                    .iter()
+                    .filter(|(w, _)| loaded.contains(w))
+                    .copied()
+                    .collect();
+                *selected = wells_within(&points, center, self.radius_m)
+                    .into_iter()
+                    .collect();
+                self.center = Some(center);
+            }
+            MapTool::Polygon => {
+                self.polygon.push(projection.to_world(click));
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::StorageMode;
+use crate::store::RecordStore;
+use calamine::{Data, Xlsx};
+use std::io::{Read, Seek};
+
+pub struct MemoryEstimate {
+    pub total_rows: u64,
+    pub projected_bytes: u64,
+}
+
+impl MemoryEstimate {
+    pub fn projected_mb(&self) -> u64 {
+        self.projected_bytes / (1024 * 1024)
+    }
+}
+
+// Оценка по тегу <dimension> каждого листа, без чтения самих данных.
+// Пик памяти = загруженные записи + самый большой лист, который calamine держит целиком.
+// При хранении на диске записи в памяти не копятся, остается только лист
+pub fn estimate<RS: Read + Seek>(
+    workbook: &mut Xlsx<RS>,
+    sheets: &[&String],
+    storage: StorageMode,
+) -> MemoryEstimate {
+    let mut total_rows = 0u64;
+    let mut largest_sheet_cells = 0u64;
+
+    for sheet in sheets {
+        let Ok(reader) = workbook.worksheet_cells_reader(sheet) else {
+            continue;
+        };
+        let dims = reader.dimensions();
+        if dims.end.0 < dims.start.0 {
+            continue;
+        }
+        total_rows += (dims.end.0 - dims.start.0) as u64;
+        largest_sheet_cells = largest_sheet_cells.max(dims.len());
+    }
+
+    // Имена скважин хранятся один раз на файл, в расчет строки не входят
+    let record_bytes = match storage {
+        StorageMode::Memory => RecordStore::BYTES_PER_ROW as u64,
+        StorageMode::Disk => 0,
+    };
+    let cell_bytes = size_of::<Data>() as u64;
+
+    MemoryEstimate {
+        total_rows,
+        projected_bytes: total_rows * record_bytes + largest_sheet_cells * cell_bytes,
+    }
+}
@@ -0,0 +1,430 @@
+use crate::cancel::CancellationToken;
+use crate::csv_import::parse_number;
+use crate::error::WellDataError;
+use crate::header::{self, Header};
+use crate::progress::ProgressSink;
+use crate::rules::{ValidationRules, Violation, ViolationKind};
+use crate::status::WellStatus;
+use crate::store::{Dataset, RecordRef};
+use crate::timing::Timings;
+use crate::well_type::WellType;
+use crate::year_sheet::{self, sheet_month};
+use crate::{
+    CHOKE_COL, FREQUENCY_COL, INJECTION_COL, ImportOptions, LoadedData, NAME_COL, P_BOTTOM_COL,
+    P_HEAD_COL, STATUS_COL, TEMPERATURE_COL, TYPE_COL,
+};
+use calamine::{Data, Range, Reader, Xlsx};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Шапка ищется только в начале листа
+const HEADER_SEARCH_ROWS: usize = 15;
+
+fn month_end(year: i32, month: u32) -> Option<NaiveDate> {
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    };
+    next?.pred_opt()
+}
+
+// МЭР - книга без листов-годов, где листы названы месяцами. Помесячную книгу
+// суточных замеров ("2022-07", "Июль 2022") отличает колонка даты в шапке
+pub fn is_mer_file(path: &Path, import: &ImportOptions) -> bool {
+    let Ok(mut workbook) = calamine::open_workbook::<Xlsx<_>, _>(path) else {
+        return false;
+    };
+    let sheets = workbook.sheet_names();
+    if sheets
+        .iter()
+        .any(|s| year_sheet::is_year_or_quarter_sheet(s))
+    {
+        return false;
+    }
+    let Some(sheet) = sheets.iter().find(|s| sheet_month(s).is_some()) else {
+        return false;
+    };
+    let Ok(rows) = first_rows(&mut workbook, sheet) else {
+        return true;
+    };
+    // Шапка разбирается как при загрузке: несколько строк и строка единиц
+    !(0..rows.len()).any(|start| {
+        let mut lines: Vec<Vec<String>> = rows[start..]
+            .iter()
+            .take(import.header_rows.max(1) as usize)
+            .cloned()
+            .collect();
+        if let Some(next) = rows.get(start + lines.len())
+            && header::looks_like_units(next)
+        {
+            lines.push(next.clone());
+        }
+        let mut col_map = Header::parse(&lines).columns();
+        import.columns.apply(&mut col_map);
+        col_map.contains_key(NAME_COL) && col_map.contains_key("Date")
+    })
+}
+
+// Первые строки листа текстом, без чтения всего листа
+fn first_rows(
+    workbook: &mut Xlsx<BufReader<File>>,
+    sheet: &str,
+) -> Result<Vec<Vec<String>>, calamine::XlsxError> {
+    let mut reader = workbook.worksheet_cells_reader(sheet)?;
+    let (start, first_col) = reader.dimensions().start;
+    let (start, first_col) = (start as usize, first_col as usize);
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    while let Some(cell) = reader.next_cell()? {
+        let (row, col) = cell.get_position();
+        let (row, col) = (row as usize - start, col as usize - first_col);
+        if row >= HEADER_SEARCH_ROWS {
+            break;
+        }
+        if rows.len() <= row {
+            rows.resize(row + 1, Vec::new());
+        }
+        if rows[row].len() <= col {
+            rows[row].resize(col + 1, String::new());
+        }
+        rows[row][col] = text(&cell.get_value().clone().into());
+    }
+    Ok(rows)
+}
+
+// Колонки МЭР, найденные по тексту многострочной шапки
+struct MerColumns {
+    well: usize,
+    oil_rate: Option<usize>,
+    liq_rate: Option<usize>,
+    oil_volume: Option<usize>,
+    liq_volume: Option<usize>,
+    work_time: Option<usize>,
+    // Время работы в часах, а не в сутках
+    work_hours: bool,
+    temperature: Option<usize>,
+    p_bottom: Option<usize>,
+    p_head: Option<usize>,
+    frequency: Option<usize>,
+    choke: Option<usize>,
+    // Приемистость или месячный объем закачки
+    injection_rate: Option<usize>,
+    injection_volume: Option<usize>,
+    status: Option<usize>,
+    well_type: Option<usize>,
+}
+
+impl MerColumns {
+    fn detect(headers: &[String]) -> Option<Self> {
+        let find = |pred: &dyn Fn(&str) -> bool| headers.iter().position(|h| pred(h));
+        let is_rate = |h: &str| h.contains("дебит") || h.contains("среднесут");
+        let oil = |h: &str| h.contains("нефт");
+        let liq = |h: &str| h.contains("жидк");
+        let work_time = find(&|h| {
+            h.contains("отработ") || h.contains("время работы") || h.contains("дни работы")
+        });
+
+        Some(Self {
+            well: find(&|h| h.contains("скв"))?,
+            oil_rate: find(&|h| is_rate(h) && oil(h)),
+            liq_rate: find(&|h| is_rate(h) && liq(h)),
+            oil_volume: find(&|h| !is_rate(h) && h.contains("добыч") && oil(h)),
+            liq_volume: find(&|h| !is_rate(h) && h.contains("добыч") && liq(h)),
+            work_time,
+            work_hours: work_time.is_some_and(|i| headers[i].contains("час")),
+            temperature: find(&|h| h.contains("темп")),
+            p_bottom: find(&|h| h.contains("забойн") || h.contains("рзаб") || h.contains("pзаб")),
+            p_head: find(&|h| h.contains("буферн") || h.contains("рбуф") || h.contains("pбуф")),
+            frequency: find(&|h| h.contains("частот")),
+            choke: find(&|h| h.contains("штуцер")),
+            injection_rate: find(&|h| h.contains("приемист")),
+            injection_volume: find(&|h| !h.contains("приемист") && h.contains("закач")),
+            status: find(&|h| h.contains("состоян") || h.contains("статус") || h == "status"),
+            well_type: find(&|h| {
+                h.contains("назначен") || h.contains("характер") || h == "тип" || h == "type"
+            }),
+        })
+    }
+
+    // Какие внутренние колонки удалось получить - для обязательных колонок и границ
+    fn provides(&self, column: &str) -> bool {
+        match column {
+            NAME_COL | "Date" => true,
+            "PdLiq" => self.liq_rate.is_some() || self.liq_volume.is_some(),
+            "PdOil" => self.oil_rate.is_some() || self.oil_volume.is_some(),
+            TEMPERATURE_COL => self.temperature.is_some(),
+            P_BOTTOM_COL => self.p_bottom.is_some(),
+            P_HEAD_COL => self.p_head.is_some(),
+            FREQUENCY_COL => self.frequency.is_some(),
+            CHOKE_COL => self.choke.is_some(),
+            INJECTION_COL => self.injection_rate.is_some() || self.injection_volume.is_some(),
+            STATUS_COL => self.status.is_some(),
+            TYPE_COL => self.well_type.is_some(),
+            _ => false,
+        }
+    }
+}
+
+fn number(cell: Option<&Data>) -> Option<f64> {
+    match cell? {
+        Data::Float(f) => Some(*f),
+        Data::Int(i) => Some(*i as f64),
+        Data::String(s) => parse_number(s),
+        _ => None,
+    }
+}
+
+fn text(cell: &Data) -> String {
+    match cell {
+        Data::String(s) => s.trim().to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Int(i) => i.to_string(),
+        _ => String::new(),
+    }
+}
+
+// Строка с номерами колонок "1 2 3 ..." под шапкой
+fn is_numbering_row(row: &[Data]) -> bool {
+    let numbers: Vec<f64> = row.iter().filter_map(|c| number(Some(c))).collect();
+    numbers.len() > 1
+        && numbers.len() == row.iter().filter(|c| !matches!(c, Data::Empty)).count()
+        && numbers.windows(2).all(|w| w[1] - w[0] == 1.0)
+}
+
+// Текст шапки по колонкам: строки от ячейки "скважина" до первой строки данных.
+// Объединенные ячейки (группы "Добыча" над "нефть, т") растягиваются на свою область
+fn read_header(
+    range: &Range<Data>,
+    merged: &[calamine::Dimensions],
+) -> Option<(Vec<String>, usize)> {
+    let rows: Vec<&[Data]> = range.rows().take(HEADER_SEARCH_ROWS).collect();
+    let start = rows
+        .iter()
+        .position(|r| r.iter().any(|c| text(c).to_lowercase().contains("скв")))?;
+    let well_col = rows[start]
+        .iter()
+        .position(|c| text(c).to_lowercase().contains("скв"))?;
+    // Данные начинаются с первой строки, где в колонке скважины есть значение
+    // и хотя бы одно число, не считая строки нумерации
+    let data_start = (start + 1..range.height())
+        .find(|&i| {
+            let row = &range[i];
+            !text(&row[well_col]).is_empty()
+                && !is_numbering_row(row)
+                && row
+                    .iter()
+                    .any(|c| matches!(c, Data::Float(_) | Data::Int(_)))
+        })
+        .unwrap_or(range.height());
+
+    let (row0, col0) = range.start().unwrap_or((0, 0));
+    let mut headers = vec![String::new(); range.width()];
+    for r in start..data_start.min(start + HEADER_SEARCH_ROWS) {
+        for (c, header) in headers.iter_mut().enumerate() {
+            let abs = (row0 + r as u32, col0 + c as u32);
+            // Значение объединенной ячейки хранится в ее левом верхнем углу
+            let source = merged
+                .iter()
+                .find(|m| {
+                    (m.start.0..=m.end.0).contains(&abs.0) && (m.start.1..=m.end.1).contains(&abs.1)
+                })
+                .map_or(abs, |m| m.start);
+            if source.0 != abs.0 {
+                // Текст объединения по вертикали уже взят из его верхней строки
+                continue;
+            }
+            let value = range
+                .get_value(source)
+                .map(|c| text(c).to_lowercase())
+                .unwrap_or_default();
+            if !value.is_empty() && !header.ends_with(&value) {
+                header.push(' ');
+                header.push_str(&value);
+            }
+        }
+    }
+    Some((headers, data_start))
+}
+
+// Импорт МЭР: лист на месяц, строка на скважину. Каждая строка становится записью
+// на последний день месяца со среднесуточными значениями за месяц. Если в отчете
+// только объемы добычи, дебит считается делением на время работы (или на дни месяца)
+pub fn read_mer_file(
+    path: &Path,
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    progress.report("Открытие МЭР...", 0, 0);
+    let mut timings = Timings::new(format!(
+        "Загрузка МЭР {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let mut workbook: Xlsx<_> = timings
+        .measure("Открытие книги", || {
+            import.retry.run("открытие книги", progress, cancel, || {
+                calamine::open_workbook(path)
+            })
+        })
+        .map_err(|e| WellDataError::open_failed(path, e))?;
+    let sheets = workbook.sheet_names().to_owned();
+
+    let mut dataset = Dataset::new(import.storage)?;
+    let mut valid_years = BTreeSet::new();
+    let mut unique_wells = BTreeSet::new();
+    let mut violations = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (sheet_idx, sheet_name) in sheets.iter().enumerate() {
+        cancel.check()?;
+        let Some((year, month)) = sheet_month(sheet_name) else {
+            warnings.push(WellDataError::SheetSkipped {
+                sheet: sheet_name.clone(),
+                reason: "не похож на месяц МЭР".to_string(),
+            });
+            continue;
+        };
+        progress.report(&format!("Лист '{}'", sheet_name), sheet_idx, sheets.len());
+
+        let parse_start = std::time::Instant::now();
+        let what = format!("чтение листа '{}'", sheet_name);
+        let range = import.retry.run(&what, progress, cancel, || {
+            workbook.worksheet_range(sheet_name)
+        })?;
+        let merged = workbook
+            .worksheet_merge_cells(sheet_name)
+            .and_then(Result::ok)
+            .unwrap_or_default();
+        let Some((headers, data_start)) = read_header(&range, &merged) else {
+            warnings.push(WellDataError::SheetSkipped {
+                sheet: sheet_name.clone(),
+                reason: "не найдена шапка со скважинами".to_string(),
+            });
+            continue;
+        };
+        let Some(columns) = MerColumns::detect(&headers) else {
+            continue;
+        };
+        for column in &rules.required_columns {
+            if !columns.provides(column) {
+                violations.push(Violation {
+                    kind: ViolationKind::MissingColumn,
+                    sheet: sheet_name.clone(),
+                    row: None,
+                    well: None,
+                    details: format!("Нет колонки для '{}'", column),
+                });
+            }
+        }
+
+        let Some(date) = month_end(year, month) else {
+            continue;
+        };
+        let days_in_month = date.day() as f64;
+        let date: NaiveDateTime = date.into();
+        let first_row = range.start().map_or(0, |(r, _)| r) + 1;
+        valid_years.insert(year);
+
+        for (i, row) in range.rows().enumerate().skip(data_start) {
+            let well_name = text(&row[columns.well]);
+            // Итоговые строки ("Итого по месторождению") - не скважины
+            if well_name.is_empty() || well_name.to_lowercase().starts_with("итог") {
+                continue;
+            }
+            let excel_row = first_row + i as u32;
+            if rules.is_forbidden(&well_name) {
+                violations.push(Violation {
+                    kind: ViolationKind::ForbiddenWell,
+                    sheet: sheet_name.clone(),
+                    row: Some(excel_row),
+                    well: Some(well_name),
+                    details: "Запрещенное имя скважины, строка пропущена".to_string(),
+                });
+                continue;
+            }
+
+            let get = |idx: Option<usize>| idx.and_then(|i| number(row.get(i)));
+            let work_days = get(columns.work_time)
+                .map(|t| if columns.work_hours { t / 24.0 } else { t })
+                .filter(|d| *d > 0.0)
+                .unwrap_or(days_in_month);
+            let rate = |rate: Option<usize>, volume: Option<usize>| {
+                get(rate).or_else(|| get(volume).map(|v| v / work_days))
+            };
+            let pd_liq = rate(columns.liq_rate, columns.liq_volume);
+            let pd_oil = rate(columns.oil_rate, columns.oil_volume);
+            let temperature = get(columns.temperature);
+            let p_bottom = get(columns.p_bottom);
+            let p_head = get(columns.p_head);
+            let frequency = get(columns.frequency);
+            let choke = get(columns.choke);
+            let injection = rate(columns.injection_rate, columns.injection_volume);
+
+            let mut out_of_bounds = false;
+            for (col, bounds) in &rules.bounds {
+                let value = match col.as_str() {
+                    "PdLiq" => pd_liq,
+                    "PdOil" => pd_oil,
+                    TEMPERATURE_COL => temperature,
+                    P_BOTTOM_COL => p_bottom,
+                    P_HEAD_COL => p_head,
+                    FREQUENCY_COL => frequency,
+                    CHOKE_COL => choke,
+                    INJECTION_COL => injection,
+                    _ => None,
+                };
+                if let Some(problem) = value.and_then(|v| bounds.check(v)) {
+                    out_of_bounds = true;
+                    violations.push(Violation {
+                        kind: ViolationKind::OutOfBounds,
+                        sheet: sheet_name.clone(),
+                        row: Some(excel_row),
+                        well: Some(well_name.clone()),
+                        details: format!("{}: {}", col, problem),
+                    });
+                }
+            }
+
+            dataset.push(RecordRef {
+                well_name: &well_name,
+                date: Some(date),
+                pd_liq,
+                pd_oil,
+                temperature,
+                p_bottom,
+                p_head,
+                frequency,
+                choke,
+                injection,
+                status: columns
+                    .status
+                    .and_then(|i| row.get(i))
+                    .and_then(|c| WellStatus::parse(&text(c))),
+                well_type: columns
+                    .well_type
+                    .and_then(|i| row.get(i))
+                    .and_then(|c| WellType::parse(&text(c))),
+                year_sheet: year,
+                quarter: None,
+                out_of_bounds,
+            })?;
+            unique_wells.insert(well_name);
+        }
+        timings.record(format!("Лист '{}': разбор строк", sheet_name), parse_start);
+    }
+
+    timings.measure("Завершение записи", || dataset.finish())?;
+    progress.timings(timings.finish());
+    Ok(LoadedData {
+        records: dataset,
+        years: valid_years.into_iter().collect(),
+        wells: unique_wells.into_iter().collect(),
+        violations,
+        warnings,
+        units: BTreeMap::new(),
+    })
+}
@@ -0,0 +1,82 @@
+use crate::csv_import::parse_number;
+use crate::error::WellDataError;
+use calamine::Data;
+
+// Так в ручных таблицах отмечают отсутствие замера
+const MARKERS: [&str; 9] = ["-", "—", "–", "н/д", "нд", "n/a", "na", "нет данных", "нет"];
+
+pub fn is_marker(s: &str) -> bool {
+    let s = s.trim().to_lowercase();
+    MARKERS.contains(&s.as_str())
+}
+
+// Что оказалось в числовой ячейке
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberCell {
+    Value(f64),
+    Empty,
+    // "-", "н/д" и подобное: замера не было
+    Missing,
+    // Текст, который не число и не отметка
+    Invalid,
+}
+
+impl NumberCell {
+    pub fn from_text(s: &str) -> Self {
+        if s.trim().is_empty() {
+            NumberCell::Empty
+        } else if is_marker(s) {
+            NumberCell::Missing
+        } else {
+            parse_number(s).map_or(NumberCell::Invalid, NumberCell::Value)
+        }
+    }
+
+    pub fn from_data(cell: Option<&Data>) -> Self {
+        match cell {
+            None | Some(Data::Empty) => NumberCell::Empty,
+            Some(Data::Float(f)) if f.is_finite() => NumberCell::Value(*f),
+            Some(Data::Int(i)) => NumberCell::Value(*i as f64),
+            Some(Data::String(s)) => Self::from_text(s),
+            // #Н/Д и прочие ошибки формул - тоже нет данных
+            Some(Data::Error(_)) => NumberCell::Missing,
+            Some(_) => NumberCell::Invalid,
+        }
+    }
+}
+
+// Сколько ячеек при загрузке оказались без значения и почему
+#[derive(Debug, Default)]
+pub struct MissingStats {
+    pub markers: usize,
+    pub invalid: usize,
+}
+
+impl MissingStats {
+    pub fn take(&mut self, cell: NumberCell) -> Option<f64> {
+        match cell {
+            NumberCell::Value(v) => return Some(v),
+            NumberCell::Empty => {}
+            NumberCell::Missing => self.markers += 1,
+            NumberCell::Invalid => self.invalid += 1,
+        }
+        None
+    }
+
+    pub fn warnings(&self) -> Vec<WellDataError> {
+        let mut warnings = Vec::new();
+        if self.markers > 0 {
+            warnings.push(WellDataError::Warning(format!(
+                "ячеек с отметкой \"нет данных\" (-, н/д, N/A): {}",
+                self.markers
+            )));
+        }
+        if self.invalid > 0 {
+            warnings.push(WellDataError::Warning(format!(
+                "ячеек с текстом вместо числа (значение пропущено): {}",
+                self.invalid
+            )));
+        }
+        warnings
+    }
+}
@@ -0,0 +1,199 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::las::WellLog;
+use crate::progress::ProgressSink;
+use crate::status::WellStatus;
+use crate::store::{Dataset, RecordRef};
+use crate::well_type::WellType;
+use crate::{ImportOptions, LoadedData, LoaderMessage};
+use chrono::{Datelike, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+// Запись замера в собственном виде: для других программ и JSON.
+// Поля те же, что у RecordRef, и меняются только с добавлением новых
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WellRecord {
+    pub well_name: String,
+    pub date: Option<NaiveDateTime>,
+    pub pd_liq: Option<f64>,
+    pub pd_oil: Option<f64>,
+    pub temperature: Option<f64>,
+    pub p_bottom: Option<f64>,
+    pub p_head: Option<f64>,
+    pub frequency: Option<f64>,
+    pub choke: Option<f64>,
+    pub injection: Option<f64>,
+    pub status: Option<WellStatus>,
+    pub well_type: Option<WellType>,
+    // Год листа-источника; по умолчанию - год даты
+    pub year_sheet: i32,
+    // Квартал листа-источника, если лист квартальный
+    pub quarter: Option<u8>,
+    pub out_of_bounds: bool,
+}
+
+impl Default for WellRecord {
+    fn default() -> Self {
+        Self::new(String::new(), None)
+    }
+}
+
+impl WellRecord {
+    // Запись без значений; остальные поля заполняются напрямую
+    pub fn new(well_name: impl Into<String>, date: Option<NaiveDateTime>) -> Self {
+        Self {
+            well_name: well_name.into(),
+            date,
+            pd_liq: None,
+            pd_oil: None,
+            temperature: None,
+            p_bottom: None,
+            p_head: None,
+            frequency: None,
+            choke: None,
+            injection: None,
+            status: None,
+            well_type: None,
+            year_sheet: date.map_or(0, |d| d.year()),
+            quarter: None,
+            out_of_bounds: false,
+        }
+    }
+
+    pub fn as_record_ref(&self) -> RecordRef<'_> {
+        RecordRef {
+            well_name: &self.well_name,
+            date: self.date,
+            pd_liq: self.pd_liq,
+            pd_oil: self.pd_oil,
+            temperature: self.temperature,
+            p_bottom: self.p_bottom,
+            p_head: self.p_head,
+            frequency: self.frequency,
+            choke: self.choke,
+            injection: self.injection,
+            status: self.status,
+            well_type: self.well_type,
+            year_sheet: self.year_sheet,
+            quarter: self.quarter,
+            out_of_bounds: self.out_of_bounds,
+        }
+    }
+}
+
+impl From<RecordRef<'_>> for WellRecord {
+    fn from(r: RecordRef) -> Self {
+        Self {
+            well_name: r.well_name.to_string(),
+            date: r.date,
+            pd_liq: r.pd_liq,
+            pd_oil: r.pd_oil,
+            temperature: r.temperature,
+            p_bottom: r.p_bottom,
+            p_head: r.p_head,
+            frequency: r.frequency,
+            choke: r.choke,
+            injection: r.injection,
+            status: r.status,
+            well_type: r.well_type,
+            year_sheet: r.year_sheet,
+            quarter: r.quarter,
+            out_of_bounds: r.out_of_bounds,
+        }
+    }
+}
+
+// Загруженные данные целиком: записи по порядку загрузки и каротаж
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WellData {
+    pub records: Vec<WellRecord>,
+    pub logs: Vec<WellLog>,
+}
+
+impl WellData {
+    pub fn from_dataset(data: &Dataset) -> Result<Self, WellDataError> {
+        let mut records = Vec::with_capacity(data.len());
+        data.for_each(|r| records.push(WellRecord::from(r)))?;
+        Ok(Self {
+            records,
+            logs: data.logs.clone(),
+        })
+    }
+
+    pub fn to_dataset(&self, import: &ImportOptions) -> Result<Dataset, WellDataError> {
+        let mut dataset = Dataset::new(import.storage)?;
+        for r in &self.records {
+            dataset.push(r.as_record_ref())?;
+        }
+        dataset.finish()?;
+        dataset.logs = self.logs.clone();
+        Ok(dataset)
+    }
+
+    // Скважины по имени, без повторов
+    pub fn wells(&self) -> BTreeSet<&str> {
+        self.records
+            .iter()
+            .map(|r| r.well_name.as_str())
+            .chain(self.logs.iter().map(|l| l.well_name.as_str()))
+            .collect()
+    }
+
+    pub fn years(&self) -> BTreeSet<i32> {
+        self.records.iter().map(|r| r.year_sheet).collect()
+    }
+}
+
+// Имя файла по умолчанию: данные.wdc.json
+pub const EXTENSION: &str = "wdc.json";
+
+pub fn is_model_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+pub fn save_model_file(
+    path: &Path,
+    data: &Dataset,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoaderMessage, WellDataError> {
+    progress.report("Сбор записей...", 0, 0);
+    let model = WellData::from_dataset(data)?;
+    cancel.check()?;
+    progress.report(
+        &format!("Запись {} записей в JSON...", model.records.len()),
+        1,
+        2,
+    );
+    serde_json::to_writer(BufWriter::new(File::create(path)?), &model)?;
+    Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
+}
+
+// Сохраненные программой данные уже проверены правилами при первой загрузке,
+// поэтому нарушения здесь не ищутся: признак out_of_bounds хранится в записях
+pub fn read_model_file(
+    path: &Path,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    progress.report("Чтение JSON...", 0, 0);
+    let file = File::open(path).map_err(|e| WellDataError::open_failed(path, e))?;
+    let model: WellData = serde_json::from_reader(BufReader::new(file))?;
+    cancel.check()?;
+    Ok(LoadedData {
+        records: model.to_dataset(import)?,
+        years: model.years().into_iter().collect(),
+        wells: model.wells().into_iter().map(str::to_string).collect(),
+        violations: Vec::new(),
+        warnings: Vec::new(),
+        units: BTreeMap::new(),
+    })
+}
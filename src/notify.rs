@@ -0,0 +1,29 @@
+use eframe::egui;
+use notify_rust::{Notification, Timeout};
+
+// Сколько показывать уведомление, мс
+const TIMEOUT_MS: u32 = 10_000;
+
+// Окно свернуто или пользователь перешел в другую программу
+pub fn in_background(ctx: &egui::Context) -> bool {
+    ctx.input(|i| i.viewport().minimized == Some(true) || i.viewport().focused == Some(false))
+}
+
+// Загрузка или экспорт закончились, пока окно было в фоне: значок на панели
+// задач просит внимания, и система показывает всплывающее уведомление
+pub fn job_finished(ctx: &egui::Context, summary: &str, body: &str) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+        egui::UserAttentionType::Informational,
+    ));
+    let (summary, body) = (summary.to_string(), body.to_string());
+    // Служба уведомлений может отвечать долго или не отвечать вовсе - не в
+    // потоке интерфейса. Без нее остается только значок на панели
+    std::thread::spawn(move || {
+        let _ = Notification::new()
+            .appname("Well Data App")
+            .summary(&summary)
+            .body(&body)
+            .timeout(Timeout::Milliseconds(TIMEOUT_MS))
+            .show();
+    });
+}
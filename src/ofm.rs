@@ -0,0 +1,123 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::frame::{DATE, PD_LIQ, PD_OIL, WELL};
+use crate::progress::ProgressSink;
+use crate::store::Dataset;
+use crate::{ExportOptions, LoaderMessage};
+use polars::prelude::*;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+// Месячная добыча по скважине в разрезе, который грузит OFM
+struct MonthProduction<'a> {
+    well: &'a str,
+    year: i32,
+    month: i32,
+    // Дней с ненулевым дебитом
+    days: u32,
+    oil: f64,
+    liquid: f64,
+}
+
+fn monthly_production(frame: &DataFrame) -> PolarsResult<DataFrame> {
+    let producing = col(PD_LIQ).gt(lit(0.0)).or(col(PD_OIL).gt(lit(0.0)));
+    frame
+        .clone()
+        .lazy()
+        .filter(col(DATE).is_not_null())
+        .group_by([
+            col(WELL),
+            col(DATE).dt().year().alias("year"),
+            col(DATE).dt().month().cast(DataType::Int32).alias("month"),
+        ])
+        .agg([
+            col(DATE)
+                .dt()
+                .day()
+                .filter(producing)
+                .n_unique()
+                .cast(DataType::UInt32)
+                .alias("days"),
+            col(PD_OIL).sum().cast(DataType::Float64).alias("oil"),
+            col(PD_LIQ).sum().cast(DataType::Float64).alias("liquid"),
+        ])
+        .sort([WELL, "year", "month"], SortMultipleOptions::default())
+        .collect()
+}
+
+// Ключ скважины с пробелами OFM читает только в кавычках
+fn ofm_key(well: &str) -> String {
+    if well.contains(char::is_whitespace) {
+        format!("\"{}\"", well.replace('"', "'"))
+    } else {
+        well.to_string()
+    }
+}
+
+fn write_table(rows: &[MonthProduction]) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "*METRIC");
+    let _ = writeln!(text, "*TABLENAME MONTHLYPROD");
+    let _ = writeln!(
+        text,
+        "{:<24}{:>8}{:>6}{:>14}{:>14}",
+        "*WELL", "*DATE", "*DAYS", "*OIL", "*LIQUID"
+    );
+    for r in rows {
+        let _ = writeln!(
+            text,
+            "{:<24}{:>8}{:>6}{:>14.3}{:>14.3}",
+            ofm_key(r.well),
+            format!("{}{:02}", r.year, r.month),
+            r.days,
+            r.oil,
+            r.liquid
+        );
+    }
+    text
+}
+
+// Выгрузка для OFM: текст с фиксированными колонками (скважина, месяц YYYYMM,
+// дни работы, нефть, жидкость). Объемы - сумма суточных дебитов за месяц
+pub fn save_ofm_file(
+    path: &Path,
+    data: &Dataset,
+    start_year: i32,
+    selected_wells: &HashSet<String>,
+    options: &ExportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoaderMessage, WellDataError> {
+    progress.report("Подготовка данных...", 0, 0);
+    let selected = options.drop_excluded(data.select(start_year, selected_wells)?);
+    cancel.check()?;
+
+    let grouped = monthly_production(&selected.to_frame()?)?;
+    let wells = grouped.column(WELL)?.str()?;
+    let years = grouped.column("year")?.i32()?;
+    let months = grouped.column("month")?.i32()?;
+    let days = grouped.column("days")?.u32()?;
+    let oil = grouped.column("oil")?.f64()?;
+    let liquid = grouped.column("liquid")?.f64()?;
+    let rows: Vec<MonthProduction> = (0..grouped.height())
+        .filter_map(|i| {
+            Some(MonthProduction {
+                well: wells.get(i)?,
+                year: years.get(i)?,
+                month: months.get(i)?,
+                days: days.get(i).unwrap_or(0),
+                oil: oil.get(i).unwrap_or(0.0),
+                liquid: liquid.get(i).unwrap_or(0.0),
+            })
+        })
+        .collect();
+
+    cancel.check()?;
+    progress.report("Сохранение файла на диск...", 1, 1);
+    // OFM - программа под Windows и ждет файлы в cp1251
+    let text = write_table(&rows);
+    let (bytes, _, _) = encoding_rs::WINDOWS_1251.encode(&text);
+    std::fs::write(path, bytes)?;
+    Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
+}
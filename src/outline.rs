@@ -0,0 +1,59 @@
+use crate::store::RecordRef;
+use chrono::Datelike;
+use rust_xlsxwriter::{Worksheet, XlsxError};
+
+// Отрезки подряд идущих записей одного периода (номера записей включительно).
+// Запись без даты остается в текущем отрезке
+fn runs(
+    records: &[RecordRef],
+    period: impl Fn(&RecordRef) -> Option<(i32, u32)>,
+) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut current = None;
+    for (i, record) in records.iter().enumerate() {
+        let key = period(record);
+        match runs.last_mut() {
+            Some(run) if key.is_none() || key == current => run.1 = i,
+            _ => runs.push((i, i)),
+        }
+        if key.is_some() {
+            current = key;
+        }
+    }
+    runs
+}
+
+// Структура Excel на листе скважины: группы по месяцам, а если лет несколько -
+// и по годам над ними. Первая строка периода не входит в группу и остается
+// видна при сворачивании: иначе Excel сливает соседние группы одного уровня
+// в одну. Кнопки - у первой строки, группы развернуты
+pub fn group_by_month(
+    worksheet: &mut Worksheet,
+    records: &[RecordRef],
+    first_row: u32,
+    transposed: bool,
+) -> Result<(), XlsxError> {
+    let months = runs(records, |r| r.date.map(|d| (d.year(), d.month())));
+    let years = runs(records, |r| r.date.map(|d| (d.year(), 0)));
+    if transposed {
+        worksheet.group_symbols_to_left(true);
+    } else {
+        worksheet.group_symbols_above(true);
+    }
+    let mut group = |(first, last): (usize, usize)| -> Result<(), XlsxError> {
+        if last == first {
+            return Ok(());
+        }
+        let (first, last) = (first_row + first as u32 + 1, first_row + last as u32);
+        if transposed {
+            worksheet.group_columns(first as u16, last as u16)?;
+        } else {
+            worksheet.group_rows(first, last)?;
+        }
+        Ok(())
+    };
+    if years.len() > 1 {
+        years.into_iter().try_for_each(&mut group)?;
+    }
+    months.into_iter().try_for_each(group)
+}
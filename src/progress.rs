@@ -0,0 +1,51 @@
+use crate::LoaderMessage;
+use crate::timing::Timings;
+use std::sync::mpsc::Sender;
+
+// Куда функции ядра сообщают о ходе работы. Окно программы получает сообщения
+// по каналу задачи; консоль или сервер подставляют свою реализацию
+pub trait ProgressSink: Send + Sync {
+    // phase - что делается сейчас, current из total - сколько готово
+    // (листов, скважин). total = 0 - доля неизвестна
+    fn report(&self, phase: &str, current: usize, total: usize);
+
+    // Ход внутри этапа (строки листа); по умолчанию не показывается
+    fn report_step(&self, _phase: &str, _current: usize, _total: usize) {}
+
+    // Замеры времени по фазам в конце операции
+    fn timings(&self, _timings: Timings) {}
+}
+
+fn fraction(current: usize, total: usize) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        current as f32 / total as f32
+    }
+}
+
+impl ProgressSink for Sender<LoaderMessage> {
+    fn report(&self, phase: &str, current: usize, total: usize) {
+        let _ = self.send(LoaderMessage::Progress(
+            fraction(current, total),
+            0.0,
+            phase.to_string(),
+        ));
+    }
+
+    fn report_step(&self, phase: &str, current: usize, total: usize) {
+        let _ = self.send(LoaderMessage::StepProgress(
+            fraction(current, total),
+            phase.to_string(),
+        ));
+    }
+
+    fn timings(&self, timings: Timings) {
+        let _ = self.send(LoaderMessage::Profile(timings));
+    }
+}
+
+// Без вывода: пакет читает файлы молча, чтобы не перебивать прогресс записи
+impl ProgressSink for () {
+    fn report(&self, _phase: &str, _current: usize, _total: usize) {}
+}
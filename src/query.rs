@@ -0,0 +1,207 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use crate::store::{Dataset, Records};
+use crate::{LoaderMessage, add_sheet, locked};
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rust_xlsxwriter::{Format, Workbook};
+use std::path::Path;
+use std::sync::Arc;
+
+// Таблица, доступная в запросах
+pub const TABLE: &str = "records";
+pub const TABLE_HINT: &str = "records: well_name, date, pd_liq, pd_oil, temperature, p_bottom, p_head, frequency, choke, injection, status, well_type, year_sheet, quarter, out_of_bounds";
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    pub fn display(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::Number(v) if v.fract() == 0.0 && v.abs() < 1e15 => format!("{}", *v as i64),
+            Value::Number(v) => v.to_string(),
+            Value::Text(s) => s.clone(),
+        }
+    }
+}
+
+// Результат запроса: колонки и строки значений
+#[derive(Debug, Clone, Default)]
+pub struct QueryResult {
+    pub sql: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+// Состояние окна SQL-запроса
+pub struct SqlPanel {
+    pub open: bool,
+    pub text: String,
+    pub result: Option<Arc<QueryResult>>,
+    // Имя для сохранения в библиотеку
+    pub save_name: String,
+}
+
+impl Default for SqlPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            text: format!(
+                "SELECT well_name, avg(pd_oil) AS pd_oil\nFROM {}\nGROUP BY well_name\nORDER BY well_name",
+                TABLE
+            ),
+            result: None,
+            save_name: String::new(),
+        }
+    }
+}
+
+// Запрос выполняется там же, где лежат данные:
+// в памяти - через Polars SQL, на диске - во временной базе DuckDB
+pub fn run(data: &Dataset, sql: &str) -> Result<QueryResult, WellDataError> {
+    let mut result = match &data.records {
+        Records::Memory(store) => {
+            let mut ctx = SQLContext::new();
+            ctx.register(TABLE, store.to_frame()?.drop(crate::frame::ROW)?.lazy());
+            from_frame(ctx.execute(sql)?.collect()?)?
+        }
+        Records::Disk(store) => store.query(sql)?,
+    };
+    result.sql = sql.to_string();
+    Ok(result)
+}
+
+fn from_frame(frame: DataFrame) -> PolarsResult<QueryResult> {
+    let columns = frame
+        .get_column_names()
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let mut rows = vec![Vec::with_capacity(frame.width()); frame.height()];
+
+    for column in frame.get_columns() {
+        let dtype = column.dtype();
+        if dtype.is_primitive_numeric() || dtype.is_bool() {
+            let values = column.cast(&DataType::Float64)?;
+            for (row, v) in rows.iter_mut().zip(values.f64()?.iter()) {
+                row.push(v.map_or(Value::Null, Value::Number));
+            }
+        } else {
+            let values = column.cast(&DataType::String)?;
+            for (row, v) in rows.iter_mut().zip(values.str()?.iter()) {
+                row.push(v.map_or(Value::Null, |s| Value::Text(s.to_string())));
+            }
+        }
+    }
+
+    Ok(QueryResult {
+        sql: String::new(),
+        columns,
+        rows,
+    })
+}
+
+// Сохранение результата запроса: .csv - текстом через ';', иначе - книгой Excel
+pub fn save_result(
+    path: &Path,
+    result: &QueryResult,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoaderMessage, WellDataError> {
+    let is_csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        write_csv(path, result, progress, cancel)?;
+    } else {
+        write_xlsx(path, result, progress, cancel)?;
+    }
+    Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
+}
+
+fn report_progress(progress: &dyn ProgressSink, i: usize, total: usize) {
+    progress.report(&format!("Запись строки {}/{}", i, total), i, total);
+}
+
+fn write_xlsx(
+    path: &Path,
+    result: &QueryResult,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<(), WellDataError> {
+    // Строка заголовка + данные должны поместиться на один лист
+    if result.rows.len() >= 1_048_576 {
+        return Err(WellDataError::TooLarge(format!(
+            "в результате {} строк, это больше предела листа Excel; сохраните в CSV",
+            result.rows.len()
+        )));
+    }
+
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let total = result.rows.len();
+
+    let worksheet = add_sheet(&mut workbook, true).set_name("Запрос")?;
+    for (col, name) in result.columns.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, name, &bold)?;
+    }
+    for (i, row) in result.rows.iter().enumerate() {
+        if i % 5000 == 0 {
+            cancel.check()?;
+            report_progress(progress, i, total);
+        }
+        for (col, value) in row.iter().enumerate() {
+            match value {
+                Value::Null => {}
+                Value::Number(v) => {
+                    worksheet.write_number(i as u32 + 1, col as u16, *v)?;
+                }
+                Value::Text(s) => {
+                    worksheet.write_string(i as u32 + 1, col as u16, s)?;
+                }
+            }
+        }
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+
+    // Текст запроса рядом с данными, чтобы файл можно было воспроизвести
+    let sql_sheet = workbook.add_worksheet().set_name("SQL")?;
+    for (i, line) in result.sql.lines().enumerate() {
+        sql_sheet.write_string(i as u32, 0, line)?;
+    }
+
+    cancel.check()?;
+    progress.report("Сохранение файла на диск...", 1, 1);
+    locked::save_workbook(&mut workbook, path)?;
+    Ok(())
+}
+
+fn write_csv(
+    path: &Path,
+    result: &QueryResult,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<(), WellDataError> {
+    let mut file = std::fs::File::create(path)?;
+    // BOM, чтобы Excel открыл UTF-8 без искажений
+    std::io::Write::write_all(&mut file, "\u{feff}".as_bytes())?;
+    let mut writer = csv::WriterBuilder::new().delimiter(b';').from_writer(file);
+
+    let total = result.rows.len();
+    writer.write_record(&result.columns)?;
+    for (i, row) in result.rows.iter().enumerate() {
+        if i % 5000 == 0 {
+            cancel.check()?;
+            report_progress(progress, i, total);
+        }
+        writer.write_record(row.iter().map(Value::display))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use crate::store::Dataset;
+use crate::tasks::TaskId;
+use crate::{ExportOptions, LoaderMessage};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum JobState {
+    Pending,
+    Running(TaskId),
+    Done(String),
+    Failed(String),
+}
+
+impl JobState {
+    pub fn label(&self) -> String {
+        match self {
+            JobState::Pending => "⏳ ждет".to_string(),
+            JobState::Running(_) => "▶ выполняется".to_string(),
+            JobState::Done(_) => "✔ готово".to_string(),
+            JobState::Failed(e) => format!("❌ {}", e),
+        }
+    }
+}
+
+// Выгрузка в очереди. Данные, скважины и настройки запоминаются при постановке,
+// так что дальнейшая работа с окном (и даже загрузка другого файла) на нее не влияет
+pub struct QueuedExport {
+    pub title: String,
+    pub path: PathBuf,
+    pub data: Arc<Dataset>,
+    pub start_year: i32,
+    pub wells: HashSet<String>,
+    pub options: ExportOptions,
+    pub state: JobState,
+}
+
+// Очередь выполняется по одной выгрузке за раз, в порядке постановки
+#[derive(Default)]
+pub struct ExportQueue {
+    pub jobs: Vec<QueuedExport>,
+}
+
+impl ExportQueue {
+    pub fn is_running(&self) -> bool {
+        self.jobs
+            .iter()
+            .any(|j| matches!(j.state, JobState::Running(_)))
+    }
+
+    pub fn next_pending(&mut self) -> Option<&mut QueuedExport> {
+        self.jobs
+            .iter_mut()
+            .find(|j| matches!(j.state, JobState::Pending))
+    }
+
+    // Итог задачи; сообщения чужих задач и промежуточные сообщения пропускаются
+    pub fn finish(&mut self, id: TaskId, msg: &LoaderMessage) {
+        let Some(job) = self
+            .jobs
+            .iter_mut()
+            .find(|j| matches!(j.state, JobState::Running(running) if running == id))
+        else {
+            return;
+        };
+        job.state = match msg {
+            LoaderMessage::Saved(path) => JobState::Done(path.clone()),
+            LoaderMessage::Error(e) => JobState::Failed(e.to_string()),
+            LoaderMessage::Locked(file) => JobState::Failed(file.to_string()),
+            LoaderMessage::Cancelled => JobState::Failed("отменено".to_string()),
+            _ => return,
+        };
+    }
+
+    pub fn clear_finished(&mut self) {
+        self.jobs
+            .retain(|j| matches!(j.state, JobState::Pending | JobState::Running(_)));
+    }
+}
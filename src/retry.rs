@@ -0,0 +1,108 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+// Сетевые ошибки Windows: сетевое имя удалено, неожиданная сетевая ошибка,
+// сеть занята, сетевой путь не найден, истек таймаут семафора
+const NETWORK_ERRORS: [i32; 5] = [64, 59, 54, 53, 121];
+
+// Повторы чтения и записи при сбоях сетевой папки
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    // Всего попыток, вместе с первой; 1 - без повторов
+    pub attempts: u32,
+    // Пауза перед первым повтором, дальше удваивается
+    pub initial_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_delay_ms: 500,
+        }
+    }
+}
+
+fn is_transient_io(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::NetworkDown
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::HostUnreachable
+    ) || error
+        .raw_os_error()
+        .is_some_and(|code| NETWORK_ERRORS.contains(&code))
+}
+
+// Сбой, который может пройти сам: ищем ошибку ввода-вывода по цепочке причин.
+// Нет файла, нет прав, файл занят - не повторяем
+pub fn is_transient(error: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(e) = current {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            return is_transient_io(io);
+        }
+        // Ошибка записи книги не отдает причину через source
+        if let Some(rust_xlsxwriter::XlsxError::IoError(io)) =
+            e.downcast_ref::<rust_xlsxwriter::XlsxError>()
+        {
+            return is_transient_io(io);
+        }
+        current = e.source();
+    }
+    false
+}
+
+impl RetryPolicy {
+    // what - что делаем, для сообщения о повторе ("чтение листа 2021")
+    pub fn run<T, E: Into<WellDataError>>(
+        &self,
+        what: &str,
+        progress: &dyn ProgressSink,
+        cancel: &CancellationToken,
+        mut operation: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, WellDataError> {
+        let attempts = self.attempts.max(1);
+        let mut delay = Duration::from_millis(self.initial_delay_ms);
+        let mut attempt = 1;
+        loop {
+            let error: WellDataError = match operation() {
+                Ok(value) => return Ok(value),
+                Err(e) => e.into(),
+            };
+            if attempt >= attempts || !is_transient(&error) {
+                return Err(error);
+            }
+            attempt += 1;
+            progress.report(
+                &format!(
+                    "Сбой: {} ({}). Попытка {} из {} через {:.1} с...",
+                    what,
+                    error,
+                    attempt,
+                    attempts,
+                    delay.as_secs_f64()
+                ),
+                0,
+                0,
+            );
+            std::thread::sleep(delay);
+            cancel.check()?;
+            delay *= 2;
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::error::WellDataError;
+use rust_xlsxwriter::Workbook;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const RULES_FILE_NAME: &str = "rules.toml";
+
+// Правила проверки данных, которые администратор кладет в rules.toml
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ValidationRules {
+    pub required_columns: Vec<String>,
+    pub forbidden_wells: Vec<String>,
+    pub bounds: HashMap<String, Bounds>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Bounds {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    MissingColumn,
+    ForbiddenWell,
+    OutOfBounds,
+}
+
+impl ViolationKind {
+    // Стабильный код правила для отчета
+    pub fn code(self) -> &'static str {
+        match self {
+            ViolationKind::MissingColumn => "REQUIRED_COLUMN",
+            ViolationKind::ForbiddenWell => "FORBIDDEN_WELL",
+            ViolationKind::OutOfBounds => "OUT_OF_BOUNDS",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub sheet: String,
+    pub row: Option<u32>,
+    pub well: Option<String>,
+    pub details: String,
+}
+
+impl ValidationRules {
+    // Ищем rules.toml рядом с exe, затем в папке настроек пользователя
+    pub fn find_file() -> Option<PathBuf> {
+        let next_to_exe = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.parent().map(|d| d.join(RULES_FILE_NAME)));
+        let in_config =
+            dirs::config_dir().map(|d| d.join(crate::APP_DIR_NAME).join(RULES_FILE_NAME));
+
+        [next_to_exe, in_config]
+            .into_iter()
+            .flatten()
+            .find(|p| p.is_file())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("не удалось прочитать {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("ошибка в {}: {}", path.display(), e))
+    }
+
+    pub fn is_forbidden(&self, well_name: &str) -> bool {
+        let name = well_name.trim().to_lowercase();
+        self.forbidden_wells
+            .iter()
+            .any(|w| w.trim().to_lowercase() == name)
+    }
+}
+
+impl Bounds {
+    pub fn check(&self, value: f64) -> Option<String> {
+        match (self.min, self.max) {
+            (Some(min), _) if value < min => Some(format!("{} < {}", value, min)),
+            (_, Some(max)) if value > max => Some(format!("{} > {}", value, max)),
+            _ => None,
+        }
+    }
+}
+
+pub fn save_violations_report(path: &Path, violations: &[Violation]) -> Result<(), WellDataError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Нарушения")?;
+
+    worksheet.write_string(0, 0, "Правило")?;
+    worksheet.write_string(0, 1, "Лист")?;
+    worksheet.write_string(0, 2, "Строка")?;
+    worksheet.write_string(0, 3, "Скважина")?;
+    worksheet.write_string(0, 4, "Описание")?;
+
+    for (i, v) in violations.iter().enumerate() {
+        let row = i as u32 + 1;
+        worksheet.write_string(row, 0, v.kind.code())?;
+        worksheet.write_string(row, 1, &v.sheet)?;
+        if let Some(r) = v.row {
+            worksheet.write_number(row, 2, r)?;
+        }
+        if let Some(w) = &v.well {
+            worksheet.write_string(row, 3, w)?;
+        }
+        worksheet.write_string(row, 4, &v.details)?;
+    }
+
+    crate::locked::save_workbook(&mut workbook, path)?;
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use crate::cancel::CancellationToken;
+use crate::download;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use chrono::{DateTime, Utc};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+// SHA-256 пустого тела запроса
+const EMPTY_PAYLOAD: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+const BLOCK: usize = 64;
+
+// Хранилище S3 (MinIO). Секретный ключ в настройки не пишется: он вводится
+// в окне или берется из AWS_SECRET_ACCESS_KEY
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct S3Config {
+    // http://minio:9000; адресация всегда по пути: endpoint/bucket/key
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub prefix: String,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: "us-east-1".to_string(),
+            bucket: String::new(),
+            access_key: String::new(),
+            prefix: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Object {
+    pub key: String,
+    pub size: u64,
+    pub modified: String,
+}
+
+// Окно "Хранилище S3": настройки, список объектов и выбранный
+#[derive(Debug, Default)]
+pub struct S3Dialog {
+    pub config: S3Config,
+    pub secret_key: String,
+    pub objects: Vec<S3Object>,
+    pub selected: Option<String>,
+}
+
+impl S3Dialog {
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+}
+
+pub enum S3Action {
+    Close,
+    List,
+    Load,
+}
+
+// Ключ из окна, иначе из переменных окружения
+fn credentials(config: &S3Config, secret_key: &str) -> Result<(String, String), WellDataError> {
+    let access_key = match config.access_key.trim() {
+        "" => std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_default(),
+        key => key.to_string(),
+    };
+    let secret_key = match secret_key.trim() {
+        "" => std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
+        key => key.to_string(),
+    };
+    if access_key.is_empty() || secret_key.is_empty() {
+        return Err(WellDataError::Config(
+            "не заданы ключ доступа и секретный ключ S3".to_string(),
+        ));
+    }
+    Ok((access_key, secret_key))
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(data);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Кодирование по правилам SigV4: не трогаются только A-Z a-z 0-9 - _ . ~
+fn uri_encode(text: &str, keep_slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// Подписанный запрос GET (AWS Signature Version 4)
+fn signed_get(
+    client: &reqwest::Client,
+    config: &S3Config,
+    secret_key: &str,
+    key: &str,
+    query: &[(&str, &str)],
+) -> Result<reqwest::RequestBuilder, WellDataError> {
+    let (access_key, secret_key) = credentials(config, secret_key)?;
+    let endpoint = reqwest::Url::parse(config.endpoint.trim()).map_err(|e| {
+        WellDataError::Config(format!("неверный адрес S3 {}: {}", config.endpoint, e))
+    })?;
+    let host = match (endpoint.host_str(), endpoint.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => {
+            return Err(WellDataError::Config(format!(
+                "в адресе S3 нет сервера: {}",
+                config.endpoint
+            )));
+        }
+    };
+    let bucket = config.bucket.trim();
+    if bucket.is_empty() {
+        return Err(WellDataError::Config("не задана корзина S3".to_string()));
+    }
+    let region = match config.region.trim() {
+        "" => "us-east-1",
+        region => region,
+    };
+
+    let base_path = endpoint.path().trim_end_matches('/');
+    let mut path = format!("{}/{}", base_path, uri_encode(bucket, false));
+    if !key.is_empty() {
+        path = format!("{}/{}", path, uri_encode(key, true));
+    }
+    let mut params: Vec<(String, String)> = query
+        .iter()
+        .map(|(k, v)| (uri_encode(k, false), uri_encode(v, false)))
+        .collect();
+    params.sort();
+    let query = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let now: DateTime<Utc> = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let day = now.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", day, region);
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical = format!(
+        "GET\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        path, query, host, EMPTY_PAYLOAD, amz_date, signed_headers, EMPTY_PAYLOAD
+    );
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        to_hex(&Sha256::digest(canonical.as_bytes()))
+    );
+    let mut signing_key = hmac(format!("AWS4{}", secret_key).as_bytes(), day.as_bytes());
+    for part in [region, "s3", "aws4_request"] {
+        signing_key = hmac(&signing_key, part.as_bytes());
+    }
+    let signature = to_hex(&hmac(&signing_key, to_sign.as_bytes()));
+
+    let mut url = endpoint.clone();
+    url.set_path(&path);
+    url.set_query((!query.is_empty()).then_some(query.as_str()));
+    Ok(client
+        .get(url)
+        .header("x-amz-date", amz_date)
+        .header("x-amz-content-sha256", EMPTY_PAYLOAD)
+        .header(
+            "Authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                access_key, scope, signed_headers, signature
+            ),
+        ))
+}
+
+fn child_text<'a>(node: Node<'a, 'a>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|n| n.has_tag_name(name))
+        .and_then(|n| n.text())
+}
+
+// Текст ошибки S3 из XML-ответа (<Error><Code>..</Code><Message>..</Message>)
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, WellDataError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let detail = Document::parse(&body).ok().and_then(|doc| {
+        let root = doc.root_element();
+        let code = child_text(root, "Code")?;
+        Some(match child_text(root, "Message") {
+            Some(message) => format!("{}: {}", code, message),
+            None => code.to_string(),
+        })
+    });
+    Err(WellDataError::Remote(match detail {
+        Some(detail) => format!("S3 ответил {}: {}", status, detail),
+        None => format!("S3 ответил {}", status),
+    }))
+}
+
+// Объекты корзины с ключом, начинающимся с префикса (ListObjectsV2, по 1000 за запрос)
+pub async fn list(
+    config: &S3Config,
+    secret_key: &str,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Vec<S3Object>, WellDataError> {
+    let client = download::client()?;
+    let prefix = config.prefix.trim();
+    let mut objects = Vec::new();
+    let mut token: Option<String> = None;
+    loop {
+        cancel.check()?;
+        let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+        if let Some(token) = &token {
+            query.push(("continuation-token", token));
+        }
+        let request = signed_get(&client, config, secret_key, "", &query)?;
+        let response = check(cancel.or_cancel(request.send()).await??).await?;
+        let body = cancel.or_cancel(response.text()).await??;
+        let doc = Document::parse(&body)?;
+        let root = doc.root_element();
+        for contents in root.children().filter(|n| n.has_tag_name("Contents")) {
+            let Some(key) = child_text(contents, "Key") else {
+                continue;
+            };
+            // "Папки", созданные в консоли MinIO
+            if key.ends_with('/') {
+                continue;
+            }
+            objects.push(S3Object {
+                key: key.to_string(),
+                size: child_text(contents, "Size")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+                modified: child_text(contents, "LastModified")
+                    .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                    .map(|d| {
+                        d.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M")
+                            .to_string()
+                    })
+                    .unwrap_or_default(),
+            });
+        }
+        progress.report(&format!("Получен список: {} объектов", objects.len()), 0, 0);
+        token = match child_text(root, "IsTruncated") {
+            Some("true") => child_text(root, "NextContinuationToken").map(str::to_string),
+            _ => None,
+        };
+        if token.is_none() {
+            return Ok(objects);
+        }
+    }
+}
+
+// Скачанный объект лежит в кэше под папкой корзины, с ключом в имени
+pub fn target_path(config: &S3Config, key: &str) -> Result<PathBuf, WellDataError> {
+    Ok(download::downloads_dir()?
+        .join("s3")
+        .join(config.bucket.trim())
+        .join(download::file_name(key)))
+}
+
+pub async fn get(
+    config: &S3Config,
+    secret_key: &str,
+    key: &str,
+    target: &Path,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<(), WellDataError> {
+    let client = download::client()?;
+    let request = signed_get(&client, config, secret_key, key, &[])?;
+    let response = check(cancel.or_cancel(request.send()).await??).await?;
+    download::save_response(response, target, progress, cancel).await
+}
@@ -0,0 +1,290 @@
+use crate::retry::RetryPolicy;
+use crate::s3::S3Config;
+use crate::sftp::SftpConfig;
+use crate::window_state::WindowState;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub const SETTINGS_FILE_NAME: &str = "settings.toml";
+
+// В чем в исходных файлах записаны дебиты
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RateUnits {
+    #[default]
+    TonnesPerDay,
+    CubicMetersPerDay,
+}
+
+impl RateUnits {
+    pub const ALL: [RateUnits; 2] = [RateUnits::TonnesPerDay, RateUnits::CubicMetersPerDay];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RateUnits::TonnesPerDay => "т/сут",
+            RateUnits::CubicMetersPerDay => "м³/сут",
+        }
+    }
+}
+
+// Как показывать числа в окнах программы. На выгрузку в Excel и CSV не влияет
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NumberLocale {
+    // 12 345,6
+    #[default]
+    Russian,
+    // 12345.6
+    Plain,
+}
+
+impl NumberLocale {
+    pub const ALL: [NumberLocale; 2] = [NumberLocale::Russian, NumberLocale::Plain];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NumberLocale::Russian => "12 345,6",
+            NumberLocale::Plain => "12345.6",
+        }
+    }
+
+    // decimals: None - столько знаков, сколько есть в числе
+    pub fn number(self, value: f64, decimals: Option<usize>) -> String {
+        let text = match decimals {
+            Some(decimals) => format!("{:.*}", decimals, value),
+            None => value.to_string(),
+        };
+        self.localize(&text)
+    }
+
+    pub fn count(self, value: usize) -> String {
+        self.localize(&value.to_string())
+    }
+
+    // Разряды отделяются неразрывным пробелом, чтобы число не переносилось.
+    // Четырехзначные не разбиваются - так принято, и годы остаются годами
+    fn localize(self, text: &str) -> String {
+        if self == NumberLocale::Plain {
+            return text.to_string();
+        }
+        let (sign, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", text),
+        };
+        let (int, fract) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let mut out = sign.to_string();
+        if int.len() > 4 && int.bytes().all(|b| b.is_ascii_digit()) {
+            for (i, digit) in int.chars().enumerate() {
+                if i > 0 && (int.len() - i) % 3 == 0 {
+                    out.push('\u{a0}');
+                }
+                out.push(digit);
+            }
+        } else {
+            out.push_str(int);
+        }
+        if !fract.is_empty() {
+            out.push(',');
+            out.push_str(fract);
+        }
+        out
+    }
+}
+
+// Как показывать даты в окнах программы, независимо от формата дат в выгрузке
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DateFormat {
+    // 31.12.2024
+    #[default]
+    Russian,
+    // 2024-12-31
+    Iso,
+}
+
+impl DateFormat {
+    pub const ALL: [DateFormat; 2] = [DateFormat::Russian, DateFormat::Iso];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DateFormat::Russian => "31.12.2024",
+            DateFormat::Iso => "2024-12-31",
+        }
+    }
+
+    fn date_pattern(self) -> &'static str {
+        match self {
+            DateFormat::Russian => "%d.%m.%Y",
+            DateFormat::Iso => "%Y-%m-%d",
+        }
+    }
+
+    // Время показывается, только если оно не полночь
+    pub fn datetime(self, value: NaiveDateTime) -> String {
+        if value.time() == NaiveTime::MIN {
+            value.format(self.date_pattern()).to_string()
+        } else {
+            let pattern = format!("{} %H:%M:%S", self.date_pattern());
+            value.format(&pattern).to_string()
+        }
+    }
+
+    // Дата текстом в ISO (как ее отдают запросы и журнал) - в выбранном виде;
+    // остальной текст не меняется
+    pub fn text(self, text: &str) -> String {
+        let parsed = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+            .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f"))
+            .or_else(|_| {
+                NaiveDate::parse_from_str(text, "%Y-%m-%d").map(|d| d.and_time(NaiveTime::MIN))
+            });
+        match parsed {
+            Ok(value) => self.datetime(value),
+            Err(_) => text.to_string(),
+        }
+    }
+}
+
+// Как называются основные колонки на листах-годах, если не как в стандартной выгрузке
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnMapping {
+    pub well: String,
+    pub date: String,
+    pub pd_liq: String,
+    pub pd_oil: String,
+    pub temperature: String,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            well: crate::NAME_COL.to_string(),
+            date: "Date".to_string(),
+            pd_liq: "PdLiq".to_string(),
+            pd_oil: "PdOil".to_string(),
+            temperature: crate::TEMPERATURE_COL.to_string(),
+        }
+    }
+}
+
+impl ColumnMapping {
+    // Заголовок в файле -> стандартное имя колонки
+    pub fn aliases(&self) -> [(&str, &'static str); 5] {
+        [
+            (self.well.trim(), crate::NAME_COL),
+            (self.date.trim(), "Date"),
+            (self.pd_liq.trim(), "PdLiq"),
+            (self.pd_oil.trim(), "PdOil"),
+            (self.temperature.trim(), crate::TEMPERATURE_COL),
+        ]
+    }
+
+    // Добавляет стандартные имена к найденным колонкам. Вместо заголовка
+    // можно указать номер колонки: #1, #2... - для файлов без заголовка
+    pub fn apply(&self, col_map: &mut HashMap<String, usize>) {
+        for (header, standard) in self.aliases() {
+            let index = match header.strip_prefix('#').map(str::parse::<usize>) {
+                Some(Ok(n)) if n > 0 => Some(n - 1),
+                _ => col_map.get(header).copied(),
+            };
+            if let Some(i) = index {
+                col_map.entry(standard.to_string()).or_insert(i);
+            }
+        }
+    }
+}
+
+// Начальные настройки из мастера первого запуска (папка настроек, settings.toml)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub columns: ColumnMapping,
+    // Куда по умолчанию предлагать сохранять отчеты
+    pub output_dir: Option<PathBuf>,
+    pub rate_units: RateUnits,
+    pub number_locale: NumberLocale,
+    pub date_format: DateFormat,
+    pub retry: RetryPolicy,
+    // Подключение к хранилищу S3, без секретного ключа
+    pub s3: S3Config,
+    // Сервер SFTP, без пароля
+    pub sftp: SftpConfig,
+    // Уведомлять о конце загрузки и экспорта, если окно в фоне
+    pub notifications: bool,
+    // Положение и размер окна при закрытии
+    pub window: Option<WindowState>,
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join(crate::APP_DIR_NAME).join(SETTINGS_FILE_NAME))
+    }
+
+    // None - файла нет, это первый запуск
+    pub fn load() -> Result<Option<Self>, String> {
+        let Some(path) = Self::path().filter(|p| p.is_file()) else {
+            return Ok(None);
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("не удалось прочитать {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("ошибка в {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("не найдена папка настроек пользователя")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("не удалось создать {}: {}", dir.display(), e))?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, text)
+            .map_err(|e| format!("не удалось записать {}: {}", path.display(), e))
+    }
+}
+
+// Шаги мастера первого запуска
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WizardStep {
+    Formats,
+    Columns,
+    OutputDir,
+    Units,
+}
+
+impl WizardStep {
+    pub const ALL: [WizardStep; 4] = [
+        WizardStep::Formats,
+        WizardStep::Columns,
+        WizardStep::OutputDir,
+        WizardStep::Units,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            WizardStep::Formats => "Числа и даты",
+            WizardStep::Columns => "Колонки исходных файлов",
+            WizardStep::OutputDir => "Папка для отчетов",
+            WizardStep::Units => "Единицы",
+        }
+    }
+}
+
+pub struct SetupWizard {
+    pub step: usize,
+    pub settings: Settings,
+}
+
+impl SetupWizard {
+    pub fn new(settings: Settings) -> Self {
+        Self { step: 0, settings }
+    }
+
+    pub fn current(&self) -> WizardStep {
+        WizardStep::ALL[self.step]
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.step + 1 == WizardStep::ALL.len()
+    }
+}
@@ -0,0 +1,260 @@
+use crate::cancel::CancellationToken;
+use crate::download;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use serde::{Deserialize, Serialize};
+use ssh2::{CheckResult, KnownHostFileKind, Session};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const KNOWN_HOSTS_FILE_NAME: &str = "known_hosts";
+const CHUNK: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SftpAuth {
+    #[default]
+    Password,
+    Key,
+}
+
+// Сервер SFTP. Пароль и фраза ключа в настройки не пишутся
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SftpConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub auth: SftpAuth,
+    // Закрытый ключ OpenSSH (PEM)
+    pub key_path: String,
+    // Папка, которая открывается в окне первой
+    pub remote_dir: String,
+}
+
+impl Default for SftpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 22,
+            user: String::new(),
+            auth: SftpAuth::default(),
+            key_path: String::new(),
+            remote_dir: "/".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    // Unix-время изменения
+    pub modified: Option<i64>,
+}
+
+// Окно "Сервер SFTP": подключение, текущая папка и выбранный файл
+#[derive(Debug, Default)]
+pub struct SftpDialog {
+    pub config: SftpConfig,
+    // Пароль или фраза закрытого ключа
+    pub secret: String,
+    pub dir: String,
+    pub entries: Vec<RemoteEntry>,
+    pub selected: Option<String>,
+}
+
+impl SftpDialog {
+    pub fn new(config: SftpConfig) -> Self {
+        Self {
+            dir: config.remote_dir.clone(),
+            config,
+            ..Self::default()
+        }
+    }
+}
+
+pub enum SftpAction {
+    Close,
+    // Открыть папку
+    List(String),
+    Load,
+}
+
+pub fn join(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+pub fn parent(dir: &str) -> String {
+    match dir.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+fn known_hosts_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(crate::APP_DIR_NAME).join(KNOWN_HOSTS_FILE_NAME))
+}
+
+// Ключ сервера сверяется с ~/.ssh/known_hosts и списком программы. Новый сервер
+// запоминается при первом подключении, а смена ключа - ошибка
+fn check_host_key(session: &Session, config: &SftpConfig) -> Result<(), WellDataError> {
+    let (key, key_type) = session
+        .host_key()
+        .ok_or_else(|| WellDataError::Remote("сервер не передал ключ".to_string()))?;
+    let mut known_hosts = session.known_hosts()?;
+    let own_file = known_hosts_path();
+    let ssh_file = dirs::home_dir().map(|d| d.join(".ssh").join(KNOWN_HOSTS_FILE_NAME));
+    for file in own_file.iter().chain(ssh_file.iter()) {
+        if file.is_file() {
+            known_hosts.read_file(file, KnownHostFileKind::OpenSSH)?;
+        }
+    }
+    let host = config.host.trim();
+    match known_hosts.check_port(host, config.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(WellDataError::Remote(format!(
+            "ключ сервера {} не совпадает с сохраненным: подключение прервано",
+            host
+        ))),
+        CheckResult::NotFound => {
+            let Some(own_file) = own_file else {
+                return Ok(());
+            };
+            let name = match config.port {
+                22 => host.to_string(),
+                port => format!("[{}]:{}", host, port),
+            };
+            known_hosts.add(&name, key, "", key_type.into())?;
+            if let Some(dir) = own_file.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            known_hosts.write_file(&own_file, KnownHostFileKind::OpenSSH)?;
+            Ok(())
+        }
+        CheckResult::Failure => Err(WellDataError::Remote(format!(
+            "не удалось проверить ключ сервера {}",
+            host
+        ))),
+    }
+}
+
+fn connect(config: &SftpConfig, secret: &str, timeout: Duration) -> Result<Session, WellDataError> {
+    let host = config.host.trim();
+    if host.is_empty() {
+        return Err(WellDataError::Config("не задан сервер SFTP".to_string()));
+    }
+    let address = (host, config.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| WellDataError::Remote(format!("сервер {} не найден", host)))?;
+    let tcp = TcpStream::connect_timeout(&address, timeout)?;
+    let mut session = Session::new()?;
+    session.set_timeout(timeout.as_millis() as u32);
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+    check_host_key(&session, config)?;
+
+    let user = config.user.trim();
+    match config.auth {
+        SftpAuth::Password => session.userauth_password(user, secret)?,
+        SftpAuth::Key => session.userauth_pubkey_file(
+            user,
+            None,
+            Path::new(config.key_path.trim()),
+            (!secret.is_empty()).then_some(secret),
+        )?,
+    }
+    if !session.authenticated() {
+        return Err(WellDataError::Remote(format!(
+            "сервер {} отклонил вход пользователя {}",
+            host, user
+        )));
+    }
+    Ok(session)
+}
+
+// Содержимое папки: сначала папки, потом файлы, по имени
+pub fn list(
+    config: &SftpConfig,
+    secret: &str,
+    dir: &str,
+    timeout: Duration,
+) -> Result<Vec<RemoteEntry>, WellDataError> {
+    let session = connect(config, secret, timeout)?;
+    let sftp = session.sftp()?;
+    let mut entries: Vec<RemoteEntry> = sftp
+        .readdir(Path::new(dir))?
+        .into_iter()
+        .filter_map(|(path, stat)| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            (!name.starts_with('.')).then(|| RemoteEntry {
+                name,
+                is_dir: stat.is_dir(),
+                size: stat.size.unwrap_or(0),
+                modified: stat.mtime.map(|t| t as i64),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+// Скачанный файл лежит в кэше под папкой сервера
+pub fn target_path(config: &SftpConfig, remote_path: &str) -> Result<PathBuf, WellDataError> {
+    let name = remote_path.rsplit('/').next().unwrap_or(remote_path);
+    Ok(download::downloads_dir()?
+        .join("sftp")
+        .join(config.host.trim().replace(':', "_"))
+        .join(download::file_name(name)))
+}
+
+// Скачивание во временный .part и переименование, как и по ссылке
+pub fn get(
+    config: &SftpConfig,
+    secret: &str,
+    remote_path: &str,
+    target: &Path,
+    timeout: Duration,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<(), WellDataError> {
+    progress.report(&format!("Подключение к {}...", config.host.trim()), 0, 0);
+    let session = connect(config, secret, timeout)?;
+    let sftp = session.sftp()?;
+    let mut input = sftp.open(Path::new(remote_path))?;
+    let total = input.stat()?.size;
+
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let part = target.with_extension("part");
+    let mut output = std::fs::File::create(&part)?;
+    let mut buffer = vec![0; CHUNK];
+    let mut received = 0u64;
+    loop {
+        cancel.check()?;
+        let n = input.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        output.write_all(&buffer[..n])?;
+        received += n as u64;
+        let megabytes = received as f64 / 1048576.0;
+        let text = match total {
+            Some(total) => format!(
+                "Скачивание: {:.1} из {:.1} МБ",
+                megabytes,
+                total as f64 / 1048576.0
+            ),
+            None => format!("Скачивание: {:.1} МБ", megabytes),
+        };
+        progress.report_step(&text, received as usize, total.unwrap_or(0) as usize);
+    }
+    output.flush()?;
+    drop(output);
+    std::fs::rename(&part, target)?;
+    Ok(())
+}
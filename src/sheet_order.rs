@@ -0,0 +1,67 @@
+use crate::store::RecordRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// Порядок листов скважин в отчете
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SheetOrder {
+    #[default]
+    Name,
+    // Сначала скважины с наибольшей суммарной добычей нефти за период
+    OilDesc,
+    // В том порядке, в котором скважины отмечали в списке
+    Selection,
+}
+
+impl SheetOrder {
+    pub const ALL: [SheetOrder; 3] = [SheetOrder::Name, SheetOrder::OilDesc, SheetOrder::Selection];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SheetOrder::Name => "По имени",
+            SheetOrder::OilDesc => "По добыче нефти",
+            SheetOrder::Selection => "По порядку выбора",
+        }
+    }
+}
+
+// Группы приходят отсортированными по имени; сортировка устойчивая,
+// поэтому при равной добыче и для невыбранных вручную порядок остается по имени
+pub fn sort(groups: &mut [&[RecordRef]], order: SheetOrder, selection: &[String]) {
+    match order {
+        SheetOrder::Name => {}
+        SheetOrder::OilDesc => {
+            let oil: HashMap<&str, f64> = groups
+                .iter()
+                .map(|g| (g[0].well_name, g.iter().filter_map(|r| r.pd_oil).sum()))
+                .collect();
+            groups.sort_by(|a, b| oil[b[0].well_name].total_cmp(&oil[a[0].well_name]));
+        }
+        SheetOrder::Selection => {
+            let position: HashMap<&str, usize> = selection
+                .iter()
+                .enumerate()
+                .map(|(i, well)| (well.as_str(), i))
+                .collect();
+            groups.sort_by_key(|g| position.get(g[0].well_name).copied().unwrap_or(usize::MAX));
+        }
+    }
+}
+
+// Порядок выбора скважин. Набор выбранных меняется в разных местах окна,
+// поэтому порядок догоняет его раз в кадр: снятые убираются, новые
+// добавляются в конец (отмеченные разом - по имени)
+pub fn sync(order: &mut Vec<String>, selected: &HashSet<String>) {
+    order.retain(|well| selected.contains(well));
+    if order.len() == selected.len() {
+        return;
+    }
+    let known: HashSet<&String> = order.iter().collect();
+    let mut added: Vec<String> = selected
+        .iter()
+        .filter(|well| !known.contains(well))
+        .cloned()
+        .collect();
+    added.sort();
+    order.extend(added);
+}
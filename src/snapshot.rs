@@ -0,0 +1,187 @@
+use crate::ImportOptions;
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::progress::ProgressSink;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, channel};
+use std::time::Duration;
+
+const CHUNK: usize = 1 << 20;
+
+static NEXT_SNAPSHOT: AtomicUsize = AtomicUsize::new(0);
+
+// Когда копировать исходный файл на локальный диск перед разбором
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocalCopy {
+    Never,
+    // Пути вида \\сервер\папка. Подключенный диск (Z:) так не распознать - для него "Всегда"
+    #[default]
+    NetworkPaths,
+    Always,
+}
+
+impl LocalCopy {
+    pub const ALL: [LocalCopy; 3] = [LocalCopy::Never, LocalCopy::NetworkPaths, LocalCopy::Always];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LocalCopy::Never => "нет",
+            LocalCopy::NetworkPaths => "для сетевых путей",
+            LocalCopy::Always => "всегда",
+        }
+    }
+}
+
+pub fn is_network_path(path: &Path) -> bool {
+    let text = path.to_string_lossy();
+    match text.strip_prefix(r"\\?\") {
+        Some(rest) => rest.starts_with(r"UNC\"),
+        None => text.starts_with(r"\\") || text.starts_with("//"),
+    }
+}
+
+// Временная папка копии. Удаляется, когда ее отпустят и разбор, и поток
+// копирования: брошенный по таймауту поток еще может писать в нее
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Локальная копия во временной папке под тем же именем; удаляется вместе с объектом
+pub struct Snapshot {
+    _dir: Arc<TempDir>,
+    pub path: PathBuf,
+}
+
+enum CopyEvent {
+    Started(u64),
+    Copied(u64),
+    Done,
+    Failed(std::io::Error),
+}
+
+// Копия для разбора, если она нужна по настройкам. Сбой копирования
+// повторяется по политике повторов загрузки
+pub fn prepare(
+    path: &Path,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Option<Snapshot>, WellDataError> {
+    let needed = match import.local_copy {
+        LocalCopy::Never => false,
+        LocalCopy::NetworkPaths => is_network_path(path),
+        LocalCopy::Always => true,
+    };
+    if !needed {
+        return Ok(None);
+    }
+    let timeout = Duration::from_secs(import.network_timeout_s.max(1));
+    import
+        .retry
+        .run(
+            "копирование файла",
+            progress,
+            cancel,
+            || local_copy(path, timeout, progress, cancel),
+        )
+        .map(Some)
+}
+
+// Копирование идет в отдельном потоке: чтение с отвалившегося сетевого диска
+// может зависнуть надолго, и тогда поток бросается, а загрузка завершается ошибкой
+// по таймауту вместо того, чтобы висеть посреди листа
+fn local_copy(
+    path: &Path,
+    timeout: Duration,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Snapshot, WellDataError> {
+    let file_name = path.file_name().ok_or_else(|| {
+        WellDataError::Config(format!("в пути {} нет имени файла", path.display()))
+    })?;
+    let dir = std::env::temp_dir().join(format!(
+        "well-data-snapshot-{}-{}",
+        std::process::id(),
+        NEXT_SNAPSHOT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir)?;
+    let snapshot = Snapshot {
+        path: dir.join(file_name),
+        _dir: Arc::new(TempDir(dir)),
+    };
+
+    let (events_tx, events) = channel();
+    let source = path.to_path_buf();
+    let target = snapshot.path.clone();
+    let temp_dir = Arc::clone(&snapshot._dir);
+    let cancel_copy = cancel.clone();
+    std::thread::spawn(move || {
+        let copy = || -> std::io::Result<()> {
+            let mut input = std::fs::File::open(&source)?;
+            let _ = events_tx.send(CopyEvent::Started(input.metadata()?.len()));
+            let mut output = std::fs::File::create(&target)?;
+            let mut buffer = vec![0; CHUNK];
+            let mut copied = 0;
+            loop {
+                let n = input.read(&mut buffer)?;
+                if n == 0 {
+                    return output.flush();
+                }
+                output.write_all(&buffer[..n])?;
+                copied += n as u64;
+                // Загрузку отменили или бросили - дальше копировать незачем
+                if cancel_copy.is_cancelled() {
+                    return Err(ErrorKind::Interrupted.into());
+                }
+                if events_tx.send(CopyEvent::Copied(copied)).is_err() {
+                    return Ok(());
+                }
+            }
+        };
+        let _ = events_tx.send(match copy() {
+            Ok(()) => CopyEvent::Done,
+            Err(e) => CopyEvent::Failed(e),
+        });
+        // Файл копии уже закрыт; если разбор копию бросил, папка удаляется здесь
+        drop(temp_dir);
+    });
+
+    let mut total = 0;
+    loop {
+        cancel.check()?;
+        match events.recv_timeout(timeout) {
+            Ok(CopyEvent::Started(size)) => total = size,
+            Ok(CopyEvent::Copied(copied)) => {
+                progress.report_step(
+                    &format!(
+                        "Копирование на локальный диск: {:.1} из {:.1} МБ",
+                        copied as f64 / 1048576.0,
+                        total as f64 / 1048576.0
+                    ),
+                    copied as usize,
+                    total as usize,
+                );
+            }
+            Ok(CopyEvent::Done) => return Ok(snapshot),
+            Ok(CopyEvent::Failed(e)) => return Err(e.into()),
+            // Таймаут - сбой сети, его можно повторить
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!("{} не отвечает {} с", path.display(), timeout.as_secs()),
+                )
+                .into());
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(WellDataError::ThreadPanicked("копирования"));
+            }
+        }
+    }
+}
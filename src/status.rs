@@ -0,0 +1,99 @@
+use crate::query::{self, Value};
+use crate::store::Dataset;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Состояние скважины из колонки Status исходного файла
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum WellStatus {
+    Producing,
+    ShutIn,
+    Workover,
+}
+
+impl WellStatus {
+    pub const ALL: [WellStatus; 3] = [
+        WellStatus::Producing,
+        WellStatus::ShutIn,
+        WellStatus::Workover,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WellStatus::Producing => "В работе",
+            WellStatus::ShutIn => "Простой",
+            WellStatus::Workover => "Ремонт",
+        }
+    }
+
+    // Код для колонки status в таблицах и SQL-запросах
+    pub fn code(self) -> &'static str {
+        match self {
+            WellStatus::Producing => "producing",
+            WellStatus::ShutIn => "shut_in",
+            WellStatus::Workover => "workover",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.code() == code)
+    }
+
+    // Значения из выгрузок промысла: по-русски, сокращениями или по-английски.
+    // Нераспознанное состояние считается неизвестным. "Не работает" - простой:
+    // отрицание проверяется раньше, чем "работ"
+    pub fn parse(text: &str) -> Option<Self> {
+        let t = text.trim().to_lowercase();
+        if t.is_empty() {
+            return None;
+        }
+        if let Some(status) = Self::from_code(&t) {
+            return Some(status);
+        }
+        if t.contains("ремонт")
+            || t.starts_with("крс")
+            || t.starts_with("прс")
+            || t.contains("workover")
+        {
+            Some(WellStatus::Workover)
+        } else if t.contains("прост")
+            || t.contains("не работ")
+            || t.contains("не эксплуат")
+            || t.contains("останов")
+            || t.contains("бездейств")
+            || t.contains("shut")
+        {
+            Some(WellStatus::ShutIn)
+        } else if t.contains("работ") || t.contains("эксплуат") || t.contains("producing")
+        {
+            Some(WellStatus::Producing)
+        } else {
+            None
+        }
+    }
+}
+
+// Последнее известное состояние каждой скважины - для списка скважин
+pub fn last_statuses(data: &Dataset) -> HashMap<String, WellStatus> {
+    let sql = format!(
+        "SELECT r.well_name, r.status FROM {t} r
+         JOIN (SELECT well_name, max(date) AS last FROM {t}
+               WHERE status IS NOT NULL GROUP BY well_name) m
+           ON r.well_name = m.well_name AND r.date = m.last
+         WHERE r.status IS NOT NULL",
+        t = query::TABLE
+    );
+    let Ok(result) = query::run(data, &sql) else {
+        return HashMap::new();
+    };
+    result
+        .rows
+        .into_iter()
+        .filter_map(|row| match (&row[0], &row[1]) {
+            (Value::Text(well), Value::Text(code)) => {
+                Some((well.clone(), WellStatus::from_code(code)?))
+            }
+            _ => None,
+        })
+        .collect()
+}
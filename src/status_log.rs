@@ -0,0 +1,120 @@
+use crate::settings::DateFormat;
+use crate::tasks::TaskId;
+use chrono::NaiveDateTime;
+use eframe::egui;
+use std::collections::{HashMap, VecDeque};
+
+// Сколько последних сообщений хранить
+pub const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Info,
+    Progress,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: NaiveDateTime,
+    pub level: LogLevel,
+    pub text: String,
+}
+
+// История строки состояния и прогресса задач: строка под окном постоянно
+// перезаписывается, а здесь видно, что происходило, пока никто не смотрел
+#[derive(Default)]
+pub struct StatusLog {
+    entries: VecDeque<LogEntry>,
+    // Порядковый номер первой записи: записи нумеруются по мере добавления
+    first: u64,
+    // Запись прогресса каждой задачи, которая обновляется на месте
+    progress: HashMap<TaskId, u64>,
+}
+
+impl StatusLog {
+    pub fn push(&mut self, level: LogLevel, text: String) {
+        crate::crash::remember(&text);
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+            self.first += 1;
+        }
+        self.entries.push_back(LogEntry {
+            time: chrono::Local::now().naive_local(),
+            level,
+            text,
+        });
+    }
+
+    // Ошибки в строке состояния начинаются с "ОШИБКА"
+    pub fn status(&mut self, text: &str) {
+        let level = if text.starts_with("ОШИБКА") {
+            LogLevel::Error
+        } else {
+            LogLevel::Info
+        };
+        self.push(level, text.to_string());
+    }
+
+    // Прогресс задачи меняется много раз в секунду: у каждой задачи одна запись
+    // с последним текстом, даже если задачи идут одновременно
+    pub fn progress(&mut self, task: TaskId, title: &str, text: &str) {
+        let text = format!("{}: {}", title, text);
+        let first = self.first;
+        if let Some(entry) = self
+            .progress
+            .get(&task)
+            .and_then(|&n| n.checked_sub(first))
+            .and_then(|i| self.entries.get_mut(i as usize))
+        {
+            if entry.text != text {
+                entry.time = chrono::Local::now().naive_local();
+                entry.text = text;
+            }
+            return;
+        }
+        self.push(LogLevel::Progress, text);
+        let last = self.first + self.entries.len() as u64 - 1;
+        // Записи, вытесненные из истории, больше не обновляются
+        self.progress.retain(|_, n| *n >= self.first);
+        self.progress.insert(task, last);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.first += self.entries.len() as u64;
+        self.entries.clear();
+        self.progress.clear();
+    }
+
+    // Свернутая панель, новые сообщения сверху
+    pub fn show(&mut self, ui: &mut egui::Ui, dates: DateFormat) {
+        egui::CollapsingHeader::new(format!("🗒 История сообщений ({})", self.entries.len()))
+            .id_salt("status_log")
+            .show(ui, |ui| {
+                if ui.button("Очистить").clicked() {
+                    self.clear();
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        for entry in self.entries.iter().rev() {
+                            let color = match entry.level {
+                                LogLevel::Info => ui.visuals().text_color(),
+                                LogLevel::Progress => egui::Color32::GRAY,
+                                LogLevel::Error => ui.visuals().error_fg_color,
+                            };
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new(dates.datetime(entry.time)).monospace(),
+                                );
+                                ui.label(egui::RichText::new(&entry.text).color(color));
+                            });
+                        }
+                    });
+            });
+    }
+}
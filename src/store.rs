@@ -0,0 +1,295 @@
+use crate::StorageMode;
+use crate::disk::DiskStore;
+use crate::error::WellDataError;
+use crate::frame;
+use crate::las::WellLog;
+use crate::status::WellStatus;
+use crate::well_type::WellType;
+use chrono::NaiveDateTime;
+use polars::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+// Одна строка данных: при загрузке кладется в хранилище,
+// при чтении собирается из колонок без выделения памяти
+#[derive(Debug, Clone, Copy)]
+pub struct RecordRef<'a> {
+    pub well_name: &'a str,
+    pub date: Option<NaiveDateTime>,
+    pub pd_liq: Option<f64>,
+    pub pd_oil: Option<f64>,
+    pub temperature: Option<f64>,
+    // Забойное и буферное давление
+    pub p_bottom: Option<f64>,
+    pub p_head: Option<f64>,
+    // Частота ЭЦН и диаметр штуцера
+    pub frequency: Option<f64>,
+    pub choke: Option<f64>,
+    // Приемистость нагнетательной скважины
+    pub injection: Option<f64>,
+    pub status: Option<WellStatus>,
+    pub well_type: Option<WellType>,
+    pub year_sheet: i32,
+    // Квартал листа "2021Q3"; None - лист за год
+    pub quarter: Option<u8>,
+    // Значение вышло за границы из rules.toml
+    pub out_of_bounds: bool,
+}
+
+// Колоночное хранилище записей: каждая колонка - отдельный вектор,
+// имя скважины хранится один раз, а в строках - только его номер
+#[derive(Debug, Default)]
+pub struct RecordStore {
+    wells: Vec<String>,
+    well_ids: HashMap<String, u32>,
+
+    well: Vec<u32>,
+    date: Vec<Option<NaiveDateTime>>,
+    pd_liq: Vec<Option<f64>>,
+    pd_oil: Vec<Option<f64>>,
+    temperature: Vec<Option<f64>>,
+    p_bottom: Vec<Option<f64>>,
+    p_head: Vec<Option<f64>>,
+    frequency: Vec<Option<f64>>,
+    choke: Vec<Option<f64>>,
+    injection: Vec<Option<f64>>,
+    status: Vec<Option<WellStatus>>,
+    well_type: Vec<Option<WellType>>,
+    year_sheet: Vec<i32>,
+    quarter: Vec<Option<u8>>,
+    out_of_bounds: Vec<bool>,
+}
+
+impl RecordStore {
+    // Сколько байт занимает одна строка во всех колонках
+    pub const BYTES_PER_ROW: usize = size_of::<u32>()
+        + size_of::<Option<NaiveDateTime>>()
+        + 8 * size_of::<Option<f64>>()
+        + size_of::<Option<WellStatus>>()
+        + size_of::<Option<WellType>>()
+        + size_of::<i32>()
+        + size_of::<Option<u8>>()
+        + size_of::<bool>();
+
+    pub fn len(&self) -> usize {
+        self.well.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.well.is_empty()
+    }
+
+    pub fn push(&mut self, r: RecordRef) {
+        let id = match self.well_ids.get(r.well_name) {
+            Some(&id) => id,
+            None => {
+                let id = self.wells.len() as u32;
+                self.wells.push(r.well_name.to_string());
+                self.well_ids.insert(r.well_name.to_string(), id);
+                id
+            }
+        };
+        self.well.push(id);
+        self.date.push(r.date);
+        self.pd_liq.push(r.pd_liq);
+        self.pd_oil.push(r.pd_oil);
+        self.temperature.push(r.temperature);
+        self.p_bottom.push(r.p_bottom);
+        self.p_head.push(r.p_head);
+        self.frequency.push(r.frequency);
+        self.choke.push(r.choke);
+        self.injection.push(r.injection);
+        self.status.push(r.status);
+        self.well_type.push(r.well_type);
+        self.year_sheet.push(r.year_sheet);
+        self.quarter.push(r.quarter);
+        self.out_of_bounds.push(r.out_of_bounds);
+    }
+
+    pub fn get(&self, i: usize) -> RecordRef<'_> {
+        RecordRef {
+            well_name: &self.wells[self.well[i] as usize],
+            date: self.date[i],
+            pd_liq: self.pd_liq[i],
+            pd_oil: self.pd_oil[i],
+            temperature: self.temperature[i],
+            p_bottom: self.p_bottom[i],
+            p_head: self.p_head[i],
+            frequency: self.frequency[i],
+            choke: self.choke[i],
+            injection: self.injection[i],
+            status: self.status[i],
+            well_type: self.well_type[i],
+            year_sheet: self.year_sheet[i],
+            quarter: self.quarter[i],
+            out_of_bounds: self.out_of_bounds[i],
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = RecordRef<'_>> {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    // Таблица для движка фильтрации и агрегации (см. frame.rs).
+    // Колонка row - номер строки в хранилище, по нему данные читаются обратно
+    pub fn to_frame(&self) -> PolarsResult<DataFrame> {
+        let well_name: Vec<&str> = self
+            .well
+            .iter()
+            .map(|&w| self.wells[w as usize].as_str())
+            .collect();
+        let date: Vec<Option<i64>> = self
+            .date
+            .iter()
+            .map(|d| d.map(|d| d.and_utc().timestamp_millis()))
+            .collect();
+
+        let well_type: Vec<Option<&str>> = self
+            .well_type
+            .iter()
+            .map(|t| t.map(WellType::code))
+            .collect();
+        let status: Vec<Option<&str>> = self
+            .status
+            .iter()
+            .map(|s| s.map(WellStatus::code))
+            .collect();
+        let quarter: Vec<Option<i32>> = self.quarter.iter().map(|q| q.map(i32::from)).collect();
+
+        DataFrame::new(vec![
+            Column::new(frame::WELL.into(), well_name),
+            Column::new(frame::DATE.into(), date)
+                .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?,
+            Column::new(frame::PD_LIQ.into(), &self.pd_liq),
+            Column::new(frame::PD_OIL.into(), &self.pd_oil),
+            Column::new(frame::TEMPERATURE.into(), &self.temperature),
+            Column::new(frame::P_BOTTOM.into(), &self.p_bottom),
+            Column::new(frame::P_HEAD.into(), &self.p_head),
+            Column::new(frame::FREQUENCY.into(), &self.frequency),
+            Column::new(frame::CHOKE.into(), &self.choke),
+            Column::new(frame::INJECTION.into(), &self.injection),
+            Column::new(frame::STATUS.into(), status),
+            Column::new(frame::WELL_TYPE.into(), well_type),
+            Column::new(frame::YEAR.into(), &self.year_sheet),
+            Column::new(frame::QUARTER.into(), quarter),
+            Column::new(frame::OUT_OF_BOUNDS.into(), &self.out_of_bounds),
+        ])?
+        .with_row_index(frame::ROW.into(), None)
+    }
+
+    // Копия только указанных строк
+    pub fn subset(&self, rows: &[usize]) -> RecordStore {
+        let mut store = RecordStore::default();
+        for &i in rows {
+            store.push(self.get(i));
+        }
+        store
+    }
+}
+
+// Записи: в памяти или во временной базе на диске
+pub enum Records {
+    Memory(Box<RecordStore>),
+    Disk(DiskStore),
+}
+
+// Загруженные данные: записи замеров и кривые каротажа (LAS)
+#[derive(Default)]
+pub struct Dataset {
+    pub records: Records,
+    pub logs: Vec<WellLog>,
+}
+
+impl Default for Records {
+    fn default() -> Self {
+        Records::Memory(Box::default())
+    }
+}
+
+impl Dataset {
+    pub fn new(mode: StorageMode) -> Result<Self, WellDataError> {
+        let records = match mode {
+            StorageMode::Memory => Records::Memory(Box::default()),
+            StorageMode::Disk => Records::Disk(DiskStore::create()?),
+        };
+        Ok(Self {
+            records,
+            logs: Vec::new(),
+        })
+    }
+
+    pub fn in_memory(store: RecordStore) -> Self {
+        Self {
+            records: Records::Memory(Box::new(store)),
+            logs: Vec::new(),
+        }
+    }
+
+    // Количество записей замеров (без каротажа)
+    pub fn len(&self) -> usize {
+        match &self.records {
+            Records::Memory(store) => store.len(),
+            Records::Disk(store) => store.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0 && self.logs.is_empty()
+    }
+
+    pub fn push(&mut self, r: RecordRef) -> Result<(), WellDataError> {
+        match &mut self.records {
+            Records::Memory(store) => store.push(r),
+            Records::Disk(store) => store.push(r)?,
+        }
+        Ok(())
+    }
+
+    // Все записи по порядку, из памяти или с диска
+    pub fn for_each(&self, mut f: impl FnMut(RecordRef)) -> Result<(), WellDataError> {
+        match &self.records {
+            Records::Memory(store) => store.iter().for_each(f),
+            Records::Disk(store) => store.for_each(&mut f)?,
+        }
+        Ok(())
+    }
+
+    // Записи и каротаж другого набора в конец этого (несколько книг как одна).
+    // Берутся только записи, для которых keep вернул true
+    pub fn append(
+        &mut self,
+        other: Dataset,
+        mut keep: impl FnMut(&RecordRef) -> bool,
+    ) -> Result<(), WellDataError> {
+        let mut result = Ok(());
+        other.for_each(|r| {
+            if result.is_ok() && keep(&r) {
+                result = self.push(r);
+            }
+        })?;
+        result?;
+        self.logs.extend(other.logs);
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), WellDataError> {
+        if let Records::Disk(store) = &mut self.records {
+            store.finish()?;
+        }
+        Ok(())
+    }
+
+    // Выборка для выгрузки (см. frame::select), уже отфильтрованная и отсортированная
+    pub fn select(
+        &self,
+        start_year: i32,
+        wells: &HashSet<String>,
+    ) -> Result<RecordStore, WellDataError> {
+        Ok(match &self.records {
+            Records::Memory(store) => {
+                let selected = frame::select(store.to_frame()?, start_year, wells)?;
+                store.subset(&frame::row_indices(&selected)?)
+            }
+            Records::Disk(store) => store.select(start_year, wells)?,
+        })
+    }
+}
@@ -0,0 +1,91 @@
+use crate::Parameter;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+
+// Строка сводки: (год, месяц) и значения по каждой скважине
+pub type MonthRow = ((i32, u32), Vec<Option<f64>>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Aggregate {
+    #[default]
+    Average,
+    Sum,
+    Min,
+    Max,
+    Count,
+    // Среднекалендарный дебит: добыча за месяц с учетом шага замеров / дни месяца
+    CalendarDay,
+}
+
+impl Aggregate {
+    pub const ALL: [Aggregate; 6] = [
+        Aggregate::Average,
+        Aggregate::Sum,
+        Aggregate::Min,
+        Aggregate::Max,
+        Aggregate::Count,
+        Aggregate::CalendarDay,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Aggregate::Average => "Среднее",
+            Aggregate::Sum => "Сумма",
+            Aggregate::Min => "Минимум",
+            Aggregate::Max => "Максимум",
+            Aggregate::Count => "Количество",
+            Aggregate::CalendarDay => "Календарный дебит",
+        }
+    }
+}
+
+// Агрегаты Polars считаются в несколько потоков, и порядок сложения может
+// менять последние биты результата. Для воспроизводимого файла значения
+// округляются до 12 значащих цифр
+pub fn round_stable(months: &mut [MonthRow]) {
+    for (_, values) in months.iter_mut() {
+        for v in values.iter_mut().flatten() {
+            *v = format!("{:.11e}", v).parse().unwrap_or(*v);
+        }
+    }
+}
+
+// Лист сводки: строки - месяцы, колонки - скважины
+pub fn write_monthly_sheet(
+    workbook: &mut Workbook,
+    // Уже посчитанные значения из frame::monthly
+    months: &[MonthRow],
+    wells: &[&str],
+    parameter: Parameter,
+    aggregate: Aggregate,
+) -> Result<(), XlsxError> {
+    let worksheet = workbook.add_worksheet().set_name("Monthly")?;
+    let bold = Format::new().set_bold();
+
+    worksheet.write_string_with_format(
+        0,
+        0,
+        format!(
+            "{}, {}",
+            parameter.label(),
+            aggregate.label().to_lowercase()
+        ),
+        &bold,
+    )?;
+    for (i, well) in wells.iter().enumerate() {
+        worksheet.write_string_with_format(0, i as u16 + 1, *well, &bold)?;
+    }
+
+    for (row, ((year, month), per_well)) in months.iter().enumerate() {
+        let row = row as u32 + 1;
+        worksheet.write_string(row, 0, format!("{}-{:02}", year, month))?;
+        for (col, value) in per_well.iter().enumerate() {
+            if let Some(v) = value {
+                worksheet.write_number(row, col as u16 + 1, *v)?;
+            }
+        }
+    }
+
+    worksheet.set_freeze_panes(1, 1)?;
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use crate::LoaderMessage;
+use crate::cancel::CancellationToken;
+use crate::error::{ErrorReport, WellDataError};
+use std::future::Future;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+use tokio::runtime::Runtime;
+
+pub type TaskResult = Result<LoaderMessage, WellDataError>;
+
+pub type TaskId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    Load,
+    Export,
+    Query,
+    // Сводка, тепловая карта, корреляция и мини-графики; в списке задач не видны
+    Chart,
+}
+
+// Фоновая задача со своим каналом, прогрессом и флагом отмены
+pub struct Task {
+    pub id: TaskId,
+    pub kind: TaskKind,
+    pub title: String,
+    pub status: String,
+    pub progress_global: f32,
+    pub progress_local: f32,
+    cancel: CancellationToken,
+    rx: Receiver<LoaderMessage>,
+}
+
+impl Task {
+    pub fn is_cancelling(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+
+// Задачи выполняются в среде tokio: сетевые источники работают как async,
+// а чтение и запись файлов уходят в пул блокирующих потоков.
+// С интерфейсом задачи общаются через обычный канал, который UI опрашивает каждый кадр
+pub struct TaskManager {
+    runtime: Runtime,
+    tasks: Vec<Task>,
+    next_id: TaskId,
+}
+
+impl Default for TaskManager {
+    fn default() -> Self {
+        Self {
+            runtime: tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("не удалось запустить среду выполнения задач"),
+            tasks: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl TaskManager {
+    // Блокирующая задача (чтение/запись файлов)
+    pub fn spawn<F>(&mut self, kind: TaskKind, title: String, task: F) -> TaskId
+    where
+        F: FnOnce(Sender<LoaderMessage>, CancellationToken) -> TaskResult + Send + 'static,
+    {
+        self.spawn_async(kind, title, move |tx, cancel| async move {
+            tokio::task::spawn_blocking(move || task(tx, cancel)).await?
+        })
+    }
+
+    // Асинхронная задача. При отмене задача не бросается, а доводится до точки
+    // отмены: запущенная из нее блокирующая работа иначе продолжила бы писать
+    // файл уже после сообщения об отмене
+    pub fn spawn_async<F, Fut>(&mut self, kind: TaskKind, title: String, task: F) -> TaskId
+    where
+        F: FnOnce(Sender<LoaderMessage>, CancellationToken) -> Fut,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        self.next_id += 1;
+        let id = self.next_id;
+        let cancel = CancellationToken::new();
+        let (tx, rx) = channel();
+
+        let future = task(tx.clone(), cancel.clone());
+        self.runtime.spawn(async move {
+            // Сбой после нажатия "Отмена" (недописанный файл, занятый файл,
+            // сеть) остается ошибкой: отменой считается только сама отмена
+            let msg = match future.await {
+                Ok(msg) => msg,
+                Err(e) if e.is_cancelled() => LoaderMessage::Cancelled,
+                Err(WellDataError::Locked(file)) => LoaderMessage::Locked(file),
+                Err(e) => LoaderMessage::Error(ErrorReport::new(&e)),
+            };
+            let _ = tx.send(msg);
+        });
+
+        self.tasks.push(Task {
+            id,
+            kind,
+            title,
+            status: "Запуск...".to_string(),
+            progress_global: 0.0,
+            progress_local: 0.0,
+            cancel,
+            rx,
+        });
+        id
+    }
+
+    // Забирает итоговые и диагностические сообщения всех задач;
+    // завершенные задачи удаляются из списка
+    pub fn poll(&mut self) -> Vec<(TaskId, TaskKind, LoaderMessage)> {
+        let mut finished = Vec::new();
+
+        self.tasks.retain_mut(|task| {
+            loop {
+                match task.rx.try_recv() {
+                    Ok(LoaderMessage::Progress(global, local, text)) => {
+                        task.progress_global = global;
+                        task.progress_local = local;
+                        task.status = text;
+                    }
+                    Ok(LoaderMessage::StepProgress(local, text)) => {
+                        task.progress_local = local;
+                        task.status = text;
+                    }
+                    Ok(msg @ LoaderMessage::Profile(_)) => finished.push((task.id, task.kind, msg)),
+                    Ok(msg) => {
+                        finished.push((task.id, task.kind, msg));
+                        return false;
+                    }
+                    Err(TryRecvError::Empty) => return true,
+                    Err(TryRecvError::Disconnected) => {
+                        finished.push((
+                            task.id,
+                            task.kind,
+                            LoaderMessage::Error(ErrorReport::message(format!(
+                                "задача '{}' аварийно завершилась",
+                                task.title
+                            ))),
+                        ));
+                        return false;
+                    }
+                }
+            }
+        });
+
+        finished
+    }
+
+    pub fn cancel(&self, id: TaskId) {
+        if let Some(task) = self.tasks.iter().find(|t| t.id == id) {
+            task.cancel.cancel();
+        }
+    }
+
+    pub fn is_running(&self, kind: TaskKind) -> bool {
+        self.tasks.iter().any(|t| t.kind == kind)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.iter()
+    }
+}
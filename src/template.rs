@@ -0,0 +1,506 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::locked::{self, AfterSave, LockedFile};
+use crate::progress::ProgressSink;
+use crate::store::Dataset;
+use crate::{ExportOptions, LoaderMessage, frame, manifest, summary};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use rust_xlsxwriter::ExcelDateTime;
+use rust_xlsxwriter::utility::{column_name_to_number, row_col_to_cell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+// Метки в ячейках шаблона, на место которых пишутся данные.
+// Оформление строки с меткой применяется ко всем строкам блока
+pub const RECORDS_MARKER: &str = "{{records}}";
+pub const MONTHLY_MARKER: &str = "{{monthly}}";
+
+enum Value {
+    Number(f64),
+    Text(String),
+    Blank,
+}
+
+// Ячейка листа как есть в XML шаблона
+struct Cell {
+    xml: String,
+    style: Option<String>,
+}
+
+#[derive(Default)]
+struct Row {
+    // Атрибуты строки кроме r и spans (высота, стиль, скрытие)
+    attrs: String,
+    cells: BTreeMap<u32, Cell>,
+}
+
+// Содержимое <sheetData>, разобранное по строкам; остальной XML листа не трогается
+struct Sheet {
+    head: String,
+    rows: BTreeMap<u32, Row>,
+    tail: String,
+}
+
+fn attr(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name)
+        .map(|a| String::from_utf8_lossy(&a.value).to_string())
+}
+
+// "B12" -> (11, 1), как в rust_xlsxwriter: строки и колонки с нуля
+fn parse_ref(cell: &str) -> Option<(u32, u16)> {
+    let split = cell.find(|c: char| c.is_ascii_digit())?;
+    let (col, row) = cell.split_at(split);
+    let row: u32 = row.parse().ok()?;
+    Some((row.checked_sub(1)?, column_name_to_number(col)))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn broken_sheet() -> WellDataError {
+    WellDataError::BadContent("битый XML листа шаблона".to_string())
+}
+
+fn parse_sheet(xml: &str) -> Result<Sheet, WellDataError> {
+    let Some(start) = xml.find("<sheetData") else {
+        return Err(WellDataError::BadContent(
+            "в листе шаблона нет sheetData".to_string(),
+        ));
+    };
+    // Пустой лист: <sheetData/>
+    let open_end = start + xml[start..].find('>').ok_or_else(broken_sheet)? + 1;
+    if xml[..open_end].ends_with("/>") {
+        return Ok(Sheet {
+            head: format!("{}<sheetData>", &xml[..start]),
+            rows: BTreeMap::new(),
+            tail: format!("</sheetData>{}", &xml[open_end..]),
+        });
+    }
+    let close = xml.find("</sheetData>").ok_or_else(broken_sheet)?;
+    let inner = &xml[open_end..close];
+
+    let mut rows: BTreeMap<u32, Row> = BTreeMap::new();
+    let mut reader = Reader::from_str(inner);
+    let mut current_row = 0u32;
+    let mut next_col = 0u16;
+    loop {
+        let before = reader.buffer_position() as usize;
+        let (e, has_body) = match reader.read_event()? {
+            Event::Start(e) => (e, true),
+            Event::Empty(e) => (e, false),
+            Event::Eof => break,
+            _ => continue,
+        };
+        match e.local_name().as_ref() {
+            b"row" => {
+                current_row = attr(&e, b"r")
+                    .and_then(|r| r.parse::<u32>().ok())
+                    .map_or(current_row + 1, |r| r - 1);
+                next_col = 0;
+                let attrs = e
+                    .attributes()
+                    .flatten()
+                    .filter(|a| !matches!(a.key.as_ref(), b"r" | b"spans"))
+                    .map(|a| {
+                        format!(
+                            " {}=\"{}\"",
+                            String::from_utf8_lossy(a.key.as_ref()),
+                            String::from_utf8_lossy(&a.value)
+                        )
+                    })
+                    .collect();
+                rows.entry(current_row).or_default().attrs = attrs;
+            }
+            b"c" => {
+                let col = attr(&e, b"r")
+                    .and_then(|r| parse_ref(&r))
+                    .map_or(next_col, |(_, c)| c);
+                let style = attr(&e, b"s");
+                if has_body {
+                    reader.read_to_end(e.name())?;
+                }
+                let xml = inner[before..reader.buffer_position() as usize].to_string();
+                next_col = col + 1;
+                rows.entry(current_row)
+                    .or_default()
+                    .cells
+                    .insert(col as u32, Cell { xml, style });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Sheet {
+        head: xml[..open_end].to_string(),
+        rows,
+        tail: xml[close..].to_string(),
+    })
+}
+
+// Текст ячейки: общая строка, встроенная строка или результат формулы-строки
+fn cell_text(cell: &Cell, shared: &[String]) -> Option<String> {
+    let mut reader = Reader::from_str(&cell.xml);
+    let mut kind = None;
+    let mut text = String::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) if e.local_name().as_ref() == b"c" => kind = attr(&e, b"t"),
+            Event::Start(e) => {
+                in_text = matches!(e.local_name().as_ref(), b"v" | b"t");
+            }
+            Event::Text(t) if in_text => text.push_str(&t.decode().ok()?),
+            Event::End(_) => in_text = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    match kind.as_deref() {
+        Some("s") => shared.get(text.trim().parse::<usize>().ok()?).cloned(),
+        Some("inlineStr") | Some("str") => Some(text),
+        _ => None,
+    }
+}
+
+impl Sheet {
+    fn to_xml(&self) -> String {
+        let mut xml = self.head.clone();
+        for (r, row) in &self.rows {
+            xml.push_str(&format!("<row r=\"{}\"{}>", r + 1, row.attrs));
+            for cell in row.cells.values() {
+                xml.push_str(&cell.xml);
+            }
+            xml.push_str("</row>");
+        }
+        xml.push_str(&self.tail);
+        xml
+    }
+
+    fn find_markers(&self, shared: &[String]) -> Vec<(String, u32, u32)> {
+        let mut found = Vec::new();
+        for (r, row) in &self.rows {
+            for (c, cell) in &row.cells {
+                if let Some(text) = cell_text(cell, shared) {
+                    let text = text.trim();
+                    if text == RECORDS_MARKER || text == MONTHLY_MARKER {
+                        found.push((text.to_string(), *r, *c));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    // Блок значений с левым верхним углом в (row, col). Стиль каждой колонки берется
+    // из строки метки, остальные ячейки строк шаблона сохраняются
+    fn put_block(&mut self, row: u32, col: u32, values: &[Vec<Value>]) {
+        let styles: HashMap<u32, String> = self
+            .rows
+            .get(&row)
+            .map(|r| {
+                r.cells
+                    .iter()
+                    .filter_map(|(c, cell)| cell.style.clone().map(|s| (*c, s)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let row_attrs = self
+            .rows
+            .get(&row)
+            .map(|r| r.attrs.clone())
+            .unwrap_or_default();
+
+        for (i, line) in values.iter().enumerate() {
+            let r = row + i as u32;
+            let target = self.rows.entry(r).or_insert_with(|| Row {
+                attrs: row_attrs.clone(),
+                cells: BTreeMap::new(),
+            });
+            for (j, value) in line.iter().enumerate() {
+                let c = col + j as u32;
+                let style = styles.get(&c);
+                let reference = row_col_to_cell(r, c as u16);
+                let s = style.map(|s| format!(" s=\"{}\"", s)).unwrap_or_default();
+                let xml = match value {
+                    Value::Number(v) => format!("<c r=\"{}\"{}><v>{}</v></c>", reference, s, v),
+                    Value::Text(t) => format!(
+                        "<c r=\"{}\"{} t=\"inlineStr\"><is><t>{}</t></is></c>",
+                        reference,
+                        s,
+                        escape(t)
+                    ),
+                    Value::Blank => format!("<c r=\"{}\"{}/>", reference, s),
+                };
+                target.cells.insert(
+                    c,
+                    Cell {
+                        xml,
+                        style: style.cloned(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+// Копия шаблона с замененными частями; цепочка вычислений выбрасывается
+fn write_archive<R: Read + Seek, W: Write + Seek>(
+    out: W,
+    archive: &mut ZipArchive<R>,
+    changed: &HashMap<String, String>,
+) -> Result<W, WellDataError> {
+    let mut writer = ZipWriter::new(out);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let name = file.name().to_string();
+        if name == "xl/calcChain.xml" {
+            continue;
+        }
+        match changed.get(&name) {
+            Some(xml) => {
+                drop(file);
+                writer.start_file(name, deflated)?;
+                writer.write_all(xml.as_bytes())?;
+            }
+            None => writer.raw_copy_file(file)?,
+        }
+    }
+    Ok(writer.finish()?)
+}
+
+fn read_entry<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Option<String>, WellDataError> {
+    let Ok(mut file) = archive.by_name(name) else {
+        return Ok(None);
+    };
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    Ok(Some(text))
+}
+
+fn shared_strings(xml: &str) -> Result<Vec<String>, WellDataError> {
+    let mut reader = Reader::from_str(xml);
+    let mut strings = Vec::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"si" => strings.push(String::new()),
+            Event::Start(e) => in_text = e.local_name().as_ref() == b"t",
+            Event::Text(t) if in_text => {
+                if let Some(s) = strings.last_mut() {
+                    s.push_str(&t.decode()?);
+                }
+            }
+            Event::End(_) => in_text = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(strings)
+}
+
+// Формулы шаблона пересчитываются при открытии, иначе Excel покажет старые значения
+fn force_recalc(workbook_xml: &str) -> String {
+    if let Some(pos) = workbook_xml.find("<calcPr") {
+        if workbook_xml[pos..].starts_with("<calcPr ") && !workbook_xml.contains("fullCalcOnLoad") {
+            let at = pos + "<calcPr".len();
+            return format!(
+                "{} fullCalcOnLoad=\"1\"{}",
+                &workbook_xml[..at],
+                &workbook_xml[at..]
+            );
+        }
+        return workbook_xml.to_string();
+    }
+    let anchor = ["</definedNames>", "</sheets>"]
+        .iter()
+        .find_map(|a| workbook_xml.find(a).map(|p| p + a.len()));
+    match anchor {
+        Some(at) => format!(
+            "{}<calcPr fullCalcOnLoad=\"1\"/>{}",
+            &workbook_xml[..at],
+            &workbook_xml[at..]
+        ),
+        None => workbook_xml.to_string(),
+    }
+}
+
+// Убирает пустой элемент (<Override .../> или <Relationship .../>) со ссылкой на calcChain.xml
+fn remove_calc_chain(xml: &str) -> String {
+    let Some(pos) = xml.find("calcChain") else {
+        return xml.to_string();
+    };
+    let (Some(start), Some(end)) = (xml[..pos].rfind('<'), xml[pos..].find("/>")) else {
+        return xml.to_string();
+    };
+    format!("{}{}", &xml[..start], &xml[pos + end + 2..])
+}
+
+fn excel_date(date: chrono::NaiveDateTime) -> Option<f64> {
+    ExcelDateTime::from_timestamp(date.and_utc().timestamp())
+        .ok()
+        .map(|d| d.to_excel())
+}
+
+fn number(v: Option<f64>) -> Value {
+    v.map_or(Value::Blank, Value::Number)
+}
+
+// Заполнение готовой книги: копия шаблона, где вместо меток {{records}} и {{monthly}}
+// стоят данные. Остальные листы, стили, формулы и картинки переносятся без изменений.
+// Цепочка вычислений calcChain.xml удаляется - Excel построит ее заново
+pub fn fill_template(
+    path: &Path,
+    data: &Dataset,
+    start_year: i32,
+    selected_wells: &HashSet<String>,
+    options: &ExportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoaderMessage, WellDataError> {
+    progress.report("Подготовка данных...", 0, 0);
+    let selected = options.drop_excluded(data.select(start_year, selected_wells)?);
+    cancel.check()?;
+
+    let template = options
+        .template
+        .as_ref()
+        .ok_or_else(|| WellDataError::Config("не выбран шаблон книги".to_string()))?;
+    let mut archive = ZipArchive::new(std::fs::File::open(template)?)?;
+    let shared = match read_entry(&mut archive, "xl/sharedStrings.xml")? {
+        Some(xml) => shared_strings(&xml)?,
+        None => Vec::new(),
+    };
+
+    let mut changed: HashMap<String, String> = HashMap::new();
+    let sheet_names: Vec<String> = archive
+        .file_names()
+        .filter(|n| n.starts_with("xl/worksheets/") && n.ends_with(".xml"))
+        .map(str::to_string)
+        .collect();
+    for name in &sheet_names {
+        cancel.check()?;
+        let Some(xml) = read_entry(&mut archive, name)? else {
+            continue;
+        };
+        let mut sheet = parse_sheet(&xml)?;
+        let markers = sheet.find_markers(&shared);
+        if markers.is_empty() {
+            continue;
+        }
+
+        for (marker, row, col) in markers {
+            progress.report(&format!("Заполнение {} ({})", marker, name), 1, 2);
+            let values: Vec<Vec<Value>> = if marker == RECORDS_MARKER {
+                selected
+                    .iter()
+                    .map(|r| {
+                        vec![
+                            Value::Text(r.well_name.to_string()),
+                            r.date
+                                .and_then(excel_date)
+                                .map_or(Value::Blank, Value::Number),
+                            number(r.pd_liq),
+                            number(r.pd_oil),
+                            number(r.temperature),
+                            number(r.p_bottom),
+                            number(r.p_head),
+                            number(r.frequency),
+                            number(r.choke),
+                            number(r.injection),
+                        ]
+                    })
+                    .collect()
+            } else {
+                let wells: Vec<&str> = selected
+                    .iter()
+                    .map(|r| r.well_name)
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+                let mut months = frame::monthly(
+                    &selected.to_frame()?,
+                    &wells,
+                    options.monthly_parameter,
+                    options.monthly_aggregate,
+                )?;
+                if options.deterministic {
+                    summary::round_stable(&mut months);
+                }
+                let header = std::iter::once(Value::Text("Месяц".to_string()))
+                    .chain(wells.iter().map(|w| Value::Text(w.to_string())))
+                    .collect();
+                std::iter::once(header)
+                    .chain(months.iter().map(|((year, month), values)| {
+                        std::iter::once(Value::Text(format!("{}-{:02}", year, month)))
+                            .chain(values.iter().map(|v| number(*v)))
+                            .collect()
+                    }))
+                    .collect()
+            };
+            if values.is_empty() {
+                sheet.put_block(row, col, &[vec![Value::Blank]]);
+            } else {
+                sheet.put_block(row, col, &values);
+            }
+        }
+        changed.insert(name.clone(), sheet.to_xml());
+    }
+    if changed.is_empty() {
+        return Err(WellDataError::BadContent(format!(
+            "в шаблоне нет ячеек с метками {} или {}",
+            RECORDS_MARKER, MONTHLY_MARKER
+        )));
+    }
+
+    if let Some(xml) = read_entry(&mut archive, "xl/workbook.xml")? {
+        changed.insert("xl/workbook.xml".to_string(), force_recalc(&xml));
+    }
+    for name in ["[Content_Types].xml", "xl/_rels/workbook.xml.rels"] {
+        if let Some(xml) = read_entry(&mut archive, name)? {
+            changed.insert(name.to_string(), remove_calc_chain(&xml));
+        }
+    }
+
+    cancel.check()?;
+    progress.report("Сохранение файла на диск...", 1, 1);
+    let wells = manifest::well_counts(selected.iter());
+    let write_manifest = options.manifest.then(|| {
+        let options = options.clone();
+        move |path: &Path| {
+            manifest::write_manifest(path, &wells, start_year, &options)?;
+            Ok(())
+        }
+    });
+    if locked::is_locked(path) {
+        let bytes = write_archive(Cursor::new(Vec::new()), &mut archive, &changed)?;
+        return Err(WellDataError::Locked(LockedFile {
+            path: path.to_path_buf(),
+            bytes: bytes.into_inner(),
+            after_save: write_manifest.map(|f| Box::new(f) as AfterSave),
+        }));
+    }
+    // Неизмененные части копируются из открытого шаблона, поэтому книга пишется
+    // рядом в .part: иначе отчет поверх самого шаблона затер бы его на середине
+    let part = path.with_extension("part");
+    write_archive(std::fs::File::create(&part)?, &mut archive, &changed)?;
+    drop(archive);
+    std::fs::rename(&part, path)?;
+    if let Some(write_manifest) = write_manifest {
+        write_manifest(path)?;
+    }
+    Ok(LoaderMessage::Saved(path.to_string_lossy().to_string()))
+}
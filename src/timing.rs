@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+// Время по фазам последней операции (для окна диагностики)
+#[derive(Debug, Clone)]
+pub struct Timings {
+    pub operation: String,
+    pub phases: Vec<(String, Duration)>,
+    pub total: Duration,
+    started: Instant,
+}
+
+impl Timings {
+    pub fn new(operation: impl Into<String>) -> Self {
+        Self {
+            operation: operation.into(),
+            phases: Vec::new(),
+            total: Duration::ZERO,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn measure<T>(&mut self, phase: impl Into<String>, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.phases.push((phase.into(), start.elapsed()));
+        result
+    }
+
+    pub fn record(&mut self, phase: impl Into<String>, since: Instant) {
+        self.phases.push((phase.into(), since.elapsed()));
+    }
+
+    // Фаза, измеренная в другом потоке
+    pub fn push(&mut self, phase: impl Into<String>, duration: Duration) {
+        self.phases.push((phase.into(), duration));
+    }
+
+    pub fn finish(mut self) -> Self {
+        self.total = self.started.elapsed();
+        self
+    }
+}
@@ -0,0 +1,118 @@
+use crate::store::RecordRef;
+use crate::{TEMPERATURE_COL, ValueColumn, layout};
+use rust_xlsxwriter::utility::cell_range;
+use rust_xlsxwriter::{Format, Formula, Worksheet, XlsxError};
+use serde::{Deserialize, Serialize};
+
+// Итоговая строка под записями листа скважины
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TotalsRow {
+    #[default]
+    Off,
+    // Посчитанные числа
+    Values,
+    // Формулы Excel: пересчитываются, если получатель поправит данные
+    Formulas,
+}
+
+impl TotalsRow {
+    pub const ALL: [TotalsRow; 3] = [TotalsRow::Off, TotalsRow::Values, TotalsRow::Formulas];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TotalsRow::Off => "Нет",
+            TotalsRow::Values => "Значениями",
+            TotalsRow::Formulas => "Формулами Excel",
+        }
+    }
+}
+
+// Что писать в итог по дебитам; температура всегда средняя
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RateTotal {
+    #[default]
+    Average,
+    Sum,
+}
+
+impl RateTotal {
+    pub const ALL: [RateTotal; 2] = [RateTotal::Average, RateTotal::Sum];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RateTotal::Average => "Среднее",
+            RateTotal::Sum => "Сумма",
+        }
+    }
+}
+
+// Итог по колонке значений: дебиты и температура
+fn total_of(name: &str, rates: RateTotal) -> Option<RateTotal> {
+    match name {
+        "PdLiq" | "PdOil" => Some(rates),
+        TEMPERATURE_COL => Some(RateTotal::Average),
+        _ => None,
+    }
+}
+
+// Строка сразу под записями. columns - колонки значений с третьей колонки листа
+pub fn write_totals(
+    worksheet: &mut Worksheet,
+    mode: TotalsRow,
+    rates: RateTotal,
+    records: &[RecordRef],
+    columns: &[ValueColumn],
+    first_row: u32,
+    transposed: bool,
+) -> Result<(), XlsxError> {
+    if mode == TotalsRow::Off || records.is_empty() {
+        return Ok(());
+    }
+    let bold = Format::new().set_bold();
+    let row = first_row + records.len() as u32;
+    let last_row = row - 1;
+    let at = |row: u32, col: u16| layout::cell(transposed, row, col);
+    let range = |col: u16| {
+        let (r1, c1) = at(first_row, col);
+        let (r2, c2) = at(last_row, col);
+        cell_range(r1, c1, r2, c2)
+    };
+
+    let (r, c) = at(row, 0);
+    worksheet.write_string_with_format(r, c, "Итого", &bold)?;
+
+    // Формула пишется вместе с посчитанным значением: его видно и там,
+    // где формулы не пересчитываются (просмотрщики, чтение программой)
+    let mut write = |col: u16, formula: String, value: f64| {
+        let (r, c) = at(row, col);
+        match mode {
+            TotalsRow::Formulas => {
+                let formula = Formula::new(formula).set_result(value.to_string());
+                worksheet.write_formula_with_format(r, c, formula, &bold)
+            }
+            _ => worksheet.write_number_with_format(r, c, value, &bold),
+        }
+        .map(|_| ())
+    };
+    // Число записей - по колонке имени: дата может быть пустой
+    write(1, format!("=COUNTA({})", range(0)), records.len() as f64)?;
+
+    for (k, (name, get)) in columns.iter().enumerate() {
+        let Some(total) = total_of(name, rates) else {
+            continue;
+        };
+        let col = 2 + k as u16;
+        let values: Vec<f64> = records.iter().filter_map(get).collect();
+        match total {
+            RateTotal::Sum => write(col, format!("=SUM({})", range(col)), values.iter().sum())?,
+            // Без значений в колонке среднего нет - ячейка остается пустой
+            RateTotal::Average if values.is_empty() => {}
+            RateTotal::Average => write(
+                col,
+                format!("=AVERAGE({})", range(col)),
+                values.iter().sum::<f64>() / values.len() as f64,
+            )?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::settings::RateUnits;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Как подписывать единицы измерения на листах скважин
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum UnitsHeader {
+    // Только имена колонок, как раньше
+    #[default]
+    Off,
+    // В заголовке: "PdLiq, м3/сут"
+    InHeader,
+    // Второй строкой под заголовком
+    Row,
+}
+
+impl UnitsHeader {
+    pub const ALL: [UnitsHeader; 3] = [UnitsHeader::Off, UnitsHeader::InHeader, UnitsHeader::Row];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UnitsHeader::Off => "Не указывать",
+            UnitsHeader::InHeader => "В заголовке колонки",
+            UnitsHeader::Row => "Строкой под заголовком",
+        }
+    }
+}
+
+// Единицы колонки: из строки единиц исходного файла, для дебитов - из
+// настроек загрузки, если в файле их не было. Распределенные дебиты - в тех
+// же единицах, что и замеренные
+pub fn column_unit(
+    column: &str,
+    source: &BTreeMap<String, String>,
+    rates: RateUnits,
+) -> Option<String> {
+    let column = match column {
+        crate::ALLOCATED_LIQ_COL => "PdLiq",
+        crate::ALLOCATED_OIL_COL => "PdOil",
+        column => column,
+    };
+    source
+        .get(column)
+        .cloned()
+        .or_else(|| matches!(column, "PdLiq" | "PdOil").then(|| rates.label().to_string()))
+}
+
+pub fn with_unit(column: &str, unit: Option<&str>) -> String {
+    match unit {
+        Some(unit) => format!("{}, {}", column, unit),
+        None => column.to_string(),
+    }
+}
@@ -0,0 +1,67 @@
+use crate::error::{Location, ResultExt, WellDataError};
+use calamine::{Reader, Xlsx};
+use std::collections::HashSet;
+use std::path::Path;
+
+// Что должно оказаться в книге: лист скважины и число строк данных на нем
+pub struct PlannedSheet {
+    pub well: String,
+    pub sheet: String,
+    pub rows: usize,
+    // Строк заголовка над данными: имена и, может быть, единицы
+    pub header_rows: usize,
+    // Строк под данными: итоговая строка
+    pub footer_rows: usize,
+    // Даты по столбцам: записи считаются по столбцам справа от заголовка
+    pub transposed: bool,
+}
+
+// Сверка записанной книги с планом выгрузки: книга перечитывается целиком,
+// на каждом листе скважины считаются строки под заголовком.
+// Обрезанный при записи на сетевой диск файл здесь либо не откроется, либо не сойдется
+pub fn verify_workbook(path: &Path, plan: &[PlannedSheet]) -> Result<(), WellDataError> {
+    let mut workbook: Xlsx<_> =
+        calamine::open_workbook(path).map_err(|e| WellDataError::open_failed(path, e))?;
+    let sheets: HashSet<String> = workbook.sheet_names().into_iter().collect();
+
+    let missing: Vec<&str> = plan
+        .iter()
+        .filter(|p| !sheets.contains(&p.sheet))
+        .map(|p| p.well.as_str())
+        .collect();
+    let mut problems = Vec::new();
+    if !missing.is_empty() {
+        problems.push(format!(
+            "листов скважин {} из {}, нет: {}",
+            plan.len() - missing.len(),
+            plan.len(),
+            missing.join(", ")
+        ));
+    }
+    for planned in plan.iter().filter(|p| sheets.contains(&p.sheet)) {
+        let range = workbook
+            .worksheet_range(&planned.sheet)
+            .at(|| Location::new("Проверка").sheet(&planned.sheet))?;
+        let written = if planned.transposed {
+            range.width()
+        } else {
+            range.height()
+        };
+        let rows = written.saturating_sub(planned.header_rows + planned.footer_rows);
+        if rows != planned.rows {
+            problems.push(format!(
+                "лист {}: записано {} строк, прочитано {}",
+                planned.sheet, planned.rows, rows
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(WellDataError::VerifyFailed {
+            path: path.to_path_buf(),
+            problems,
+        })
+    }
+}
@@ -0,0 +1,307 @@
+use crate::error::WellDataError;
+use crate::query::{self, Value};
+use crate::store::Dataset;
+use eframe::egui;
+use std::collections::{BTreeSet, HashMap};
+
+// Выбор скважин с клавиатуры: Enter в строке поиска переводит в список,
+// стрелки двигают курсор, пробел отмечает скважину, Escape возвращает в поиск
+#[derive(Debug, Default)]
+pub struct ListNavigation {
+    pub focused: bool,
+    pub cursor: usize,
+    // Курсор сдвинулся в этом кадре - строку надо прокрутить в область видимости
+    pub moved: bool,
+    // В следующем кадре вернуть фокус строке поиска
+    pub focus_search: bool,
+}
+
+impl ListNavigation {
+    pub fn enter(&mut self) {
+        self.focused = true;
+        self.cursor = 0;
+        self.moved = true;
+    }
+
+    pub fn leave(&mut self) {
+        self.focused = false;
+        self.focus_search = true;
+    }
+
+    // Клавиши списка забираются из ввода, чтобы пробел и стрелки не достались
+    // другим элементам окна. Возвращает строку, которую надо переключить
+    pub fn handle_keys(&mut self, ctx: &egui::Context, len: usize) -> Option<usize> {
+        self.moved = false;
+        if !self.focused {
+            return None;
+        }
+        if len == 0 {
+            self.cursor = 0;
+        } else if self.cursor >= len {
+            self.cursor = len - 1;
+        }
+
+        let mut toggle = None;
+        ctx.input_mut(|i| {
+            let none = egui::Modifiers::NONE;
+            if i.consume_key(none, egui::Key::ArrowDown) && self.cursor + 1 < len {
+                self.cursor += 1;
+                self.moved = true;
+            }
+            if i.consume_key(none, egui::Key::ArrowUp) && self.cursor > 0 {
+                self.cursor -= 1;
+                self.moved = true;
+            }
+            if i.consume_key(none, egui::Key::PageDown) && len > 0 {
+                self.cursor = (self.cursor + 10).min(len - 1);
+                self.moved = true;
+            }
+            if i.consume_key(none, egui::Key::PageUp) {
+                self.cursor = self.cursor.saturating_sub(10);
+                self.moved = true;
+            }
+            if i.consume_key(none, egui::Key::Space) && len > 0 {
+                toggle = Some(self.cursor);
+            }
+            if i.consume_key(none, egui::Key::Escape) {
+                self.leave();
+            }
+        });
+        toggle
+    }
+
+    // Рамка вокруг строки под курсором
+    pub fn mark(&self, ui: &egui::Ui, row: usize, response: &egui::Response) {
+        if !self.focused || row != self.cursor {
+            return;
+        }
+        ui.painter().rect_stroke(
+            response.rect.expand(1.0),
+            2.0,
+            ui.visuals().selection.stroke,
+            egui::StrokeKind::Outside,
+        );
+        if self.moved {
+            response.scroll_to_me(None);
+        }
+    }
+}
+
+// Строка поиска: слова через пробел должны встретиться все, "-слово" - не должно
+// встретиться, "|" разделяет варианты. "105 -bis" - есть 105 и нет bis, "G | ST" - G или ST
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    // Варианты через "|", в каждом - слова с признаком отрицания
+    alternatives: Vec<Vec<(bool, String)>>,
+}
+
+impl SearchQuery {
+    pub fn parse(text: &str) -> Self {
+        let alternatives = text
+            .to_lowercase()
+            .split('|')
+            .map(|alternative| {
+                alternative
+                    .split_whitespace()
+                    .filter_map(|token| match token.strip_prefix('-') {
+                        Some("") => None,
+                        Some(word) => Some((true, word.to_string())),
+                        None => Some((false, token.to_string())),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|tokens| !tokens.is_empty())
+            .collect();
+        Self { alternatives }
+    }
+
+    pub fn matches(&self, well: &str) -> bool {
+        if self.alternatives.is_empty() {
+            return true;
+        }
+        let well = well.to_lowercase();
+        self.alternatives.iter().any(|tokens| {
+            tokens
+                .iter()
+                .all(|(negated, word)| well.contains(word.as_str()) != *negated)
+        })
+    }
+}
+
+// Фильтр списка по годам, в которых у скважины есть записи
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YearFilter {
+    #[default]
+    Any,
+    Year(i32),
+    // Квартальный лист "2021Q3"
+    Quarter(i32, u8),
+    // Данные в каждом году выгрузки, от начального до последнего
+    AllSelected,
+}
+
+impl YearFilter {
+    pub fn label(self) -> String {
+        match self {
+            YearFilter::Any => "Любой".to_string(),
+            YearFilter::Year(year) => year.to_string(),
+            YearFilter::Quarter(year, quarter) => format!("{} кв. {}", quarter, year),
+            YearFilter::AllSelected => "Все выбранные годы".to_string(),
+        }
+    }
+
+    // selected_years - годы файла начиная с выбранного
+    pub fn matches(
+        self,
+        years: Option<&BTreeSet<i32>>,
+        quarters: Option<&BTreeSet<(i32, u8)>>,
+        selected_years: &[i32],
+    ) -> bool {
+        match self {
+            YearFilter::Any => true,
+            YearFilter::Year(year) => years.is_some_and(|y| y.contains(&year)),
+            YearFilter::Quarter(year, quarter) => {
+                quarters.is_some_and(|q| q.contains(&(year, quarter)))
+            }
+            YearFilter::AllSelected => {
+                years.is_some_and(|y| selected_years.iter().all(|year| y.contains(year)))
+            }
+        }
+    }
+}
+
+// Годы с записями по каждой скважине
+pub fn well_years(data: &Dataset) -> HashMap<String, BTreeSet<i32>> {
+    let sql = format!(
+        "SELECT DISTINCT well_name, year_sheet FROM {}",
+        query::TABLE
+    );
+    let mut years: HashMap<String, BTreeSet<i32>> = HashMap::new();
+    let Ok(result) = query::run(data, &sql) else {
+        return years;
+    };
+    for row in result.rows {
+        if let (Value::Text(well), Value::Number(year)) = (&row[0], &row[1]) {
+            years.entry(well.clone()).or_default().insert(*year as i32);
+        }
+    }
+    years
+}
+
+// Кварталы с записями по каждой скважине - только из квартальных листов
+pub fn well_quarters(data: &Dataset) -> HashMap<String, BTreeSet<(i32, u8)>> {
+    let sql = format!(
+        "SELECT DISTINCT well_name, year_sheet, quarter FROM {} WHERE quarter IS NOT NULL",
+        query::TABLE
+    );
+    let mut quarters: HashMap<String, BTreeSet<(i32, u8)>> = HashMap::new();
+    let Ok(result) = query::run(data, &sql) else {
+        return quarters;
+    };
+    for row in result.rows {
+        if let (Value::Text(well), Value::Number(year), Value::Number(quarter)) =
+            (&row[0], &row[1], &row[2])
+        {
+            quarters
+                .entry(well.clone())
+                .or_default()
+                .insert((*year as i32, *quarter as u8));
+        }
+    }
+    quarters
+}
+
+// Средний PdOil скважин с начального года, по убыванию. Скважины без дебита
+// нефти за период в рейтинг не попадают
+pub fn oil_ranking(
+    data: &Dataset,
+    start_year: Option<i32>,
+) -> Result<Vec<(String, f64)>, WellDataError> {
+    let sql = format!(
+        "SELECT well_name, avg(pd_oil) AS rate FROM {} WHERE year_sheet >= {} GROUP BY well_name",
+        query::TABLE,
+        start_year.unwrap_or(i32::MIN)
+    );
+    let result = query::run(data, &sql)?;
+    let mut ranking: Vec<(String, f64)> = result
+        .rows
+        .into_iter()
+        .filter_map(|row| match (&row[0], &row[1]) {
+            (Value::Text(well), Value::Number(rate)) => Some((well.clone(), *rate)),
+            _ => None,
+        })
+        .collect();
+    ranking.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(ranking)
+}
+
+// Сколько точек в мини-графике скважины: история сжимается до средних по отрезкам
+const SPARKLINE_POINTS: usize = 40;
+const SPARKLINE_SIZE: egui::Vec2 = egui::vec2(60.0, 14.0);
+
+// История PdOil каждой скважины по дате, сжатая до SPARKLINE_POINTS точек
+pub fn oil_sparklines(data: &Dataset) -> HashMap<String, Vec<f32>> {
+    let sql = format!(
+        "SELECT well_name, pd_oil FROM {} WHERE pd_oil IS NOT NULL AND date IS NOT NULL \
+         ORDER BY well_name, date",
+        query::TABLE
+    );
+    let Ok(result) = query::run(data, &sql) else {
+        return HashMap::new();
+    };
+    let mut history: HashMap<String, Vec<f64>> = HashMap::new();
+    for row in result.rows {
+        if let (Value::Text(well), Value::Number(rate)) = (&row[0], &row[1]) {
+            history.entry(well.clone()).or_default().push(*rate);
+        }
+    }
+    history
+        .into_iter()
+        .map(|(well, values)| {
+            let chunk = values.len().div_ceil(SPARKLINE_POINTS).max(1);
+            let points = values
+                .chunks(chunk)
+                .map(|c| (c.iter().sum::<f64>() / c.len() as f64) as f32)
+                .collect();
+            (well, points)
+        })
+        .collect()
+}
+
+// Мини-график справа от галочки. Точка в конце: красная - скважина почти
+// не дает нефти, оранжевая - дебит упал больше чем вдвое от максимума
+pub fn sparkline(ui: &mut egui::Ui, values: &[f32]) {
+    let (rect, _) = ui.allocate_exact_size(SPARKLINE_SIZE, egui::Sense::hover());
+    let (Some(&last), true) = (values.last(), values.len() >= 2) else {
+        return;
+    };
+    let max = values.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return;
+    }
+    let step = rect.width() / (values.len() - 1) as f32;
+    let point = |i: usize, v: f32| {
+        egui::pos2(
+            rect.left() + step * i as f32,
+            rect.bottom() - rect.height() * (v / max).max(0.0),
+        )
+    };
+    let line: Vec<egui::Pos2> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| point(i, *v))
+        .collect();
+    let painter = ui.painter();
+    painter.add(egui::Shape::line(
+        line,
+        egui::Stroke::new(1.0, ui.visuals().weak_text_color()),
+    ));
+    let color = if last <= max * 0.05 {
+        egui::Color32::from_rgb(220, 50, 50)
+    } else if last < max * 0.5 {
+        egui::Color32::from_rgb(230, 150, 30)
+    } else {
+        egui::Color32::from_rgb(0, 170, 0)
+    };
+    painter.circle_filled(point(values.len() - 1, last), 2.0, color);
+}
@@ -0,0 +1,152 @@
+use crate::query::{self, Value};
+use crate::store::Dataset;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Тип скважины: из колонки Type исходного файла, а если ее нет - по данным.
+// У нагнетательных вместо дебитов приемистость и давление на устье
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum WellType {
+    Producer,
+    Injector,
+    Observation,
+}
+
+impl WellType {
+    pub const ALL: [WellType; 3] = [
+        WellType::Producer,
+        WellType::Injector,
+        WellType::Observation,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WellType::Producer => "Добывающие",
+            WellType::Injector => "Нагнетательные",
+            WellType::Observation => "Наблюдательные",
+        }
+    }
+
+    // Код для колонки well_type в таблицах и SQL-запросах
+    pub fn code(self) -> &'static str {
+        match self {
+            WellType::Producer => "producer",
+            WellType::Injector => "injector",
+            WellType::Observation => "observation",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.code() == code)
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let t = text.trim().to_lowercase();
+        if t.is_empty() {
+            return None;
+        }
+        if let Some(well_type) = Self::from_code(&t) {
+            return Some(well_type);
+        }
+        if t.contains("нагнет") || t.starts_with("inj") || t.contains("ппд") {
+            Some(WellType::Injector)
+        } else if t.contains("набл")
+            || t.contains("пьез")
+            || t.contains("контрол")
+            || t.starts_with("obs")
+        {
+            Some(WellType::Observation)
+        } else if t.contains("доб") || t.contains("нефт") || t.starts_with("prod") {
+            Some(WellType::Producer)
+        } else {
+            None
+        }
+    }
+
+    // Суффикс книги при выгрузке по типам: добывающие остаются в основном файле
+    fn file_suffix(self) -> Option<&'static str> {
+        match self {
+            WellType::Producer => None,
+            WellType::Injector => Some("нагнетательные"),
+            WellType::Observation => Some("наблюдательные"),
+        }
+    }
+}
+
+// Как раскладывать скважины разных типов при выгрузке
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TypeExport {
+    // Листы всех скважин подряд
+    #[default]
+    Combined,
+    // В одной книге, листы сгруппированы по типу, перед группой - лист-заголовок
+    Sections,
+    // Отдельная книга на каждый тип
+    Split,
+}
+
+impl TypeExport {
+    pub const ALL: [TypeExport; 3] = [
+        TypeExport::Combined,
+        TypeExport::Sections,
+        TypeExport::Split,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TypeExport::Combined => "Вперемешку",
+            TypeExport::Sections => "Разделами в одной книге",
+            TypeExport::Split => "Отдельными книгами",
+        }
+    }
+}
+
+// Тип каждой скважины. Без колонки Type: есть приемистость - нагнетательная,
+// есть дебиты - добывающая, иначе (только давление, уровень) - наблюдательная
+pub fn well_types(data: &Dataset) -> HashMap<String, WellType> {
+    let sql = format!(
+        "SELECT well_name, max(well_type) AS well_type, count(injection) AS injection,
+                count(pd_liq) + count(pd_oil) AS production
+         FROM {} GROUP BY well_name",
+        query::TABLE
+    );
+    let Ok(result) = query::run(data, &sql) else {
+        return HashMap::new();
+    };
+    result
+        .rows
+        .into_iter()
+        .filter_map(|row| {
+            let Value::Text(well) = &row[0] else {
+                return None;
+            };
+            let count = |v: &Value| matches!(v, Value::Number(n) if *n > 0.0);
+            let well_type = match &row[1] {
+                Value::Text(code) => WellType::from_code(code),
+                _ => None,
+            }
+            .unwrap_or(if count(&row[2]) {
+                WellType::Injector
+            } else if count(&row[3]) {
+                WellType::Producer
+            } else {
+                WellType::Observation
+            });
+            Some((well.clone(), well_type))
+        })
+        .collect()
+}
+
+// отчет.xlsx -> отчет_нагнетательные.xlsx
+pub fn split_path(path: &Path, well_type: WellType) -> PathBuf {
+    let Some(suffix) = well_type.file_suffix() else {
+        return path.to_path_buf();
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, suffix),
+    };
+    path.with_file_name(name)
+}
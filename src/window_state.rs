@@ -0,0 +1,163 @@
+use eframe::{UserEvent, egui};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use winit::application::ApplicationHandler;
+use winit::event::{DeviceEvent, DeviceId, StartCause, WindowEvent};
+use winit::event_loop::ActiveEventLoop;
+use winit::window::WindowId;
+
+// Размер окна при первом запуске: влезают 2 колонки
+const DEFAULT_SIZE: [f32; 2] = [700.0, 650.0];
+
+// Сколько окна должно остаться на каком-нибудь мониторе, чтобы его можно
+// было ухватить мышью
+const MIN_VISIBLE: f32 = 40.0;
+
+// Мониторы рабочего стола в точках интерфейса. egui знает только размер
+// монитора, на котором окно уже стоит, поэтому список берется у winit
+static MONITORS: OnceLock<Vec<egui::Rect>> = OnceLock::new();
+
+// Окно в прошлый раз: положение на общем рабочем столе всех мониторов,
+// размер без рамки, в точках интерфейса
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub maximized: bool,
+}
+
+// Окно открывается там же и такого же размера; развернутое - развернутым
+// на том же мониторе
+pub fn viewport(saved: Option<&WindowState>) -> egui::ViewportBuilder {
+    let builder = egui::ViewportBuilder::default();
+    match saved {
+        Some(s) => builder
+            .with_position([s.x, s.y])
+            .with_inner_size([s.width, s.height])
+            .with_maximized(s.maximized),
+        None => builder.with_inner_size(DEFAULT_SIZE),
+    }
+}
+
+// Запоминает окно за кадр. Свернутое и во весь экран не запоминается, у
+// развернутого - признак и положение, чтобы открыться на том же мониторе;
+// размер остается прежним: он нужен, чтобы было куда вернуться
+pub fn track(ctx: &egui::Context, state: &mut Option<WindowState>) {
+    ctx.input(|i| {
+        let info = i.viewport();
+        if info.minimized == Some(true) || info.fullscreen == Some(true) {
+            return;
+        }
+        let Some(outer) = info.outer_rect else {
+            return;
+        };
+        if info.maximized == Some(true) {
+            let [width, height] = state.map_or(DEFAULT_SIZE, |s| [s.width, s.height]);
+            *state = Some(WindowState {
+                x: outer.min.x,
+                y: outer.min.y,
+                width,
+                height,
+                maximized: true,
+            });
+            return;
+        }
+        let Some(inner) = info.inner_rect else {
+            return;
+        };
+        *state = Some(WindowState {
+            x: outer.min.x,
+            y: outer.min.y,
+            width: inner.width(),
+            height: inner.height(),
+            maximized: false,
+        });
+    });
+}
+
+// Первый кадр: если окно не попадает ни на один из мониторов, прежний,
+// скорее всего, отключен - окно переносится на основной.
+// Возвращает false, пока окно еще не получило положение
+pub fn check_monitor(ctx: &egui::Context, saved: Option<&WindowState>) -> bool {
+    if ctx.input(|i| i.viewport().outer_rect.is_none()) {
+        return false;
+    }
+    if let Some(saved) = saved
+        && let Some(monitors) = MONITORS.get()
+        && !monitors.is_empty()
+        && !monitors.iter().any(|m| visible(*m, saved))
+    {
+        ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(0.0, 0.0)));
+        if saved.maximized {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(true));
+        }
+    }
+    true
+}
+
+fn visible(monitor: egui::Rect, saved: &WindowState) -> bool {
+    let window = egui::Rect::from_min_size(
+        egui::pos2(saved.x, saved.y),
+        egui::vec2(saved.width, saved.height),
+    );
+    let common = monitor.intersect(window);
+    common.width() >= MIN_VISIBLE && common.height() >= MIN_VISIBLE
+}
+
+// Приложение eframe, которое при запуске цикла событий, до создания окна,
+// запоминает мониторы. Остальные события передаются как есть
+pub struct MonitorProbe<A>(pub A);
+
+impl<A: ApplicationHandler<UserEvent>> ApplicationHandler<UserEvent> for MonitorProbe<A> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        MONITORS.get_or_init(|| {
+            event_loop
+                .available_monitors()
+                .map(|m| {
+                    let scale = m.scale_factor();
+                    let position = m.position().to_logical::<f32>(scale);
+                    let size = m.size().to_logical::<f32>(scale);
+                    egui::Rect::from_min_size(
+                        egui::pos2(position.x, position.y),
+                        egui::vec2(size.width, size.height),
+                    )
+                })
+                .collect()
+        });
+        self.0.resumed(event_loop);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        self.0.window_event(event_loop, id, event);
+    }
+
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        self.0.new_events(event_loop, cause);
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.0.user_event(event_loop, event);
+    }
+
+    fn device_event(&mut self, event_loop: &ActiveEventLoop, id: DeviceId, event: DeviceEvent) {
+        self.0.device_event(event_loop, id, event);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.0.about_to_wait(event_loop);
+    }
+
+    fn suspended(&mut self, event_loop: &ActiveEventLoop) {
+        self.0.suspended(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.0.exiting(event_loop);
+    }
+
+    fn memory_warning(&mut self, event_loop: &ActiveEventLoop) {
+        self.0.memory_warning(event_loop);
+    }
+}
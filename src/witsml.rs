@@ -0,0 +1,242 @@
+use crate::cancel::CancellationToken;
+use crate::error::WellDataError;
+use crate::las::{Curve, WellLog};
+use crate::progress::ProgressSink;
+use crate::rules::{ValidationRules, Violation, ViolationKind};
+use crate::store::{Dataset, RecordRef};
+use crate::timing::Timings;
+use crate::{
+    CHOKE_COL, FREQUENCY_COL, INJECTION_COL, ImportOptions, LoadedData, P_BOTTOM_COL, P_HEAD_COL,
+    TEMPERATURE_COL,
+};
+use chrono::{DateTime, Datelike, NaiveDateTime};
+use roxmltree::{Document, Node};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+pub fn is_witsml_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xml") || ext.eq_ignore_ascii_case("witsml"))
+}
+
+// Кривые, которые ложатся в колонки записей при индексе по времени
+fn record_column(mnemonic: &str) -> Option<&'static str> {
+    match mnemonic.to_lowercase().as_str() {
+        "pdliq" | "liq" | "qliq" => Some("PdLiq"),
+        "pdoil" | "oil" | "qoil" => Some("PdOil"),
+        "temperature" | "temp" => Some(TEMPERATURE_COL),
+        "pzab" | "bhp" | "pbh" => Some(P_BOTTOM_COL),
+        "pbuf" | "whp" | "thp" => Some(P_HEAD_COL),
+        "freq" | "espfreq" | "hz" => Some(FREQUENCY_COL),
+        "choke" | "chk" | "bean" => Some(CHOKE_COL),
+        "qinj" | "inj" | "injrate" => Some(INJECTION_COL),
+        _ => None,
+    }
+}
+
+fn child<'a>(node: Node<'a, 'a>, name: &str) -> Option<Node<'a, 'a>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, 'a>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|n| n.text()).map(str::trim)
+}
+
+fn parse_time(s: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|d| d.naive_local())
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").ok())
+}
+
+// Объекты log из WITSML 1.4 (<logs><log>...</log></logs>).
+// Лог по времени превращается в записи (дата + PdLiq/PdOil/температура),
+// лог по глубине - в каротаж, как из LAS
+pub fn read_witsml_file(
+    path: &Path,
+    rules: &ValidationRules,
+    import: &ImportOptions,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<LoadedData, WellDataError> {
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    progress.report("Чтение WITSML...", 0, 0);
+
+    let mut timings = Timings::new(format!("Загрузка {}", file_name));
+    let text = timings
+        .measure("Чтение файла", || {
+            import.retry.run("чтение файла", progress, cancel, || {
+                std::fs::read_to_string(path)
+            })
+        })
+        .map_err(|e| WellDataError::open_failed(path, e))?;
+    let doc = timings.measure("Разбор XML", || Document::parse(&text))?;
+
+    let logs: Vec<Node> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("log"))
+        .collect();
+    if logs.is_empty() {
+        return Err(WellDataError::BadContent(
+            "в файле нет объектов WITSML log".to_string(),
+        ));
+    }
+
+    let mut dataset = Dataset::new(import.storage)?;
+    let mut valid_years = BTreeSet::new();
+    let mut unique_wells = BTreeSet::new();
+    let mut violations = Vec::new();
+    let mut warnings = Vec::new();
+    let parse_start = std::time::Instant::now();
+
+    for (log_idx, log) in logs.iter().enumerate() {
+        cancel.check()?;
+        let name = child_text(*log, "name").unwrap_or("log").to_string();
+        progress.report(&format!("Лог '{}'", name), log_idx, logs.len());
+
+        let well_name = child_text(*log, "nameWell")
+            .or_else(|| log.attribute("uidWell"))
+            .unwrap_or_default()
+            .to_string();
+        if well_name.is_empty() {
+            warnings.push(WellDataError::Warning(format!(
+                "лог '{}' без имени скважины пропущен",
+                name
+            )));
+            continue;
+        }
+        if rules.is_forbidden(&well_name) {
+            violations.push(Violation {
+                kind: ViolationKind::ForbiddenWell,
+                sheet: name,
+                row: None,
+                well: Some(well_name),
+                details: "Запрещенное имя скважины, лог пропущен".to_string(),
+            });
+            continue;
+        }
+
+        let Some(log_data) = child(*log, "logData") else {
+            continue;
+        };
+        let split = |s: Option<&str>| -> Vec<String> {
+            s.unwrap_or_default()
+                .split(',')
+                .map(|m| m.trim().to_string())
+                .collect()
+        };
+        let mnemonics = split(child_text(log_data, "mnemonicList"));
+        let units = split(child_text(log_data, "unitList"));
+        let null_value = child_text(*log, "nullValue").map(str::to_string);
+        let by_time = child_text(*log, "indexType").is_some_and(|t| t.contains("time"));
+
+        let rows: Vec<Vec<&str>> = log_data
+            .children()
+            .filter(|n| n.has_tag_name("data"))
+            .filter_map(|n| n.text())
+            .map(|line| line.split(',').map(str::trim).collect())
+            .collect();
+        let value = |row: &[&str], i: usize| -> Option<f64> {
+            let cell = *row.get(i)?;
+            if cell.is_empty() || null_value.as_deref() == Some(cell) {
+                return None;
+            }
+            cell.parse().ok()
+        };
+
+        if by_time {
+            let column_of = |column: &str| {
+                mnemonics
+                    .iter()
+                    .position(|m| record_column(m) == Some(column))
+            };
+            let idx_liq = column_of("PdLiq");
+            let idx_oil = column_of("PdOil");
+            let idx_temp = column_of(TEMPERATURE_COL);
+            let idx_p_bottom = column_of(P_BOTTOM_COL);
+            let idx_p_head = column_of(P_HEAD_COL);
+            let idx_frequency = column_of(FREQUENCY_COL);
+            let idx_choke = column_of(CHOKE_COL);
+            let idx_injection = column_of(INJECTION_COL);
+            let bounded: Vec<(&String, usize, &crate::rules::Bounds)> = rules
+                .bounds
+                .iter()
+                .filter_map(|(col, b)| column_of(col).map(|i| (col, i, b)))
+                .collect();
+
+            for (i, row) in rows.iter().enumerate() {
+                let Some(date) = row.first().and_then(|t| parse_time(t)) else {
+                    continue;
+                };
+                let mut out_of_bounds = false;
+                for (col, idx, bounds) in &bounded {
+                    if let Some(problem) = value(row, *idx).and_then(|v| bounds.check(v)) {
+                        out_of_bounds = true;
+                        violations.push(Violation {
+                            kind: ViolationKind::OutOfBounds,
+                            sheet: name.clone(),
+                            row: Some(i as u32 + 1),
+                            well: Some(well_name.clone()),
+                            details: format!("{}: {}", col, problem),
+                        });
+                    }
+                }
+
+                let get = |idx: Option<usize>| idx.and_then(|i| value(row, i));
+                valid_years.insert(date.year());
+                dataset.push(RecordRef {
+                    well_name: &well_name,
+                    date: Some(date),
+                    pd_liq: get(idx_liq),
+                    pd_oil: get(idx_oil),
+                    temperature: get(idx_temp),
+                    p_bottom: get(idx_p_bottom),
+                    p_head: get(idx_p_head),
+                    frequency: get(idx_frequency),
+                    choke: get(idx_choke),
+                    injection: get(idx_injection),
+                    status: None,
+                    well_type: None,
+                    year_sheet: date.year(),
+                    quarter: None,
+                    out_of_bounds,
+                })?;
+            }
+        } else {
+            let curves = mnemonics
+                .iter()
+                .enumerate()
+                .map(|(i, m)| Curve {
+                    mnemonic: m.clone(),
+                    unit: units.get(i).cloned().unwrap_or_default(),
+                })
+                .collect();
+            let values = rows
+                .iter()
+                .map(|row| (0..mnemonics.len()).map(|i| value(row, i)).collect())
+                .collect();
+            dataset.logs.push(WellLog {
+                well_name: well_name.clone(),
+                curves,
+                rows: values,
+            });
+        }
+        unique_wells.insert(well_name);
+    }
+    timings.record("Разбор логов", parse_start);
+
+    timings.measure("Завершение записи", || dataset.finish())?;
+    progress.timings(timings.finish());
+    Ok(LoadedData {
+        records: dataset,
+        years: valid_years.into_iter().collect(),
+        wells: unique_wells.into_iter().collect(),
+        violations,
+        warnings,
+        units: BTreeMap::new(),
+    })
+}
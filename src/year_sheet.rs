@@ -0,0 +1,244 @@
+use chrono::{Datelike, NaiveDateTime};
+
+// Листы с данными: "2021", в старых книгах - "98", "99", "00", квартальные -
+// "2021Q3", "2021 3кв", помесячные - "2022-07", "Июль 2022".
+// Двузначный год меньше границы - этот век, остальные - прошлый
+pub const DEFAULT_PIVOT: u32 = 50;
+
+// Русские названия месяцев целиком: сокращение, именительный, родительный и
+// предложный падеж. По началу слова "ма" ловило бы и "Материалы 2021"
+const MONTH_NAMES: [&[&str]; 12] = [
+    &["янв", "январь", "января", "январе"],
+    &["фев", "февр", "февраль", "февраля", "феврале"],
+    &["мар", "март", "марта", "марте"],
+    &["апр", "апрель", "апреля", "апреле"],
+    &["май", "мая", "мае"],
+    &["июн", "июнь", "июня", "июне"],
+    &["июл", "июль", "июля", "июле"],
+    &["авг", "август", "августа", "августе"],
+    &["сен", "сент", "сентябрь", "сентября", "сентябре"],
+    &["окт", "октябрь", "октября", "октябре"],
+    &["ноя", "нояб", "ноябрь", "ноября", "ноябре"],
+    &["дек", "декабрь", "декабря", "декабре"],
+];
+
+// Номер месяца по слову в нижнем регистре
+pub fn month_number(word: &str) -> Option<u32> {
+    MONTH_NAMES
+        .iter()
+        .position(|names| names.contains(&word))
+        .map(|i| i as u32 + 1)
+}
+
+// Период листа: год и, для квартального или месячного листа, квартал или месяц
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SheetPeriod {
+    pub year: i32,
+    pub quarter: Option<u8>,
+    pub month: Option<u32>,
+}
+
+impl SheetPeriod {
+    // Дата из месяца листа; для листов за год и квартал не проверяется
+    pub fn covers(&self, date: NaiveDateTime) -> bool {
+        self.month
+            .is_none_or(|month| date.year() == self.year && date.month() == month)
+    }
+}
+
+// Год - четыре цифры или две; лист "202" или "7" - не год
+pub fn sheet_year(name: &str, pivot: u32) -> Option<i32> {
+    let name = name.trim();
+    if !matches!(name.len(), 2 | 4) || !name.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let year = name.parse::<i32>().ok()?;
+    if name.len() == 2 {
+        let century = if (year as u32) < pivot { 2000 } else { 1900 };
+        return Some(century + year);
+    }
+    Some(year)
+}
+
+// "2021Q3", "2021 Q3", "Q3 2021", "2021 3кв", "3 кв. 2021"
+fn quarter_sheet(name: &str, pivot: u32) -> Option<SheetPeriod> {
+    let lower = name.trim().to_lowercase();
+    let rest = lower.replace("кв", "");
+    if rest.len() == lower.len() && !lower.contains('q') {
+        return None;
+    }
+    // Кроме года, квартала и обозначения - только пробелы и знаки
+    if rest
+        .chars()
+        .any(|c| !(c.is_ascii_digit() || c == 'q' || c.is_whitespace() || ".-_".contains(c)))
+    {
+        return None;
+    }
+    let groups: Vec<&str> = rest
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|g| !g.is_empty())
+        .collect();
+    let [a, b] = groups.as_slice() else {
+        return None;
+    };
+    let (year, quarter) = if a.len() == 1 { (b, a) } else { (a, b) };
+    if quarter.len() != 1 || year.len() != 2 && year.len() != 4 {
+        return None;
+    }
+    let quarter: u8 = quarter.parse().ok().filter(|q| (1..=4).contains(q))?;
+    Some(SheetPeriod {
+        year: sheet_year(year, pivot)?,
+        quarter: Some(quarter),
+        month: None,
+    })
+}
+
+// Месяц листа: "01.2023", "2023-01", "Январь 2023". Год только четырехзначный
+pub fn sheet_month(name: &str) -> Option<(i32, u32)> {
+    let lower = name.to_lowercase();
+    let tokens: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let year = tokens
+        .iter()
+        .find(|t| t.len() == 4 && t.chars().all(|c| c.is_ascii_digit()))?
+        .parse()
+        .ok()?;
+    let month = tokens.iter().find_map(|t| {
+        if t.len() <= 2 {
+            t.parse::<u32>().ok().filter(|m| (1..=12).contains(m))
+        } else {
+            month_number(t)
+        }
+    })?;
+    Some((year, month))
+}
+
+// "3 кв. 2020" похоже и на месяц, поэтому квартал проверяется раньше
+pub fn sheet_period(name: &str, pivot: u32) -> Option<SheetPeriod> {
+    if let Some(year) = sheet_year(name, pivot) {
+        return Some(SheetPeriod {
+            year,
+            quarter: None,
+            month: None,
+        });
+    }
+    quarter_sheet(name, pivot).or_else(|| {
+        let (year, month) = sheet_month(name)?;
+        Some(SheetPeriod {
+            year,
+            quarter: None,
+            month: Some(month),
+        })
+    })
+}
+
+// Лист за год или квартал: такие книги - не МЭР, даже если есть листы-месяцы
+pub fn is_year_or_quarter_sheet(name: &str) -> bool {
+    sheet_period(name, DEFAULT_PIVOT).is_some_and(|p| p.month.is_none())
+}
+
+// Лист с данными за год, квартал или месяц
+pub fn is_data_sheet(name: &str) -> bool {
+    sheet_period(name, DEFAULT_PIVOT).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn year_sheet_names() {
+        assert_eq!(sheet_year("2021", DEFAULT_PIVOT), Some(2021));
+        assert_eq!(sheet_year(" 2021 ", DEFAULT_PIVOT), Some(2021));
+        assert_eq!(sheet_year("98", DEFAULT_PIVOT), Some(1998));
+        assert_eq!(sheet_year("2021г", DEFAULT_PIVOT), None);
+        assert_eq!(sheet_year("202", DEFAULT_PIVOT), None);
+        assert_eq!(sheet_year("7", DEFAULT_PIVOT), None);
+        assert_eq!(sheet_year("-21", DEFAULT_PIVOT), None);
+    }
+
+    #[test]
+    fn two_digit_year_around_pivot() {
+        assert_eq!(sheet_year("00", DEFAULT_PIVOT), Some(2000));
+        assert_eq!(sheet_year("49", DEFAULT_PIVOT), Some(2049));
+        assert_eq!(sheet_year("50", DEFAULT_PIVOT), Some(1950));
+        assert_eq!(sheet_year("99", DEFAULT_PIVOT), Some(1999));
+        assert_eq!(sheet_year("00", 0), Some(1900));
+        assert_eq!(sheet_year("99", 100), Some(2099));
+    }
+
+    fn quarter(year: i32, quarter: u8) -> Option<SheetPeriod> {
+        Some(SheetPeriod {
+            year,
+            quarter: Some(quarter),
+            month: None,
+        })
+    }
+
+    #[test]
+    fn quarter_sheet_names() {
+        for name in [
+            "2021Q3",
+            "2021 Q3",
+            "Q3 2021",
+            "2021 3кв",
+            "3 кв. 2021",
+            "21q3",
+        ] {
+            assert_eq!(
+                sheet_period(name, DEFAULT_PIVOT),
+                quarter(2021, 3),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(sheet_period("4кв 99", DEFAULT_PIVOT), quarter(1999, 4));
+        assert!(is_year_or_quarter_sheet("2021Q1"));
+    }
+
+    #[test]
+    fn quarter_sheet_rejects_bad_quarter_or_year() {
+        for name in [
+            "2021 5кв",
+            "2021Q0",
+            "202 Q1",
+            "2021 Q1 Q2",
+            "Q 2021",
+            "2021 кв. итог",
+        ] {
+            assert_eq!(sheet_period(name, DEFAULT_PIVOT), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn month_sheet_names() {
+        assert_eq!(sheet_month("01.2023"), Some((2023, 1)));
+        assert_eq!(sheet_month("2022-07"), Some((2022, 7)));
+        assert_eq!(sheet_month("Июль 2022"), Some((2022, 7)));
+        assert_eq!(sheet_month("марта 2021"), Some((2021, 3)));
+        assert_eq!(sheet_month("Май 2021"), Some((2021, 5)));
+        assert_eq!(sheet_month("сент. 2021"), Some((2021, 9)));
+        assert_eq!(
+            sheet_period("Дек 2020", DEFAULT_PIVOT).and_then(|p| p.month),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn month_sheet_rejects_partial_names() {
+        for name in [
+            "Материалы 2021",
+            "Ма 2021",
+            "Июльский 2022",
+            "Итоги 2021",
+            "13.2023",
+        ] {
+            assert_eq!(sheet_month(name), None, "{}", name);
+        }
+        // Год только четырехзначный
+        assert_eq!(sheet_month("Июль 22"), None);
+        assert!(!is_data_sheet("Материалы 2021"));
+    }
+}